@@ -0,0 +1,384 @@
+use crate::formats::midi::ImportOptions;
+use crate::sync::SyncMode;
+
+/// Options passed to BSDj on the command line.
+///
+/// Inserted as a resource before any plugins are added so that plugins
+/// can read it while building.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CliArgs {
+    /// Path to a user-supplied LSDj ROM to pull kits, fonts and palettes from.
+    pub rom: Option<String>,
+    /// Path to a MIDI file to import as the song.
+    pub midi: Option<String>,
+    /// How the MIDI file is quantized into phrases.
+    pub midi_options: ImportOptions,
+    /// Path to a hUGETracker song to import as the song.
+    pub uge: Option<String>,
+    /// Path to a VGM file to import the Game Boy sound of as the song.
+    pub vgm: Option<String>,
+    /// Path to a text song to load.
+    pub song: Option<String>,
+    /// Paths to the base, our and their copies of a text song to merge and load.
+    pub merge: Option<(String, String, String)>,
+    /// The port to host a session on, for others on the LAN to join.
+    pub host: Option<u16>,
+    /// The address of a session to join, as `host:port`.
+    pub join: Option<String>,
+    /// Which side of the tempo sync to start on.
+    pub sync: Option<SyncMode>,
+    /// The address the sync master sends to, as `host:port`. Slaves listen on its port.
+    pub sync_addr: Option<String>,
+    /// The MIDI backend to send and receive MIDI through while running.
+    pub midi_backend: Option<String>,
+    /// The rows the Phrase screen's cursor moves down after a note is entered from MIDI.
+    pub note_step: Option<u8>,
+    /// Path to a key bindings file to use instead of the one in the user's config directory.
+    pub bindings: Option<String>,
+    /// If true, virtual Game Boy buttons are shown below the screen for touchscreens.
+    pub touch_pad: bool,
+    /// If true, fonts, glyphs and palettes in the user's config directory aren't loaded.
+    pub no_user_assets: bool,
+    /// Path to record every change to the inputs to.
+    pub record: Option<String>,
+    /// Path to a recording of the inputs to replay.
+    pub replay: Option<String>,
+    /// A command to run instead of opening the app.
+    pub command: Option<CliCommand>,
+}
+
+/// Commands that run without opening the app.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CliCommand {
+    /// `bsdj patch <in.gb> <out.gb> [options]`
+    Patch(PatchArgs),
+    /// `bsdj fmt [--check] <file.bsdj>...`
+    Fmt(FmtArgs),
+    /// `bsdj diff <old.bsdj> <new.bsdj>`
+    Diff(DiffArgs),
+    /// `bsdj merge <base.bsdj> <ours.bsdj> <theirs.bsdj> [-o <out.bsdj>]`
+    Merge(MergeArgs),
+    /// `bsdj screenshot <out.png> [options]`
+    Screenshot(ScreenshotArgs),
+    /// `bsdj capture <out.gif> [options]`, which takes the same options as `screenshot`.
+    Capture(ScreenshotArgs),
+}
+
+/// Arguments for the `patch` command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PatchArgs {
+    /// The LSDj ROM to patch.
+    pub input: String,
+    /// Where to write the patched ROM.
+    pub output: String,
+    /// The font slots to replace, along with the `.tilesprite` to replace them with.
+    pub fonts: Vec<(usize, String)>,
+    /// The palette slots to replace, along with the `.colorset` to replace them with.
+    pub palettes: Vec<(usize, String)>,
+    /// Another LSDj ROM to copy the kits from.
+    pub kits: Option<String>,
+    /// Where to write a `.sav` holding the song.
+    pub sav: Option<String>,
+    /// The text song to put in the `.sav`. It holds an empty song without one.
+    pub song: Option<String>,
+}
+
+/// Arguments for the `fmt` command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct FmtArgs {
+    /// The text songs to format.
+    pub files: Vec<String>,
+    /// If true, the files are only checked and not rewritten.
+    pub check: bool,
+}
+
+/// Arguments for the `diff` command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiffArgs {
+    pub old: String,
+    pub new: String,
+}
+
+/// Arguments for the `merge` command.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct MergeArgs {
+    /// The song both copies were edited from.
+    pub base: String,
+    /// Our copy, which conflicting cells are taken from.
+    pub ours: String,
+    /// Their copy.
+    pub theirs: String,
+    /// Where to write the merged song. If None, it's printed.
+    pub output: Option<String>,
+}
+
+/// Arguments for the `screenshot` and `capture` commands.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ScreenshotArgs {
+    /// Where to write the PNG or GIF.
+    pub output: String,
+    /// A text song to load.
+    pub song: Option<String>,
+    /// A recording of the inputs to replay before taking the screenshot, or while capturing.
+    pub replay: Option<String>,
+    /// The frames to run after the replay, or after loading if there's none.
+    pub frames: usize,
+    /// How many pixels wide and high each of the screen's pixels is.
+    pub scale: u32,
+    /// The name of the palette to draw with.
+    pub palette: Option<String>,
+}
+
+impl CliArgs {
+    /// Parses the arguments the program was started with.
+    pub fn from_env() -> CliArgs {
+        CliArgs::parse(std::env::args().skip(1))
+    }
+
+    /// Parses a list of arguments, excluding the program name.
+    ///
+    /// Unknown arguments are reported and otherwise ignored.
+    pub fn parse(args: impl IntoIterator<Item = String>) -> CliArgs {
+        let mut cli = CliArgs::default();
+        let mut args = args.into_iter().peekable();
+
+        if args.peek().map(|arg| arg.as_str()) == Some("patch") {
+            args.next();
+            cli.command = Some(CliCommand::Patch(PatchArgs::parse(args)));
+            return cli;
+        }
+        if args.peek().map(|arg| arg.as_str()) == Some("fmt") {
+            args.next();
+            cli.command = Some(CliCommand::Fmt(FmtArgs::parse(args)));
+            return cli;
+        }
+        if args.peek().map(|arg| arg.as_str()) == Some("diff") {
+            args.next();
+            cli.command = Some(CliCommand::Diff(DiffArgs {
+                old: expect_value("diff", args.next()),
+                new: expect_value("diff", args.next()),
+            }));
+            return cli;
+        }
+        if args.peek().map(|arg| arg.as_str()) == Some("merge") {
+            args.next();
+            cli.command = Some(CliCommand::Merge(MergeArgs::parse(args)));
+            return cli;
+        }
+        if args.peek().map(|arg| arg.as_str()) == Some("screenshot") {
+            args.next();
+            let screenshot = ScreenshotArgs::parse("screenshot", args);
+            cli.command = Some(CliCommand::Screenshot(screenshot));
+            return cli;
+        }
+        if args.peek().map(|arg| arg.as_str()) == Some("capture") {
+            args.next();
+            cli.command = Some(CliCommand::Capture(ScreenshotArgs::parse("capture", args)));
+            return cli;
+        }
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--rom" => cli.rom = Some(expect_value(&arg, args.next())),
+                "--uge" => cli.uge = Some(expect_value(&arg, args.next())),
+                "--vgm" => cli.vgm = Some(expect_value(&arg, args.next())),
+                "--song" => cli.song = Some(expect_value(&arg, args.next())),
+                "--merge" => {
+                    cli.merge = Some((
+                        expect_value(&arg, args.next()),
+                        expect_value(&arg, args.next()),
+                        expect_value(&arg, args.next()),
+                    ))
+                }
+                "--host" => cli.host = Some(expect_in_range(&arg, args.next())),
+                "--join" => cli.join = Some(expect_value(&arg, args.next())),
+                "--sync" => {
+                    let value = expect_value(&arg, args.next());
+                    let mode = SyncMode::iter()
+                        .into_iter()
+                        .find(|mode| mode.to_string() == value);
+                    cli.sync = Some(mode.unwrap_or_else(|| {
+                        eprintln!(
+                            "Expected `off`, `master`, `slave` or `midi` after `--sync`, got `{}`",
+                            value
+                        );
+                        std::process::exit(1)
+                    }));
+                }
+                "--sync-addr" => cli.sync_addr = Some(expect_value(&arg, args.next())),
+                "--midi-backend" => cli.midi_backend = Some(expect_value(&arg, args.next())),
+                "--bindings" => cli.bindings = Some(expect_value(&arg, args.next())),
+                "--touch-pad" => cli.touch_pad = true,
+                "--no-user-assets" => cli.no_user_assets = true,
+                "--record" => cli.record = Some(expect_value(&arg, args.next())),
+                "--replay" => cli.replay = Some(expect_value(&arg, args.next())),
+                "--note-step" => cli.note_step = Some(expect_in_range(&arg, args.next())),
+                "--midi" => cli.midi = Some(expect_value(&arg, args.next())),
+                "--midi-rows" => {
+                    cli.midi_options.rows_per_quarter = expect_number(&arg, args.next()) as u32
+                }
+                "--midi-chain-length" => {
+                    cli.midi_options.phrases_per_chain = expect_number(&arg, args.next())
+                }
+                "--midi-tracks" => {
+                    let value = expect_value(&arg, args.next());
+                    let tracks = value
+                        .split(',')
+                        .map(|track| expect_number(&arg, Some(track.to_string())))
+                        .collect();
+                    cli.midi_options.tracks = Some(tracks);
+                }
+                _ => eprintln!("Ignoring unknown argument `{}`", arg),
+            }
+        }
+
+        cli
+    }
+}
+
+impl PatchArgs {
+    /// Parses the arguments following `patch`.
+    fn parse(args: impl Iterator<Item = String>) -> PatchArgs {
+        let mut patch = PatchArgs::default();
+        let mut args = args.peekable();
+
+        patch.input = expect_value("patch", args.next());
+        patch.output = expect_value("patch", args.next());
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--font" => patch.fonts.push((
+                    expect_number(&arg, args.next()),
+                    expect_value(&arg, args.next()),
+                )),
+                "--palette" => patch.palettes.push((
+                    expect_number(&arg, args.next()),
+                    expect_value(&arg, args.next()),
+                )),
+                "--kits" => patch.kits = Some(expect_value(&arg, args.next())),
+                "--sav" => patch.sav = Some(expect_value(&arg, args.next())),
+                "--song" => patch.song = Some(expect_value(&arg, args.next())),
+                _ => eprintln!("Ignoring unknown argument `{}`", arg),
+            }
+        }
+
+        patch
+    }
+}
+
+impl FmtArgs {
+    /// Parses the arguments following `fmt`.
+    fn parse(args: impl Iterator<Item = String>) -> FmtArgs {
+        let mut fmt = FmtArgs::default();
+        for arg in args {
+            match arg.as_str() {
+                "--check" => fmt.check = true,
+                _ => fmt.files.push(arg),
+            }
+        }
+        if fmt.files.is_empty() {
+            expect_value("fmt", None);
+        }
+        fmt
+    }
+}
+
+impl MergeArgs {
+    /// Parses the arguments following `merge`.
+    fn parse(args: impl Iterator<Item = String>) -> MergeArgs {
+        let mut merge = MergeArgs::default();
+        let mut args = args.peekable();
+
+        merge.base = expect_value("merge", args.next());
+        merge.ours = expect_value("merge", args.next());
+        merge.theirs = expect_value("merge", args.next());
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-o" | "--output" => merge.output = Some(expect_value(&arg, args.next())),
+                _ => eprintln!("Ignoring unknown argument `{}`", arg),
+            }
+        }
+
+        merge
+    }
+}
+
+impl ScreenshotArgs {
+    /// Parses the arguments following `screenshot` or `capture`.
+    fn parse(command: &str, args: impl Iterator<Item = String>) -> ScreenshotArgs {
+        let mut args = args.peekable();
+        let mut screenshot = ScreenshotArgs {
+            output: expect_value(command, args.next()),
+            song: None,
+            replay: None,
+            frames: 1,
+            scale: 1,
+            palette: None,
+        };
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--song" => screenshot.song = Some(expect_value(&arg, args.next())),
+                "--replay" => screenshot.replay = Some(expect_value(&arg, args.next())),
+                "--frames" => screenshot.frames = expect_number(&arg, args.next()),
+                "--scale" => screenshot.scale = expect_number(&arg, args.next()) as u32,
+                "--palette" => screenshot.palette = Some(expect_value(&arg, args.next())),
+                _ => eprintln!("Ignoring unknown argument `{}`", arg),
+            }
+        }
+
+        screenshot
+    }
+}
+
+/// Runs a command, returning the exit code.
+pub fn run_command(command: &CliCommand) -> i32 {
+    let result = match command {
+        CliCommand::Patch(args) => crate::rom::patch::run(args).map_err(|e| e.to_string()),
+        CliCommand::Fmt(args) => crate::formats::text::fmt::run(args).map_err(|e| e.to_string()),
+        CliCommand::Diff(args) => crate::merge::command::run_diff(args).map_err(|e| e.to_string()),
+        CliCommand::Merge(args) => {
+            crate::merge::command::run_merge(args).map_err(|e| e.to_string())
+        }
+        CliCommand::Screenshot(args) => crate::screenshot::run(args).map_err(|e| e.to_string()),
+        CliCommand::Capture(args) => {
+            crate::screenshot::run_capture(args).map_err(|e| e.to_string())
+        }
+    };
+
+    match result {
+        Ok(()) => 0,
+        Err(e) => {
+            eprintln!("{}", e);
+            1
+        }
+    }
+}
+
+/// Returns the value following a flag, exiting if there was none.
+fn expect_value(flag: &str, value: Option<String>) -> String {
+    value.unwrap_or_else(|| {
+        eprintln!("Expected a value after `{}`", flag);
+        std::process::exit(1)
+    })
+}
+
+/// Returns the number following a flag, exiting if there was none.
+fn expect_number(flag: &str, value: Option<String>) -> usize {
+    let value = expect_value(flag, value);
+    value.parse().unwrap_or_else(|_| {
+        eprintln!("Expected a number after `{}`, got `{}`", flag, value);
+        std::process::exit(1)
+    })
+}
+
+/// Returns the number following a flag as a smaller type, exiting if there was none
+/// or it doesn't fit.
+fn expect_in_range<T: TryFrom<usize>>(flag: &str, value: Option<String>) -> T {
+    let number = expect_number(flag, value);
+    T::try_from(number).unwrap_or_else(|_| {
+        eprintln!("The number after `{}` is too large, got `{}`", flag, number);
+        std::process::exit(1)
+    })
+}
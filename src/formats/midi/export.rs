@@ -0,0 +1,241 @@
+use super::smf::{self, Track};
+use super::*;
+
+/// The velocity every note is played at.
+const VELOCITY: u8 = 100;
+
+/// Exports the song as a type 1 Standard MIDI File.
+///
+/// The first track holds the song title and tempo changes, followed by a track
+/// for each channel. Each channel plays until its first empty song row, using
+/// the default groove of 6 ticks per row.
+pub fn export_song(
+    channels: &Channels,
+    chains: &Chains,
+    phrases: &Phrases,
+    project: &Project,
+) -> Vec<u8> {
+    let mut conductor = Track::default();
+    if !project.get_title().is_empty() {
+        conductor.meta(0, smf::META_TRACK_NAME, project.get_title().as_bytes());
+    }
+    conductor.tempo(0, project.get_tempo());
+
+    let mut tracks = vec![];
+    for (idx, channel) in channels.get_all().iter().enumerate() {
+        let mut writer = ChannelWriter::new(idx);
+        let mut time = 0;
+
+        for chain in (0..=0xff).map_while(|row| channel.get_chain(row)) {
+            let chain = chains.get(chain as usize).unwrap();
+            for step in 0..0x10 {
+                let phrase = match chain.get_phrase(step) {
+                    Some(phrase) => phrases.get(phrase as usize).unwrap(),
+                    // The chain ends at its first empty step.
+                    None => break,
+                };
+                let transpose = chain.get_transpose(step).unwrap() as i8;
+
+                for row in 0..0x10 {
+                    writer.write_row(time, phrase, row, transpose, &mut conductor);
+                    time += TICKS_PER_ROW;
+                }
+            }
+        }
+
+        tracks.push(writer.finish(time));
+    }
+
+    tracks.insert(0, conductor);
+    smf::write_smf(&tracks, TICKS_PER_QUARTER)
+}
+
+/// Converts the rows of one channel into a track.
+struct ChannelWriter {
+    track: Track,
+    /// The channel's index in `Channels`, which is also its MIDI channel.
+    channel: usize,
+    /// The last instrument a program change was written for.
+    instr: Option<u8>,
+    /// The MIDI note currently playing and the time a Kill command stops it.
+    playing: Option<(u8, Option<u32>)>,
+}
+
+impl ChannelWriter {
+    fn new(channel: usize) -> ChannelWriter {
+        let mut track = Track::default();
+        track.meta(0, smf::META_TRACK_NAME, CHANNEL_NAMES[channel].as_bytes());
+        ChannelWriter {
+            track,
+            channel,
+            instr: None,
+            playing: None,
+        }
+    }
+
+    /// Writes the events for a single phrase row starting at `time`.
+    fn write_row(
+        &mut self,
+        time: u32,
+        phrase: &phrase::Phrase,
+        row: usize,
+        transpose: i8,
+        conductor: &mut Track,
+    ) {
+        let cmd = phrase.get_cmd(row);
+        let val = phrase.get_cmd_val(row).unwrap();
+
+        if cmd == Some(Command::Tempo) {
+            let mut project = Project::default();
+            project.set_tempo_byte(val);
+            conductor.tempo(time, project.get_tempo());
+        }
+
+        // Notes and instruments are delayed along with the rest of the row.
+        let start = match cmd {
+            Some(Command::Delay) => time + val as u32 * TICKS_PER_LSDJ_TICK,
+            _ => time,
+        };
+
+        if let Some(instr) = phrase.get_instr(row) {
+            if self.instr != Some(instr) {
+                self.track.program_change(start, self.channel as u8, instr);
+                self.instr = Some(instr);
+            }
+        }
+
+        let note = phrase.get_note(row).and_then(|note| {
+            let note = note.0 as i16 + transpose as i16 + note_offset(self.channel);
+            u8::try_from(note).ok().filter(|note| *note <= 0x7f)
+        });
+
+        if let Some(note) = note {
+            self.stop(start);
+            self.start(start, note);
+        }
+
+        match cmd {
+            Some(Command::Kill) => {
+                let kill_at = start + val as u32 * TICKS_PER_LSDJ_TICK;
+                if let Some((_, kill)) = &mut self.playing {
+                    *kill = Some(kill_at);
+                }
+            }
+            // Retrigger the note every `y` ticks until the end of the row.
+            Some(Command::Retrigger) if val & 0x0f > 0 => {
+                let rate = (val & 0x0f) as u32 * TICKS_PER_LSDJ_TICK;
+                let mut retrigger = start + rate;
+                while retrigger < time + TICKS_PER_ROW {
+                    match self.playing {
+                        Some((note, kill)) if !matches!(kill, Some(k) if k <= retrigger) => {
+                            self.stop(retrigger);
+                            self.start(retrigger, note);
+                            self.playing = Some((note, kill));
+                        }
+                        _ => break,
+                    }
+                    retrigger += rate;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    fn start(&mut self, time: u32, note: u8) {
+        self.track.note_on(time, self.channel as u8, note, VELOCITY);
+        self.playing = Some((note, None));
+    }
+
+    /// Stops the playing note at `time`, or earlier if it was killed.
+    fn stop(&mut self, time: u32) {
+        if let Some((note, kill)) = self.playing.take() {
+            let time = kill.map_or(time, |kill| kill.min(time));
+            self.track.note_off(time, self.channel as u8, note);
+        }
+    }
+
+    /// Stops the playing note and returns the finished track.
+    fn finish(mut self, time: u32) -> Track {
+        self.stop(time);
+        self.track
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    /// Returns the note on and note off events in a track chunk, ignoring meta events.
+    fn note_events(chunk: &[u8]) -> Vec<(u32, u8, u8)> {
+        let mut events = vec![];
+        let (mut idx, mut time) = (8, 0);
+        while idx < chunk.len() {
+            let mut delta = 0;
+            loop {
+                delta = delta << 7 | (chunk[idx] & 0x7f) as u32;
+                idx += 1;
+                if chunk[idx - 1] & 0x80 == 0 {
+                    break;
+                }
+            }
+            time += delta;
+            match chunk[idx] & 0xf0 {
+                smf::NOTE_ON | smf::NOTE_OFF => {
+                    events.push((time, chunk[idx] & 0xf0, chunk[idx + 1]));
+                    idx += 3;
+                }
+                smf::PROGRAM_CHANGE => idx += 2,
+                _ => idx += 3 + chunk[idx + 2] as usize,
+            }
+        }
+        events
+    }
+
+    #[test]
+    /// Export a transposed chain with a killed note and check the note timing.
+    fn export_kill_and_transpose() {
+        let mut channels = Channels::default();
+        let mut chains = Chains::default();
+        let mut phrases = Phrases::default();
+
+        channels.get_mut(0).set_chain(0, 0);
+        let chain = chains.get_mut(0).unwrap();
+        chain.set_phrase(0, 0);
+        chain.set_transpose(0, 0x0c);
+
+        let phrase = phrases.get_mut(0).unwrap();
+        phrase.set_note(0, 1);
+        phrase.set_note(4, 2);
+        phrase.set_cmd(4, Command::Kill);
+        phrase.set_cmd_val(4, 3);
+
+        let mut writer = ChannelWriter::new(0);
+        let mut conductor = Track::default();
+        for row in 0..0x10 {
+            writer.write_row(
+                row as u32 * TICKS_PER_ROW,
+                phrases.get(0).unwrap(),
+                row,
+                0x0c,
+                &mut conductor,
+            );
+        }
+        let chunk = writer.finish(0x10 * TICKS_PER_ROW).to_chunk();
+
+        let c4 = (1 + 12 + PULSE_NOTE_OFFSET) as u8;
+        let row = TICKS_PER_ROW;
+        assert_eq!(
+            vec![
+                (0, smf::NOTE_ON, c4),
+                (4 * row, smf::NOTE_OFF, c4),
+                (4 * row, smf::NOTE_ON, c4 + 1),
+                (4 * row + 3 * TICKS_PER_LSDJ_TICK, smf::NOTE_OFF, c4 + 1),
+            ],
+            note_events(&chunk)
+        );
+
+        // The whole song exports as a conductor track and four channel tracks.
+        let smf = export_song(&channels, &chains, &phrases, &Project::default());
+        assert_eq!(&smf[10..12], &5u16.to_be_bytes());
+    }
+}
@@ -0,0 +1,525 @@
+use super::smf::{self, Event};
+use super::*;
+use crate::formats::{allocate, CHAIN_LIMIT, PHRASE_LIMIT, ROWS_PER_PHRASE, SONG_ROW_LIMIT};
+use crate::resources::types::note::LARGEST_NOTE;
+use std::fmt;
+
+/// The rows a track is quantized to at most. Longer tracks can't fit on the Song screen
+/// even with the longest chains, so one chain more is kept for `arrange` to report the cut.
+const ROW_LIMIT: usize = (SONG_ROW_LIMIT + 1) * 0x10 * ROWS_PER_PHRASE;
+/// The number of instruments a program change can select, 0x00 to 0x3f.
+const PROGRAM_LIMIT: u8 = 0x40;
+
+/// Settings for how a MIDI file is quantized into phrases.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ImportOptions {
+    /// The number of phrase rows per quarter note.
+    pub rows_per_quarter: u32,
+    /// The number of phrases in each chain, up to 16.
+    pub phrases_per_chain: usize,
+    /// The tracks to import into pu1, pu2, wav and noi.
+    /// If None, the first 4 tracks with notes are used.
+    pub tracks: Option<Vec<usize>>,
+}
+
+impl Default for ImportOptions {
+    fn default() -> Self {
+        ImportOptions {
+            rows_per_quarter: ROWS_PER_QUARTER,
+            phrases_per_chain: 4,
+            tracks: None,
+        }
+    }
+}
+
+/// What a channel ran out of when it stopped being imported part way through.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DropReason {
+    Phrases,
+    Chains,
+    SongRows,
+}
+
+/// Everything that was left out of an imported song.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Tracks with notes that were not assigned to a channel.
+    pub unused_tracks: Vec<usize>,
+    /// Notes dropped because another note started on the same row.
+    pub overlapping_notes: usize,
+    /// Notes dropped because they are outside of LSDj's range for their channel.
+    pub out_of_range_notes: usize,
+    /// Channels that were cut short, the song row they were cut at, and why.
+    pub dropped: Vec<(usize, usize, DropReason)>,
+}
+
+impl ImportReport {
+    /// Returns true if nothing was left out.
+    pub fn is_complete(&self) -> bool {
+        *self == ImportReport::default()
+    }
+}
+
+impl fmt::Display for ImportReport {
+    /// Lists everything that was left out, one item per line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if !self.unused_tracks.is_empty() {
+            writeln!(f, "Tracks not imported: {:?}", self.unused_tracks)?;
+        }
+        if self.overlapping_notes > 0 {
+            writeln!(
+                f,
+                "{} notes dropped as they overlap other notes",
+                self.overlapping_notes
+            )?;
+        }
+        if self.out_of_range_notes > 0 {
+            writeln!(
+                f,
+                "{} notes dropped as they are out of range",
+                self.out_of_range_notes
+            )?;
+        }
+        for (channel, row, reason) in &self.dropped {
+            let reason = match reason {
+                DropReason::Phrases => "all 0xff phrases are in use",
+                DropReason::Chains => "all 0x80 chains are in use",
+                DropReason::SongRows => "the song is out of rows",
+            };
+            writeln!(
+                f,
+                "{} dropped from song row {:02x} onwards as {}",
+                CHANNEL_NAMES[*channel], row, reason
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Imports a MIDI file, replacing the song.
+///
+/// Each track is quantized to phrase rows, keeping the first note to start on a row.
+/// Notes that end before the next note are ended with a `k00` command, and program
+/// changes set the instrument. Identical phrases and chains share a slot.
+///
+/// # ERRORS
+///
+/// Errors if the file could not be read or `options.tracks` names a track that doesn't exist.
+pub fn import_song(
+    data: &[u8],
+    options: &ImportOptions,
+    channels: &mut Channels,
+    chains: &mut Chains,
+    phrases: &mut Phrases,
+    project: &mut Project,
+) -> Result<ImportReport, MidiError> {
+    let smf = smf::read_smf(data)?;
+    let mut report = ImportReport::default();
+
+    let with_notes = (0..smf.tracks.len())
+        .filter(|idx| {
+            smf.tracks[*idx]
+                .iter()
+                .any(|(_, event)| matches!(event, Event::NoteOn { .. }))
+        })
+        .collect::<Vec<_>>();
+    let tracks = match &options.tracks {
+        Some(tracks) => {
+            if let Some(track) = tracks.iter().find(|t| **t >= smf.tracks.len()) {
+                return Err(MidiError::InvalidTrack(*track, smf.tracks.len()));
+            }
+            tracks.iter().take(4).cloned().collect::<Vec<_>>()
+        }
+        None => with_notes.iter().take(4).cloned().collect(),
+    };
+    report.unused_tracks = with_notes
+        .into_iter()
+        .filter(|idx| !tracks.contains(idx))
+        .collect();
+
+    // LSDj's tempo is always 4 rows per beat, so scale it to keep the rows at the same speed.
+    let tempo = smf
+        .tracks
+        .iter()
+        .flatten()
+        .find_map(|(_, event)| match event {
+            Event::Tempo(micros) => Some(*micros),
+            _ => None,
+        });
+    let bpm = 60_000_000. / tempo.unwrap_or(500_000) as f64;
+    project.set_tempo(
+        (bpm * options.rows_per_quarter as f64 / ROWS_PER_QUARTER as f64).round() as u16,
+    );
+
+    // Split each channel into chains of phrases.
+    let ticks_per_row = smf.ticks_per_quarter as f64 / options.rows_per_quarter.max(1) as f64;
+    let phrases_per_chain = options.phrases_per_chain.clamp(1, 0x10);
+    let channel_chains = tracks
+        .iter()
+        .enumerate()
+        .map(|(channel, track)| {
+            let rows = quantize(&smf.tracks[*track], channel, ticks_per_row, &mut report);
+            rows.chunks(ROWS_PER_PHRASE * phrases_per_chain)
+                .map(|chain| {
+                    chain
+                        .chunks(ROWS_PER_PHRASE)
+                        .map(to_phrase)
+                        .collect::<Vec<_>>()
+                })
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    report.dropped = arrange(&channel_chains, channels, chains, phrases);
+
+    Ok(report)
+}
+
+/// Replaces the song with chains of phrases for each channel, one chain per song row.
+///
+/// Slots are allocated a song row at a time, so that when LSDj runs out of phrases,
+/// chains or song rows all channels are cut at the same point. Identical phrases and
+/// chains share a slot. Returns the channels that were cut short, where, and why.
+pub fn arrange(
+    channel_chains: &[Vec<Vec<phrase::Phrase>>],
+    channels: &mut Channels,
+    chains: &mut Chains,
+    phrases: &mut Phrases,
+) -> Vec<(usize, usize, DropReason)> {
+    clear_song(channels, chains, phrases);
+
+    let mut phrase_list: Vec<phrase::Phrase> = vec![];
+    let mut chain_list: Vec<Vec<u8>> = vec![];
+    let mut dropped = vec![];
+    let mut stopped = [false; 4];
+    let song_rows = channel_chains.iter().map(Vec::len).max().unwrap_or(0);
+    for row in 0..song_rows {
+        for (channel, channel_chains) in channel_chains.iter().enumerate() {
+            let chain = match channel_chains.get(row) {
+                Some(chain) if !stopped[channel] => chain,
+                _ => continue,
+            };
+
+            let reason = if row >= SONG_ROW_LIMIT {
+                Some(DropReason::SongRows)
+            } else {
+                let mut new_phrases = vec![];
+                for phrase in chain {
+                    if !phrase_list.contains(phrase) && !new_phrases.contains(&phrase) {
+                        new_phrases.push(phrase);
+                    }
+                }
+                let existing_chain = new_phrases.is_empty()
+                    && chain_list.iter().any(|ids| {
+                        ids.len() == chain.len()
+                            && ids
+                                .iter()
+                                .zip(chain)
+                                .all(|(id, phrase)| phrase_list[*id as usize] == *phrase)
+                    });
+
+                if phrase_list.len() + new_phrases.len() > PHRASE_LIMIT {
+                    Some(DropReason::Phrases)
+                } else if !existing_chain && chain_list.len() >= CHAIN_LIMIT {
+                    Some(DropReason::Chains)
+                } else {
+                    None
+                }
+            };
+            if let Some(reason) = reason {
+                stopped[channel] = true;
+                dropped.push((channel, row, reason));
+                continue;
+            }
+
+            let ids = chain
+                .iter()
+                .map(|phrase| allocate(&mut phrase_list, phrase) as u8)
+                .collect::<Vec<_>>();
+            let chain = allocate(&mut chain_list, &ids);
+            channels.get_mut(channel).set_chain(row as u8, chain as u8);
+        }
+    }
+
+    for (idx, phrase) in phrase_list.iter().enumerate() {
+        copy_phrase(phrase, phrases.get_mut(idx).unwrap());
+    }
+    for (idx, ids) in chain_list.into_iter().enumerate() {
+        let chain = chains.get_mut(idx).unwrap();
+        for (step, phrase) in ids.into_iter().enumerate() {
+            chain.set_phrase(step, phrase);
+        }
+    }
+
+    dropped
+}
+
+/// Empties every song row, chain and phrase through their setters.
+fn clear_song(channels: &mut Channels, chains: &mut Chains, phrases: &mut Phrases) {
+    for channel in 0..4 {
+        let channel = channels.get_mut(channel);
+        for row in 0..SONG_ROW_LIMIT {
+            channel.clear_chain(row as u8);
+            channel.clear_bookmark(row as u8);
+        }
+    }
+    for idx in 0..CHAIN_LIMIT {
+        if let Some(chain) = chains.get_mut(idx) {
+            for step in 0..0x10 {
+                chain.clear_phrase(step);
+                chain.set_transpose(step, 0);
+            }
+        }
+    }
+    for idx in 0..PHRASE_LIMIT {
+        if let Some(phrase) = phrases.get_mut(idx) {
+            for row in 0..ROWS_PER_PHRASE {
+                phrase.clear_note(row);
+                phrase.clear_instr(row);
+                phrase.clear_cmd(row);
+                phrase.set_cmd_val(row, 0);
+            }
+        }
+    }
+}
+
+/// Copies every row of a phrase into another through its setters.
+fn copy_phrase(from: &phrase::Phrase, to: &mut phrase::Phrase) {
+    for row in 0..ROWS_PER_PHRASE {
+        match from.get_note(row).and_then(|note| note.get()) {
+            Some(note) => to.set_note(row, note),
+            None => to.clear_note(row),
+        };
+        match from.get_instr(row) {
+            Some(instr) => to.set_instr(row, instr),
+            None => to.clear_instr(row),
+        };
+        to.set_cmd(row, from.get_cmd(row).unwrap_or_default());
+        to.set_cmd_val(row, from.get_cmd_val(row).unwrap_or(0));
+    }
+}
+
+/// A single quantized phrase row.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Row {
+    note: Option<u8>,
+    instr: Option<u8>,
+    kill: bool,
+}
+
+/// Quantizes a track to rows, padded to a whole number of phrases.
+fn quantize(
+    events: &[(u32, Event)],
+    channel: usize,
+    ticks_per_row: f64,
+    report: &mut ImportReport,
+) -> Vec<Row> {
+    let to_row = |time: u32| (time as f64 / ticks_per_row).round() as usize;
+    let mut rows: Vec<Row> = vec![];
+    let mut program = None;
+    // The MIDI note playing and the row it started on.
+    let mut sounding: Option<(u8, usize)> = None;
+
+    for (time, event) in events {
+        let row = to_row(*time);
+        if row + 2 > ROW_LIMIT {
+            // Keep the track as long as the limit, so `arrange` reports where it was cut.
+            rows.resize(ROW_LIMIT, Row::default());
+            return rows;
+        }
+        if rows.len() <= row + 1 {
+            rows.resize(row + 2, Row::default());
+        }
+
+        match *event {
+            Event::NoteOn { note, .. } => {
+                let lsdj_note = note as i16 - note_offset(channel);
+                if !(1..=LARGEST_NOTE as i16).contains(&lsdj_note) {
+                    report.out_of_range_notes += 1;
+                } else if rows[row].note.is_some() {
+                    report.overlapping_notes += 1;
+                } else {
+                    rows[row] = Row {
+                        note: Some(lsdj_note as u8),
+                        instr: program,
+                        kill: false,
+                    };
+                    sounding = Some((note, row));
+                }
+            }
+            Event::NoteOff { note, .. } => {
+                if let Some((playing, start)) = sounding {
+                    if playing == note {
+                        let row = row.max(start + 1);
+                        if rows[row].note.is_none() {
+                            rows[row].kill = true;
+                        }
+                        sounding = None;
+                    }
+                }
+            }
+            Event::ProgramChange { program: p, .. } => program = Some(p % PROGRAM_LIMIT),
+            Event::Tempo(_) => (),
+        }
+    }
+
+    // Drop the empty rows left after the last note or kill.
+    while rows.last() == Some(&Row::default()) {
+        rows.pop();
+    }
+
+    let padding = (ROWS_PER_PHRASE - rows.len() % ROWS_PER_PHRASE) % ROWS_PER_PHRASE;
+    rows.resize(rows.len() + padding, Row::default());
+    rows
+}
+
+/// Converts a phrase's worth of rows into a phrase.
+fn to_phrase(rows: &[Row]) -> phrase::Phrase {
+    let mut phrase = phrase::Phrase::default();
+    for (idx, row) in rows.iter().enumerate() {
+        if let Some(note) = row.note {
+            phrase.set_note(idx, note);
+        }
+        if let Some(instr) = row.instr {
+            phrase.set_instr(idx, instr);
+        }
+        if row.kill {
+            phrase.set_cmd(idx, Command::Kill);
+            phrase.set_cmd_val(idx, 0);
+        }
+    }
+    phrase
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    /// Export a song and import it again, which should give back the same phrases.
+    fn export_round_trip() {
+        let mut channels = Channels::default();
+        let mut chains = Chains::default();
+        let mut phrases = Phrases::default();
+        let project = Project::default();
+
+        let phrase = phrases.get_mut(0).unwrap();
+        phrase.set_note(0, 13);
+        phrase.set_instr(0, 2);
+        phrase.set_note(4, 15);
+        phrase.set_instr(4, 2);
+        phrase.set_cmd(6, Command::Kill);
+        chains.get_mut(0).unwrap().set_phrase(0, 0);
+        channels.get_mut(0).set_chain(0, 0);
+        channels.get_mut(0).set_chain(1, 0);
+
+        let data = export_song(&channels, &chains, &phrases, &project);
+        let options = ImportOptions {
+            phrases_per_chain: 1,
+            ..Default::default()
+        };
+        // Importing replaces whatever was in the song before.
+        let (mut new_channels, mut new_chains, mut new_phrases, mut new_project): (
+            Channels,
+            Chains,
+            Phrases,
+            Project,
+        ) = Default::default();
+        new_channels.get_mut(1).set_chain(2, 5);
+        new_chains.get_mut(5).unwrap().set_transpose(3, 0x0c);
+        new_phrases.get_mut(0).unwrap().set_cmd(9, Command::Kill);
+        let report = import_song(
+            &data,
+            &options,
+            &mut new_channels,
+            &mut new_chains,
+            &mut new_phrases,
+            &mut new_project,
+        )
+        .unwrap();
+
+        assert!(report.is_complete());
+        assert_eq!(project.get_tempo(), new_project.get_tempo());
+        assert_eq!(phrases.get(0), new_phrases.get(0));
+        // Both song rows play the same notes, so they share the same chain.
+        assert_eq!(Some(0), new_channels.get(0).get_chain(0));
+        assert_eq!(Some(0), new_channels.get(0).get_chain(1));
+        assert_eq!(None, new_channels.get(0).get_chain(2));
+        assert_eq!(None, new_channels.get(1).get_chain(2));
+        assert_eq!(Some(0), new_chains.get(5).unwrap().get_transpose(3));
+    }
+
+    #[test]
+    /// Songs with more unique phrases than LSDj has slots are cut short and reported.
+    fn too_many_phrases() {
+        let mut track = smf::Track::default();
+        for idx in 0..0x100u32 {
+            let time = idx * 16 * 24;
+            let note = 48 + (idx % 64) as u8;
+            // Give every phrase a different pair of notes.
+            track.note_on(time, 0, note, 100);
+            track.note_off(time + 24, 0, note);
+            track.note_on(time + 48 + (idx / 64) * 24, 0, note, 100);
+            track.note_off(time + 72 + (idx / 64) * 24, 0, note);
+        }
+        let data = smf::write_smf(&[track], 96);
+
+        let (mut channels, mut chains, mut phrases, mut project) = Default::default();
+        let report = import_song(
+            &data,
+            &ImportOptions::default(),
+            &mut channels,
+            &mut chains,
+            &mut phrases,
+            &mut project,
+        )
+        .unwrap();
+
+        assert_eq!(vec![(0, 0x3f, DropReason::Phrases)], report.dropped);
+    }
+
+    #[test]
+    /// Files with no ticks per quarter note or events too late to time are rejected,
+    /// and notes far past the end of the Song screen are cut without filling memory.
+    fn reject_broken_timing() {
+        let (mut channels, mut chains, mut phrases, mut project) = Default::default();
+        let mut import = |data: &[u8]| {
+            import_song(
+                data,
+                &ImportOptions::default(),
+                &mut channels,
+                &mut chains,
+                &mut phrases,
+                &mut project,
+            )
+        };
+
+        let mut track = smf::Track::default();
+        track.note_on(0, 0, 60, 100);
+        let data = smf::write_smf(&[track], 0);
+        assert!(matches!(import(&data), Err(MidiError::ZeroDivision)));
+
+        // 17 notes, each the longest gap a file can hold after the last.
+        let mut events = vec![];
+        for _ in 0..17 {
+            smf::write_vlq(&mut events, 0x0fff_ffff);
+            events.extend([smf::NOTE_ON, 60, 100]);
+        }
+        let mut data = smf::write_smf(&[], 96);
+        data[10..12].copy_from_slice(&1u16.to_be_bytes());
+        data.extend(smf::TRACK_ID);
+        data.extend((events.len() as u32).to_be_bytes());
+        data.extend(events);
+        assert!(matches!(import(&data), Err(MidiError::TimeOverflow)));
+
+        let mut track = smf::Track::default();
+        track.note_on(0, 0, 60, 100);
+        track.note_on(0x0fff_ffff, 0, 62, 100);
+        let data = smf::write_smf(&[track], 1);
+        let report = import(&data).unwrap();
+        assert_eq!(
+            vec![(0, SONG_ROW_LIMIT, DropReason::SongRows)],
+            report.dropped
+        );
+    }
+}
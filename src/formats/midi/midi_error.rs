@@ -0,0 +1,20 @@
+use std::io;
+
+/// An error that occurs while reading or writing a MIDI file.
+#[derive(thiserror::Error, Debug)]
+pub enum MidiError {
+    #[error("Could not access `{0}`: {1}")]
+    Io(String, io::Error),
+    #[error("The file is not a Standard MIDI File.")]
+    NotMidi,
+    #[error("The MIDI file ends part way through a chunk or event.")]
+    Truncated,
+    #[error("MIDI files timed in SMPTE frames are not supported.")]
+    SmpteTiming,
+    #[error("The MIDI file has 0 ticks per quarter note.")]
+    ZeroDivision,
+    #[error("The MIDI file has events too late to be timed.")]
+    TimeOverflow,
+    #[error("The MIDI file has no track `{0}`: It only has `{1}` tracks.")]
+    InvalidTrack(usize, usize),
+}
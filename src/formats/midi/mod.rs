@@ -0,0 +1,67 @@
+use crate::resources::types::*;
+use std::fs;
+
+pub mod export;
+pub mod import;
+pub mod midi_error;
+pub mod smf;
+
+pub use export::export_song;
+pub use import::{import_song, ImportOptions, ImportReport};
+pub use midi_error::*;
+
+/// MIDI ticks per quarter note in exported files.
+pub const TICKS_PER_QUARTER: u16 = 96;
+/// LSDj ticks per phrase row with the default groove.
+pub const LSDJ_TICKS_PER_ROW: u32 = 6;
+/// Phrase rows per quarter note, which is what LSDj's tempo is measured in.
+pub const ROWS_PER_QUARTER: u32 = 4;
+/// MIDI ticks per LSDj tick.
+pub const TICKS_PER_LSDJ_TICK: u32 =
+    TICKS_PER_QUARTER as u32 / ROWS_PER_QUARTER / LSDJ_TICKS_PER_ROW;
+/// MIDI ticks per phrase row.
+pub const TICKS_PER_ROW: u32 = TICKS_PER_LSDJ_TICK * LSDJ_TICKS_PER_ROW;
+
+/// The MIDI note of LSDj's C3 in the pulse and noise channels.
+/// LSDj's C3 is `Note(1)`.
+pub const PULSE_NOTE_OFFSET: i16 = 48 - 1;
+/// The MIDI note of `Note(1)` in the wave channel, which plays an octave lower.
+pub const WAVE_NOTE_OFFSET: i16 = PULSE_NOTE_OFFSET - 12;
+/// The index of the wave channel in `Channels`.
+pub const WAVE_CHANNEL: usize = 2;
+/// The name of each channel, which is used as the track name.
+pub const CHANNEL_NAMES: [&str; 4] = ["pu1", "pu2", "wav", "noi"];
+
+/// Returns the offset between a `Note` and a MIDI note in a channel.
+pub fn note_offset(channel: usize) -> i16 {
+    if channel == WAVE_CHANNEL {
+        WAVE_NOTE_OFFSET
+    } else {
+        PULSE_NOTE_OFFSET
+    }
+}
+
+/// Exports the song and writes it to a `.mid` file.
+pub fn save_song(
+    filename: &str,
+    channels: &Channels,
+    chains: &Chains,
+    phrases: &Phrases,
+    project: &Project,
+) -> Result<(), MidiError> {
+    let data = export_song(channels, chains, phrases, project);
+    fs::write(filename, data).map_err(|e| MidiError::Io(filename.to_string(), e))
+}
+
+/// Reads a `.mid` file and imports it, replacing the song.
+pub fn load_song(
+    filename: &str,
+    options: &ImportOptions,
+    channels: &mut Channels,
+    chains: &mut Chains,
+    phrases: &mut Phrases,
+    project: &mut Project,
+) -> Result<ImportReport, MidiError> {
+    let data = fs::read(filename).map_err(|e| MidiError::Io(filename.to_string(), e))?;
+    import_song(&data, options, channels, chains, phrases, project)
+}
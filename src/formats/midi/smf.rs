@@ -0,0 +1,306 @@
+//! Reading and writing the chunks of a Standard MIDI File.
+
+use super::MidiError;
+
+/// The header chunk's ID.
+pub const HEADER_ID: &[u8; 4] = b"MThd";
+/// A track chunk's ID.
+pub const TRACK_ID: &[u8; 4] = b"MTrk";
+/// A file with multiple tracks played at the same time.
+pub const FORMAT_MULTI_TRACK: u16 = 1;
+
+pub const NOTE_OFF: u8 = 0x80;
+pub const NOTE_ON: u8 = 0x90;
+pub const PROGRAM_CHANGE: u8 = 0xc0;
+pub const META: u8 = 0xff;
+pub const META_TRACK_NAME: u8 = 0x03;
+pub const META_END_OF_TRACK: u8 = 0x2f;
+pub const META_TEMPO: u8 = 0x51;
+
+/// A single track, built up from events at absolute times.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Track {
+    /// Each event's absolute time in ticks and its bytes, excluding the delta time.
+    events: Vec<(u32, Vec<u8>)>,
+}
+
+impl Track {
+    /// Adds an event at an absolute time.
+    /// Events at the same time are written in the order they were added.
+    pub fn push(&mut self, time: u32, bytes: Vec<u8>) {
+        self.events.push((time, bytes));
+    }
+
+    pub fn note_on(&mut self, time: u32, channel: u8, note: u8, velocity: u8) {
+        self.push(time, vec![NOTE_ON | channel, note, velocity]);
+    }
+
+    pub fn note_off(&mut self, time: u32, channel: u8, note: u8) {
+        self.push(time, vec![NOTE_OFF | channel, note, 0]);
+    }
+
+    pub fn program_change(&mut self, time: u32, channel: u8, program: u8) {
+        self.push(time, vec![PROGRAM_CHANGE | channel, program & 0x7f]);
+    }
+
+    /// Adds a meta event, such as a tempo change or the track's name.
+    pub fn meta(&mut self, time: u32, kind: u8, data: &[u8]) {
+        let mut bytes = vec![META, kind];
+        write_vlq(&mut bytes, data.len() as u32);
+        bytes.extend_from_slice(data);
+        self.push(time, bytes);
+    }
+
+    /// Adds a tempo change in beats per minute.
+    pub fn tempo(&mut self, time: u32, bpm: u16) {
+        let micros = 60_000_000 / bpm.max(1) as u32;
+        self.meta(time, META_TEMPO, &micros.to_be_bytes()[1..]);
+    }
+
+    /// Returns the track chunk, sorted by time and ended with an end of track event.
+    pub fn to_chunk(&self) -> Vec<u8> {
+        let mut events = self.events.clone();
+        // A stable sort keeps events at the same time in the order they were added.
+        events.sort_by_key(|(time, _)| *time);
+        let end = events.last().map(|(time, _)| *time).unwrap_or(0);
+
+        let mut data = vec![];
+        let mut last = 0;
+        for (time, bytes) in events {
+            write_vlq(&mut data, time - last);
+            data.extend(bytes);
+            last = time;
+        }
+        write_vlq(&mut data, end - last);
+        data.extend([META, META_END_OF_TRACK, 0]);
+
+        let mut chunk = TRACK_ID.to_vec();
+        chunk.extend((data.len() as u32).to_be_bytes());
+        chunk.extend(data);
+        chunk
+    }
+}
+
+/// Writes a type 1 Standard MIDI File.
+pub fn write_smf(tracks: &[Track], ticks_per_quarter: u16) -> Vec<u8> {
+    let mut data = HEADER_ID.to_vec();
+    data.extend(6u32.to_be_bytes());
+    data.extend(FORMAT_MULTI_TRACK.to_be_bytes());
+    data.extend((tracks.len() as u16).to_be_bytes());
+    data.extend(ticks_per_quarter.to_be_bytes());
+
+    for track in tracks {
+        data.extend(track.to_chunk());
+    }
+    data
+}
+
+/// Writes a variable length quantity, 7 bits at a time with the most significant first.
+pub fn write_vlq(data: &mut Vec<u8>, mut value: u32) {
+    let mut bytes = vec![(value & 0x7f) as u8];
+    value >>= 7;
+    while value > 0 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    data.extend(bytes.iter().rev());
+}
+
+/// An event read from a track.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Event {
+    NoteOn {
+        channel: u8,
+        note: u8,
+        velocity: u8,
+    },
+    NoteOff {
+        channel: u8,
+        note: u8,
+    },
+    ProgramChange {
+        channel: u8,
+        program: u8,
+    },
+    /// Microseconds per quarter note.
+    Tempo(u32),
+}
+
+/// A Standard MIDI File, with each track's events at absolute times.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Smf {
+    pub ticks_per_quarter: u16,
+    pub tracks: Vec<Vec<(u32, Event)>>,
+}
+
+/// Reads a Standard MIDI File of any type.
+/// Events BSDj has no use for are skipped.
+///
+/// # ERRORS
+///
+/// Errors if the file is not a Standard MIDI File, is cut short, uses SMPTE timing,
+/// has 0 ticks per quarter note, or has events past the largest time it can hold.
+pub fn read_smf(data: &[u8]) -> Result<Smf, MidiError> {
+    let mut reader = Reader { data, pos: 0 };
+    if reader.take(4)? != HEADER_ID {
+        return Err(MidiError::NotMidi);
+    }
+    let header_length = reader.u32()? as usize;
+    let header = reader.take(header_length)?;
+    if header_length < 6 {
+        return Err(MidiError::NotMidi);
+    }
+
+    let track_count = u16::from_be_bytes([header[2], header[3]]);
+    let ticks_per_quarter = u16::from_be_bytes([header[4], header[5]]);
+    if ticks_per_quarter & 0x8000 != 0 {
+        return Err(MidiError::SmpteTiming);
+    }
+    if ticks_per_quarter == 0 {
+        return Err(MidiError::ZeroDivision);
+    }
+
+    let mut tracks = vec![];
+    while tracks.len() < track_count as usize && reader.pos < data.len() {
+        let id = reader.take(4)?;
+        let length = reader.u32()? as usize;
+        let chunk = reader.take(length)?;
+        // Unknown chunks must be skipped.
+        if id == TRACK_ID {
+            tracks.push(read_track(chunk)?);
+        }
+    }
+
+    Ok(Smf {
+        ticks_per_quarter,
+        tracks,
+    })
+}
+
+/// Reads the events of a track chunk, excluding its ID and length.
+fn read_track(data: &[u8]) -> Result<Vec<(u32, Event)>, MidiError> {
+    let mut reader = Reader { data, pos: 0 };
+    let mut events = vec![];
+    let mut time: u32 = 0;
+    let mut running_status = None;
+
+    while reader.pos < data.len() {
+        time = time
+            .checked_add(reader.vlq()?)
+            .ok_or(MidiError::TimeOverflow)?;
+
+        let mut status = reader.u8()?;
+        if status < 0x80 {
+            // Running status reuses the last status byte.
+            reader.pos -= 1;
+            status = running_status.ok_or(MidiError::Truncated)?;
+        }
+
+        match status {
+            META => {
+                let kind = reader.u8()?;
+                let length = reader.vlq()? as usize;
+                let data = reader.take(length)?;
+                match kind {
+                    META_END_OF_TRACK => break,
+                    META_TEMPO if length == 3 => {
+                        let tempo = u32::from_be_bytes([0, data[0], data[1], data[2]]);
+                        events.push((time, Event::Tempo(tempo)));
+                    }
+                    _ => (),
+                }
+            }
+            // System exclusive messages.
+            0xf0 | 0xf7 => {
+                let length = reader.vlq()? as usize;
+                reader.take(length)?;
+            }
+            _ => {
+                running_status = Some(status);
+                let channel = status & 0x0f;
+                let first = reader.u8()?;
+                match status & 0xf0 {
+                    NOTE_ON => {
+                        let velocity = reader.u8()?;
+                        events.push((
+                            time,
+                            match velocity {
+                                0 => Event::NoteOff {
+                                    channel,
+                                    note: first,
+                                },
+                                _ => Event::NoteOn {
+                                    channel,
+                                    note: first,
+                                    velocity,
+                                },
+                            },
+                        ));
+                    }
+                    NOTE_OFF => {
+                        reader.u8()?;
+                        events.push((
+                            time,
+                            Event::NoteOff {
+                                channel,
+                                note: first,
+                            },
+                        ));
+                    }
+                    PROGRAM_CHANGE => events.push((
+                        time,
+                        Event::ProgramChange {
+                            channel,
+                            program: first,
+                        },
+                    )),
+                    // Channel pressure only has a single data byte.
+                    0xd0 => (),
+                    _ => {
+                        reader.u8()?;
+                    }
+                }
+            }
+        }
+    }
+    Ok(events)
+}
+
+/// Reads big-endian values from a slice, erroring if it runs out.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], MidiError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + length)
+            .ok_or(MidiError::Truncated)?;
+        self.pos += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, MidiError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> Result<u32, MidiError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_be_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn vlq(&mut self) -> Result<u32, MidiError> {
+        let mut value = 0;
+        // A variable length quantity is at most 4 bytes long.
+        for _ in 0..4 {
+            let byte = self.u8()?;
+            value = value << 7 | (byte & 0x7f) as u32;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+        }
+        Err(MidiError::Truncated)
+    }
+}
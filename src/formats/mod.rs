@@ -0,0 +1,190 @@
+//! Converting songs to and from the formats of other programs.
+
+use crate::cli::CliArgs;
+use crate::resources::types::*;
+use bevy::prelude::*;
+
+pub mod midi;
+pub mod text;
+pub mod uge;
+pub mod vgm;
+
+// region:      Import limits
+
+/// The number of rows in a phrase.
+pub const ROWS_PER_PHRASE: usize = 0x10;
+/// The number of phrases available to imported songs, 0x00 to 0xfe.
+pub const PHRASE_LIMIT: usize = 0xff;
+/// The number of chains available to imported songs, 0x00 to 0x7f.
+pub const CHAIN_LIMIT: usize = 0x80;
+/// The number of rows on the Song screen.
+pub const SONG_ROW_LIMIT: usize = 0x100;
+
+/// Returns the index of an item in a list, adding it to the end if it isn't there yet.
+/// Imports use it to share a phrase or chain slot between identical ones.
+pub fn allocate<T: PartialEq + Clone>(list: &mut Vec<T>, item: &T) -> usize {
+    list.iter().position(|i| i == item).unwrap_or_else(|| {
+        list.push(item.clone());
+        list.len() - 1
+    })
+}
+
+// endregion:   Import limits
+
+// region:      FormatsPlugin
+
+/// Imports the song passed on the command line, if any.
+pub struct FormatsPlugin;
+
+impl Plugin for FormatsPlugin {
+    fn build(&self, app: &mut App) {
+        let cli = app
+            .world
+            .get_resource::<CliArgs>()
+            .cloned()
+            .unwrap_or_default();
+
+        if let Some(filename) = &cli.midi {
+            import_midi(filename, &cli.midi_options, &mut app.world);
+        }
+        if let Some(filename) = &cli.uge {
+            import_uge(filename, &mut app.world);
+        }
+        if let Some(filename) = &cli.vgm {
+            import_vgm(filename, &mut app.world);
+        }
+        if let Some(filename) = &cli.song {
+            load_text(filename, &mut app.world);
+        }
+    }
+}
+
+/// Imports a MIDI file into the song, logging anything that was left out.
+fn import_midi(filename: &str, options: &midi::ImportOptions, world: &mut World) {
+    let mut channels = Channels::default();
+    let mut chains = Chains::default();
+    let mut phrases = Phrases::default();
+    let mut project = world
+        .get_resource::<Project>()
+        .expect("FormatsPlugin must be added after the ResourcePlugin.")
+        .clone();
+
+    match midi::load_song(
+        filename,
+        options,
+        &mut channels,
+        &mut chains,
+        &mut phrases,
+        &mut project,
+    ) {
+        Ok(report) => {
+            for line in report.to_string().lines() {
+                warn!("{}", line);
+            }
+            world.insert_resource(channels);
+            world.insert_resource(chains);
+            world.insert_resource(phrases);
+            world.insert_resource(project);
+            info!("Imported MIDI file `{}`", filename);
+        }
+        Err(e) => error!("Could not import MIDI file `{}`: {}", filename, e),
+    }
+}
+
+/// Imports a hUGETracker song, logging anything that was not converted exactly.
+fn import_uge(filename: &str, world: &mut World) {
+    let mut channels = Channels::default();
+    let mut chains = Chains::default();
+    let mut phrases = Phrases::default();
+    let mut instruments = Instruments::default();
+    let mut project = world
+        .get_resource::<Project>()
+        .expect("FormatsPlugin must be added after the ResourcePlugin.")
+        .clone();
+
+    match uge::load_song(
+        filename,
+        &mut channels,
+        &mut chains,
+        &mut phrases,
+        &mut instruments,
+        &mut project,
+    ) {
+        Ok(report) => {
+            for line in report.to_string().lines() {
+                warn!("{}", line);
+            }
+            world.insert_resource(channels);
+            world.insert_resource(chains);
+            world.insert_resource(phrases);
+            world.insert_resource(instruments);
+            world.insert_resource(project);
+            info!("Imported hUGETracker song `{}`", filename);
+        }
+        Err(e) => error!("Could not import hUGETracker song `{}`: {}", filename, e),
+    }
+}
+
+/// Imports the Game Boy sound of a VGM file, logging anything that was not imported exactly.
+fn import_vgm(filename: &str, world: &mut World) {
+    let mut channels = Channels::default();
+    let mut chains = Chains::default();
+    let mut phrases = Phrases::default();
+    let mut instruments = Instruments::default();
+    let mut project = world
+        .get_resource::<Project>()
+        .expect("FormatsPlugin must be added after the ResourcePlugin.")
+        .clone();
+
+    match vgm::load_song(
+        filename,
+        &mut channels,
+        &mut chains,
+        &mut phrases,
+        &mut instruments,
+        &mut project,
+    ) {
+        Ok(report) => {
+            for line in report.to_string().lines() {
+                warn!("{}", line);
+            }
+            world.insert_resource(channels);
+            world.insert_resource(chains);
+            world.insert_resource(phrases);
+            world.insert_resource(instruments);
+            world.insert_resource(project);
+            info!("Imported VGM file `{}`", filename);
+        }
+        Err(e) => error!("Could not import VGM file `{}`: {}", filename, e),
+    }
+}
+
+/// Loads a text song, replacing the song and instruments.
+fn load_text(filename: &str, world: &mut World) {
+    let mut channels = Channels::default();
+    let mut chains = Chains::default();
+    let mut phrases = Phrases::default();
+    let mut instruments = Instruments::default();
+    let mut project = Project::default();
+
+    match text::load_song(
+        filename,
+        &mut channels,
+        &mut chains,
+        &mut phrases,
+        &mut instruments,
+        &mut project,
+    ) {
+        Ok(()) => {
+            world.insert_resource(channels);
+            world.insert_resource(chains);
+            world.insert_resource(phrases);
+            world.insert_resource(instruments);
+            world.insert_resource(project);
+            info!("Loaded song `{}`", filename);
+        }
+        Err(e) => error!("Could not load song `{}`: {}", filename, e),
+    }
+}
+
+// endregion:   FormatsPlugin
@@ -0,0 +1,55 @@
+//! `bsdj fmt`, which rewrites text songs in the canonical layout.
+
+use super::*;
+use crate::cli::FmtArgs;
+
+/// Returns a text song in the canonical layout.
+///
+/// # ERRORS
+///
+/// Errors if the song can't be read.
+pub fn format_text(text: &str) -> Result<String, TextError> {
+    let (mut channels, mut chains, mut phrases, mut instruments, mut project) = Default::default();
+    read_song(
+        text,
+        &mut channels,
+        &mut chains,
+        &mut phrases,
+        &mut instruments,
+        &mut project,
+    )?;
+    Ok(write_song(
+        &channels,
+        &chains,
+        &phrases,
+        &instruments,
+        &project,
+    ))
+}
+
+/// Formats each file in place. With `--check`, lists the files that
+/// aren't formatted instead, and errors if there are any.
+pub fn run(args: &FmtArgs) -> Result<(), TextError> {
+    let mut unformatted = 0;
+    for filename in &args.files {
+        let text = fs::read_to_string(filename).map_err(|e| TextError::Io(filename.clone(), e))?;
+        let formatted =
+            format_text(&text).map_err(|e| TextError::InFile(filename.clone(), Box::new(e)))?;
+        if formatted == text {
+            continue;
+        }
+
+        if args.check {
+            eprintln!("`{}` is not formatted", filename);
+            unformatted += 1;
+        } else {
+            fs::write(filename, formatted).map_err(|e| TextError::Io(filename.clone(), e))?;
+        }
+    }
+
+    if unformatted > 0 {
+        Err(TextError::NotFormatted(unformatted))
+    } else {
+        Ok(())
+    }
+}
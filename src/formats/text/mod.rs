@@ -0,0 +1,153 @@
+//! A plain text format for songs, made to be kept in version control.
+//!
+//! Only chains, phrases and instruments with something in them are written,
+//! one line per row with something on it, in the notation the screens use.
+//! Notes are written as they appear on the pulse channels, with a `-` in place
+//! of the space in natural notes. A `*` marks a bookmarked song row.
+//!
+//! ```text
+//! title   mysong
+//! tempo   128
+//!
+//! [song]
+//! 00 00 -- 01 --
+//! 01 00*-- 01 --
+//!
+//! [chain 00]
+//! 0 00 00
+//! 1 01 0c
+//!
+//! [phrase 00]
+//! 0 c#4 02 v34
+//! 4 --- -- k00
+//!
+//! [instruments]
+//! 02 pulse envelope=f3 duty=1 length=-- sweep=00
+//! ```
+//!
+//! Blank lines and lines starting with `#` are ignored when reading.
+
+use crate::resources::types::*;
+use std::fs;
+
+pub mod fmt;
+pub mod read;
+pub mod text_error;
+pub mod write;
+
+pub use read::read_song;
+pub use text_error::*;
+pub use write::write_song;
+
+/// The extension of text songs.
+pub const EXTENSION: &str = "bsdj";
+
+/// Reads a text song, replacing the song and instruments.
+///
+/// Nothing is replaced if the file has an error in it.
+pub fn load_song(
+    filename: &str,
+    channels: &mut Channels,
+    chains: &mut Chains,
+    phrases: &mut Phrases,
+    instruments: &mut Instruments,
+    project: &mut Project,
+) -> Result<(), TextError> {
+    let text = fs::read_to_string(filename).map_err(|e| TextError::Io(filename.to_string(), e))?;
+    read_song(&text, channels, chains, phrases, instruments, project)
+}
+
+/// Writes the song and instruments to a text file.
+pub fn save_song(
+    filename: &str,
+    channels: &Channels,
+    chains: &Chains,
+    phrases: &Phrases,
+    instruments: &Instruments,
+    project: &Project,
+) -> Result<(), TextError> {
+    let text = write_song(channels, chains, phrases, instruments, project);
+    fs::write(filename, text).map_err(|e| TextError::Io(filename.to_string(), e))
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::resources::types::instrument::{Instrument, WaveInstrument};
+
+    #[test]
+    /// Write a song as text and read it back, which should give back the same song and text.
+    fn round_trip() {
+        let mut channels = Channels::default();
+        let mut chains = Chains::default();
+        let mut phrases = Phrases::default();
+        let mut instruments = Instruments::default();
+        let mut project = Project::default();
+        project.set_title("mysong");
+        project.set_tempo(150);
+
+        channels.get_mut(0).set_chain(0, 0);
+        channels.get_mut(2).set_chain(1, 0x7f);
+        channels.get_mut(3).set_bookmark(4).unwrap();
+        let chain = chains.get_mut(0x7f).unwrap();
+        chain.set_phrase(0, 0xfe);
+        chain.set_transpose(3, 0xf4);
+        let phrase = phrases.get_mut(0xfe).unwrap();
+        phrase.set_note(0, 14);
+        phrase.set_instr(0, 2);
+        phrase.set_cmd(0, Command::Vibrato);
+        phrase.set_cmd_val(0, 0x34);
+        phrase.set_note(3, 108);
+        phrase.set_cmd_val(5, 0x12);
+        *instruments.get_mut(0x40).unwrap() = Instrument::Wave(WaveInstrument {
+            volume: 1,
+            wave: 2,
+            length: Some(0x80),
+        });
+
+        let text = write_song(&channels, &chains, &phrases, &instruments, &project);
+        assert!(text.contains("\n[phrase fe]\n0 c#4 02 v34\n3 b-b -- -00\n5 --- -- -12\n"));
+        assert!(text.contains("\n[chain 7f]\n0 fe 00\n3 -- f4\n"));
+        assert!(text.contains("\n04 -- -- -- --*\n"));
+
+        let (mut new_channels, mut new_chains, mut new_phrases, mut new_instruments) =
+            Default::default();
+        let mut new_project = Project::default();
+        read_song(
+            &text,
+            &mut new_channels,
+            &mut new_chains,
+            &mut new_phrases,
+            &mut new_instruments,
+            &mut new_project,
+        )
+        .unwrap();
+
+        assert_eq!(channels, new_channels);
+        assert_eq!(chains, new_chains);
+        assert_eq!(phrases, new_phrases);
+        assert_eq!(instruments, new_instruments);
+        assert_eq!(project, new_project);
+    }
+
+    #[test]
+    /// Hand-written text is rewritten in the canonical layout, and errors give the line.
+    fn format() {
+        let text = "# A comment\n\
+            [phrase 1]\n\
+            4   C#4  2  V34\n\
+            [song]\n\
+            0 01 -- -- --\n\
+            tempo 128\n";
+        assert!(matches!(
+            fmt::format_text(text),
+            Err(TextError::InvalidLine(6, _))
+        ));
+
+        let text = "tempo 128\n[song]\n0 01 -- -- --\n\n[phrase 1]\n4   C#4  2  V34\n";
+        assert_eq!(
+            "tempo   128\n\n[song]\n00 01 -- -- --\n\n[phrase 01]\n4 c#4 02 v34\n",
+            fmt::format_text(text).unwrap()
+        );
+    }
+}
@@ -0,0 +1,290 @@
+use super::*;
+use crate::resources::types::instrument::*;
+use crate::resources::types::note::LARGEST_NOTE;
+use crate::resources::types::project::{MAX_TEMPO, MIN_TEMPO};
+
+/// The note names in the order of `Note`, as the Phrase screen writes them.
+const NOTE_NAMES: [&str; 12] = [
+    "c", "c#", "d", "d#", "e", "f", "f#", "g", "g#", "a", "a#", "b",
+];
+/// The octave of `Note(1)` on the pulse channels.
+const LOWEST_OCTAVE: u8 = 3;
+
+/// The part of the file that lines are read into.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Section {
+    Project,
+    Song,
+    Chain(usize),
+    Phrase(usize),
+    Instruments,
+}
+
+/// Reads a text song, replacing the song and instruments.
+///
+/// Rows, chains, phrases and instruments that aren't in the text are left empty.
+/// Any amount of space can be used between values, and sections can be in any order.
+///
+/// # ERRORS
+///
+/// Errors with the line number if a line can't be read. Nothing is replaced if so.
+pub fn read_song(
+    text: &str,
+    channels: &mut Channels,
+    chains: &mut Chains,
+    phrases: &mut Phrases,
+    instruments: &mut Instruments,
+    project: &mut Project,
+) -> Result<(), TextError> {
+    let mut new_channels = Channels::default();
+    let mut new_chains = Chains::default();
+    let mut new_phrases = Phrases::default();
+    let mut new_instruments = Instruments::default();
+    let mut new_project = Project::default();
+    let mut section = Section::Project;
+
+    for (idx, line) in text.lines().enumerate() {
+        let invalid = |what: &str| TextError::InvalidLine(idx + 1, what.to_string());
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        if let Some(header) = line.strip_prefix('[') {
+            let header = header
+                .strip_suffix(']')
+                .ok_or_else(|| invalid("Missing `]`"))?;
+            let words = header.split_whitespace().collect::<Vec<_>>();
+            section = match words.as_slice() {
+                ["song"] => Section::Song,
+                ["instruments"] => Section::Instruments,
+                ["chain", id] => {
+                    let id = parse_hex(id).ok_or_else(|| invalid("Expected a chain number"))?;
+                    new_chains
+                        .get(id as usize)
+                        .ok_or_else(|| invalid("Chain numbers go up to 7f"))?;
+                    Section::Chain(id as usize)
+                }
+                ["phrase", id] => {
+                    let id = parse_hex(id).ok_or_else(|| invalid("Expected a phrase number"))?;
+                    new_phrases
+                        .get(id as usize)
+                        .ok_or_else(|| invalid("Phrase numbers go up to fe"))?;
+                    Section::Phrase(id as usize)
+                }
+                _ => return Err(invalid("Unknown section")),
+            };
+            continue;
+        }
+
+        let words = line.split_whitespace().collect::<Vec<_>>();
+        match section {
+            Section::Project => match words[0] {
+                "title" => new_project.set_title(line["title".len()..].trim()),
+                "tempo" => {
+                    let tempo = words
+                        .get(1)
+                        .and_then(|tempo| tempo.parse::<u16>().ok())
+                        .filter(|tempo| (MIN_TEMPO..=MAX_TEMPO).contains(tempo))
+                        .ok_or_else(|| invalid("Expected a tempo from 40 to 295"))?;
+                    new_project.set_tempo(tempo);
+                }
+                _ => return Err(invalid("Expected `title` or `tempo`")),
+            },
+            Section::Song => {
+                let row = parse_hex(words[0]).ok_or_else(|| invalid("Expected a song row"))?;
+                // Chains may be written with or without space between them.
+                let cells = words[1..].concat();
+                let mut cells = cells.as_str();
+                for channel in 0..4 {
+                    if cells.len() < 2 || !cells.is_char_boundary(2) {
+                        return Err(invalid("Expected a chain for each channel"));
+                    }
+                    let (chain, rest) = cells.split_at(2);
+                    let song_channel = new_channels.get_mut(channel);
+                    match chain {
+                        "--" => (),
+                        chain => {
+                            let chain = parse_hex(chain)
+                                .filter(|chain| *chain <= 0x7f)
+                                .ok_or_else(|| invalid("Chain numbers go up to 7f"))?;
+                            song_channel.set_chain(row, chain);
+                        }
+                    }
+                    cells = match rest.strip_prefix('*') {
+                        Some(rest) => {
+                            song_channel.set_bookmark(row).map_err(invalid)?;
+                            rest
+                        }
+                        None => rest,
+                    };
+                }
+                if !cells.is_empty() {
+                    return Err(invalid("Expected 4 chains"));
+                }
+            }
+            Section::Chain(id) => {
+                let chain = new_chains.get_mut(id).unwrap();
+                let [step, phrase, transpose] = match words.as_slice() {
+                    [step, phrase, transpose] => [*step, *phrase, *transpose],
+                    _ => return Err(invalid("Expected a step, phrase and transpose")),
+                };
+                let step = parse_hex(step)
+                    .filter(|step| *step < 0x10)
+                    .ok_or_else(|| invalid("Expected a step from 0 to f"))?
+                    as usize;
+                if phrase != "--" {
+                    let phrase = parse_hex(phrase)
+                        .filter(|phrase| *phrase != 0xff)
+                        .ok_or_else(|| invalid("Phrase numbers go up to fe"))?;
+                    chain.set_phrase(step, phrase);
+                }
+                let transpose =
+                    parse_hex(transpose).ok_or_else(|| invalid("Expected a transpose"))?;
+                chain.set_transpose(step, transpose);
+            }
+            Section::Phrase(id) => {
+                let phrase = new_phrases.get_mut(id).unwrap();
+                let [row, note, instr, cmd] = match words.as_slice() {
+                    [row, note, instr, cmd] => [*row, *note, *instr, *cmd],
+                    _ => return Err(invalid("Expected a row, note, instrument and command")),
+                };
+                let row = parse_hex(row)
+                    .filter(|row| *row < 0x10)
+                    .ok_or_else(|| invalid("Expected a row from 0 to f"))?
+                    as usize;
+                if let Some(note) = parse_note(note).ok_or_else(|| invalid("Invalid note"))? {
+                    phrase.set_note(row, note);
+                }
+                if instr != "--" {
+                    let instr = parse_hex(instr)
+                        .filter(|instr| *instr != 0xff)
+                        .ok_or_else(|| invalid("Instrument numbers go up to fe"))?;
+                    phrase.set_instr(row, instr);
+                }
+                let (cmd, val) = parse_command(cmd).ok_or_else(|| invalid("Invalid command"))?;
+                phrase.set_cmd(row, cmd);
+                phrase.set_cmd_val(row, val);
+            }
+            Section::Instruments => {
+                let id = parse_hex(words[0])
+                    .filter(|id| new_instruments.get(*id as usize).is_some())
+                    .ok_or_else(|| invalid("Instrument numbers go up to 40"))?;
+                let instrument = parse_instrument(&words[1..]).map_err(|e| invalid(e.as_str()))?;
+                *new_instruments.get_mut(id as usize).unwrap() = instrument;
+            }
+        }
+    }
+
+    *channels = new_channels;
+    *chains = new_chains;
+    *phrases = new_phrases;
+    *instruments = new_instruments;
+    *project = new_project;
+    Ok(())
+}
+
+/// Parses a hex byte, which must be written with 1 or 2 digits.
+fn parse_hex(text: &str) -> Option<u8> {
+    if text.is_empty() || text.len() > 2 {
+        return None;
+    }
+    u8::from_str_radix(text, 16).ok()
+}
+
+/// Parses a note written by `note_to_text`. `---` is an empty note, giving `Some(None)`.
+fn parse_note(text: &str) -> Option<Option<u8>> {
+    if text == "---" {
+        return Some(None);
+    }
+    if let Some(value) = text.strip_prefix('?') {
+        return parse_hex(value)
+            .filter(|value| *value > LARGEST_NOTE)
+            .map(Some);
+    }
+
+    let text = text.to_lowercase();
+    if text.len() != 3 || !text.is_char_boundary(2) {
+        return None;
+    }
+    let (name, octave) = text.split_at(2);
+    let name = NOTE_NAMES
+        .iter()
+        .position(|n| *n == name.trim_end_matches('-'))? as u8;
+    let octave = u8::from_str_radix(octave, 16)
+        .ok()?
+        .checked_sub(LOWEST_OCTAVE)?;
+    let note = octave.checked_mul(12)? + name + 1;
+    if note > LARGEST_NOTE {
+        return None;
+    }
+    Some(Some(note))
+}
+
+/// Parses a command abbreviation followed by its value, such as `v34` or `-00`.
+fn parse_command(text: &str) -> Option<(Command, u8)> {
+    let mut chars = text.chars();
+    let abbr = chars.next()?;
+    let cmd = match abbr {
+        '-' => Command::None,
+        abbr => *Command::iter()
+            .iter()
+            .find(|cmd| cmd.get_abbr() == Some(abbr.to_ascii_lowercase()))?,
+    };
+    let val = chars.as_str();
+    if val.len() != 2 {
+        return None;
+    }
+    Some((cmd, parse_hex(val)?))
+}
+
+/// Parses an instrument's type followed by its settings, which are all optional.
+fn parse_instrument(words: &[&str]) -> Result<Instrument, String> {
+    let (kind, settings) = words
+        .split_first()
+        .ok_or_else(|| "Expected an instrument type".to_string())?;
+    let mut instrument = match *kind {
+        "pulse" => Instrument::Pulse(Default::default()),
+        "wave" => Instrument::Wave(Default::default()),
+        "noise" => Instrument::Noise(Default::default()),
+        "kit" => Instrument::Kit(),
+        "speech" => Instrument::Speech(),
+        _ => return Err(format!("Unknown instrument type `{}`", kind)),
+    };
+
+    for setting in settings {
+        let (key, value) = setting
+            .split_once('=')
+            .ok_or_else(|| format!("Expected `setting=value`, got `{}`", setting))?;
+        let invalid = || format!("Invalid value for `{}`", key);
+        let byte = || parse_hex(value).ok_or_else(invalid);
+        // Duties and volumes go from 0 to 3.
+        let level = || byte().and_then(|level| if level < 4 { Ok(level) } else { Err(invalid()) });
+        let length = || match value {
+            "--" => Ok(None),
+            _ => byte().map(Some),
+        };
+
+        match (&mut instrument, key) {
+            (Instrument::Pulse(pulse), "envelope") => pulse.envelope = byte()?,
+            (Instrument::Pulse(pulse), "duty") => pulse.duty = level()?,
+            (Instrument::Pulse(pulse), "length") => pulse.length = length()?,
+            (Instrument::Pulse(pulse), "sweep") => pulse.sweep = byte()?,
+            (Instrument::Wave(wave), "volume") => wave.volume = level()?,
+            (Instrument::Wave(wave), "wave") => wave.wave = byte()?,
+            (Instrument::Wave(wave), "length") => wave.length = length()?,
+            (Instrument::Noise(noise), "envelope") => noise.envelope = byte()?,
+            (Instrument::Noise(noise), "length") => noise.length = length()?,
+            (Instrument::Noise(noise), "short") => {
+                noise.short = match value {
+                    "0" => false,
+                    "1" => true,
+                    _ => return Err(invalid()),
+                }
+            }
+            _ => return Err(format!("`{}` has no setting `{}`", kind, key)),
+        }
+    }
+
+    Ok(instrument)
+}
@@ -0,0 +1,14 @@
+use std::io;
+
+/// An error that occurs while reading or writing a text song.
+#[derive(thiserror::Error, Debug)]
+pub enum TextError {
+    #[error("Could not access `{0}`: {1}")]
+    Io(String, io::Error),
+    #[error("Line {0}: {1}")]
+    InvalidLine(usize, String),
+    #[error("{0}: {1}")]
+    InFile(String, Box<TextError>),
+    #[error("{0} files are not formatted.")]
+    NotFormatted(usize),
+}
@@ -0,0 +1,156 @@
+use super::*;
+use crate::resources::types::instrument::Instrument;
+use crate::resources::types::note::Note;
+use std::fmt::Write;
+
+/// Writes the song and instruments as text, in the canonical layout.
+///
+/// Writing a song that was read from text gives back the same text if it was
+/// already formatted, which is what `bsdj fmt` relies on.
+pub fn write_song(
+    channels: &Channels,
+    chains: &Chains,
+    phrases: &Phrases,
+    instruments: &Instruments,
+    project: &Project,
+) -> String {
+    let mut text = String::new();
+    if !project.get_title().is_empty() {
+        writeln!(text, "title   {}", project.get_title()).unwrap();
+    }
+    writeln!(text, "tempo   {}", project.get_tempo()).unwrap();
+
+    // Each chain is followed by a `*` if it's bookmarked, or a space to keep the columns lined up.
+    let mut song_rows = vec![];
+    for row in 0..=0xff {
+        let cells = channels
+            .get_all()
+            .iter()
+            .map(|channel| {
+                let chain = match channel.get_chain(row) {
+                    Some(chain) => format!("{:02x}", chain),
+                    None => "--".to_string(),
+                };
+                let bookmark = if channel.is_bookmarked(row) { '*' } else { ' ' };
+                format!("{}{}", chain, bookmark)
+            })
+            .collect::<Vec<_>>();
+        if cells.iter().any(|cell| cell != "-- ") {
+            song_rows.push(format!("{:02x} {}", row, cells.concat().trim_end()));
+        }
+    }
+    if !song_rows.is_empty() {
+        writeln!(text, "\n[song]").unwrap();
+        for row in song_rows {
+            writeln!(text, "{}", row).unwrap();
+        }
+    }
+
+    for (idx, chain) in (0..).map_while(|idx| chains.get(idx)).enumerate() {
+        if *chain == Default::default() {
+            continue;
+        }
+
+        writeln!(text, "\n[chain {:02x}]", idx).unwrap();
+        for step in 0..0x10 {
+            let transpose = chain.get_transpose(step).unwrap();
+            let phrase = match chain.get_phrase(step) {
+                Some(phrase) => format!("{:02x}", phrase),
+                None if transpose == 0 => continue,
+                None => "--".to_string(),
+            };
+            writeln!(text, "{:x} {} {:02x}", step, phrase, transpose).unwrap();
+        }
+    }
+
+    for (idx, phrase) in (0..).map_while(|idx| phrases.get(idx)).enumerate() {
+        if *phrase == Default::default() {
+            continue;
+        }
+
+        writeln!(text, "\n[phrase {:02x}]", idx).unwrap();
+        for row in 0..0x10 {
+            let note = phrase.get_note(row);
+            let instr = phrase.get_instr(row);
+            let cmd = phrase.get_cmd(row).unwrap_or_default();
+            let val = phrase.get_cmd_val(row).unwrap();
+            if note.is_none() && instr.is_none() && cmd == Command::None && val == 0 {
+                continue;
+            }
+
+            let instr = match instr {
+                Some(instr) => format!("{:02x}", instr),
+                None => "--".to_string(),
+            };
+            writeln!(
+                text,
+                "{:x} {} {} {}{:02x}",
+                row,
+                note_to_text(note.cloned()),
+                instr,
+                cmd,
+                val
+            )
+            .unwrap();
+        }
+    }
+
+    let instrument_lines = (0..)
+        .map_while(|idx| instruments.get(idx))
+        .enumerate()
+        .filter(|(_, instrument)| **instrument != Instrument::default())
+        .map(|(idx, instrument)| format!("{:02x} {}", idx, instrument_to_text(instrument)))
+        .collect::<Vec<_>>();
+    if !instrument_lines.is_empty() {
+        writeln!(text, "\n[instruments]").unwrap();
+        for line in instrument_lines {
+            writeln!(text, "{}", line).unwrap();
+        }
+    }
+
+    text
+}
+
+/// Writes a note as the Phrase screen shows it on a pulse channel, with `-` for
+/// the space. Notes too high to show are written as `?` and their value in hex.
+pub fn note_to_text(note: Option<Note>) -> String {
+    match note {
+        Some(note) => match note.to_string(false) {
+            Some(text) => text.replace(' ', "-"),
+            None => format!("?{:02x}", note.0),
+        },
+        None => "---".to_string(),
+    }
+}
+
+/// Writes an instrument's type followed by its settings.
+pub fn instrument_to_text(instrument: &Instrument) -> String {
+    let length = |length: Option<u8>| match length {
+        Some(length) => format!("{:02x}", length),
+        None => "--".to_string(),
+    };
+
+    match instrument {
+        Instrument::Pulse(pulse) => format!(
+            "pulse envelope={:02x} duty={:x} length={} sweep={:02x}",
+            pulse.envelope,
+            pulse.duty,
+            length(pulse.length),
+            pulse.sweep
+        ),
+        Instrument::Wave(wave) => format!(
+            "wave volume={:x} wave={:02x} length={}",
+            wave.volume,
+            wave.wave,
+            length(wave.length)
+        ),
+        Instrument::Noise(noise) => format!(
+            "noise envelope={:02x} length={} short={:x}",
+            noise.envelope,
+            length(noise.length),
+            noise.short as u8
+        ),
+        Instrument::Kit() => "kit".to_string(),
+        Instrument::Speech() => "speech".to_string(),
+    }
+}
@@ -0,0 +1,678 @@
+use super::*;
+use crate::formats::vgm::player::WAVES;
+use crate::formats::{allocate, CHAIN_LIMIT, PHRASE_LIMIT, ROWS_PER_PHRASE, SONG_ROW_LIMIT};
+use crate::resources::types::instrument::{
+    Instrument, NoiseInstrument, PulseInstrument, WaveInstrument,
+};
+use std::collections::BTreeMap;
+use std::fmt;
+
+/// hUGETracker updates once per frame, at the Game Boy's frame rate.
+const FRAME_RATE: f64 = 59.73;
+/// The first BSDj instrument duty, wave and noise instruments are imported into.
+const INSTRUMENT_BASES: [usize; 3] = [0x00, 0x10, 0x20];
+/// The number of LSDj notes hUGETracker can play, C3 to B8.
+const NOTE_COUNT: u32 = 72;
+
+/// The things that could not be converted exactly, and how many times each happened.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ConversionReport {
+    lossy: BTreeMap<String, usize>,
+}
+
+impl ConversionReport {
+    /// Records something that could not be converted exactly.
+    pub fn add(&mut self, what: &str) {
+        *self.lossy.entry(what.to_string()).or_default() += 1;
+    }
+
+    /// Returns everything that could not be converted exactly, and how many times each happened.
+    pub fn get_all(&self) -> &BTreeMap<String, usize> {
+        &self.lossy
+    }
+
+    /// Returns true if everything was converted exactly.
+    pub fn is_lossless(&self) -> bool {
+        self.lossy.is_empty()
+    }
+}
+
+impl fmt::Display for ConversionReport {
+    /// Lists each lossy conversion on its own line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (what, count) in &self.lossy {
+            writeln!(f, "{}x {}", count, what)?;
+        }
+        Ok(())
+    }
+}
+
+/// Returns the type of instrument a channel plays, as an index into `INSTRUMENT_BASES`.
+fn instrument_kind(channel: usize) -> usize {
+    match channel {
+        0 | 1 => 0,
+        2 => 1,
+        _ => 2,
+    }
+}
+
+/// Converts hUGETracker's ticks per row into BPM, assuming 4 rows per beat.
+fn ticks_to_tempo(ticks: u32) -> u16 {
+    (FRAME_RATE * 15. / ticks.max(1) as f64).round() as u16
+}
+
+/// Converts BPM into hUGETracker's ticks per row, assuming 4 rows per beat.
+fn tempo_to_ticks(tempo: u16) -> u32 {
+    (FRAME_RATE * 15. / tempo as f64).round().max(1.) as u32
+}
+
+/// Returns the wave bank holding BSDj's waves, repeated to fill every slot
+/// so each wave instrument plays the wave it plays in BSDj.
+fn wave_bank() -> Vec<[u8; WAVE_LENGTH]> {
+    (0..WAVE_COUNT)
+        .map(|idx| {
+            let mut wave = [0; WAVE_LENGTH];
+            for (samples, byte) in wave.chunks_exact_mut(2).zip(WAVES[idx % WAVES.len()]) {
+                samples.copy_from_slice(&[byte >> 4, byte & 0x0f]);
+            }
+            wave
+        })
+        .collect()
+}
+
+// region:      Import
+
+/// Converts a hUGETracker song, replacing the song.
+///
+/// Each pattern becomes a chain of 4 phrases, and each order becomes a song row.
+/// Duty, wave and noise instruments are placed at instruments 0x00, 0x10 and 0x20.
+pub fn import_song(
+    song: &UgeSong,
+    channels: &mut Channels,
+    chains: &mut Chains,
+    phrases: &mut Phrases,
+    instruments: &mut Instruments,
+    project: &mut Project,
+) -> ConversionReport {
+    let mut report = ConversionReport::default();
+    *channels = Channels::default();
+    *chains = Chains::default();
+    *phrases = Phrases::default();
+    *instruments = Instruments::default();
+
+    project.set_title(&song.name);
+    if project.get_title() != song.name {
+        report.add("song name shortened to 8 characters");
+    }
+    if !song.artist.is_empty() || !song.comment.is_empty() {
+        report.add("artist and comment dropped");
+    }
+    project.set_tempo(ticks_to_tempo(song.ticks_per_row));
+    if song.timer_enabled {
+        report.add("timer based tempo converted to the frame based tempo");
+    }
+    if song.waves != wave_bank() && song.waves.iter().any(|wave| wave.iter().any(|s| *s != 0)) {
+        report.add("wave bank dropped");
+    }
+    if song.routines.iter().any(|routine| !routine.is_empty()) {
+        report.add("routines dropped");
+    }
+
+    for (kind, list) in [
+        &song.duty_instruments,
+        &song.wave_instruments,
+        &song.noise_instruments,
+    ]
+    .iter()
+    .enumerate()
+    {
+        for (idx, uge) in list.iter().enumerate() {
+            *instruments.get_mut(INSTRUMENT_BASES[kind] + idx).unwrap() =
+                import_instrument(uge, &mut report);
+        }
+    }
+
+    let mut phrase_list: Vec<phrase::Phrase> = vec![];
+    // The pattern key and instrument type of each chain.
+    let mut chain_list: Vec<(u32, usize)> = vec![];
+    let mut chain_phrases: Vec<Vec<u8>> = vec![];
+
+    for (channel, order) in song.orders.iter().enumerate() {
+        let kind = instrument_kind(channel);
+        for (row, key) in order.iter().enumerate() {
+            if row >= SONG_ROW_LIMIT {
+                report.add("order rows past 0xff dropped");
+                continue;
+            }
+
+            let chain = match chain_list.iter().position(|c| *c == (*key, kind)) {
+                Some(chain) => chain,
+                None => {
+                    let pattern = match song.patterns.iter().find(|(k, _)| k == key) {
+                        Some((_, pattern)) => pattern,
+                        None => {
+                            report.add("orders of missing patterns dropped");
+                            continue;
+                        }
+                    };
+                    let new_phrases = pattern
+                        .chunks(ROWS_PER_PHRASE)
+                        .map(|rows| import_phrase(rows, kind, channel, &mut report))
+                        .collect::<Vec<_>>();
+                    let new_count = new_phrases
+                        .iter()
+                        .filter(|p| !phrase_list.contains(p))
+                        .count();
+                    if chain_list.len() >= CHAIN_LIMIT
+                        || phrase_list.len() + new_count > PHRASE_LIMIT
+                    {
+                        report.add("orders dropped as there are no free chains or phrases");
+                        continue;
+                    }
+
+                    let ids = new_phrases
+                        .iter()
+                        .map(|phrase| allocate(&mut phrase_list, phrase) as u8)
+                        .collect();
+                    chain_list.push((*key, kind));
+                    chain_phrases.push(ids);
+                    chain_list.len() - 1
+                }
+            };
+            channels.get_mut(channel).set_chain(row as u8, chain as u8);
+        }
+    }
+
+    for (idx, phrase) in phrase_list.into_iter().enumerate() {
+        *phrases.get_mut(idx).unwrap() = phrase;
+    }
+    for (idx, ids) in chain_phrases.into_iter().enumerate() {
+        let chain = chains.get_mut(idx).unwrap();
+        for (step, phrase) in ids.into_iter().enumerate() {
+            chain.set_phrase(step, phrase);
+        }
+    }
+
+    report
+}
+
+/// Converts a hUGETracker instrument into the matching BSDj instrument variant.
+fn import_instrument(uge: &UgeInstrument, report: &mut ConversionReport) -> Instrument {
+    if uge.subpattern_enabled {
+        report.add("instrument subpatterns dropped");
+    }
+
+    let envelope = (uge.initial_volume & 0x0f) << 4
+        | ((uge.volume_direction == 0) as u8) << 3
+        | (uge.volume_sweep & 0x07);
+    let length = if uge.length_enabled {
+        Some(uge.length as u8)
+    } else {
+        None
+    };
+
+    match uge.kind {
+        WAVE_INSTRUMENT => Instrument::Wave(WaveInstrument {
+            volume: uge.output_level as u8 & 0x03,
+            wave: uge.waveform as u8,
+            length,
+        }),
+        NOISE_INSTRUMENT => Instrument::Noise(NoiseInstrument {
+            envelope,
+            length,
+            short: uge.counter_step != 0,
+        }),
+        _ => Instrument::Pulse(PulseInstrument {
+            envelope,
+            duty: uge.duty & 0x03,
+            length,
+            sweep: (uge.sweep_time as u8 & 0x07) << 4
+                | ((uge.sweep_direction != 0) as u8) << 3
+                | (uge.sweep_shift as u8 & 0x07),
+        }),
+    }
+}
+
+/// Converts 16 rows of a pattern into a phrase.
+fn import_phrase(
+    rows: &[UgeCell],
+    kind: usize,
+    channel: usize,
+    report: &mut ConversionReport,
+) -> phrase::Phrase {
+    let mut phrase = phrase::Phrase::default();
+    for (idx, cell) in rows.iter().enumerate() {
+        if cell.note < NOTE_COUNT {
+            phrase.set_note(idx, cell.note as u8 + 1);
+        } else if cell.note != NO_NOTE {
+            report.add("invalid notes dropped");
+        }
+
+        if (1..=INSTRUMENT_COUNT as u32).contains(&cell.instrument) {
+            phrase.set_instr(
+                idx,
+                (INSTRUMENT_BASES[kind] + cell.instrument as usize - 1) as u8,
+            );
+        }
+
+        if let Some((cmd, val)) =
+            import_effect(cell.effect_code, cell.effect_param, channel, report)
+        {
+            phrase.set_cmd(idx, cmd);
+            phrase.set_cmd_val(idx, val);
+        }
+    }
+    phrase
+}
+
+/// Converts an effect into the nearest command.
+fn import_effect(
+    code: u32,
+    param: u8,
+    channel: usize,
+    report: &mut ConversionReport,
+) -> Option<(Command, u8)> {
+    match code {
+        0x0 if param == 0 => None,
+        0x0 => Some((Command::Chord, param)),
+        0x1 => {
+            report.add("1xx portamento up converted to P");
+            Some((Command::Pitch, param.min(0x7f)))
+        }
+        0x2 => {
+            report.add("2xx portamento down converted to P");
+            Some((Command::Pitch, 0u8.wrapping_sub(param.min(0x80))))
+        }
+        0x3 => {
+            report.add("3xx tone portamento converted to L");
+            Some((Command::Legato, param))
+        }
+        0x4 => Some((Command::Vibrato, param)),
+        0x5 => Some((Command::Master, param)),
+        0x7 => Some((Command::Delay, param)),
+        0x8 => {
+            report.add("8xx panning only kept for its own channel");
+            let left = (param >> (4 + channel)) & 1;
+            let right = (param >> channel) & 1;
+            Some((Command::Pan, left | right << 1))
+        }
+        0x9 => Some((Command::Wave, param >> 6)),
+        0xc => {
+            report.add("Cxx set volume converted to E");
+            Some((Command::Envelope, param))
+        }
+        0xd => {
+            report.add("Dxx pattern break converted to H00");
+            Some((Command::Hop, 0))
+        }
+        0xe => Some((Command::Kill, param)),
+        0xf => {
+            let mut project = Project::default();
+            project.set_tempo(ticks_to_tempo(param as u32));
+            Some((Command::Tempo, project.get_tempo_byte()))
+        }
+        0x6 => {
+            report.add("6xx call routine dropped");
+            None
+        }
+        0xa => {
+            report.add("Axy volume slide dropped");
+            None
+        }
+        0xb => {
+            report.add("Bxx position jump dropped");
+            None
+        }
+        _ => {
+            report.add("unknown effects dropped");
+            None
+        }
+    }
+}
+
+// endregion:   Import
+
+// region:      Export
+
+/// Converts the song into a hUGETracker song.
+///
+/// Each channel's phrases are played out in order and cut into 64 row patterns,
+/// with identical patterns shared. Instruments are given hUGETracker slots in
+/// the order they are first played.
+pub fn export_song(
+    channels: &Channels,
+    chains: &Chains,
+    phrases: &Phrases,
+    instruments: &Instruments,
+    project: &Project,
+) -> (UgeSong, ConversionReport) {
+    let mut report = ConversionReport::default();
+    let mut song = UgeSong {
+        name: project.get_title().to_string(),
+        ticks_per_row: export_tempo(project.get_tempo(), &mut report),
+        waves: wave_bank(),
+        ..Default::default()
+    };
+
+    // The BSDj instruments given each duty, wave and noise slot.
+    let mut slots: [Vec<u8>; 3] = Default::default();
+    let mut channel_rows = vec![];
+    for (channel, song_channel) in channels.get_all().iter().enumerate() {
+        let mut rows = vec![];
+        for chain in (0..=0xff).map_while(|row| song_channel.get_chain(row)) {
+            let chain = chains.get(chain as usize).unwrap();
+            for step in 0..0x10 {
+                let phrase = match chain.get_phrase(step) {
+                    Some(phrase) => phrases.get(phrase as usize).unwrap(),
+                    // The chain ends at its first empty step.
+                    None => break,
+                };
+                let transpose = chain.get_transpose(step).unwrap() as i8;
+                for row in 0..ROWS_PER_PHRASE {
+                    rows.push(export_cell(
+                        phrase,
+                        row,
+                        transpose,
+                        channel,
+                        &mut slots,
+                        &mut report,
+                    ));
+                }
+            }
+        }
+        channel_rows.push(rows);
+    }
+
+    // All channels move through the orders together, so give them the same number of patterns.
+    let pattern_count = channel_rows
+        .iter()
+        .map(|rows| rows.chunks(PATTERN_LENGTH).len())
+        .max()
+        .unwrap_or(0)
+        .max(1);
+    for (channel, mut rows) in channel_rows.into_iter().enumerate() {
+        rows.resize(pattern_count * PATTERN_LENGTH, UgeCell::default());
+        for pattern in rows.chunks(PATTERN_LENGTH) {
+            let key = match song.patterns.iter().find(|(_, p)| p == pattern) {
+                Some((key, _)) => *key,
+                None => {
+                    let key = song.patterns.len() as u32;
+                    song.patterns.push((key, pattern.to_vec()));
+                    key
+                }
+            };
+            song.orders[channel].push(key);
+        }
+    }
+
+    for (kind, ids) in slots.iter().enumerate() {
+        for (slot, id) in ids.iter().enumerate() {
+            let instrument = instruments.get(*id as usize).unwrap();
+            let uge = export_instrument(instrument, kind, &mut report);
+            match kind {
+                0 => song.duty_instruments[slot] = uge,
+                1 => song.wave_instruments[slot] = uge,
+                _ => song.noise_instruments[slot] = uge,
+            }
+        }
+    }
+
+    (song, report)
+}
+
+/// Converts a phrase row into a cell, applying the chain's transpose.
+fn export_cell(
+    phrase: &phrase::Phrase,
+    row: usize,
+    transpose: i8,
+    channel: usize,
+    slots: &mut [Vec<u8>; 3],
+    report: &mut ConversionReport,
+) -> UgeCell {
+    let mut cell = UgeCell::default();
+
+    if let Some(note) = phrase.get_note(row) {
+        let note = note.0 as i32 - 1 + transpose as i32;
+        if (0..NOTE_COUNT as i32).contains(&note) {
+            cell.note = note as u32;
+        } else {
+            report.add("notes out of hUGETracker's range dropped");
+        }
+    }
+
+    if let Some(instr) = phrase.get_instr(row) {
+        let slots = &mut slots[instrument_kind(channel)];
+        let slot = slots.iter().position(|id| *id == instr).or_else(|| {
+            (slots.len() < INSTRUMENT_COUNT).then(|| {
+                slots.push(instr);
+                slots.len() - 1
+            })
+        });
+        match slot {
+            Some(slot) => cell.instrument = slot as u32 + 1,
+            None => report.add("instruments past the 15th of a type dropped"),
+        }
+    }
+
+    if let Some(cmd) = phrase.get_cmd(row) {
+        let val = phrase.get_cmd_val(row).unwrap();
+        if let Some((code, param)) = export_command(cmd, val, channel, report) {
+            cell.effect_code = code;
+            cell.effect_param = param;
+        }
+    }
+    cell
+}
+
+/// Converts BPM into ticks per row, reporting tempos that fall between two whole ticks.
+fn export_tempo(tempo: u16, report: &mut ConversionReport) -> u32 {
+    let ticks = tempo_to_ticks(tempo).min(0xff);
+    if ticks_to_tempo(ticks) != tempo {
+        report.add("tempos rounded to whole ticks per row");
+    }
+    ticks
+}
+
+/// Converts a command into the nearest effect.
+fn export_command(
+    cmd: Command,
+    val: u8,
+    channel: usize,
+    report: &mut ConversionReport,
+) -> Option<(u32, u8)> {
+    match cmd {
+        Command::Chord => Some((0x0, val)),
+        Command::Pitch => {
+            report.add("P converted to portamento");
+            if val < 0x80 {
+                Some((0x1, val))
+            } else {
+                Some((0x2, 0u8.wrapping_sub(val)))
+            }
+        }
+        Command::Legato => {
+            report.add("L converted to 3xx tone portamento");
+            Some((0x3, val))
+        }
+        Command::Vibrato => Some((0x4, val)),
+        Command::Master => Some((0x5, val)),
+        Command::Delay => Some((0x7, val)),
+        Command::Pan => {
+            report.add("O converted to 8xx, which pans every channel");
+            let (left, right) = (val & 1, (val >> 1) & 1);
+            Some((0x8, left << (4 + channel) | right << channel))
+        }
+        Command::Wave => Some((0x9, (val & 0x03) << 6)),
+        Command::Envelope => {
+            report.add("E converted to Cxx set volume");
+            Some((0xc, val))
+        }
+        Command::Hop => {
+            report.add("H converted to D00 pattern break");
+            Some((0xd, 0))
+        }
+        Command::Kill => Some((0xe, val)),
+        Command::Tempo => {
+            let mut project = Project::default();
+            project.set_tempo_byte(val);
+            Some((0xf, export_tempo(project.get_tempo(), report) as u8))
+        }
+        cmd => {
+            report.add(&format!("{} commands dropped", cmd));
+            None
+        }
+    }
+}
+
+/// Converts a BSDj instrument into a hUGETracker instrument of a type.
+fn export_instrument(
+    instrument: &Instrument,
+    kind: usize,
+    report: &mut ConversionReport,
+) -> UgeInstrument {
+    let uge_kind = [DUTY_INSTRUMENT, WAVE_INSTRUMENT, NOISE_INSTRUMENT][kind];
+    let mut uge = UgeInstrument::new(uge_kind);
+
+    let set_envelope = |uge: &mut UgeInstrument, envelope: u8, length: Option<u8>| {
+        uge.initial_volume = envelope >> 4;
+        uge.volume_direction = (envelope & 0x08 == 0) as u32;
+        uge.volume_sweep = envelope & 0x07;
+        uge.length_enabled = length.is_some();
+        uge.length = length.unwrap_or(0) as u32;
+    };
+
+    match (instrument, uge_kind) {
+        (Instrument::Pulse(pulse), DUTY_INSTRUMENT) => {
+            set_envelope(&mut uge, pulse.envelope, pulse.length);
+            uge.duty = pulse.duty;
+            uge.sweep_time = (pulse.sweep >> 4 & 0x07) as u32;
+            uge.sweep_direction = (pulse.sweep >> 3 & 1) as u32;
+            uge.sweep_shift = (pulse.sweep & 0x07) as u32;
+        }
+        (Instrument::Wave(wave), WAVE_INSTRUMENT) => {
+            uge.output_level = wave.volume as u32;
+            uge.waveform = wave.wave as u32;
+            uge.length_enabled = wave.length.is_some();
+            uge.length = wave.length.unwrap_or(0) as u32;
+        }
+        (Instrument::Noise(noise), NOISE_INSTRUMENT) => {
+            set_envelope(&mut uge, noise.envelope, noise.length);
+            uge.counter_step = noise.short as u32;
+        }
+        _ => report.add("instruments played on the wrong channel type replaced with defaults"),
+    }
+    uge
+}
+
+// endregion:   Export
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    /// Export a song to hUGETracker and back, through the bytes of a `.uge` file.
+    fn round_trip() {
+        let mut channels = Channels::default();
+        let mut chains = Chains::default();
+        let mut phrases = Phrases::default();
+        let mut instruments = Instruments::default();
+        let mut project = Project::default();
+        project.set_title("song");
+
+        *instruments.get_mut(0x20).unwrap() = Instrument::Noise(NoiseInstrument {
+            envelope: 0xf3,
+            length: Some(0x10),
+            short: true,
+        });
+        for (idx, chain) in [0, 1, 2, 3].iter().enumerate() {
+            channels.get_mut(idx).set_chain(0, *chain);
+            for step in 0..PATTERN_LENGTH / ROWS_PER_PHRASE {
+                chains
+                    .get_mut(*chain as usize)
+                    .unwrap()
+                    .set_phrase(step, *chain);
+            }
+        }
+        let phrase = phrases.get_mut(3).unwrap();
+        phrase.set_note(0, 25);
+        phrase.set_instr(0, 0x20);
+        phrase.set_cmd(0, Command::Kill);
+        phrase.set_cmd_val(0, 2);
+        phrase.set_note(8, 13);
+        phrase.set_cmd(8, Command::Chord);
+        phrase.set_cmd_val(8, 0x37);
+        let phrase = *phrase;
+
+        let (song, report) = export_song(&channels, &chains, &phrases, &instruments, &project);
+        assert!(report.is_lossless(), "{}", report);
+        let song = UgeSong::from_bytes(&song.to_bytes()).unwrap();
+
+        let (
+            mut new_channels,
+            mut new_chains,
+            mut new_phrases,
+            mut new_instruments,
+            mut new_project,
+        ) = Default::default();
+        let report = import_song(
+            &song,
+            &mut new_channels,
+            &mut new_chains,
+            &mut new_phrases,
+            &mut new_instruments,
+            &mut new_project,
+        );
+        assert!(report.is_lossless(), "{}", report);
+
+        assert_eq!(project, new_project);
+        assert_eq!(instruments.get(0x20), new_instruments.get(0x20));
+        // Each channel has a single pattern, which becomes a chain of 4 phrases.
+        let noise_chain = new_channels.get(3).get_chain(0).unwrap();
+        let noise_phrase = new_chains.get(noise_chain as usize).unwrap().get_phrase(0);
+        assert_eq!(
+            Some(&phrase),
+            new_phrases.get(noise_phrase.unwrap() as usize)
+        );
+    }
+
+    #[test]
+    /// A tempo between two whole ticks per row is reported, and comes back at the rounded tempo.
+    fn round_tempo() {
+        let (channels, chains, phrases, instruments) = Default::default();
+        let mut project = Project::default();
+        project.set_tempo(120);
+
+        let (song, report) = export_song(&channels, &chains, &phrases, &instruments, &project);
+        assert_eq!(song.ticks_per_row, 7);
+        assert!(report
+            .get_all()
+            .contains_key("tempos rounded to whole ticks per row"));
+        assert_eq!(song.waves, wave_bank());
+
+        let (
+            mut new_channels,
+            mut new_chains,
+            mut new_phrases,
+            mut new_instruments,
+            mut new_project,
+        ) = Default::default();
+        let report = import_song(
+            &song,
+            &mut new_channels,
+            &mut new_chains,
+            &mut new_phrases,
+            &mut new_instruments,
+            &mut new_project,
+        );
+        assert!(report.is_lossless(), "{}", report);
+        assert_eq!(new_project.get_tempo(), 128);
+
+        let mut report = ConversionReport::default();
+        assert_eq!(
+            export_command(Command::Tempo, 120, 0, &mut report),
+            Some((0xf, 7))
+        );
+        assert!(!report.is_lossless());
+    }
+}
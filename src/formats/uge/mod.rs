@@ -0,0 +1,412 @@
+//! hUGETracker's `.uge` song format.
+//!
+//! hUGETracker saves its songs as packed Free Pascal records, so integers are
+//! 4 byte little-endian values and strings are either a length byte followed by
+//! 255 bytes, or a 4 byte length followed by the string.
+
+use crate::resources::types::*;
+use std::fs;
+
+pub mod convert;
+pub mod uge_error;
+
+pub use convert::{export_song, import_song, ConversionReport};
+pub use uge_error::*;
+
+/// The only version of the format BSDj reads and writes.
+pub const UGE_VERSION: u32 = 6;
+/// The number of instruments of each type.
+pub const INSTRUMENT_COUNT: usize = 15;
+/// The number of waves in the wave bank.
+pub const WAVE_COUNT: usize = 16;
+/// The number of 4-bit samples in a wave.
+pub const WAVE_LENGTH: usize = 32;
+/// The number of rows in a pattern.
+pub const PATTERN_LENGTH: usize = 64;
+/// The number of routines that can be called from the `6xx` effect.
+pub const ROUTINE_COUNT: usize = 16;
+/// The note value of an empty cell.
+pub const NO_NOTE: u32 = 90;
+/// The number of bytes a short string takes up, including its length byte.
+const SHORT_STRING_SIZE: usize = 256;
+
+/// The type of an instrument, as stored in the file.
+pub const DUTY_INSTRUMENT: u32 = 0;
+pub const WAVE_INSTRUMENT: u32 = 1;
+pub const NOISE_INSTRUMENT: u32 = 2;
+
+/// A single row of a pattern.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UgeCell {
+    /// The note from 0 (C3) to 71 (B8), or `NO_NOTE`.
+    pub note: u32,
+    /// The instrument from 1 to 15, or 0 for none.
+    pub instrument: u32,
+    /// Unused by hUGETracker.
+    pub volume: u32,
+    pub effect_code: u32,
+    pub effect_param: u8,
+}
+
+impl Default for UgeCell {
+    fn default() -> Self {
+        UgeCell {
+            note: NO_NOTE,
+            instrument: 0,
+            volume: 0,
+            effect_code: 0,
+            effect_param: 0,
+        }
+    }
+}
+
+/// An instrument, holding the settings of all 3 types even though only
+/// those of its own type are used.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UgeInstrument {
+    pub kind: u32,
+    pub name: String,
+    pub length: u32,
+    pub length_enabled: bool,
+    pub initial_volume: u8,
+    /// 0 if the volume increases, 1 if it decreases.
+    pub volume_direction: u32,
+    pub volume_sweep: u8,
+    pub sweep_time: u32,
+    /// 0 if the frequency increases, 1 if it decreases.
+    pub sweep_direction: u32,
+    pub sweep_shift: u32,
+    /// The duty cycle from 0 to 3.
+    pub duty: u8,
+    /// The wave volume from 0 (mute) to 3 (full volume).
+    pub output_level: u32,
+    pub waveform: u32,
+    /// 0 for 15-bit noise, 1 for 7-bit noise.
+    pub counter_step: u32,
+    pub subpattern_enabled: bool,
+    pub subpattern: Vec<UgeCell>,
+}
+
+impl UgeInstrument {
+    /// Creates an empty instrument of a type.
+    pub fn new(kind: u32) -> UgeInstrument {
+        UgeInstrument {
+            kind,
+            name: String::new(),
+            length: 0,
+            length_enabled: false,
+            initial_volume: 15,
+            volume_direction: 1,
+            volume_sweep: 0,
+            sweep_time: 0,
+            sweep_direction: 1,
+            sweep_shift: 0,
+            duty: 2,
+            output_level: 1,
+            waveform: 0,
+            counter_step: 0,
+            subpattern_enabled: false,
+            subpattern: vec![UgeCell::default(); PATTERN_LENGTH],
+        }
+    }
+}
+
+/// A hUGETracker song.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct UgeSong {
+    pub name: String,
+    pub artist: String,
+    pub comment: String,
+    pub duty_instruments: Vec<UgeInstrument>,
+    pub wave_instruments: Vec<UgeInstrument>,
+    pub noise_instruments: Vec<UgeInstrument>,
+    pub waves: Vec<[u8; WAVE_LENGTH]>,
+    pub ticks_per_row: u32,
+    pub timer_enabled: bool,
+    pub timer_divider: u32,
+    /// Each pattern's key, which the order lists refer to, and its cells.
+    pub patterns: Vec<(u32, Vec<UgeCell>)>,
+    /// The pattern keys played by each channel.
+    pub orders: [Vec<u32>; 4],
+    pub routines: Vec<String>,
+}
+
+impl Default for UgeSong {
+    fn default() -> Self {
+        UgeSong {
+            name: String::new(),
+            artist: String::new(),
+            comment: String::new(),
+            duty_instruments: vec![UgeInstrument::new(DUTY_INSTRUMENT); INSTRUMENT_COUNT],
+            wave_instruments: vec![UgeInstrument::new(WAVE_INSTRUMENT); INSTRUMENT_COUNT],
+            noise_instruments: vec![UgeInstrument::new(NOISE_INSTRUMENT); INSTRUMENT_COUNT],
+            waves: vec![[0; WAVE_LENGTH]; WAVE_COUNT],
+            ticks_per_row: 7,
+            timer_enabled: false,
+            timer_divider: 0,
+            patterns: vec![],
+            orders: Default::default(),
+            routines: vec![String::new(); ROUTINE_COUNT],
+        }
+    }
+}
+
+impl UgeSong {
+    /// Reads a song from the bytes of a `.uge` file.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if the file is cut short or is not version 6.
+    pub fn from_bytes(data: &[u8]) -> Result<UgeSong, UgeError> {
+        let mut reader = Reader { data, pos: 0 };
+        let version = reader.u32()?;
+        if version != UGE_VERSION {
+            return Err(UgeError::UnsupportedVersion(version));
+        }
+
+        let mut song = UgeSong {
+            name: reader.short_string()?,
+            artist: reader.short_string()?,
+            comment: reader.short_string()?,
+            ..Default::default()
+        };
+        song.duty_instruments = reader.instruments()?;
+        song.wave_instruments = reader.instruments()?;
+        song.noise_instruments = reader.instruments()?;
+
+        song.waves = (0..WAVE_COUNT)
+            .map(|_| {
+                let mut wave = [0; WAVE_LENGTH];
+                wave.copy_from_slice(reader.take(WAVE_LENGTH)?);
+                Ok(wave)
+            })
+            .collect::<Result<_, UgeError>>()?;
+
+        song.ticks_per_row = reader.u32()?;
+        song.timer_enabled = reader.bool()?;
+        song.timer_divider = reader.u32()?;
+
+        let pattern_count = reader.u32()?;
+        song.patterns = (0..pattern_count)
+            .map(|_| Ok((reader.u32()?, reader.pattern()?)))
+            .collect::<Result<_, UgeError>>()?;
+
+        for order in song.orders.iter_mut() {
+            // hUGETracker stores one more order than is played.
+            let length = reader.u32()?;
+            *order = (0..length)
+                .map(|_| reader.u32())
+                .collect::<Result<_, _>>()?;
+            order.pop();
+        }
+
+        song.routines = (0..ROUTINE_COUNT)
+            .map(|_| reader.long_string())
+            .collect::<Result<_, _>>()?;
+
+        Ok(song)
+    }
+
+    /// Converts the song into the bytes of a `.uge` file.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut data = vec![];
+        write_u32(&mut data, UGE_VERSION);
+        write_short_string(&mut data, &self.name);
+        write_short_string(&mut data, &self.artist);
+        write_short_string(&mut data, &self.comment);
+        for instrument in self
+            .duty_instruments
+            .iter()
+            .chain(&self.wave_instruments)
+            .chain(&self.noise_instruments)
+        {
+            write_instrument(&mut data, instrument);
+        }
+        for wave in &self.waves {
+            data.extend(wave);
+        }
+
+        write_u32(&mut data, self.ticks_per_row);
+        data.push(self.timer_enabled as u8);
+        write_u32(&mut data, self.timer_divider);
+
+        write_u32(&mut data, self.patterns.len() as u32);
+        for (key, pattern) in &self.patterns {
+            write_u32(&mut data, *key);
+            write_pattern(&mut data, pattern);
+        }
+
+        for order in &self.orders {
+            write_u32(&mut data, order.len() as u32 + 1);
+            for key in order.iter().chain(&[0]) {
+                write_u32(&mut data, *key);
+            }
+        }
+
+        for routine in &self.routines {
+            write_u32(&mut data, routine.len() as u32);
+            data.extend(routine.bytes());
+        }
+        data
+    }
+}
+
+/// Reads a `.uge` file and imports it, replacing the song.
+pub fn load_song(
+    filename: &str,
+    channels: &mut Channels,
+    chains: &mut Chains,
+    phrases: &mut Phrases,
+    instruments: &mut Instruments,
+    project: &mut Project,
+) -> Result<ConversionReport, UgeError> {
+    let data = fs::read(filename).map_err(|e| UgeError::Io(filename.to_string(), e))?;
+    let song = UgeSong::from_bytes(&data)?;
+    Ok(import_song(
+        &song,
+        channels,
+        chains,
+        phrases,
+        instruments,
+        project,
+    ))
+}
+
+/// Exports the song and writes it to a `.uge` file.
+pub fn save_song(
+    filename: &str,
+    channels: &Channels,
+    chains: &Chains,
+    phrases: &Phrases,
+    instruments: &Instruments,
+    project: &Project,
+) -> Result<ConversionReport, UgeError> {
+    let (song, report) = export_song(channels, chains, phrases, instruments, project);
+    fs::write(filename, song.to_bytes()).map_err(|e| UgeError::Io(filename.to_string(), e))?;
+    Ok(report)
+}
+
+// region:      Binary helpers
+
+fn write_u32(data: &mut Vec<u8>, value: u32) {
+    data.extend(value.to_le_bytes());
+}
+
+/// Writes a Pascal short string, cutting it down to 255 bytes.
+fn write_short_string(data: &mut Vec<u8>, string: &str) {
+    let bytes = &string.as_bytes()[..string.len().min(SHORT_STRING_SIZE - 1)];
+    data.push(bytes.len() as u8);
+    data.extend(bytes);
+    data.extend(vec![0; SHORT_STRING_SIZE - 1 - bytes.len()]);
+}
+
+fn write_pattern(data: &mut Vec<u8>, pattern: &[UgeCell]) {
+    for cell in pattern {
+        write_u32(data, cell.note);
+        write_u32(data, cell.instrument);
+        write_u32(data, cell.volume);
+        write_u32(data, cell.effect_code);
+        data.push(cell.effect_param);
+    }
+}
+
+fn write_instrument(data: &mut Vec<u8>, instrument: &UgeInstrument) {
+    write_u32(data, instrument.kind);
+    write_short_string(data, &instrument.name);
+    write_u32(data, instrument.length);
+    data.push(instrument.length_enabled as u8);
+    data.push(instrument.initial_volume);
+    write_u32(data, instrument.volume_direction);
+    data.push(instrument.volume_sweep);
+    write_u32(data, instrument.sweep_time);
+    write_u32(data, instrument.sweep_direction);
+    write_u32(data, instrument.sweep_shift);
+    data.push(instrument.duty);
+    write_u32(data, instrument.output_level);
+    write_u32(data, instrument.waveform);
+    write_u32(data, instrument.counter_step);
+    data.push(instrument.subpattern_enabled as u8);
+    write_pattern(data, &instrument.subpattern);
+}
+
+/// Reads little-endian values from a slice, erroring if it runs out.
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, length: usize) -> Result<&'a [u8], UgeError> {
+        let bytes = self
+            .data
+            .get(self.pos..self.pos + length)
+            .ok_or(UgeError::Truncated(self.pos))?;
+        self.pos += length;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> Result<u8, UgeError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, UgeError> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn u32(&mut self) -> Result<u32, UgeError> {
+        let bytes = self.take(4)?;
+        Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+    }
+
+    fn short_string(&mut self) -> Result<String, UgeError> {
+        let bytes = self.take(SHORT_STRING_SIZE)?;
+        let length = bytes[0] as usize;
+        Ok(String::from_utf8_lossy(&bytes[1..=length]).to_string())
+    }
+
+    fn long_string(&mut self) -> Result<String, UgeError> {
+        let length = self.u32()? as usize;
+        Ok(String::from_utf8_lossy(self.take(length)?).to_string())
+    }
+
+    fn pattern(&mut self) -> Result<Vec<UgeCell>, UgeError> {
+        (0..PATTERN_LENGTH)
+            .map(|_| {
+                Ok(UgeCell {
+                    note: self.u32()?,
+                    instrument: self.u32()?,
+                    volume: self.u32()?,
+                    effect_code: self.u32()?,
+                    effect_param: self.u8()?,
+                })
+            })
+            .collect()
+    }
+
+    fn instruments(&mut self) -> Result<Vec<UgeInstrument>, UgeError> {
+        (0..INSTRUMENT_COUNT)
+            .map(|_| {
+                Ok(UgeInstrument {
+                    kind: self.u32()?,
+                    name: self.short_string()?,
+                    length: self.u32()?,
+                    length_enabled: self.bool()?,
+                    initial_volume: self.u8()?,
+                    volume_direction: self.u32()?,
+                    volume_sweep: self.u8()?,
+                    sweep_time: self.u32()?,
+                    sweep_direction: self.u32()?,
+                    sweep_shift: self.u32()?,
+                    duty: self.u8()?,
+                    output_level: self.u32()?,
+                    waveform: self.u32()?,
+                    counter_step: self.u32()?,
+                    subpattern_enabled: self.bool()?,
+                    subpattern: self.pattern()?,
+                })
+            })
+            .collect()
+    }
+}
+
+// endregion:   Binary helpers
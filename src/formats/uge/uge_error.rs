@@ -0,0 +1,12 @@
+use std::io;
+
+/// An error that occurs while reading or writing a hUGETracker song.
+#[derive(thiserror::Error, Debug)]
+pub enum UgeError {
+    #[error("Could not access `{0}`: {1}")]
+    Io(String, io::Error),
+    #[error("Unsupported .uge version `{0}`: Only version 6 is supported.")]
+    UnsupportedVersion(u32),
+    #[error("The .uge file ends early, at byte `{0:#x}`.")]
+    Truncated(usize),
+}
@@ -0,0 +1,576 @@
+use super::player::{freq_to_note, noise_to_note, WAVES};
+use super::*;
+use crate::formats::midi::import::{arrange, DropReason};
+use crate::formats::midi::CHANNEL_NAMES;
+use crate::formats::ROWS_PER_PHRASE;
+use crate::resources::types::instrument::*;
+use crate::resources::types::project::{MAX_TEMPO, MIN_TEMPO};
+use std::fmt;
+
+/// The number of phrases in each imported chain.
+const PHRASES_PER_CHAIN: usize = 4;
+/// The number of instruments imported notes can use, 0x00 to 0x3f.
+const INSTRUMENT_LIMIT: usize = 0x40;
+/// The samples in a 60Hz frame, which is how often most games update their music.
+const FRAME_SAMPLES: f64 = SAMPLE_RATE as f64 / 60.;
+/// The start of the commands in files older than 1.50, which have no data offset.
+const OLD_DATA_START: usize = 0x40;
+/// Registers are numbered in groups of 5 per channel, from NR10 to NR44.
+const REGISTERS_PER_CHANNEL: u8 = 5;
+
+/// Everything that couldn't be imported exactly.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ImportReport {
+    /// Notes dropped because another note started on the same row.
+    pub overlapping_notes: usize,
+    /// Notes played with the closest of BSDj's waves, as their wave isn't one of them.
+    pub approximated_waves: usize,
+    /// Notes left without an instrument because all of the instruments were in use.
+    pub missing_instruments: usize,
+    /// Channels that were cut short, the song row they were cut at, and why.
+    pub dropped: Vec<(usize, usize, DropReason)>,
+}
+
+impl ImportReport {
+    /// Returns true if everything was imported.
+    pub fn is_complete(&self) -> bool {
+        *self == ImportReport::default()
+    }
+}
+
+impl fmt::Display for ImportReport {
+    /// Lists everything that couldn't be imported exactly, one item per line.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.overlapping_notes > 0 {
+            writeln!(
+                f,
+                "{} notes dropped as they overlap other notes",
+                self.overlapping_notes
+            )?;
+        }
+        if self.approximated_waves > 0 {
+            writeln!(
+                f,
+                "{} notes play the closest wave BSDj has",
+                self.approximated_waves
+            )?;
+        }
+        if self.missing_instruments > 0 {
+            writeln!(
+                f,
+                "{} notes have no instrument as all 0x40 instruments are in use",
+                self.missing_instruments
+            )?;
+        }
+        for (channel, row, reason) in &self.dropped {
+            let reason = match reason {
+                DropReason::Phrases => "all 0xff phrases are in use",
+                DropReason::Chains => "all 0x80 chains are in use",
+                DropReason::SongRows => "the song is out of rows",
+            };
+            writeln!(
+                f,
+                "{} dropped from song row {:02x} onwards as {}",
+                CHANNEL_NAMES[*channel], row, reason
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Something a channel did, found from its register writes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Event {
+    /// A note was triggered with the settings of an instrument.
+    Note(u8, Instrument),
+    /// The duty cycle changed while a note was playing.
+    Duty(u8),
+    /// The channel was silenced.
+    Stop,
+}
+
+/// Imports the Game Boy sound of a VGM file, replacing the song and instruments.
+///
+/// Each triggered note becomes a phrase row, with an instrument made from the
+/// channel's envelope, duty, length, sweep and wave settings at the time.
+/// Duty changes while a note plays become Wave commands and silenced channels
+/// become `k00`. Pitch changes without a trigger, such as vibrato and slides, are skipped.
+///
+/// The row length is taken from the most common gap between notes, doubled or
+/// halved until the tempo is one LSDj can play.
+///
+/// # ERRORS
+///
+/// Errors if the file isn't a VGM file with Game Boy sound, or its commands can't be read.
+pub fn import_song(
+    data: &[u8],
+    channels: &mut Channels,
+    chains: &mut Chains,
+    phrases: &mut Phrases,
+    instruments: &mut Instruments,
+    project: &mut Project,
+) -> Result<ImportReport, VgmError> {
+    let writes = read_writes(data)?;
+    let mut report = ImportReport::default();
+    let events = find_events(&writes, &mut report);
+
+    let row_samples = row_length(&events, project);
+    project.set_tempo((SAMPLE_RATE as f64 * 60. / (row_samples * 4.)).round() as u16);
+
+    *instruments = Instruments::default();
+    let mut instrument_list = vec![];
+    let channel_chains = events
+        .iter()
+        .enumerate()
+        .map(|(channel, events)| {
+            let rows = quantize(
+                events,
+                channel,
+                row_samples,
+                &mut instrument_list,
+                &mut report,
+            );
+            rows.chunks(ROWS_PER_PHRASE * PHRASES_PER_CHAIN)
+                .map(|chain| chain.chunks(ROWS_PER_PHRASE).map(to_phrase).collect())
+                .collect::<Vec<_>>()
+        })
+        .collect::<Vec<_>>();
+
+    for (idx, instrument) in instrument_list.into_iter().enumerate() {
+        *instruments.get_mut(idx).unwrap() = instrument;
+    }
+    report.dropped = arrange(&channel_chains, channels, chains, phrases);
+
+    Ok(report)
+}
+
+// region:      Reading
+
+/// Reads the Game Boy register writes in a VGM file, along with the sample they happen on.
+fn read_writes(data: &[u8]) -> Result<Vec<(u32, u8, u8)>, VgmError> {
+    if data.len() < OLD_DATA_START || &data[..4] != b"Vgm " {
+        return Err(VgmError::NotVgm);
+    }
+    let version = read_u32(data, VERSION)?;
+    if version < VGM_VERSION || read_u32(data, DMG_CLOCK_OFFSET)? == 0 {
+        return Err(VgmError::NoDmg);
+    }
+    let mut pos = match read_u32(data, DATA_OFFSET)? {
+        0 => OLD_DATA_START,
+        offset => DATA_OFFSET + offset as usize,
+    };
+
+    let byte = |pos: usize| data.get(pos).cloned().ok_or(VgmError::Truncated(pos));
+    let mut writes = vec![];
+    let mut sample = 0;
+    loop {
+        let cmd = byte(pos)?;
+        match cmd {
+            CMD_END => break,
+            CMD_DMG_WRITE => {
+                let (register, value) = (byte(pos + 1)?, byte(pos + 2)?);
+                // The top bit picks the second Game Boy, which is left out.
+                if register & 0x80 == 0 {
+                    writes.push((sample, register, value));
+                }
+                pos += 3;
+            }
+            CMD_WAIT => {
+                let wait = u16::from_le_bytes([byte(pos + 1)?, byte(pos + 2)?]) as u32;
+                sample = add_wait(sample, wait, pos)?;
+                pos += 3;
+            }
+            CMD_WAIT_60TH => {
+                sample = add_wait(sample, 735, pos)?;
+                pos += 1;
+            }
+            CMD_WAIT_50TH => {
+                sample = add_wait(sample, 882, pos)?;
+                pos += 1;
+            }
+            0x70..=0x7f => {
+                sample = add_wait(sample, (cmd - CMD_WAIT_SHORT) as u32 + 1, pos)?;
+                pos += 1;
+            }
+            // A YM2612 sample write followed by a wait of 0 to 15 samples.
+            0x80..=0x8f => {
+                sample = add_wait(sample, (cmd & 0x0f) as u32, pos)?;
+                pos += 1;
+            }
+            // A data block, which has its size after a compatibility byte and the type.
+            0x67 => pos += 7 + read_u32(data, pos + 3)? as usize,
+            _ => pos += 1 + operand_count(cmd).ok_or(VgmError::UnknownCommand(cmd, pos))?,
+        }
+    }
+
+    Ok(writes)
+}
+
+/// Returns the sample after waiting from `sample`, or an error if it's past the largest
+/// sample a file can be timed to.
+fn add_wait(sample: u32, wait: u32, pos: usize) -> Result<u32, VgmError> {
+    sample.checked_add(wait).ok_or(VgmError::TooLong(pos))
+}
+
+/// Returns the number of bytes following a command for other sound chips, which are skipped.
+fn operand_count(cmd: u8) -> Option<usize> {
+    match cmd {
+        0x30..=0x3f | 0x4f | 0x50 | 0x94 => Some(1),
+        0x40..=0x4e | 0x51..=0x5f | 0xa0..=0xbf => Some(2),
+        0xc0..=0xdf => Some(3),
+        0x90 | 0x91 | 0x95 | 0xe0..=0xff => Some(4),
+        0x92 => Some(5),
+        0x93 => Some(10),
+        0x68 => Some(11),
+        _ => None,
+    }
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, VgmError> {
+    match data.get(offset..offset + 4) {
+        Some(bytes) => Ok(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]])),
+        None => Err(VgmError::Truncated(data.len())),
+    }
+}
+
+// endregion:   Reading
+
+// region:      Events
+
+/// Follows the register writes to find what each channel played.
+fn find_events(writes: &[(u32, u8, u8)], report: &mut ImportReport) -> [Vec<(u32, Event)>; 4] {
+    let mut events: [Vec<(u32, Event)>; 4] = Default::default();
+    // NRx0 to NRx4 of each channel.
+    let mut registers = [[0u8; 5]; 4];
+    let mut wave_ram = [0u8; 16];
+    let mut playing = [false; 4];
+
+    for (sample, register, value) in writes.iter().cloned() {
+        let mut stop = |channel: usize, events: &mut [Vec<(u32, Event)>; 4]| {
+            if playing[channel] {
+                events[channel].push((sample, Event::Stop));
+                playing[channel] = false;
+            }
+        };
+
+        if (WAVE_RAM..WAVE_RAM + 16).contains(&register) {
+            wave_ram[(register - WAVE_RAM) as usize] = value;
+            continue;
+        }
+        if register == NR52 && value & 0x80 == 0 {
+            for channel in 0..4 {
+                stop(channel, &mut events);
+            }
+            continue;
+        }
+        if register >= NR50 {
+            continue;
+        }
+
+        let channel = (register / REGISTERS_PER_CHANNEL) as usize;
+        let idx = (register % REGISTERS_PER_CHANNEL) as usize;
+        let old = registers[channel][idx];
+        registers[channel][idx] = value;
+        let regs = registers[channel];
+
+        match (channel, idx) {
+            // Writing 0 to the top 5 bits of the envelope turns the channel off.
+            (0 | 1 | 3, 2) if value & 0xf8 == 0 => stop(channel, &mut events),
+            (2, 0) if value & 0x80 == 0 => stop(channel, &mut events),
+            (0 | 1, 1) if playing[channel] && (old ^ value) & 0xc0 != 0 => {
+                events[channel].push((sample, Event::Duty(value >> 6)));
+            }
+            (_, 4) if value & 0x80 != 0 => {
+                let length = |mask: u8| match value & 0x40 {
+                    0 => None,
+                    _ => Some(regs[1] & mask),
+                };
+                let freq = regs[3] as u16 | (value as u16 & 0x07) << 8;
+                let (note, instrument) = match channel {
+                    2 => {
+                        let volume = [0, 3, 2, 1][(regs[2] >> 5 & 0x03) as usize];
+                        let wave = closest_wave(&wave_ram);
+                        if WAVES[wave as usize] != wave_ram {
+                            report.approximated_waves += 1;
+                        }
+                        let wave = WaveInstrument {
+                            volume,
+                            wave,
+                            length: length(0xff),
+                        };
+                        (freq_to_note(freq), Instrument::Wave(wave))
+                    }
+                    3 => {
+                        let noise = NoiseInstrument {
+                            envelope: regs[2],
+                            length: length(0x3f),
+                            short: regs[3] & 0x08 != 0,
+                        };
+                        (noise_to_note(regs[3]), Instrument::Noise(noise))
+                    }
+                    _ => {
+                        let pulse = PulseInstrument {
+                            envelope: regs[2],
+                            duty: regs[1] >> 6,
+                            length: length(0x3f),
+                            sweep: if channel == 0 { regs[0] } else { 0 },
+                        };
+                        (freq_to_note(freq), Instrument::Pulse(pulse))
+                    }
+                };
+
+                // Triggering a channel with its DAC off plays nothing.
+                let dac_on = match channel {
+                    2 => regs[0] & 0x80 != 0,
+                    _ => regs[2] & 0xf8 != 0,
+                };
+                if dac_on {
+                    events[channel].push((sample, Event::Note(note, instrument)));
+                    playing[channel] = true;
+                }
+            }
+            _ => (),
+        }
+    }
+
+    events
+}
+
+/// Returns the index of the wave in `WAVES` closest to the contents of wave RAM.
+fn closest_wave(wave_ram: &[u8; 16]) -> u8 {
+    let distance = |wave: &[u8; 16]| {
+        wave.iter()
+            .zip(wave_ram)
+            .map(|(a, b)| (a >> 4).abs_diff(b >> 4) as u32 + (a & 0x0f).abs_diff(b & 0x0f) as u32)
+            .sum::<u32>()
+    };
+    (0..WAVES.len())
+        .min_by_key(|idx| distance(&WAVES[*idx]))
+        .unwrap() as u8
+}
+
+/// Returns the number of samples per row, from the most common gap between notes.
+///
+/// The gaps are grouped by 60Hz frame, and the row length is the average of the
+/// most common group. If there are no notes, the project's tempo is kept.
+fn row_length(events: &[Vec<(u32, Event)>; 4], project: &Project) -> f64 {
+    let mut gaps: Vec<(u32, u32)> = vec![];
+    for channel in events {
+        let starts = channel
+            .iter()
+            .filter(|(_, event)| matches!(event, Event::Note(..)))
+            .map(|(sample, _)| *sample)
+            .collect::<Vec<_>>();
+        for pair in starts.windows(2) {
+            let gap = pair[1] - pair[0];
+            gaps.push(((gap as f64 / FRAME_SAMPLES).round() as u32, gap));
+        }
+    }
+    gaps.retain(|(frames, _)| *frames > 0);
+
+    let mut frames = gaps.iter().map(|(frames, _)| *frames).collect::<Vec<_>>();
+    frames.sort_unstable();
+    frames.dedup();
+    let count = |frames: u32| gaps.iter().filter(|(f, _)| *f == frames).count();
+    // Prefer the shorter gap on a tie, as it fits the longer one too.
+    let most_common = frames.into_iter().rev().max_by_key(|frames| count(*frames));
+
+    let mut row_samples = match most_common {
+        Some(most_common) => {
+            let group = gaps.iter().filter(|(f, _)| *f == most_common);
+            group.clone().map(|(_, gap)| *gap as f64).sum::<f64>() / group.count() as f64
+        }
+        None => samples_per_tick(project.get_tempo()) * TICKS_PER_ROW as f64,
+    };
+
+    let tempo = |row_samples: f64| SAMPLE_RATE as f64 * 60. / (row_samples * 4.);
+    while tempo(row_samples) > MAX_TEMPO as f64 {
+        row_samples *= 2.;
+    }
+    while tempo(row_samples) < MIN_TEMPO as f64 {
+        row_samples /= 2.;
+    }
+    row_samples
+}
+
+// endregion:   Events
+
+// region:      Quantizing
+
+/// A single quantized phrase row.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+struct Row {
+    note: Option<u8>,
+    instr: Option<u8>,
+    cmd: Option<(Command, u8)>,
+}
+
+/// Quantizes a channel's events to rows, padded to a whole number of phrases.
+fn quantize(
+    events: &[(u32, Event)],
+    channel: usize,
+    row_samples: f64,
+    instrument_list: &mut Vec<Instrument>,
+    report: &mut ImportReport,
+) -> Vec<Row> {
+    let mut rows: Vec<Row> = vec![];
+    let mut last_note = None;
+
+    for (sample, event) in events {
+        let row = (*sample as f64 / row_samples).round() as usize;
+        if rows.len() <= row + 1 {
+            rows.resize(row + 2, Row::default());
+        }
+
+        match *event {
+            Event::Note(note, instrument) => {
+                if rows[row].note.is_some() {
+                    report.overlapping_notes += 1;
+                    continue;
+                }
+                let instr = match instrument_list.iter().position(|i| *i == instrument) {
+                    Some(idx) => Some(idx as u8),
+                    None if instrument_list.len() < INSTRUMENT_LIMIT => {
+                        instrument_list.push(instrument);
+                        Some(instrument_list.len() as u8 - 1)
+                    }
+                    None => {
+                        report.missing_instruments += 1;
+                        None
+                    }
+                };
+                // A note replaces any duty change or kill on its row.
+                rows[row] = Row {
+                    note: Some(note),
+                    instr,
+                    cmd: None,
+                };
+                last_note = Some(row);
+            }
+            Event::Duty(duty) if channel < 2 => {
+                if rows[row].note.is_none() && rows[row].cmd.is_none() {
+                    rows[row].cmd = Some((Command::Wave, duty));
+                }
+            }
+            Event::Stop => {
+                // A note stopped on the row it started on still plays for a row.
+                let row = match last_note {
+                    Some(start) => row.max(start + 1),
+                    None => continue,
+                };
+                if rows.len() <= row {
+                    rows.resize(row + 1, Row::default());
+                }
+                if rows[row].note.is_none() && rows[row].cmd.is_none() {
+                    rows[row].cmd = Some((Command::Kill, 0));
+                }
+            }
+            Event::Duty(_) => (),
+        }
+    }
+
+    // Drop the empty rows left after the last note or kill.
+    while rows.last() == Some(&Row::default()) {
+        rows.pop();
+    }
+
+    let padding = (ROWS_PER_PHRASE - rows.len() % ROWS_PER_PHRASE) % ROWS_PER_PHRASE;
+    rows.resize(rows.len() + padding, Row::default());
+    rows
+}
+
+/// Converts a phrase's worth of rows into a phrase.
+fn to_phrase(rows: &[Row]) -> phrase::Phrase {
+    let mut phrase = phrase::Phrase::default();
+    for (idx, row) in rows.iter().enumerate() {
+        if let Some(note) = row.note {
+            phrase.set_note(idx, note);
+        }
+        if let Some(instr) = row.instr {
+            phrase.set_instr(idx, instr);
+        }
+        if let Some((cmd, val)) = row.cmd {
+            phrase.set_cmd(idx, cmd);
+            phrase.set_cmd_val(idx, val);
+        }
+    }
+    phrase
+}
+
+// endregion:   Quantizing
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    /// Export a song and import it again, which should give back the notes, kill and instrument.
+    fn export_round_trip() {
+        let mut channels = Channels::default();
+        let mut chains = Chains::default();
+        let mut phrases = Phrases::default();
+        let mut instruments = Instruments::default();
+        let project = Project::default();
+
+        let pulse = PulseInstrument {
+            envelope: 0xf3,
+            duty: 1,
+            ..Default::default()
+        };
+        *instruments.get_mut(3).unwrap() = Instrument::Pulse(pulse);
+        let phrase = phrases.get_mut(0).unwrap();
+        for (row, note) in [13, 15, 17, 18].into_iter().enumerate() {
+            phrase.set_note(row, note);
+            phrase.set_instr(row, 3);
+        }
+        phrase.set_cmd(6, Command::Kill);
+        phrase.set_cmd_val(6, 0);
+        let phrase = *phrase;
+        chains.get_mut(0).unwrap().set_phrase(0, 0);
+        channels.get_mut(0).set_chain(0, 0);
+
+        let data = export_song(&channels, &chains, &phrases, &instruments, &project);
+        let (mut new_channels, mut new_chains, mut new_phrases, mut new_instruments) =
+            Default::default();
+        let mut new_project = Project::default();
+        new_project.set_tempo(200);
+        let report = import_song(
+            &data,
+            &mut new_channels,
+            &mut new_chains,
+            &mut new_phrases,
+            &mut new_instruments,
+            &mut new_project,
+        )
+        .unwrap();
+
+        assert!(report.is_complete());
+        assert_eq!(project.get_tempo(), new_project.get_tempo());
+        assert_eq!(Some(&Instrument::Pulse(pulse)), new_instruments.get(0));
+        let new_phrase = new_phrases.get(0).unwrap();
+        for row in 0..0x10 {
+            assert_eq!(phrase.get_note(row), new_phrase.get_note(row));
+            assert_eq!(phrase.get_cmd(row), new_phrase.get_cmd(row));
+        }
+        assert_eq!(Some(0), new_phrase.get_instr(0));
+        assert_eq!(Some(0), new_channels.get(0).get_chain(0));
+    }
+
+    #[test]
+    /// Waiting past the largest sample a file can be timed to is an error rather than an overflow.
+    fn reject_long_waits() {
+        let mut data = export_song(
+            &Channels::default(),
+            &Chains::default(),
+            &Phrases::default(),
+            &Instruments::default(),
+            &Project::default(),
+        );
+        data.truncate(HEADER_SIZE);
+        for _ in 0..=u32::MAX / 0xffff {
+            data.extend([CMD_WAIT, 0xff, 0xff]);
+        }
+        data.push(CMD_END);
+        assert!(matches!(read_writes(&data), Err(VgmError::TooLong(_))));
+    }
+}
@@ -0,0 +1,277 @@
+//! VGM logs of the Game Boy's sound registers.
+
+use crate::resources::types::note::LARGEST_NOTE;
+use crate::resources::types::*;
+use std::fs;
+
+pub mod import;
+pub mod player;
+pub mod vgm_error;
+
+pub use import::{import_song, ImportReport};
+pub use player::play_song;
+pub use vgm_error::*;
+
+/// The VGM version written, the first to support the Game Boy.
+pub const VGM_VERSION: u32 = 0x161;
+/// VGM files count time in samples at 44100Hz.
+pub const SAMPLE_RATE: u32 = 44100;
+/// The Game Boy's clock speed.
+pub const DMG_CLOCK: u32 = 4194304;
+/// The size of the header. Commands start straight after it.
+pub const HEADER_SIZE: usize = 0x100;
+/// LSDj ticks per phrase row with the default groove.
+pub const TICKS_PER_ROW: u32 = 6;
+
+// region:      Header offsets
+
+const EOF_OFFSET: usize = 0x04;
+const VERSION: usize = 0x08;
+const GD3_OFFSET: usize = 0x14;
+const TOTAL_SAMPLES: usize = 0x18;
+const LOOP_OFFSET: usize = 0x1c;
+const LOOP_SAMPLES: usize = 0x20;
+const RATE: usize = 0x24;
+const DATA_OFFSET: usize = 0x34;
+const DMG_CLOCK_OFFSET: usize = 0x80;
+
+// endregion:   Header offsets
+
+// region:      Commands
+
+pub const CMD_DMG_WRITE: u8 = 0xb3;
+pub const CMD_WAIT: u8 = 0x61;
+pub const CMD_WAIT_60TH: u8 = 0x62;
+pub const CMD_WAIT_50TH: u8 = 0x63;
+pub const CMD_END: u8 = 0x66;
+/// Waits of 1 to 16 samples are a single byte from 0x70 to 0x7f.
+pub const CMD_WAIT_SHORT: u8 = 0x70;
+
+// endregion:   Commands
+
+// region:      Registers
+
+// Registers are numbered from NR10 at 0xff10, as VGM files store them.
+pub const NR10: u8 = 0x00;
+pub const NR11: u8 = 0x01;
+pub const NR21: u8 = 0x06;
+pub const NR30: u8 = 0x0a;
+pub const NR31: u8 = 0x0b;
+pub const NR41: u8 = 0x10;
+pub const NR43: u8 = 0x12;
+pub const NR50: u8 = 0x14;
+pub const NR51: u8 = 0x15;
+pub const NR52: u8 = 0x16;
+pub const WAVE_RAM: u8 = 0x20;
+
+// endregion:   Registers
+
+/// Returns the number of samples an LSDj tick lasts at a tempo.
+/// LSDj plays 24 ticks per beat with the default groove.
+pub fn samples_per_tick(tempo: u16) -> f64 {
+    SAMPLE_RATE as f64 * 60. / (tempo as f64 * 24.)
+}
+
+/// Exports the song as a VGM file of Game Boy register writes.
+///
+/// The GD3 tag holds the song title. If every channel loops back to the
+/// start at the same time, the whole song is marked as looping.
+pub fn export_song(
+    channels: &Channels,
+    chains: &Chains,
+    phrases: &Phrases,
+    instruments: &Instruments,
+    project: &Project,
+) -> Vec<u8> {
+    let playback = play_song(channels, chains, phrases, instruments, project);
+
+    let mut data = vec![0; HEADER_SIZE];
+    data[..4].copy_from_slice(b"Vgm ");
+    write_u32(&mut data, VERSION, VGM_VERSION);
+    write_u32(&mut data, RATE, 60);
+    write_u32(&mut data, DATA_OFFSET, (HEADER_SIZE - DATA_OFFSET) as u32);
+    write_u32(&mut data, DMG_CLOCK_OFFSET, DMG_CLOCK);
+
+    // The sound is turned on before the loop point, so it isn't repeated on every loop.
+    for (register, value) in &playback.setup {
+        data.extend([CMD_DMG_WRITE, *register, *value]);
+    }
+    let loop_offset = data.len();
+
+    let mut tempos = playback.tempos.iter().peekable();
+    let mut tempo = project.get_tempo();
+    let (mut tick, mut samples, mut written) = (0, 0., 0u32);
+    let mut wait_until = |data: &mut Vec<u8>, until: u32| {
+        while tick < until {
+            while let Some((_, new_tempo)) = tempos.next_if(|(t, _)| *t <= tick) {
+                tempo = *new_tempo;
+            }
+            samples += samples_per_tick(tempo);
+            tick += 1;
+        }
+        let wait = samples.round() as u32 - written;
+        write_wait(data, wait);
+        written += wait;
+    };
+
+    for (write_tick, register, value) in &playback.writes {
+        wait_until(&mut data, *write_tick);
+        data.extend([CMD_DMG_WRITE, *register, *value]);
+    }
+    wait_until(&mut data, playback.length);
+    data.push(CMD_END);
+
+    write_u32(&mut data, TOTAL_SAMPLES, written);
+    if playback.loops {
+        write_u32(&mut data, LOOP_OFFSET, (loop_offset - LOOP_OFFSET) as u32);
+        write_u32(&mut data, LOOP_SAMPLES, written);
+    }
+
+    let gd3_offset = data.len();
+    data.extend(gd3_tag(project.get_title()));
+    write_u32(&mut data, GD3_OFFSET, (gd3_offset - GD3_OFFSET) as u32);
+    let eof = data.len() - EOF_OFFSET;
+    write_u32(&mut data, EOF_OFFSET, eof as u32);
+    data
+}
+
+/// Writes a wait using the shortest commands.
+fn write_wait(data: &mut Vec<u8>, mut samples: u32) {
+    while samples > 0 {
+        match samples {
+            735 => {
+                data.push(CMD_WAIT_60TH);
+                samples = 0;
+            }
+            882 => {
+                data.push(CMD_WAIT_50TH);
+                samples = 0;
+            }
+            1..=16 => {
+                data.push(CMD_WAIT_SHORT + samples as u8 - 1);
+                samples = 0;
+            }
+            _ => {
+                let wait = samples.min(0xffff);
+                data.push(CMD_WAIT);
+                data.extend((wait as u16).to_le_bytes());
+                samples -= wait;
+            }
+        }
+    }
+}
+
+/// Creates a GD3 tag, which holds the track name along with where it came from.
+fn gd3_tag(title: &str) -> Vec<u8> {
+    // English and Japanese track, game, system and author names, then the date, ripper and notes.
+    let strings = [
+        title,
+        "",
+        "",
+        "",
+        "Nintendo Game Boy",
+        "",
+        "",
+        "",
+        "",
+        "BSDj",
+        "",
+    ];
+    let mut text = vec![];
+    for string in strings {
+        for unit in string.encode_utf16().chain(std::iter::once(0)) {
+            text.extend(unit.to_le_bytes());
+        }
+    }
+
+    let mut tag = b"Gd3 ".to_vec();
+    tag.extend(0x100u32.to_le_bytes());
+    tag.extend((text.len() as u32).to_le_bytes());
+    tag.extend(text);
+    tag
+}
+
+fn write_u32(data: &mut [u8], offset: usize, value: u32) {
+    data[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// Reads a `.vgm` file and imports its Game Boy sound, replacing the song and instruments.
+pub fn load_song(
+    filename: &str,
+    channels: &mut Channels,
+    chains: &mut Chains,
+    phrases: &mut Phrases,
+    instruments: &mut Instruments,
+    project: &mut Project,
+) -> Result<ImportReport, VgmError> {
+    let data = fs::read(filename).map_err(|e| VgmError::Io(filename.to_string(), e))?;
+    import_song(&data, channels, chains, phrases, instruments, project)
+}
+
+/// Exports the song and writes it to a `.vgm` file.
+pub fn save_song(
+    filename: &str,
+    channels: &Channels,
+    chains: &Chains,
+    phrases: &Phrases,
+    instruments: &Instruments,
+    project: &Project,
+) -> Result<(), VgmError> {
+    let data = export_song(channels, chains, phrases, instruments, project);
+    fs::write(filename, data).map_err(|e| VgmError::Io(filename.to_string(), e))
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::player::note_to_freq;
+    use super::*;
+
+    #[test]
+    /// Export a looping phrase with a single note and check the header, trigger and GD3 tag.
+    fn export_single_note() {
+        let mut channels = Channels::default();
+        let mut chains = Chains::default();
+        let mut phrases = Phrases::default();
+        let mut project = Project::default();
+        project.set_title("test");
+
+        channels.get_mut(0).set_chain(0, 0);
+        chains.get_mut(0).unwrap().set_phrase(0, 0);
+        phrases.get_mut(0).unwrap().set_note(0, 1);
+
+        let data = export_song(
+            &channels,
+            &chains,
+            &phrases,
+            &Instruments::default(),
+            &project,
+        );
+        let read_u32 = |offset: usize| {
+            u32::from_le_bytes([
+                data[offset],
+                data[offset + 1],
+                data[offset + 2],
+                data[offset + 3],
+            ])
+        };
+
+        assert_eq!(b"Vgm ", &data[..4]);
+        assert_eq!(data.len() - EOF_OFFSET, read_u32(EOF_OFFSET) as usize);
+        // A phrase is 16 rows of 6 ticks.
+        let samples = (samples_per_tick(project.get_tempo()) * 96.).round() as u32;
+        assert_eq!(samples, read_u32(TOTAL_SAMPLES));
+        assert_eq!(samples, read_u32(LOOP_SAMPLES));
+
+        // C3 triggers pulse 1 with the lowest frequency LSDj plays.
+        let freq = note_to_freq(1);
+        let trigger = [CMD_DMG_WRITE, 0x04, 0x80 | (freq >> 8) as u8];
+        assert!(data.windows(3).any(|w| w == trigger));
+
+        let gd3 = GD3_OFFSET + read_u32(GD3_OFFSET) as usize;
+        assert_eq!(b"Gd3 ", &data[gd3..gd3 + 4]);
+        assert_eq!(
+            &[b't', 0, b'e', 0, b's', 0, b't', 0, 0, 0],
+            &data[gd3 + 12..gd3 + 22]
+        );
+    }
+}
@@ -0,0 +1,296 @@
+//! Plays a song into a list of timed sound register writes.
+
+use super::*;
+use crate::resources::types::instrument::{Instrument, PulseInstrument};
+
+/// The frequency of LSDj's lowest note, C3, in Hz.
+const LOWEST_NOTE_HZ: f64 = 65.406;
+/// The waves played by wave instruments, as 32 4-bit samples packed into 16 bytes.
+/// BSDj has no wave editor yet, so instruments pick one of these by their `wave` index.
+pub const WAVES: [[u8; 16]; 4] = [
+    // Triangle
+    [
+        0x01, 0x23, 0x45, 0x67, 0x89, 0xab, 0xcd, 0xef, 0xfe, 0xdc, 0xba, 0x98, 0x76, 0x54, 0x32,
+        0x10,
+    ],
+    // Saw
+    [
+        0x00, 0x11, 0x22, 0x33, 0x44, 0x55, 0x66, 0x77, 0x88, 0x99, 0xaa, 0xbb, 0xcc, 0xdd, 0xee,
+        0xff,
+    ],
+    // Square
+    [
+        0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00,
+        0x00,
+    ],
+    // Sine
+    [
+        0x8a, 0xbd, 0xef, 0xff, 0xff, 0xfe, 0xdb, 0xa8, 0x75, 0x42, 0x10, 0x00, 0x00, 0x01, 0x24,
+        0x57,
+    ],
+];
+
+/// A song played out as register writes.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Playback {
+    /// The register writes that turn on the sound before the song starts.
+    pub setup: Vec<(u8, u8)>,
+    /// The LSDj tick, register and value of each write, sorted by tick.
+    pub writes: Vec<(u32, u8, u8)>,
+    /// The tick and BPM of each tempo change, sorted by tick.
+    pub tempos: Vec<(u32, u16)>,
+    /// The number of ticks the song plays for.
+    pub length: u32,
+    /// True if every channel loops back to the start at the same time.
+    pub loops: bool,
+}
+
+/// Plays the song, with the default groove of 6 ticks per row.
+///
+/// Each channel plays its first block of song rows, up to the first empty row.
+/// Notes are triggered with their instrument's settings, and Delay, Kill, Tempo
+/// and Wave commands are followed. Other commands are skipped.
+pub fn play_song(
+    channels: &Channels,
+    chains: &Chains,
+    phrases: &Phrases,
+    instruments: &Instruments,
+    project: &Project,
+) -> Playback {
+    let mut playback = Playback {
+        setup: vec![(NR52, 0x80), (NR50, 0x77), (NR51, 0xff)],
+        tempos: vec![(0, project.get_tempo())],
+        ..Default::default()
+    };
+
+    let mut lengths = vec![];
+    for (idx, channel) in channels.get_all().iter().enumerate() {
+        let mut player = ChannelPlayer::new(idx);
+        let mut tick = 0;
+
+        for chain in (0..=0xff).map_while(|row| channel.get_chain(row)) {
+            let chain = chains.get(chain as usize).unwrap();
+            for step in 0..0x10 {
+                let phrase = match chain.get_phrase(step) {
+                    Some(phrase) => phrases.get(phrase as usize).unwrap(),
+                    // The chain ends at its first empty step.
+                    None => break,
+                };
+                let transpose = chain.get_transpose(step).unwrap() as i8;
+
+                for row in 0..0x10 {
+                    player.play_row(tick, phrase, row, transpose, instruments, &mut playback);
+                    tick += TICKS_PER_ROW;
+                }
+            }
+        }
+
+        player.stop(tick, &mut playback);
+        if tick > 0 {
+            lengths.push(tick);
+        }
+    }
+
+    playback.length = lengths.iter().cloned().max().unwrap_or(0);
+    playback.loops = !lengths.is_empty() && lengths.iter().all(|l| *l == playback.length);
+    playback.writes.sort_by_key(|(tick, _, _)| *tick);
+    playback.tempos.sort_by_key(|(tick, _)| *tick);
+    playback
+}
+
+/// The registers of one channel, offset from NR10.
+struct Registers {
+    length: u8,
+    envelope: u8,
+    freq_low: u8,
+    freq_high: u8,
+}
+
+/// Tracks the state of one channel while it is played.
+struct ChannelPlayer {
+    channel: usize,
+    instrument: Instrument,
+    /// The duty cycle, which the Wave command changes until the next instrument.
+    duty: u8,
+    /// The wave currently in wave RAM.
+    wave: Option<u8>,
+}
+
+impl ChannelPlayer {
+    fn new(channel: usize) -> ChannelPlayer {
+        let instrument = match channel {
+            2 => Instrument::Wave(Default::default()),
+            3 => Instrument::Noise(Default::default()),
+            _ => Instrument::Pulse(Default::default()),
+        };
+        ChannelPlayer {
+            channel,
+            instrument,
+            duty: PulseInstrument::default().duty,
+            wave: None,
+        }
+    }
+
+    fn registers(&self) -> Registers {
+        let base = [NR11, NR21, NR31, NR41][self.channel];
+        Registers {
+            length: base,
+            envelope: base + 1,
+            freq_low: base + 2,
+            freq_high: base + 3,
+        }
+    }
+
+    /// Plays a single phrase row starting at `tick`.
+    fn play_row(
+        &mut self,
+        tick: u32,
+        phrase: &phrase::Phrase,
+        row: usize,
+        transpose: i8,
+        instruments: &Instruments,
+        playback: &mut Playback,
+    ) {
+        let cmd = phrase.get_cmd(row);
+        let val = phrase.get_cmd_val(row).unwrap();
+        let start = match cmd {
+            Some(Command::Delay) => tick + val as u32,
+            _ => tick,
+        };
+
+        if let Some(instr) = phrase.get_instr(row) {
+            self.instrument = *instruments.get(instr as usize).unwrap_or(&self.instrument);
+            if let Instrument::Pulse(pulse) = self.instrument {
+                self.duty = pulse.duty;
+            }
+        }
+
+        match cmd {
+            Some(Command::Tempo) => {
+                let mut project = Project::default();
+                project.set_tempo_byte(val);
+                playback.tempos.push((tick, project.get_tempo()));
+            }
+            Some(Command::Wave) if self.channel < 2 => {
+                self.duty = val & 0x03;
+                let regs = self.registers();
+                playback.writes.push((start, regs.length, self.duty << 6));
+            }
+            _ => (),
+        }
+
+        if let Some(note) = phrase.get_note(row) {
+            let note = (note.0 as i16 + transpose as i16).clamp(1, LARGEST_NOTE as i16) as u8;
+            self.trigger(start, note, playback);
+        }
+
+        if cmd == Some(Command::Kill) {
+            self.stop(start + val as u32, playback);
+        }
+    }
+
+    /// Starts a note with the current instrument.
+    fn trigger(&mut self, tick: u32, note: u8, playback: &mut Playback) {
+        let regs = self.registers();
+        let mut write = |register, value| playback.writes.push((tick, register, value));
+
+        let (length, freq) = match self.instrument {
+            Instrument::Wave(wave) => {
+                if self.wave != Some(wave.wave) {
+                    // Wave RAM can only be written while the channel is off.
+                    write(NR30, 0x00);
+                    for (idx, byte) in WAVES[wave.wave as usize % WAVES.len()].iter().enumerate() {
+                        write(WAVE_RAM + idx as u8, *byte);
+                    }
+                    self.wave = Some(wave.wave);
+                }
+                write(NR30, 0x80);
+                write(regs.length, wave.length.unwrap_or(0));
+                // NR32 counts down in volume, from 1 (full) to 3 (quarter), with 0 muting it.
+                write(
+                    regs.envelope,
+                    [0, 3, 2, 1][wave.volume as usize & 0x03] << 5,
+                );
+                (wave.length, note_to_freq(note))
+            }
+            Instrument::Noise(noise) => {
+                write(regs.length, noise.length.unwrap_or(0) & 0x3f);
+                write(regs.envelope, noise.envelope);
+                write(NR43, note_to_noise(note) | ((noise.short as u8) << 3));
+                (noise.length, 0)
+            }
+            Instrument::Pulse(pulse) => {
+                if self.channel == 0 {
+                    write(NR10, pulse.sweep);
+                }
+                write(
+                    regs.length,
+                    (self.duty << 6) | (pulse.length.unwrap_or(0) & 0x3f),
+                );
+                write(regs.envelope, pulse.envelope);
+                (pulse.length, note_to_freq(note))
+            }
+            // Kits and speech play samples, which can't be written as register writes.
+            _ => return,
+        };
+
+        if self.channel != 3 {
+            write(regs.freq_low, (freq & 0xff) as u8);
+        }
+        let length_enabled = (length.is_some() as u8) << 6;
+        write(
+            regs.freq_high,
+            0x80 | length_enabled | ((freq >> 8) as u8 & 0x07),
+        );
+    }
+
+    /// Silences the channel.
+    fn stop(&mut self, tick: u32, playback: &mut Playback) {
+        let register = match self.channel {
+            2 => NR30,
+            _ => self.registers().envelope,
+        };
+        playback.writes.push((tick, register, 0x00));
+    }
+}
+
+/// Returns the frequency register value of a note.
+/// The wave channel plays the same value an octave lower.
+pub fn note_to_freq(note: u8) -> u16 {
+    let hz = LOWEST_NOTE_HZ * 2f64.powf((note as f64 - 1.) / 12.);
+    (2048. - 131072. / hz).round().clamp(0., 2047.) as u16
+}
+
+/// Returns the note closest to a frequency register value, the inverse of `note_to_freq`.
+pub fn freq_to_note(freq: u16) -> u8 {
+    let hz = 131072. / (2048. - (freq & 0x7ff) as f64);
+    let note = 12. * (hz / LOWEST_NOTE_HZ).log2() + 1.;
+    note.round().clamp(1., LARGEST_NOTE as f64) as u8
+}
+
+/// Returns the NR43 clock shift and divisor closest to a note.
+pub fn note_to_noise(note: u8) -> u8 {
+    // Noise has no real pitch, so aim for a range that spreads the notes over all the shifts.
+    let target = LOWEST_NOTE_HZ * 2f64.powf((note as f64 - 1.) / 12.) * 16.;
+    let mut best = (f64::MAX, 0);
+    for shift in 0..14u8 {
+        for divisor in 0..8u8 {
+            let divisor_hz = if divisor == 0 { 0.5 } else { divisor as f64 };
+            let hz = 524288. / divisor_hz / 2f64.powi(shift as i32 + 1);
+            let distance = (hz / target).ln().abs();
+            if distance < best.0 {
+                best = (distance, (shift << 4) | divisor);
+            }
+        }
+    }
+    best.1
+}
+
+/// Returns the note closest to an NR43 clock shift and divisor, the inverse of `note_to_noise`.
+pub fn noise_to_note(nr43: u8) -> u8 {
+    let (shift, divisor) = (nr43 >> 4, nr43 & 0x07);
+    let divisor_hz = if divisor == 0 { 0.5 } else { divisor as f64 };
+    let hz = 524288. / divisor_hz / 2f64.powi(shift as i32 + 1);
+    let note = 12. * (hz / 16. / LOWEST_NOTE_HZ).log2() + 1.;
+    note.round().clamp(1., LARGEST_NOTE as f64) as u8
+}
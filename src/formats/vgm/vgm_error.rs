@@ -0,0 +1,18 @@
+use std::io;
+
+/// An error that occurs while reading or writing a VGM file.
+#[derive(thiserror::Error, Debug)]
+pub enum VgmError {
+    #[error("Could not access `{0}`: {1}")]
+    Io(String, io::Error),
+    #[error("The file is not a VGM file.")]
+    NotVgm,
+    #[error("The VGM file has no Game Boy sound.")]
+    NoDmg,
+    #[error("The VGM file ends early, at byte `{0:#x}`.")]
+    Truncated(usize),
+    #[error("The VGM file waits too long to be timed, at byte `{0:#x}`.")]
+    TooLong(usize),
+    #[error("Unknown VGM command `{0:#04x}` at byte `{1:#x}`.")]
+    UnknownCommand(u8, usize),
+}
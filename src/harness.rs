@@ -0,0 +1,170 @@
+//! Runs the app without a window or GPU, for testing the scenes.
+//!
+//! Inputs are queued and applied at the start of the next frame, as if they had just
+//! been pressed, and the layers can be read back as text.
+
+use crate::cli::CliArgs;
+use crate::resources::input::{InputRes, InputType};
+use crate::resources::recording::{InputChange, InputRecording};
+use crate::tilerender::LayerHandler;
+use bevy::prelude::*;
+
+/// An app with every plugin but the ones that need a window or GPU.
+pub struct Harness {
+    app: App,
+    /// The changes to apply at the start of the next frame.
+    queued: Vec<InputChange>,
+}
+
+impl Harness {
+    /// Builds the app and runs its first frame, which loads the Song screen.
+    pub fn new() -> Harness {
+        let mut harness = Harness {
+            // The user's fonts and palettes would change what the screens look like.
+            app: crate::headless_app(CliArgs {
+                no_user_assets: true,
+                ..Default::default()
+            }),
+            queued: vec![],
+        };
+        harness.step();
+        harness
+    }
+
+    /// Runs a frame, applying the queued inputs at its start.
+    pub fn step(&mut self) {
+        if !self.queued.is_empty() {
+            let queued = std::mem::take(&mut self.queued);
+            let mut input = self.resource_mut::<InputRes>();
+            let frame = input.get_time() + 1;
+            let changes = queued.into_iter().map(|change| (frame, change)).collect();
+            input.start_replay(InputRecording::new(changes));
+        }
+        self.app.update();
+    }
+
+    /// Runs a number of frames.
+    pub fn step_frames(&mut self, frames: usize) {
+        for _ in 0..frames {
+            self.step();
+        }
+    }
+
+    /// Queues a change to the inputs for the next frame.
+    pub fn queue(&mut self, change: InputChange) {
+        self.queued.push(change);
+    }
+
+    /// Queues a key press for the next frame. It's held until released.
+    pub fn press(&mut self, key: KeyCode) {
+        self.queue(InputChange::Press(InputType::Key(key)));
+    }
+
+    /// Queues a key release for the next frame.
+    pub fn release(&mut self, key: KeyCode) {
+        self.queue(InputChange::Release(InputType::Key(key)));
+    }
+
+    /// Presses a key for a frame, then releases it the next.
+    pub fn tap(&mut self, key: KeyCode) {
+        self.press(key);
+        self.step();
+        self.release(key);
+        self.step();
+    }
+
+    /// Moves the cursor to a tile and clicks it, releasing the next frame.
+    pub fn click(&mut self, x: i32, y: i32) {
+        let lmb = InputType::Mouse(MouseButton::Left);
+        self.queue(InputChange::Cursor(x * 8, y * 8));
+        self.queue(InputChange::Press(lmb));
+        self.step();
+        self.queue(InputChange::Release(lmb));
+        self.step();
+    }
+
+    /// Moves the cursor to a tile and scrolls there for a frame.
+    pub fn scroll(&mut self, x: i32, y: i32, delta: f32) {
+        self.queue(InputChange::Cursor(x * 8, y * 8));
+        self.queue(InputChange::Scroll(delta));
+        self.step();
+    }
+
+    /// Returns a resource of the app.
+    ///
+    /// # PANICS
+    ///
+    /// Panics if the app doesn't have the resource.
+    pub fn resource<T: Send + Sync + 'static>(&self) -> &T {
+        self.app.world.get_resource::<T>().unwrap()
+    }
+
+    /// Returns a resource of the app to change.
+    ///
+    /// # PANICS
+    ///
+    /// Panics if the app doesn't have the resource.
+    pub fn resource_mut<T: Send + Sync + 'static>(&mut self) -> Mut<'_, T> {
+        self.app.world.get_resource_mut::<T>().unwrap()
+    }
+
+    /// Returns the text on a row of a layer, without the trailing spaces.
+    /// Font tiles read as their character, empty tiles as spaces,
+    /// and any other glyph as `*`.
+    ///
+    /// # PANICS
+    ///
+    /// Panics if there's no such layer or row.
+    pub fn row(&self, layer_id: &str, y: usize) -> String {
+        let lh = self.resource::<LayerHandler>();
+        let layer = lh.get_layer(layer_id).unwrap();
+        assert!(y < layer.get_height(), "Row {} is off the layer", y);
+
+        let prefixes = [
+            format!("{}_", lh.active_font),
+            format!("{}_", lh.active_glyph),
+        ];
+        let text: String = (0..layer.get_width())
+            .map(|x| {
+                let name = match layer.get_tile(x, y) {
+                    Ok(tile) => prefixes
+                        .iter()
+                        .find_map(|prefix| tile.id.strip_prefix(prefix.as_str()))
+                        .unwrap_or(&tile.id),
+                    Err(_) => "space",
+                };
+                match name {
+                    "space" => ' ',
+                    _ if name.chars().count() == 1 => name.chars().next().unwrap(),
+                    _ => '*',
+                }
+            })
+            .collect();
+        text.trim_end().to_string()
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::states;
+
+    #[test]
+    /// The app starts on the Song screen, and inputs change screens.
+    fn start_and_switch_screens() {
+        let mut harness = Harness::new();
+        assert_eq!(harness.row("map", 0), "song");
+        assert_eq!(harness.row("map", 2), "00 -- -- -- --");
+
+        // Select + Right moves along the navbar to the Chain screen.
+        harness.press(KeyCode::Tab);
+        harness.tap(KeyCode::Right);
+        harness.release(KeyCode::Tab);
+        harness.step_frames(2);
+        assert_eq!(
+            harness.resource::<State<states::States>>().current(),
+            &states::States::Chain
+        );
+        assert!(harness.row("map", 0).starts_with("chain"));
+    }
+}
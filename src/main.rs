@@ -3,11 +3,21 @@
 use bevy::{prelude::*, window::WindowMode};
 // use bevy_inspector_egui::WorldInspectorPlugin;
 
+mod cli;
 mod events;
+mod formats;
+#[cfg(test)]
+mod harness;
+mod merge;
 mod meta_actions;
+mod midi;
 mod resources;
+mod rom;
 mod scenes;
+mod screenshot;
+mod session;
 mod states;
+mod sync;
 mod tilerender;
 mod utils;
 
@@ -22,13 +32,29 @@ fn main() {
     #[cfg(target_arch = "wasm32")]
     console_error_panic_hook::set_once();
 
+    // Parse the command line before any plugins need it.
+    let cli = cli::CliArgs::from_env();
+
+    // Commands run on their own without opening the app.
+    if let Some(command) = &cli.command {
+        std::process::exit(cli::run_command(command));
+    }
+
+    // Grow the window to fit the touch pad below the screen.
+    let touch_pad_height = if cli.touch_pad {
+        resources::touch_pad::TOUCH_PAD_HEIGHT
+    } else {
+        0.
+    };
+
     let mut app = App::new();
+    app.insert_resource(cli);
 
     // Set the properties of the window itself
     app.insert_resource(WindowDescriptor {
         title: "LSDj".to_string(),
         width: 160.,
-        height: 144.,
+        height: 144. + touch_pad_height,
         vsync: true,
         resizable: false,
         mode: WindowMode::Windowed,
@@ -38,31 +64,77 @@ fn main() {
 
     app.add_plugin(bevy::diagnostic::FrameTimeDiagnosticsPlugin::default());
 
-    // region:      ADD THE PLUGINS
     app.add_plugins(DefaultPlugins);
     // app.add_plugin(WorldInspectorPlugin::new());
+    add_plugins(&mut app, false);
+
+    // Add the setup for the app.
+    app.add_startup_system(setup);
+
+    add_states(&mut app);
+
+    // RUN THE PROGRAM
+    app.run();
+}
+
+/// Adds BSDj's own plugins.
+/// Headless apps, which have no window or GPU, only keep the tile renderer's layers
+/// rather than drawing them.
+fn add_plugins(app: &mut App, headless: bool) {
+    // region:      ADD THE PLUGINS
     app.add_plugin(events::EventsPlugin);
     app.add_plugin(resources::ResourcePlugin);
     app.add_plugin(scenes::ScenePlugin);
-    app.add_plugin(tilerender::TileRenderPlugin);
+    if headless {
+        app.add_plugin(tilerender::init_renderer::InitRendererPlugin);
+        app.add_system_to_stage(CoreStage::First, tilerender::mark_layers_drawn);
+    } else {
+        app.add_plugin(tilerender::TileRenderPlugin);
+    }
     app.add_plugin(meta_actions::MetaActionsPlugin);
+    app.add_plugin(screenshot::ScreenshotPlugin);
+    app.add_plugin(rom::RomPlugin);
+    app.add_plugin(formats::FormatsPlugin);
+    app.add_plugin(merge::MergePlugin);
+    app.add_plugin(session::SessionPlugin);
+    app.add_plugin(sync::SyncPlugin);
+    app.add_plugin(midi::MidiPlugin);
     // endregion:   ADD THE PLUGINS
+}
 
-    // Add the setup for the app.
-    app.add_startup_system(setup);
+/// Builds the app without a window or GPU, for commands and tests that only need the layers.
+/// Nothing runs until the app is updated.
+fn headless_app(cli: cli::CliArgs) -> App {
+    let mut app = App::new();
+    app.insert_resource(cli);
 
+    app.add_plugins(MinimalPlugins);
+    app.add_plugin(bevy::diagnostic::DiagnosticsPlugin);
+    app.add_plugin(bevy::window::WindowPlugin::default());
+    app.add_plugin(bevy::input::InputPlugin);
+    add_plugins(&mut app, true);
+    add_states(&mut app);
+
+    app
+}
+
+/// Adds the screen states, starting on the song screen.
+fn add_states(app: &mut App) {
     // Set the current game state to be the song screen.
     app.add_state(states::States::Song);
     // Add a resource indicating if the scene should be reloaded.
     app.insert_resource(states::LoadState(true));
     app.insert_resource(states::NextState(None));
+    app.insert_resource(states::NextScreen(None));
     app.insert_resource(states::CurrentHelpScreen(states::HelpScreen::Home));
-
-    // RUN THE PROGRAM
-    app.run();
 }
 
-fn setup(mut commands: Commands) {
+fn setup(mut commands: Commands, cli: Res<cli::CliArgs>) {
     // Add a camera to the scene.
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
+    // Look further down when the touch pad is shown, so the screen stays at the top of the window.
+    let mut camera = OrthographicCameraBundle::new_2d();
+    if cli.touch_pad {
+        camera.transform.translation.y -= resources::touch_pad::TOUCH_PAD_HEIGHT / 2.;
+    }
+    commands.spawn_bundle(camera);
 }
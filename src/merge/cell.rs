@@ -0,0 +1,215 @@
+use super::*;
+use crate::formats::text::write::{instrument_to_text, note_to_text};
+use crate::resources::types::instrument::Instrument;
+use crate::resources::types::note::Note;
+use std::fmt;
+
+/// The smallest part of a song that is merged on its own.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Cell {
+    Title,
+    Tempo,
+    /// A row of a channel on the Song screen.
+    SongRow {
+        channel: usize,
+        row: u8,
+    },
+    /// A step of a chain on the Chain screen.
+    ChainStep {
+        chain: usize,
+        step: usize,
+    },
+    /// A row of a phrase on the Phrase screen.
+    PhraseRow {
+        phrase: usize,
+        row: usize,
+    },
+    Instrument(usize),
+}
+
+impl fmt::Display for Cell {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Cell::Title => write!(f, "title"),
+            Cell::Tempo => write!(f, "tempo"),
+            Cell::SongRow { channel, row } => {
+                write!(f, "song row {:02x} {}", row, CHANNEL_NAMES[*channel])
+            }
+            Cell::ChainStep { chain, step } => write!(f, "chain {:02x} step {:x}", chain, step),
+            Cell::PhraseRow { phrase, row } => write!(f, "phrase {:02x} row {:x}", phrase, row),
+            Cell::Instrument(id) => write!(f, "instrument {:02x}", id),
+        }
+    }
+}
+
+/// The contents of a cell.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CellValue {
+    Title(String),
+    Tempo(u16),
+    SongRow {
+        chain: Option<u8>,
+        bookmarked: bool,
+    },
+    ChainStep {
+        phrase: Option<u8>,
+        transpose: u8,
+    },
+    PhraseRow {
+        note: Option<u8>,
+        instr: Option<u8>,
+        cmd: Command,
+        val: u8,
+    },
+    Instrument(Instrument),
+}
+
+impl fmt::Display for CellValue {
+    /// Writes the value in the notation of the text format.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hex = |value: Option<u8>| match value {
+            Some(value) => format!("{:02x}", value),
+            None => "--".to_string(),
+        };
+
+        match self {
+            CellValue::Title(title) => write!(f, "{:?}", title),
+            CellValue::Tempo(tempo) => write!(f, "{}", tempo),
+            CellValue::SongRow { chain, bookmarked } => {
+                write!(f, "{}{}", hex(*chain), if *bookmarked { "*" } else { "" })
+            }
+            CellValue::ChainStep { phrase, transpose } => {
+                write!(f, "{} {:02x}", hex(*phrase), transpose)
+            }
+            CellValue::PhraseRow {
+                note,
+                instr,
+                cmd,
+                val,
+            } => write!(
+                f,
+                "{} {} {}{:02x}",
+                note_to_text(note.map(Note)),
+                hex(*instr),
+                cmd,
+                val
+            ),
+            CellValue::Instrument(instrument) => write!(f, "{}", instrument_to_text(instrument)),
+        }
+    }
+}
+
+impl Song {
+    /// Returns every cell of the song, in order.
+    pub fn cells(&self) -> Vec<Cell> {
+        let mut cells = vec![Cell::Title, Cell::Tempo];
+        for channel in 0..4 {
+            cells.extend((0..=0xff).map(|row| Cell::SongRow { channel, row }));
+        }
+        let chain_count = (0..).map_while(|idx| self.chains.get(idx)).count();
+        for chain in 0..chain_count {
+            cells.extend((0..0x10).map(|step| Cell::ChainStep { chain, step }));
+        }
+        let phrase_count = (0..).map_while(|idx| self.phrases.get(idx)).count();
+        for phrase in 0..phrase_count {
+            cells.extend((0..0x10).map(|row| Cell::PhraseRow { phrase, row }));
+        }
+        let instrument_count = (0..).map_while(|idx| self.instruments.get(idx)).count();
+        cells.extend((0..instrument_count).map(Cell::Instrument));
+        cells
+    }
+
+    /// Returns the contents of a cell.
+    ///
+    /// # PANICS
+    ///
+    /// Panics if the cell is out of bounds.
+    pub fn get_cell(&self, cell: Cell) -> CellValue {
+        match cell {
+            Cell::Title => CellValue::Title(self.project.get_title().to_string()),
+            Cell::Tempo => CellValue::Tempo(self.project.get_tempo()),
+            Cell::SongRow { channel, row } => {
+                let channel = self.channels.get(channel);
+                CellValue::SongRow {
+                    chain: channel.get_chain(row),
+                    bookmarked: channel.is_bookmarked(row),
+                }
+            }
+            Cell::ChainStep { chain, step } => {
+                let chain = self.chains.get(chain).unwrap();
+                CellValue::ChainStep {
+                    phrase: chain.get_phrase(step),
+                    transpose: chain.get_transpose(step).unwrap(),
+                }
+            }
+            Cell::PhraseRow { phrase, row } => {
+                let phrase = self.phrases.get(phrase).unwrap();
+                CellValue::PhraseRow {
+                    note: phrase.get_note(row).map(|note| note.0),
+                    instr: phrase.get_instr(row),
+                    cmd: phrase.get_cmd(row).unwrap_or_default(),
+                    val: phrase.get_cmd_val(row).unwrap(),
+                }
+            }
+            Cell::Instrument(id) => CellValue::Instrument(*self.instruments.get(id).unwrap()),
+        }
+    }
+
+    /// Sets the contents of a cell.
+    /// A bookmark is left off if the channel already has the most bookmarks it can.
+    ///
+    /// # PANICS
+    ///
+    /// Panics if the cell is out of bounds, or the value is for a different kind of cell.
+    pub fn set_cell(&mut self, cell: Cell, value: CellValue) {
+        match (cell, value) {
+            (Cell::Title, CellValue::Title(title)) => self.project.set_title(&title),
+            (Cell::Tempo, CellValue::Tempo(tempo)) => self.project.set_tempo(tempo),
+            (Cell::SongRow { channel, row }, CellValue::SongRow { chain, bookmarked }) => {
+                let channel = self.channels.get_mut(channel);
+                match chain {
+                    Some(chain) => channel.set_chain(row, chain),
+                    None => channel.clear_chain(row),
+                };
+                if bookmarked {
+                    channel.set_bookmark(row).ok();
+                } else {
+                    channel.clear_bookmark(row);
+                }
+            }
+            (Cell::ChainStep { chain, step }, CellValue::ChainStep { phrase, transpose }) => {
+                let chain = self.chains.get_mut(chain).unwrap();
+                match phrase {
+                    Some(phrase) => chain.set_phrase(step, phrase),
+                    None => chain.clear_phrase(step),
+                };
+                chain.set_transpose(step, transpose);
+            }
+            (
+                Cell::PhraseRow { phrase, row },
+                CellValue::PhraseRow {
+                    note,
+                    instr,
+                    cmd,
+                    val,
+                },
+            ) => {
+                let phrase = self.phrases.get_mut(phrase).unwrap();
+                match note {
+                    Some(note) => phrase.set_note(row, note),
+                    None => phrase.clear_note(row),
+                };
+                match instr {
+                    Some(instr) => phrase.set_instr(row, instr),
+                    None => phrase.clear_instr(row),
+                };
+                phrase.set_cmd(row, cmd);
+                phrase.set_cmd_val(row, val);
+            }
+            (Cell::Instrument(id), CellValue::Instrument(instrument)) => {
+                *self.instruments.get_mut(id).unwrap() = instrument;
+            }
+            (cell, value) => panic!("Cannot set {} to {:?}", cell, value),
+        }
+    }
+}
@@ -0,0 +1,44 @@
+//! `bsdj diff` and `bsdj merge`, which work on songs saved as text.
+
+use super::*;
+use crate::cli::{DiffArgs, MergeArgs};
+use std::fs;
+
+/// Prints every cell that differs between two text songs, one per line.
+pub fn run_diff(args: &DiffArgs) -> Result<(), MergeError> {
+    let old = Song::load(&args.old)?;
+    let new = Song::load(&args.new)?;
+    for (cell, old_value, new_value) in diff(&old, &new) {
+        println!("{}: {} -> {}", cell, old_value, new_value);
+    }
+    Ok(())
+}
+
+/// Merges two text songs edited from the same base, writing the result to
+/// `--output` or printing it. Conflicting cells keep our side and are listed.
+///
+/// # ERRORS
+///
+/// Errors if any song can't be read, or if there were conflicts.
+pub fn run_merge(args: &MergeArgs) -> Result<(), MergeError> {
+    let (song, conflicts) = merge(
+        &Song::load(&args.base)?,
+        &Song::load(&args.ours)?,
+        &Song::load(&args.theirs)?,
+    );
+
+    let text = song.to_text();
+    match &args.output {
+        Some(output) => fs::write(output, text).map_err(MergeError::Io)?,
+        None => print!("{}", text),
+    }
+
+    for (cell, (ours, theirs)) in conflicts.get_all() {
+        eprintln!("Conflict at {}: {} / {}", cell, ours, theirs);
+    }
+    if conflicts.is_empty() {
+        Ok(())
+    } else {
+        Err(MergeError::Conflicts(conflicts.len()))
+    }
+}
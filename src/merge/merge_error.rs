@@ -0,0 +1,12 @@
+use crate::formats::text::TextError;
+
+/// An error that occurs while diffing or merging songs.
+#[derive(thiserror::Error, Debug)]
+pub enum MergeError {
+    #[error("{0}")]
+    Text(#[from] TextError),
+    #[error("Could not write the merged song: {0}")]
+    Io(std::io::Error),
+    #[error("{0} cells conflict.")]
+    Conflicts(usize),
+}
@@ -0,0 +1,178 @@
+//! Diffing and three-way merging of songs, a song row, chain step, phrase row
+//! or instrument at a time.
+
+use crate::cli::CliArgs;
+use crate::formats::midi::CHANNEL_NAMES;
+use crate::formats::text::{self, TextError};
+use crate::resources::types::*;
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+
+pub mod cell;
+pub mod command;
+pub mod merge_error;
+pub mod three_way;
+
+pub use cell::{Cell, CellValue};
+pub use merge_error::*;
+pub use three_way::{diff, merge};
+
+/// A copy of every song resource, so that whole songs can be compared and merged.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Song {
+    pub channels: Channels,
+    pub chains: Chains,
+    pub phrases: Phrases,
+    pub instruments: Instruments,
+    pub project: Project,
+}
+
+impl Song {
+    /// Reads a song saved as text.
+    pub fn load(filename: &str) -> Result<Song, TextError> {
+        let mut song = Song::default();
+        text::load_song(
+            filename,
+            &mut song.channels,
+            &mut song.chains,
+            &mut song.phrases,
+            &mut song.instruments,
+            &mut song.project,
+        )?;
+        Ok(song)
+    }
+
+    /// Writes the song as text.
+    pub fn to_text(&self) -> String {
+        text::write_song(
+            &self.channels,
+            &self.chains,
+            &self.phrases,
+            &self.instruments,
+            &self.project,
+        )
+    }
+}
+
+/// The cells a merge couldn't decide on, which the screens show in the conflict color.
+///
+/// A conflict is resolved once its cell is edited.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Conflicts {
+    /// Each conflicting cell, with what it was merged to and what the other side had.
+    cells: BTreeMap<Cell, (CellValue, CellValue)>,
+}
+
+impl Conflicts {
+    /// Marks a cell as conflicting.
+    pub fn insert(&mut self, cell: Cell, ours: CellValue, theirs: CellValue) {
+        self.cells.insert(cell, (ours, theirs));
+    }
+
+    /// Returns true if the cell is conflicting.
+    pub fn contains(&self, cell: &Cell) -> bool {
+        self.cells.contains_key(cell)
+    }
+
+    /// Returns the number of conflicting cells.
+    pub fn len(&self) -> usize {
+        self.cells.len()
+    }
+
+    /// Returns true if there are no conflicts left.
+    pub fn is_empty(&self) -> bool {
+        self.cells.is_empty()
+    }
+
+    /// Returns every conflicting cell, with what it was merged to and what the other side had.
+    pub fn get_all(&self) -> &BTreeMap<Cell, (CellValue, CellValue)> {
+        &self.cells
+    }
+
+    /// Resolves the conflicts whose cells no longer hold what they were merged to.
+    /// Returns the number of conflicts resolved.
+    pub fn resolve(&mut self, song: &Song) -> usize {
+        let count = self.cells.len();
+        self.cells
+            .retain(|cell, (merged, _)| song.get_cell(*cell) == *merged);
+        count - self.cells.len()
+    }
+}
+
+// region:      MergePlugin
+
+/// Merges the songs passed to `--merge`, if any, and keeps track of its conflicts.
+pub struct MergePlugin;
+
+impl Plugin for MergePlugin {
+    fn build(&self, app: &mut App) {
+        let cli = app
+            .world
+            .get_resource::<CliArgs>()
+            .cloned()
+            .unwrap_or_default();
+
+        let mut conflicts = Conflicts::default();
+        if let Some((base, ours, theirs)) = &cli.merge {
+            match load_and_merge(base, ours, theirs) {
+                Ok((song, merge_conflicts)) => {
+                    for (cell, (ours, theirs)) in merge_conflicts.get_all() {
+                        warn!("Conflict at {}: {} / {}", cell, ours, theirs);
+                    }
+                    app.insert_resource(song.channels);
+                    app.insert_resource(song.chains);
+                    app.insert_resource(song.phrases);
+                    app.insert_resource(song.instruments);
+                    app.insert_resource(song.project);
+                    info!("Merged `{}` with {} conflicts", ours, merge_conflicts.len());
+                    conflicts = merge_conflicts;
+                }
+                Err(e) => error!("Could not merge: {}", e),
+            }
+        }
+
+        app.insert_resource(conflicts);
+        app.add_system(resolve_conflicts);
+    }
+}
+
+/// Loads three text songs and merges them.
+fn load_and_merge(base: &str, ours: &str, theirs: &str) -> Result<(Song, Conflicts), TextError> {
+    Ok(merge(
+        &Song::load(base)?,
+        &Song::load(ours)?,
+        &Song::load(theirs)?,
+    ))
+}
+
+/// Clears the conflict color from cells once they have been edited.
+fn resolve_conflicts(
+    mut conflicts: ResMut<Conflicts>,
+    channels: Res<Channels>,
+    chains: Res<Chains>,
+    phrases: Res<Phrases>,
+    instruments: Res<Instruments>,
+    project: Res<Project>,
+) {
+    let changed = channels.is_changed()
+        || chains.is_changed()
+        || phrases.is_changed()
+        || instruments.is_changed()
+        || project.is_changed();
+    if conflicts.is_empty() || !changed {
+        return;
+    }
+
+    let song = Song {
+        channels: channels.clone(),
+        chains: *chains,
+        phrases: *phrases,
+        instruments: *instruments,
+        project: project.clone(),
+    };
+    if conflicts.resolve(&song) > 0 {
+        info!("{} conflicts left", conflicts.len());
+    }
+}
+
+// endregion:   MergePlugin
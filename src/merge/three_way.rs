@@ -0,0 +1,76 @@
+use super::*;
+
+/// Returns every cell that differs between two songs, with the old and new contents.
+pub fn diff(old: &Song, new: &Song) -> Vec<(Cell, CellValue, CellValue)> {
+    let mut changes = vec![];
+    for cell in old.cells() {
+        let (old_value, new_value) = (old.get_cell(cell), new.get_cell(cell));
+        if old_value != new_value {
+            changes.push((cell, old_value, new_value));
+        }
+    }
+    changes
+}
+
+/// Merges the changes two copies made to a song since `base`, cell by cell.
+///
+/// A cell changed by only one side takes that side's contents. Cells both sides
+/// changed differently keep `ours`, and are returned in the conflicts.
+pub fn merge(base: &Song, ours: &Song, theirs: &Song) -> (Song, Conflicts) {
+    let mut merged = ours.clone();
+    let mut conflicts = Conflicts::default();
+
+    for cell in base.cells() {
+        let ours_value = ours.get_cell(cell);
+        let theirs_value = theirs.get_cell(cell);
+        if ours_value == theirs_value {
+            continue;
+        }
+
+        let base_value = base.get_cell(cell);
+        if ours_value == base_value {
+            merged.set_cell(cell, theirs_value);
+        } else if theirs_value != base_value {
+            conflicts.insert(cell, ours_value, theirs_value);
+        }
+    }
+
+    (merged, conflicts)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    /// Edits to different cells merge, and edits to the same cell conflict.
+    fn merge_rows() {
+        let mut base = Song::default();
+        base.phrases.get_mut(0).unwrap().set_note(0, 1);
+
+        let mut ours = base.clone();
+        ours.phrases.get_mut(0).unwrap().set_note(1, 2);
+        ours.phrases.get_mut(0).unwrap().set_note(4, 4);
+        ours.channels.get_mut(0).set_chain(0, 0);
+
+        let mut theirs = base.clone();
+        theirs.phrases.get_mut(0).unwrap().set_cmd(1, Command::Kill);
+        theirs.phrases.get_mut(0).unwrap().set_note(4, 5);
+        theirs.project.set_tempo(150);
+
+        let (merged, conflicts) = merge(&base, &ours, &theirs);
+
+        let phrase = merged.phrases.get(0).unwrap();
+        assert_eq!(Some(0), merged.channels.get(0).get_chain(0));
+        assert_eq!(150, merged.project.get_tempo());
+        // Both sides changed rows 1 and 4, so they conflict and keep our side.
+        assert_eq!(2, conflicts.len());
+        assert!(conflicts.contains(&Cell::PhraseRow { phrase: 0, row: 1 }));
+        assert_eq!(Some(2), phrase.get_note(1).map(|note| note.0));
+        assert_eq!(None, phrase.get_cmd(1));
+        assert_eq!(Some(4), phrase.get_note(4).map(|note| note.0));
+
+        assert_eq!(3, diff(&base, &ours).len());
+        assert!(diff(&merged, &merged).is_empty());
+    }
+}
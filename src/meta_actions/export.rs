@@ -0,0 +1,135 @@
+use crate::formats::{midi, text, uge, vgm};
+use crate::resources::types::*;
+use crate::resources::{bindings::Action, input::InputRes};
+use bevy::prelude::*;
+
+/// The name exported files are given if the song has no title.
+const UNTITLED: &str = "untitled";
+
+pub struct ExportPlugin;
+
+impl Plugin for ExportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(export_midi);
+        app.add_system(export_uge);
+        app.add_system(export_vgm);
+        app.add_system(save_text);
+    }
+}
+
+/// Returns the name of an exported file, named after the song title.
+pub fn export_filename(project: &Project, extension: &str) -> String {
+    let title = project.get_title().trim();
+    let title = if title.is_empty() { UNTITLED } else { title };
+    format!("{}.{}", title.to_lowercase(), extension)
+}
+
+/// Exports the song to a `.mid` file in the working directory when `exp-mid`
+/// (F5 by default) is pressed.
+fn export_midi(
+    inputs: Res<InputRes>,
+    channels: Res<Channels>,
+    chains: Res<Chains>,
+    phrases: Res<Phrases>,
+    project: Res<Project>,
+) {
+    if !inputs.action_just_pressed(Action::ExportMidi) {
+        return;
+    }
+
+    let filename = export_filename(&project, "mid");
+    match midi::save_song(&filename, &channels, &chains, &phrases, &project) {
+        Ok(()) => info!("Exported the song to `{}`", filename),
+        Err(e) => error!("Could not export the song: {}", e),
+    }
+}
+
+/// Exports the song to a `.uge` file in the working directory when `exp-uge`
+/// (F6 by default) is pressed,
+/// logging anything that was not converted exactly.
+fn export_uge(
+    inputs: Res<InputRes>,
+    channels: Res<Channels>,
+    chains: Res<Chains>,
+    phrases: Res<Phrases>,
+    instruments: Res<Instruments>,
+    project: Res<Project>,
+) {
+    if !inputs.action_just_pressed(Action::ExportUge) {
+        return;
+    }
+
+    let filename = export_filename(&project, "uge");
+    match uge::save_song(
+        &filename,
+        &channels,
+        &chains,
+        &phrases,
+        &instruments,
+        &project,
+    ) {
+        Ok(report) => {
+            for line in report.to_string().lines() {
+                warn!("{}", line);
+            }
+            info!("Exported the song to `{}`", filename);
+        }
+        Err(e) => error!("Could not export the song: {}", e),
+    }
+}
+
+/// Exports the song to a `.vgm` file in the working directory when `exp-vgm`
+/// (F7 by default) is pressed.
+fn export_vgm(
+    inputs: Res<InputRes>,
+    channels: Res<Channels>,
+    chains: Res<Chains>,
+    phrases: Res<Phrases>,
+    instruments: Res<Instruments>,
+    project: Res<Project>,
+) {
+    if !inputs.action_just_pressed(Action::ExportVgm) {
+        return;
+    }
+
+    let filename = export_filename(&project, "vgm");
+    match vgm::save_song(
+        &filename,
+        &channels,
+        &chains,
+        &phrases,
+        &instruments,
+        &project,
+    ) {
+        Ok(()) => info!("Exported the song to `{}`", filename),
+        Err(e) => error!("Could not export the song: {}", e),
+    }
+}
+
+/// Saves the song as text to a `.bsdj` file in the working directory when `save`
+/// (F8 by default) is pressed.
+fn save_text(
+    inputs: Res<InputRes>,
+    channels: Res<Channels>,
+    chains: Res<Chains>,
+    phrases: Res<Phrases>,
+    instruments: Res<Instruments>,
+    project: Res<Project>,
+) {
+    if !inputs.action_just_pressed(Action::SaveText) {
+        return;
+    }
+
+    let filename = export_filename(&project, text::EXTENSION);
+    match text::save_song(
+        &filename,
+        &channels,
+        &chains,
+        &phrases,
+        &instruments,
+        &project,
+    ) {
+        Ok(()) => info!("Saved the song to `{}`", filename),
+        Err(e) => error!("Could not save the song: {}", e),
+    }
+}
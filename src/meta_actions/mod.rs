@@ -1,5 +1,6 @@
 use bevy::prelude::*;
 
+pub mod export;
 pub mod resize;
 pub mod switch_appearance;
 
@@ -7,6 +8,7 @@ pub struct MetaActionsPlugin;
 
 impl Plugin for MetaActionsPlugin {
     fn build(&self, app: &mut App) {
+        app.add_plugin(export::ExportPlugin);
         app.add_plugin(resize::ResizePlugin);
         app.add_plugin(switch_appearance::SwitchAppearancePlugin);
     }
@@ -1,4 +1,4 @@
-use crate::resources::input::{InputRes, InputType};
+use crate::resources::{bindings::Action, input::InputRes};
 use bevy::prelude::*;
 
 pub struct ResizePlugin;
@@ -10,17 +10,17 @@ impl Plugin for ResizePlugin {
 }
 
 fn resize_window(mut windows: ResMut<Windows>, inputs: Res<InputRes>) {
-    if inputs.is_pressed(&[InputType::Key(KeyCode::LControl)]) {
+    let zoom_in = inputs.action_just_pressed(Action::ZoomIn);
+    let zoom_out = inputs.action_just_pressed(Action::ZoomOut);
+
+    if zoom_in || zoom_out {
         let window = windows.get_primary_mut().unwrap();
         let mut scale = window.scale_factor();
 
-        // Zoom in
-        if inputs.just_pressed(&InputType::Key(KeyCode::Equals)) {
+        if zoom_in {
             scale += 1.;
         }
-
-        // Zoom out
-        if inputs.just_pressed(&InputType::Key(KeyCode::Minus)) {
+        if zoom_out {
             scale -= 1.;
         }
 
@@ -1,4 +1,4 @@
-use crate::resources::input::{InputRes, InputType};
+use crate::resources::{bindings::Action, input::InputRes};
 use crate::states::LoadState;
 use crate::tilerender::*;
 use bevy::prelude::*;
@@ -16,8 +16,8 @@ fn switch_palette(
     inputs: Res<InputRes>,
     mut reload_state: ResMut<LoadState>,
 ) {
-    let switch_back = inputs.just_pressed(&InputType::Key(KeyCode::F1));
-    let switch_forth = inputs.just_pressed(&InputType::Key(KeyCode::F2));
+    let switch_back = inputs.action_just_pressed(Action::PrevPalette);
+    let switch_forth = inputs.action_just_pressed(Action::NextPalette);
 
     if switch_back || switch_forth {
         let colors = &lh.color_names;
@@ -0,0 +1,119 @@
+use super::*;
+use alsa::seq::{EvNote, EvQueueControl, Event, EventType, PortCap, PortInfo, PortType, Seq};
+use std::ffi::CString;
+
+/// The name BSDj's client and port show up as, such as in `aconnect -l`.
+const NAME: &str = "BSDj";
+
+impl From<alsa::Error> for MidiError {
+    fn from(e: alsa::Error) -> Self {
+        MidiError::Backend(e.to_string())
+    }
+}
+
+/// A backend on the ALSA sequencer, with a port that other clients connect to
+/// with `aconnect` or a patchbay.
+pub struct AlsaBackend {
+    seq: Seq,
+    port: i32,
+}
+
+impl AlsaBackend {
+    /// Opens the sequencer and creates BSDj's port.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if the sequencer can't be opened, such as when ALSA isn't running.
+    pub fn open() -> Result<AlsaBackend, MidiError> {
+        let name = CString::new(NAME).unwrap();
+        let seq = Seq::open(None, None, true)?;
+        seq.set_client_name(&name)?;
+
+        let mut port_info = PortInfo::empty()?;
+        port_info.set_capability(
+            PortCap::READ | PortCap::SUBS_READ | PortCap::WRITE | PortCap::SUBS_WRITE,
+        );
+        port_info.set_type(PortType::MIDI_GENERIC | PortType::APPLICATION);
+        port_info.set_name(&name);
+        seq.create_port(&port_info)?;
+
+        Ok(AlsaBackend {
+            port: port_info.get_port(),
+            seq,
+        })
+    }
+}
+
+impl MidiBackend for AlsaBackend {
+    fn get_name(&self) -> &str {
+        "alsa"
+    }
+
+    fn send(&mut self, message: MidiMessage) -> Result<(), MidiError> {
+        let note = |channel, note, velocity| EvNote {
+            channel,
+            note,
+            velocity,
+            off_velocity: 0,
+            duration: 0,
+        };
+        let control = EvQueueControl {
+            queue: 0,
+            value: (),
+        };
+
+        let mut event = match message {
+            MidiMessage::NoteOn {
+                channel,
+                note: n,
+                velocity,
+            } => Event::new(EventType::Noteon, &note(channel, n, velocity)),
+            MidiMessage::NoteOff { channel, note: n } => {
+                Event::new(EventType::Noteoff, &note(channel, n, 0))
+            }
+            MidiMessage::Clock => Event::new(EventType::Clock, &control),
+            MidiMessage::Start => Event::new(EventType::Start, &control),
+            MidiMessage::Continue => Event::new(EventType::Continue, &control),
+            MidiMessage::Stop => Event::new(EventType::Stop, &control),
+        };
+        event.set_source(self.port);
+        event.set_subs();
+        event.set_direct();
+        self.seq.event_output_direct(&mut event)?;
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<Vec<MidiMessage>, MidiError> {
+        let mut input = self.seq.input();
+        let mut messages = vec![];
+        while input.event_input_pending(true)? > 0 {
+            let event = input.event_input()?;
+            let message = match event.get_type() {
+                EventType::Noteon | EventType::Noteoff => {
+                    event.get_data::<EvNote>().map(|note| {
+                        // A note on with no velocity is a note off.
+                        if event.get_type() == EventType::Noteon && note.velocity > 0 {
+                            MidiMessage::NoteOn {
+                                channel: note.channel,
+                                note: note.note,
+                                velocity: note.velocity,
+                            }
+                        } else {
+                            MidiMessage::NoteOff {
+                                channel: note.channel,
+                                note: note.note,
+                            }
+                        }
+                    })
+                }
+                EventType::Clock => Some(MidiMessage::Clock),
+                EventType::Start => Some(MidiMessage::Start),
+                EventType::Continue => Some(MidiMessage::Continue),
+                EventType::Stop => Some(MidiMessage::Stop),
+                _ => None,
+            };
+            messages.extend(message);
+        }
+        Ok(messages)
+    }
+}
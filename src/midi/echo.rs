@@ -0,0 +1,87 @@
+use super::*;
+use crate::formats::midi::note_offset;
+
+/// The velocity every echoed note is played at.
+const VELOCITY: u8 = 100;
+
+/// Echoes the notes of each channel on its own MIDI channel as the song plays,
+/// like Arduinoboy's MIDI out mode. Pulse 1, pulse 2, wave and noise go out on
+/// MIDI channels 1 to 4.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct NoteEcho {
+    /// The MIDI note each channel is playing.
+    playing: [Option<u8>; 4],
+}
+
+impl NoteEcho {
+    /// Returns the messages for a phrase row being played, counting rows from the
+    /// start of the song. Each channel loops back to its start once it runs out.
+    pub fn play_row(
+        &mut self,
+        row: u64,
+        channels: &Channels,
+        chains: &Chains,
+        phrases: &Phrases,
+    ) -> Vec<MidiMessage> {
+        let mut messages = vec![];
+        for (idx, channel) in channels.get_all().iter().enumerate() {
+            let sequence = phrase_sequence(channel, chains);
+            if sequence.is_empty() {
+                continue;
+            }
+            let (phrase, transpose) = sequence[(row / 0x10) as usize % sequence.len()];
+            let phrase = phrases.get(phrase as usize).unwrap();
+            let phrase_row = (row % 0x10) as usize;
+
+            let note = phrase.get_note(phrase_row).and_then(|note| {
+                let note = note.0 as i16 + transpose as i8 as i16 + note_offset(idx);
+                u8::try_from(note).ok().filter(|note| *note <= 0x7f)
+            });
+            let kill = phrase.get_cmd(phrase_row) == Some(Command::Kill);
+            if note.is_some() || kill {
+                messages.extend(self.release(idx));
+            }
+            if let Some(note) = note.filter(|_| !kill) {
+                messages.push(MidiMessage::NoteOn {
+                    channel: idx as u8,
+                    note,
+                    velocity: VELOCITY,
+                });
+                self.playing[idx] = Some(note);
+            }
+        }
+        messages
+    }
+
+    /// Returns the messages to stop every note, for when playback stops.
+    pub fn stop(&mut self) -> Vec<MidiMessage> {
+        (0..self.playing.len())
+            .filter_map(|idx| self.release(idx))
+            .collect()
+    }
+
+    /// Stops the note a channel is playing, if any.
+    fn release(&mut self, channel: usize) -> Option<MidiMessage> {
+        let note = self.playing[channel].take()?;
+        Some(MidiMessage::NoteOff {
+            channel: channel as u8,
+            note,
+        })
+    }
+}
+
+/// Returns the phrases a channel plays in order, with their transposes. The
+/// channel ends at its first empty song row, and each chain at its first empty step.
+fn phrase_sequence(channel: &channel::SongChannel, chains: &Chains) -> Vec<(u8, u8)> {
+    let mut sequence = vec![];
+    for chain in (0..=0xff).map_while(|row| channel.get_chain(row)) {
+        let chain = chains.get(chain as usize).unwrap();
+        for step in 0..0x10 {
+            match chain.get_phrase(step) {
+                Some(phrase) => sequence.push((phrase, chain.get_transpose(step).unwrap())),
+                None => break,
+            }
+        }
+    }
+    sequence
+}
@@ -0,0 +1,64 @@
+use crate::formats::midi::{note_offset, WAVE_CHANNEL};
+use crate::resources::types::note::LARGEST_NOTE;
+use crate::sync::{Transport, PULSES_PER_ROW};
+
+/// The largest number of rows the cursor can advance by after a note is entered.
+pub const MAX_STEP: u8 = 0xf;
+
+/// How notes played on a MIDI keyboard are entered on the Phrase screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoteEntry {
+    /// The number of rows the cursor moves down after a note is entered.
+    step: u8,
+    /// If true, notes land on the row being played rather than the cursor's.
+    live_record: bool,
+}
+
+impl NoteEntry {
+    /// Returns the number of rows the cursor moves down after a note is entered.
+    pub fn get_step(&self) -> u8 {
+        self.step
+    }
+
+    /// Sets the number of rows the cursor moves down after a note is entered,
+    /// clamping it to `MAX_STEP`.
+    pub fn set_step(&mut self, step: u8) {
+        self.step = step.min(MAX_STEP);
+    }
+
+    /// Returns true if notes land on the row being played.
+    pub fn is_live_record(&self) -> bool {
+        self.live_record
+    }
+
+    /// Sets whether notes land on the row being played.
+    pub fn set_live_record(&mut self, live_record: bool) {
+        self.live_record = live_record;
+    }
+}
+
+impl Default for NoteEntry {
+    fn default() -> Self {
+        NoteEntry {
+            step: 1,
+            live_record: false,
+        }
+    }
+}
+
+/// Converts a MIDI note to the note the Phrase screen shows for it, the inverse
+/// of how notes are exported. Returns None if the note is out of LSDj's range.
+pub fn from_midi_note(note: u8, is_wav: bool) -> Option<u8> {
+    let channel = if is_wav { WAVE_CHANNEL } else { 0 };
+    let note = note as i16 - note_offset(channel);
+    u8::try_from(note)
+        .ok()
+        .filter(|note| (1..=LARGEST_NOTE).contains(note))
+}
+
+/// Returns the row of the phrase being played, rounded to the nearest row so
+/// that notes played slightly early land where they were meant to.
+pub fn playing_row(transport: &Transport) -> usize {
+    let pulse = transport.get_pulse() + PULSES_PER_ROW as u64 / 2;
+    (pulse / PULSES_PER_ROW as u64 % 0x10) as usize
+}
@@ -0,0 +1,63 @@
+use super::*;
+use std::cell::RefCell;
+use std::collections::VecDeque;
+use std::rc::Rc;
+
+/// A backend that passes messages around in memory, for tests.
+///
+/// A single loopback receives what it sends. A pair of them act as the two ends
+/// of a cable, like BSDj and a synth.
+pub struct Loopback {
+    /// Where sent messages go.
+    outgoing: Rc<RefCell<VecDeque<MidiMessage>>>,
+    /// Where received messages come from.
+    incoming: Rc<RefCell<VecDeque<MidiMessage>>>,
+}
+
+impl Loopback {
+    /// Creates a loopback that receives what it sends.
+    pub fn new() -> Loopback {
+        let queue = Rc::new(RefCell::new(VecDeque::new()));
+        Loopback {
+            outgoing: queue.clone(),
+            incoming: queue,
+        }
+    }
+
+    /// Creates the two ends of a cable, each receiving what the other sends.
+    pub fn pair() -> (Loopback, Loopback) {
+        let a = Rc::new(RefCell::new(VecDeque::new()));
+        let b = Rc::new(RefCell::new(VecDeque::new()));
+        (
+            Loopback {
+                outgoing: a.clone(),
+                incoming: b.clone(),
+            },
+            Loopback {
+                outgoing: b,
+                incoming: a,
+            },
+        )
+    }
+}
+
+impl MidiBackend for Loopback {
+    fn get_name(&self) -> &str {
+        "loopback"
+    }
+
+    fn send(&mut self, message: MidiMessage) -> Result<(), MidiError> {
+        self.outgoing.borrow_mut().push_back(message);
+        Ok(())
+    }
+
+    fn receive(&mut self) -> Result<Vec<MidiMessage>, MidiError> {
+        Ok(self.incoming.borrow_mut().drain(..).collect())
+    }
+}
+
+impl Default for Loopback {
+    fn default() -> Self {
+        Loopback::new()
+    }
+}
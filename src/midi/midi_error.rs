@@ -0,0 +1,10 @@
+/// An error from a MIDI backend.
+#[derive(thiserror::Error, Debug)]
+pub enum MidiError {
+    #[error("MIDI backend error: {0}")]
+    Backend(String),
+    #[error("Unknown MIDI backend `{0}`, expected `alsa` or `loopback`")]
+    UnknownBackend(String),
+    #[error("BSDj was built without the `{0}` MIDI backend")]
+    Unsupported(&'static str),
+}
@@ -0,0 +1,350 @@
+//! Sending and receiving MIDI while the app runs, through a swappable backend.
+//!
+//! While playing, the clock goes out at 24 pulses per beat along with start and
+//! stop, and each channel's notes are echoed on its own MIDI channel. With the
+//! sync mode set to `midi`, playback follows the incoming clock instead.
+//!
+//! Everything received is also sent on as a `MidiMessage` event, which the
+//! Phrase screen enters notes from.
+
+use crate::cli::CliArgs;
+use crate::resources::types::*;
+use crate::sync::{ClockFollower, SyncMode, SyncSettings, Transport, PULSES_PER_ROW};
+use bevy::prelude::*;
+
+#[cfg(feature = "alsa")]
+pub mod alsa;
+pub mod echo;
+pub mod entry;
+pub mod loopback;
+pub mod midi_error;
+
+#[cfg(feature = "alsa")]
+pub use self::alsa::AlsaBackend;
+pub use echo::NoteEcho;
+pub use entry::NoteEntry;
+pub use loopback::Loopback;
+pub use midi_error::*;
+
+/// A MIDI message BSDj sends or understands. Channels count from 0.
+///
+/// Also the event for each message received.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MidiMessage {
+    NoteOn { channel: u8, note: u8, velocity: u8 },
+    NoteOff { channel: u8, note: u8 },
+    Clock,
+    Start,
+    Continue,
+    Stop,
+}
+
+/// Somewhere MIDI messages can be sent to and received from.
+pub trait MidiBackend {
+    /// Returns the name the backend is chosen by.
+    fn get_name(&self) -> &str;
+
+    /// Sends a message.
+    fn send(&mut self, message: MidiMessage) -> Result<(), MidiError>;
+
+    /// Returns every message received since the last call, without blocking.
+    fn receive(&mut self) -> Result<Vec<MidiMessage>, MidiError>;
+}
+
+/// Opens a backend by name.
+///
+/// # ERRORS
+///
+/// Errors if there is no backend by that name, or it couldn't be opened.
+pub fn open_backend(name: &str) -> Result<Box<dyn MidiBackend>, MidiError> {
+    match name {
+        #[cfg(feature = "alsa")]
+        "alsa" => Ok(Box::new(AlsaBackend::open()?)),
+        #[cfg(not(feature = "alsa"))]
+        "alsa" => Err(MidiError::Unsupported("alsa")),
+        "loopback" => Ok(Box::new(Loopback::new())),
+        _ => Err(MidiError::UnknownBackend(name.to_string())),
+    }
+}
+
+/// The MIDI backend, and what has been sent and received through it.
+pub struct Midi {
+    backend: Box<dyn MidiBackend>,
+    echo: NoteEcho,
+    /// The pulse the clock was last sent for, while playing.
+    pulse: Option<u64>,
+    /// The phrase row notes were last echoed for, while playing.
+    row: Option<u64>,
+    /// Works out the tempo of the incoming clock.
+    follower: ClockFollower,
+}
+
+impl Midi {
+    /// Wraps a backend.
+    pub fn new(backend: Box<dyn MidiBackend>) -> Midi {
+        Midi {
+            backend,
+            echo: NoteEcho::default(),
+            pulse: None,
+            row: None,
+            follower: ClockFollower::default(),
+        }
+    }
+
+    /// Returns the backend.
+    pub fn get_backend(&mut self) -> &mut dyn MidiBackend {
+        self.backend.as_mut()
+    }
+
+    /// Follows the incoming clock, start and stop messages.
+    pub fn follow(&mut self, messages: &[MidiMessage], now: f64, transport: &mut Transport) {
+        for message in messages {
+            match message {
+                MidiMessage::Start => {
+                    transport.start();
+                    self.follower.reset();
+                }
+                MidiMessage::Continue => transport.resume(),
+                MidiMessage::Stop => transport.stop(),
+                MidiMessage::Clock if transport.is_playing() => {
+                    // Each pulse moves playback on by exactly one.
+                    transport.move_to(transport.get_pulse() as f64 + 1.);
+                    self.follower.receive(transport.get_pulse(), now);
+                }
+                _ => (),
+            }
+        }
+
+        if let Some(tempo) = self.follower.get_tempo() {
+            transport.set_tempo(tempo);
+        }
+    }
+
+    /// Returns the clock, start and stop messages for where playback is,
+    /// and the notes of the rows played since the last call.
+    pub fn play(
+        &mut self,
+        transport: &Transport,
+        send_clock: bool,
+        channels: &Channels,
+        chains: &Chains,
+        phrases: &Phrases,
+    ) -> Vec<MidiMessage> {
+        let mut messages = vec![];
+
+        if !transport.is_playing() {
+            if self.pulse.take().is_some() && send_clock {
+                messages.push(MidiMessage::Stop);
+            }
+            self.row = None;
+            messages.extend(self.echo.stop());
+            return messages;
+        }
+
+        let pulse = transport.get_pulse();
+        match self.pulse {
+            // Playback started again from the beginning.
+            Some(last) if pulse < last => {
+                self.row = None;
+                if send_clock {
+                    messages.push(MidiMessage::Start);
+                }
+            }
+            Some(last) if send_clock => {
+                messages.extend((last..pulse).map(|_| MidiMessage::Clock));
+            }
+            Some(_) => (),
+            None if send_clock => messages.push(MidiMessage::Start),
+            None => (),
+        }
+        self.pulse = Some(pulse);
+
+        let row = pulse / PULSES_PER_ROW as u64;
+        let first = match self.row {
+            Some(last) => last + 1,
+            None => row,
+        };
+        for row in first..=row {
+            messages.extend(self.echo.play_row(row, channels, chains, phrases));
+        }
+        self.row = Some(row);
+
+        messages
+    }
+}
+
+// region:      MidiPlugin
+
+/// Opens the MIDI backend passed to `--midi-backend`, if any, and runs MIDI through it.
+pub struct MidiPlugin;
+
+impl Plugin for MidiPlugin {
+    fn build(&self, app: &mut App) {
+        let cli = app
+            .world
+            .get_resource::<CliArgs>()
+            .cloned()
+            .unwrap_or_default();
+
+        let mut note_entry = NoteEntry::default();
+        if let Some(step) = cli.note_step {
+            note_entry.set_step(step);
+        }
+        app.insert_resource(note_entry);
+        app.add_event::<MidiMessage>();
+
+        if let Some(name) = &cli.midi_backend {
+            match open_backend(name) {
+                Ok(backend) => {
+                    info!("Opened the {} MIDI backend", backend.get_name());
+                    app.insert_non_send_resource(Midi::new(backend));
+                }
+                Err(e) => error!("Could not open MIDI: {}", e),
+            }
+        }
+
+        app.add_system(run_midi);
+    }
+}
+
+/// Follows or sends the clock, and echoes the notes being played.
+#[allow(clippy::too_many_arguments)]
+fn run_midi(
+    midi: Option<NonSendMut<Midi>>,
+    mut events: EventWriter<MidiMessage>,
+    time: Res<Time>,
+    sync_settings: Res<SyncSettings>,
+    mut transport: ResMut<Transport>,
+    channels: Res<Channels>,
+    chains: Res<Chains>,
+    phrases: Res<Phrases>,
+) {
+    let mut midi = match midi {
+        Some(midi) => midi,
+        None => return,
+    };
+
+    let received = match midi.get_backend().receive() {
+        Ok(received) => received,
+        Err(e) => {
+            warn!("Could not receive MIDI: {}", e);
+            vec![]
+        }
+    };
+
+    for message in &received {
+        events.send(*message);
+    }
+
+    let following = sync_settings.get_mode() == SyncMode::Midi;
+    if following {
+        midi.follow(&received, time.seconds_since_startup(), &mut transport);
+    }
+
+    // The clock only goes out when it isn't coming in.
+    let messages = midi.play(&transport, !following, &channels, &chains, &phrases);
+    for message in messages {
+        if let Err(e) = midi.get_backend().send(message) {
+            warn!("Could not send {:?}: {}", message, e);
+        }
+    }
+}
+
+// endregion:   MidiPlugin
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    /// Play a song into a loopback and check the clock and notes that come out of the other end.
+    fn clock_and_notes_out() {
+        let (ours, mut synth) = Loopback::pair();
+        let mut midi = Midi::new(Box::new(ours));
+        let mut channels = Channels::default();
+        let mut chains = Chains::default();
+        let mut phrases = Phrases::default();
+        channels.get_mut(2).set_chain(0, 0);
+        chains.get_mut(0).unwrap().set_phrase(0, 1);
+        phrases.get_mut(1).unwrap().set_note(1, 1);
+        phrases.get_mut(1).unwrap().set_cmd(2, Command::Kill);
+
+        let mut transport = Transport::default();
+        transport.start();
+        let play = |transport: &Transport, midi: &mut Midi| {
+            for message in midi.play(transport, true, &channels, &chains, &phrases) {
+                midi.get_backend().send(message).unwrap();
+            }
+        };
+        play(&transport, &mut midi);
+        transport.move_to((PULSES_PER_ROW * 2) as f64);
+        play(&transport, &mut midi);
+        transport.stop();
+        play(&transport, &mut midi);
+
+        let received = synth.receive().unwrap();
+        let clocks = received
+            .iter()
+            .filter(|message| **message == MidiMessage::Clock)
+            .count();
+        assert_eq!(PULSES_PER_ROW as usize * 2, clocks);
+        assert_eq!(Some(&MidiMessage::Start), received.first());
+        assert_eq!(Some(&MidiMessage::Stop), received.last());
+        // The wave channel goes out on channel 3, an octave below the pulse channels.
+        let notes = received
+            .into_iter()
+            .filter(|message| !matches!(message, MidiMessage::Clock))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            vec![
+                MidiMessage::Start,
+                MidiMessage::NoteOn {
+                    channel: 2,
+                    note: 36,
+                    velocity: 100
+                },
+                MidiMessage::NoteOff {
+                    channel: 2,
+                    note: 36
+                },
+                MidiMessage::Stop,
+            ],
+            notes
+        );
+    }
+
+    #[test]
+    /// Follow an incoming clock, which moves playback a pulse at a time.
+    fn follow_clock() {
+        let mut midi = Midi::new(Box::new(Loopback::new()));
+        let mut transport = Transport::default();
+        let mut messages = vec![MidiMessage::Start];
+        messages.extend([MidiMessage::Clock; 30]);
+        midi.follow(&messages, 0., &mut transport);
+        assert!(transport.is_playing());
+        assert_eq!(30, transport.get_pulse());
+
+        midi.follow(
+            &[MidiMessage::Stop, MidiMessage::Clock],
+            0.1,
+            &mut transport,
+        );
+        assert!(!transport.is_playing());
+        assert_eq!(30, transport.get_pulse());
+    }
+
+    #[test]
+    /// MIDI notes convert back to the notes they were exported from, and live
+    /// recorded notes round to the nearest row.
+    fn note_entry() {
+        assert_eq!(Some(1), entry::from_midi_note(48, false));
+        assert_eq!(Some(13), entry::from_midi_note(48, true));
+        assert_eq!(None, entry::from_midi_note(47, false));
+        assert_eq!(Some(80), entry::from_midi_note(127, false));
+
+        let mut transport = Transport::default();
+        transport.start();
+        transport.move_to((PULSES_PER_ROW * 0x13 - 1) as f64);
+        assert_eq!(3, entry::playing_row(&transport));
+    }
+}
@@ -0,0 +1,14 @@
+use std::io;
+
+/// An error that occurs while reading a key bindings file.
+#[derive(thiserror::Error, Debug)]
+pub enum BindingsError {
+    #[error("Could not read the key bindings: {0}")]
+    Io(#[from] io::Error),
+    #[error("Line {line}: unknown action `{name}`")]
+    UnknownAction { line: usize, name: String },
+    #[error("Line {line}: unknown key or button `{name}`")]
+    UnknownInput { line: usize, name: String },
+    #[error("Line {line}: `{action}` has no keys")]
+    NoInputs { line: usize, action: String },
+}
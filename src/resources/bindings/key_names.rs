@@ -0,0 +1,124 @@
+use super::super::input::InputType;
+use bevy::prelude::*;
+
+/// The names of the inputs that can be bound, as written in the bindings file.
+/// Every name is drawable with the font, so the Help screen can show them.
+pub const INPUT_NAMES: [(&str, InputType); 95] = [
+    ("a", InputType::Key(KeyCode::A)),
+    ("b", InputType::Key(KeyCode::B)),
+    ("c", InputType::Key(KeyCode::C)),
+    ("d", InputType::Key(KeyCode::D)),
+    ("e", InputType::Key(KeyCode::E)),
+    ("f", InputType::Key(KeyCode::F)),
+    ("g", InputType::Key(KeyCode::G)),
+    ("h", InputType::Key(KeyCode::H)),
+    ("i", InputType::Key(KeyCode::I)),
+    ("j", InputType::Key(KeyCode::J)),
+    ("k", InputType::Key(KeyCode::K)),
+    ("l", InputType::Key(KeyCode::L)),
+    ("m", InputType::Key(KeyCode::M)),
+    ("n", InputType::Key(KeyCode::N)),
+    ("o", InputType::Key(KeyCode::O)),
+    ("p", InputType::Key(KeyCode::P)),
+    ("q", InputType::Key(KeyCode::Q)),
+    ("r", InputType::Key(KeyCode::R)),
+    ("s", InputType::Key(KeyCode::S)),
+    ("t", InputType::Key(KeyCode::T)),
+    ("u", InputType::Key(KeyCode::U)),
+    ("v", InputType::Key(KeyCode::V)),
+    ("w", InputType::Key(KeyCode::W)),
+    ("x", InputType::Key(KeyCode::X)),
+    ("y", InputType::Key(KeyCode::Y)),
+    ("z", InputType::Key(KeyCode::Z)),
+    ("0", InputType::Key(KeyCode::Key0)),
+    ("1", InputType::Key(KeyCode::Key1)),
+    ("2", InputType::Key(KeyCode::Key2)),
+    ("3", InputType::Key(KeyCode::Key3)),
+    ("4", InputType::Key(KeyCode::Key4)),
+    ("5", InputType::Key(KeyCode::Key5)),
+    ("6", InputType::Key(KeyCode::Key6)),
+    ("7", InputType::Key(KeyCode::Key7)),
+    ("8", InputType::Key(KeyCode::Key8)),
+    ("9", InputType::Key(KeyCode::Key9)),
+    ("f1", InputType::Key(KeyCode::F1)),
+    ("f2", InputType::Key(KeyCode::F2)),
+    ("f3", InputType::Key(KeyCode::F3)),
+    ("f4", InputType::Key(KeyCode::F4)),
+    ("f5", InputType::Key(KeyCode::F5)),
+    ("f6", InputType::Key(KeyCode::F6)),
+    ("f7", InputType::Key(KeyCode::F7)),
+    ("f8", InputType::Key(KeyCode::F8)),
+    ("f9", InputType::Key(KeyCode::F9)),
+    ("f10", InputType::Key(KeyCode::F10)),
+    ("f11", InputType::Key(KeyCode::F11)),
+    ("f12", InputType::Key(KeyCode::F12)),
+    ("up", InputType::Key(KeyCode::Up)),
+    ("down", InputType::Key(KeyCode::Down)),
+    ("left", InputType::Key(KeyCode::Left)),
+    ("right", InputType::Key(KeyCode::Right)),
+    ("space", InputType::Key(KeyCode::Space)),
+    ("enter", InputType::Key(KeyCode::Return)),
+    ("tab", InputType::Key(KeyCode::Tab)),
+    ("esc", InputType::Key(KeyCode::Escape)),
+    ("back", InputType::Key(KeyCode::Back)),
+    ("del", InputType::Key(KeyCode::Delete)),
+    ("ins", InputType::Key(KeyCode::Insert)),
+    ("home", InputType::Key(KeyCode::Home)),
+    ("end", InputType::Key(KeyCode::End)),
+    ("pgup", InputType::Key(KeyCode::PageUp)),
+    ("pgdn", InputType::Key(KeyCode::PageDown)),
+    ("ctrl", InputType::Key(KeyCode::LControl)),
+    ("rctrl", InputType::Key(KeyCode::RControl)),
+    ("shift", InputType::Key(KeyCode::LShift)),
+    ("rshift", InputType::Key(KeyCode::RShift)),
+    ("alt", InputType::Key(KeyCode::LAlt)),
+    ("ralt", InputType::Key(KeyCode::RAlt)),
+    ("-", InputType::Key(KeyCode::Minus)),
+    ("=", InputType::Key(KeyCode::Equals)),
+    (",", InputType::Key(KeyCode::Comma)),
+    (".", InputType::Key(KeyCode::Period)),
+    (";", InputType::Key(KeyCode::Semicolon)),
+    ("/", InputType::Key(KeyCode::Slash)),
+    ("lbracket", InputType::Key(KeyCode::LBracket)),
+    ("rbracket", InputType::Key(KeyCode::RBracket)),
+    ("plus", InputType::Key(KeyCode::Plus)),
+    ("lmb", InputType::Mouse(MouseButton::Left)),
+    ("rmb", InputType::Mouse(MouseButton::Right)),
+    ("mmb", InputType::Mouse(MouseButton::Middle)),
+    ("pad-s", InputType::Gamepad(GamepadButtonType::South)),
+    ("pad-e", InputType::Gamepad(GamepadButtonType::East)),
+    ("pad-n", InputType::Gamepad(GamepadButtonType::North)),
+    ("pad-w", InputType::Gamepad(GamepadButtonType::West)),
+    ("pad-l", InputType::Gamepad(GamepadButtonType::LeftTrigger)),
+    ("pad-r", InputType::Gamepad(GamepadButtonType::RightTrigger)),
+    (
+        "pad-l2",
+        InputType::Gamepad(GamepadButtonType::LeftTrigger2),
+    ),
+    (
+        "pad-r2",
+        InputType::Gamepad(GamepadButtonType::RightTrigger2),
+    ),
+    ("pad-sel", InputType::Gamepad(GamepadButtonType::Select)),
+    ("pad-st", InputType::Gamepad(GamepadButtonType::Start)),
+    ("pad-up", InputType::Gamepad(GamepadButtonType::DPadUp)),
+    ("pad-dn", InputType::Gamepad(GamepadButtonType::DPadDown)),
+    ("pad-lt", InputType::Gamepad(GamepadButtonType::DPadLeft)),
+    ("pad-rt", InputType::Gamepad(GamepadButtonType::DPadRight)),
+];
+
+/// Returns the input with this name.
+pub fn input_from_name(name: &str) -> Option<InputType> {
+    INPUT_NAMES
+        .iter()
+        .find(|(input_name, _)| *input_name == name)
+        .map(|(_, input)| *input)
+}
+
+/// Returns the name of an input, or None if it can't be bound.
+pub fn input_name(input: &InputType) -> Option<&'static str> {
+    INPUT_NAMES
+        .iter()
+        .find(|(_, named_input)| named_input == input)
+        .map(|(name, _)| *name)
+}
@@ -0,0 +1,363 @@
+//! Which keys and buttons do what, loaded from a user's bindings file on top of the defaults.
+
+use super::input::InputType;
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs;
+
+pub mod bindings_error;
+pub mod key_names;
+
+pub use bindings_error::*;
+pub use key_names::*;
+
+/// The name of the bindings file in the user's config directory.
+pub const BINDINGS_FILENAME: &str = "bindings.txt";
+
+/// Something the user can do with a key or button.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Action {
+    /// Bookmark the chain under the Song screen's cursor.
+    Bookmark,
+    /// Delete the value under the cursor.
+    Delete,
+    /// Held while scrolling to change the value under the mouse.
+    Edit,
+    PrevPalette,
+    NextPalette,
+    ZoomIn,
+    ZoomOut,
+    /// Start or stop playback.
+    Play,
+    /// Toggle the Phrase screen's piano mode.
+    Piano,
+    /// Move the piano down an octave.
+    OctaveDown,
+    /// Move the piano up an octave.
+    OctaveUp,
+    /// Toggle live recording of MIDI notes.
+    Record,
+    /// Move the cursor further after a note is entered from a MIDI keyboard.
+    NoteStepUp,
+    /// Move the cursor less far after a note is entered from a MIDI keyboard.
+    NoteStepDown,
+    ExportMidi,
+    ExportUge,
+    ExportVgm,
+    SaveText,
+    /// The Game Boy's A button.
+    ButtonA,
+    /// The Game Boy's B button.
+    ButtonB,
+    /// The Game Boy's Select button.
+    Select,
+    /// Save the screen as a PNG.
+    Screenshot,
+    /// Start or stop capturing the screen to a GIF.
+    Capture,
+    /// Open the font editor.
+    FontEditor,
+    /// Open the palette editor.
+    PaletteEditor,
+    /// Save what an editor is editing to a file of its own.
+    SaveAs,
+}
+
+impl Action {
+    /// Returns every action, in the order the Help screen shows them.
+    pub fn iter() -> [Action; 26] {
+        [
+            Action::Bookmark,
+            Action::Delete,
+            Action::Edit,
+            Action::PrevPalette,
+            Action::NextPalette,
+            Action::ZoomIn,
+            Action::ZoomOut,
+            Action::Play,
+            Action::Piano,
+            Action::OctaveDown,
+            Action::OctaveUp,
+            Action::Record,
+            Action::NoteStepUp,
+            Action::NoteStepDown,
+            Action::ExportMidi,
+            Action::ExportUge,
+            Action::ExportVgm,
+            Action::SaveText,
+            Action::ButtonA,
+            Action::ButtonB,
+            Action::Select,
+            Action::Screenshot,
+            Action::Capture,
+            Action::FontEditor,
+            Action::PaletteEditor,
+            Action::SaveAs,
+        ]
+    }
+
+    /// Returns the name of the action, as written in the bindings file.
+    pub fn get_name(&self) -> &'static str {
+        match self {
+            Action::Bookmark => "bookmark",
+            Action::Delete => "delete",
+            Action::Edit => "edit",
+            Action::PrevPalette => "prev-pal",
+            Action::NextPalette => "next-pal",
+            Action::ZoomIn => "zoom-in",
+            Action::ZoomOut => "zoom-out",
+            Action::Play => "play",
+            Action::Piano => "piano",
+            Action::OctaveDown => "oct-dn",
+            Action::OctaveUp => "oct-up",
+            Action::Record => "record",
+            Action::NoteStepUp => "step-up",
+            Action::NoteStepDown => "step-dn",
+            Action::ExportMidi => "exp-mid",
+            Action::ExportUge => "exp-uge",
+            Action::ExportVgm => "exp-vgm",
+            Action::SaveText => "save",
+            Action::ButtonA => "btn-a",
+            Action::ButtonB => "btn-b",
+            Action::Select => "select",
+            Action::Screenshot => "shot",
+            Action::Capture => "capture",
+            Action::FontEditor => "font",
+            Action::PaletteEditor => "palette",
+            Action::SaveAs => "save-as",
+        }
+    }
+
+    /// Returns the action with this name.
+    pub fn from_name(name: &str) -> Option<Action> {
+        Action::iter()
+            .into_iter()
+            .find(|action| action.get_name() == name)
+    }
+
+    /// Returns the bindings the action has unless the user changes them.
+    pub fn default_bindings(&self) -> Vec<Binding> {
+        let key = |code| InputType::Key(code);
+        let pad = |button| InputType::Gamepad(button);
+        let ctrl = key(KeyCode::LControl);
+        let bindings = match self {
+            Action::Bookmark => vec![vec![key(KeyCode::M)]],
+            Action::Delete => vec![vec![key(KeyCode::Delete)], vec![key(KeyCode::Back)]],
+            Action::Edit => vec![vec![ctrl]],
+            Action::PrevPalette => vec![vec![key(KeyCode::F1)]],
+            Action::NextPalette => vec![vec![key(KeyCode::F2)]],
+            Action::ZoomIn => vec![vec![ctrl, key(KeyCode::Equals)]],
+            Action::ZoomOut => vec![vec![ctrl, key(KeyCode::Minus)]],
+            // Start plays, as on the Game Boy.
+            Action::Play => vec![
+                vec![key(KeyCode::Space)],
+                vec![pad(GamepadButtonType::Start)],
+            ],
+            Action::Piano => vec![vec![ctrl, key(KeyCode::P)]],
+            Action::OctaveDown => vec![vec![key(KeyCode::Minus)]],
+            Action::OctaveUp => vec![vec![key(KeyCode::Equals)]],
+            Action::Record => vec![vec![ctrl, key(KeyCode::R)]],
+            Action::NoteStepUp => vec![vec![ctrl, key(KeyCode::Up)]],
+            Action::NoteStepDown => vec![vec![ctrl, key(KeyCode::Down)]],
+            Action::ExportMidi => vec![vec![key(KeyCode::F5)]],
+            Action::ExportUge => vec![vec![key(KeyCode::F6)]],
+            Action::ExportVgm => vec![vec![key(KeyCode::F7)]],
+            Action::SaveText => vec![vec![key(KeyCode::F8)]],
+            Action::Screenshot => vec![vec![key(KeyCode::F12)]],
+            Action::Capture => vec![vec![key(KeyCode::F11)]],
+            Action::FontEditor => vec![vec![key(KeyCode::F9)]],
+            Action::PaletteEditor => vec![vec![key(KeyCode::F10)]],
+            Action::SaveAs => vec![vec![ctrl, key(KeyCode::S)]],
+            // On gamepads, A and B are on the right and bottom, as on the Game Boy.
+            // The keys stay clear of the hex and piano keys.
+            Action::ButtonA => vec![
+                vec![key(KeyCode::LShift)],
+                vec![pad(GamepadButtonType::East)],
+            ],
+            Action::ButtonB => vec![
+                vec![key(KeyCode::LAlt)],
+                vec![pad(GamepadButtonType::South)],
+            ],
+            Action::Select => vec![
+                vec![key(KeyCode::Tab)],
+                vec![pad(GamepadButtonType::Select)],
+            ],
+        };
+        bindings.into_iter().map(Binding::new).collect()
+    }
+}
+
+impl fmt::Display for Action {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.get_name())
+    }
+}
+
+/// Inputs that are held together to do an action, such as `ctrl+=`.
+/// The action happens when the last input is pressed while the others are held.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Binding {
+    inputs: Vec<InputType>,
+}
+
+impl Binding {
+    /// # PANICS
+    ///
+    /// Panics if there are no inputs.
+    pub fn new(inputs: Vec<InputType>) -> Binding {
+        assert!(!inputs.is_empty(), "A binding needs at least one input");
+        Binding { inputs }
+    }
+
+    /// Returns every input of the binding.
+    pub fn get_inputs(&self) -> &[InputType] {
+        &self.inputs
+    }
+
+    /// Returns the input that does the action when pressed.
+    pub fn get_trigger(&self) -> &InputType {
+        self.inputs.last().unwrap()
+    }
+}
+
+impl fmt::Display for Binding {
+    /// Writes the binding as in the bindings file, such as `ctrl+=`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let names: Vec<_> = self
+            .inputs
+            .iter()
+            .map(|input| input_name(input).unwrap_or("?"))
+            .collect();
+        write!(f, "{}", names.join("+"))
+    }
+}
+
+/// The bindings of every action.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyBindings {
+    bindings: BTreeMap<Action, Vec<Binding>>,
+}
+
+impl Default for KeyBindings {
+    fn default() -> KeyBindings {
+        KeyBindings {
+            bindings: Action::iter()
+                .into_iter()
+                .map(|action| (action, action.default_bindings()))
+                .collect(),
+        }
+    }
+}
+
+impl KeyBindings {
+    /// Reads a bindings file, keeping the default bindings of the actions it doesn't mention.
+    ///
+    /// # FILE STRUCTURE
+    ///
+    /// Each line is an action followed by its bindings, separated by spaces.
+    /// The inputs of a binding are joined with `+`. Lines starting with `#` are comments.
+    /// For example, `delete del back` or `zoom-in ctrl+=`.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if the file can't be read, or names an unknown action or input.
+    pub fn load(filename: &str) -> Result<KeyBindings, BindingsError> {
+        KeyBindings::parse(&fs::read_to_string(filename)?)
+    }
+
+    /// Parses the contents of a bindings file. See `KeyBindings::load`.
+    pub fn parse(contents: &str) -> Result<KeyBindings, BindingsError> {
+        let mut key_bindings = KeyBindings::default();
+
+        for (idx, line) in contents.lines().enumerate() {
+            let line_number = idx + 1;
+            let mut words = line.split_whitespace();
+            let name = match words.next() {
+                Some(name) if !name.starts_with('#') => name,
+                _ => continue,
+            };
+            let action = Action::from_name(name).ok_or_else(|| BindingsError::UnknownAction {
+                line: line_number,
+                name: name.to_string(),
+            })?;
+
+            let mut bindings = vec![];
+            for word in words {
+                let inputs = word
+                    .split('+')
+                    .map(|input| {
+                        input_from_name(input).ok_or_else(|| BindingsError::UnknownInput {
+                            line: line_number,
+                            name: input.to_string(),
+                        })
+                    })
+                    .collect::<Result<Vec<_>, _>>()?;
+                bindings.push(Binding::new(inputs));
+            }
+            if bindings.is_empty() {
+                return Err(BindingsError::NoInputs {
+                    line: line_number,
+                    action: name.to_string(),
+                });
+            }
+
+            key_bindings.set(action, bindings);
+        }
+
+        Ok(key_bindings)
+    }
+
+    /// Returns the bindings of an action.
+    pub fn get(&self, action: Action) -> &[Binding] {
+        self.bindings
+            .get(&action)
+            .map(|bindings| bindings.as_slice())
+            .unwrap_or(&[])
+    }
+
+    /// Replaces the bindings of an action.
+    pub fn set(&mut self, action: Action, bindings: Vec<Binding>) {
+        self.bindings.insert(action, bindings);
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    /// Actions in the file replace their defaults, and the others are kept.
+    fn parse_bindings() {
+        let contents = "# Bookmark with B\nbookmark b\n\nzoom-in ctrl+shift+= plus\n";
+        let bindings = KeyBindings::parse(contents).unwrap();
+
+        assert_eq!(
+            bindings.get(Action::Bookmark),
+            &[Binding::new(vec![InputType::Key(KeyCode::B)])]
+        );
+        let zoom_in: Vec<_> = bindings
+            .get(Action::ZoomIn)
+            .iter()
+            .map(|binding| binding.to_string())
+            .collect();
+        assert_eq!(zoom_in, ["ctrl+shift+=", "plus"]);
+        assert_eq!(
+            bindings.get(Action::Delete),
+            KeyBindings::default().get(Action::Delete)
+        );
+
+        assert!(matches!(
+            KeyBindings::parse("bookmark b\njump j"),
+            Err(BindingsError::UnknownAction { line: 2, .. })
+        ));
+        assert!(matches!(
+            KeyBindings::parse("delete ctrl+nope"),
+            Err(BindingsError::UnknownInput { line: 1, .. })
+        ));
+        assert!(matches!(
+            KeyBindings::parse("delete"),
+            Err(BindingsError::NoInputs { line: 1, .. })
+        ));
+    }
+}
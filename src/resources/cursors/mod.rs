@@ -1,13 +1,19 @@
 use bevy::prelude::*;
 
 pub mod chain;
+pub mod font;
 pub mod nav;
+pub mod palette;
 pub mod phrase;
+pub mod project;
 pub mod song;
 
 pub use chain::ChainCursor;
+pub use font::FontCursor;
 pub use nav::NavCursor;
+pub use palette::PaletteCursor;
 pub use phrase::PhraseCursor;
+pub use project::ProjectCursor;
 pub use song::SongCursor;
 
 pub struct CursorPlugin;
@@ -18,6 +24,9 @@ impl Plugin for CursorPlugin {
         app.insert_resource(SongCursor::new());
         app.insert_resource(ChainCursor::new());
         app.insert_resource(PhraseCursor::new());
+        app.insert_resource(ProjectCursor::new());
+        app.insert_resource(FontCursor::new());
+        app.insert_resource(PaletteCursor::new());
     }
 }
 
@@ -0,0 +1,52 @@
+use super::Cursor;
+
+/// Indicates the value the cursor is on in the palette editor.
+/// The first rows are the palette's slots, and the rest the edited slot's colors,
+/// with X being the red, green or blue of the color.
+pub struct PaletteCursor {
+    /// The X position of the cursor
+    x: u8,
+    /// The Y position of the cursor
+    y: u8,
+}
+
+impl Cursor for PaletteCursor {
+    const MIN_X: isize = 0;
+    const MAX_X: isize = 2;
+    const MIN_Y: isize = 0;
+    const MAX_Y: isize = 9;
+
+    fn new() -> PaletteCursor {
+        PaletteCursor { x: 0, y: 0 }
+    }
+
+    fn get_pos(&self) -> (u8, u8) {
+        (self.x, self.y)
+    }
+    fn get_x(&self) -> u8 {
+        self.x
+    }
+    fn get_y(&self) -> u8 {
+        self.y
+    }
+
+    fn set_x(&mut self, x: isize) {
+        self.x = self.clamp_x(x) as u8;
+    }
+    fn add_x(&mut self) {
+        self.set_x(self.x as isize + 1)
+    }
+    fn sub_x(&mut self) {
+        self.set_x(self.x as isize - 1)
+    }
+
+    fn set_y(&mut self, y: isize) {
+        self.y = self.clamp_y(y) as u8;
+    }
+    fn add_y(&mut self) {
+        self.set_y(self.y as isize + 1)
+    }
+    fn sub_y(&mut self) {
+        self.set_y(self.y as isize - 1)
+    }
+}
@@ -0,0 +1,42 @@
+use super::Cursor;
+
+/// Indicates the setting the cursor is on on the project screen.
+pub struct ProjectCursor {
+    /// The Y position of the cursor
+    y: u8,
+}
+
+impl Cursor for ProjectCursor {
+    const MIN_X: isize = 0;
+    const MAX_X: isize = 0;
+    const MIN_Y: isize = 0;
+    const MAX_Y: isize = 1;
+
+    fn new() -> ProjectCursor {
+        ProjectCursor { y: 0 }
+    }
+
+    fn get_pos(&self) -> (u8, u8) {
+        (0, self.y)
+    }
+    fn get_x(&self) -> u8 {
+        0
+    }
+    fn get_y(&self) -> u8 {
+        self.y
+    }
+
+    fn set_x(&mut self, _x: isize) {}
+    fn add_x(&mut self) {}
+    fn sub_x(&mut self) {}
+
+    fn set_y(&mut self, y: isize) {
+        self.y = self.clamp_y(y) as u8;
+    }
+    fn add_y(&mut self) {
+        self.set_y(self.y as isize + 1)
+    }
+    fn sub_y(&mut self) {
+        self.set_y(self.y as isize - 1)
+    }
+}
@@ -12,6 +12,8 @@ impl Plugin for EditedPlugin {
         app.insert_resource(EditedSynth(0));
         app.insert_resource(EditedWaveframe(0));
         app.insert_resource(EditedWord(0));
+        app.insert_resource(EditedGlyph(0));
+        app.insert_resource(EditedColorSlot(0));
     }
 }
 
@@ -23,3 +25,7 @@ pub struct EditedGroove(pub u8);
 pub struct EditedSynth(pub u8);
 pub struct EditedWaveframe(pub u8);
 pub struct EditedWord(pub u8);
+/// The index of the glyph in the font editor, in the order the font's file has them.
+pub struct EditedGlyph(pub u8);
+/// The index of the slot in the palette editor, in the order of `Colors::iter`.
+pub struct EditedColorSlot(pub u8);
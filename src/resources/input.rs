@@ -1,43 +1,102 @@
+use super::bindings::{Action, KeyBindings, BINDINGS_FILENAME};
+use super::recording::{InputChange, InputRecording};
+use super::touch_pad;
+use crate::cli::CliArgs;
 use crate::utils;
 use bevy::input::{
+    gamepad::GamepadSettings,
     keyboard::KeyboardInput,
     mouse::{MouseButtonInput, MouseWheel},
+    touch::{TouchInput, TouchPhase},
 };
 use bevy::prelude::*;
 use std::collections::HashMap;
 use std::collections::HashSet;
+use std::collections::VecDeque;
 
 /// The number of frames between a left click being released and repressed
 /// for it to be considered a double-click. By default, this program runs
 /// at 60fps.
 pub const DOUBLE_CLICK_FRAME_COUNT: u64 = 30;
 
+/// How far a d-pad or stick axis must be pushed for its direction to be pressed.
+pub const AXIS_THRESHOLD: f32 = 0.5;
+
+/// The pixels a finger moves up or down the screen to scroll by one.
+pub const SWIPE_STEP: f32 = 8.;
+
+/// The label of the system that reads this frame's inputs, for systems that must run after it.
+pub const UPDATE_INPUTS: &str = "update_inputs";
+
 pub struct InputPlugin;
 
 impl Plugin for InputPlugin {
     fn build(&self, app: &mut App) {
-        app.insert_resource(InputRes::new());
+        let cli = app
+            .world
+            .get_resource::<CliArgs>()
+            .cloned()
+            .unwrap_or_default();
+
+        let mut input = InputRes::new();
+        if let Some(bindings) = load_bindings(cli.bindings) {
+            input.set_bindings(bindings);
+        }
+        input.touch_pad = cli.touch_pad;
+        app.insert_resource(input);
         app.add_system_set_to_stage(
             CoreStage::PreUpdate,
-            SystemSet::new().with_system(update_inputs),
+            SystemSet::new().with_system(update_inputs.label(UPDATE_INPUTS)),
         );
     }
 }
 
+/// Loads the bindings file passed to `--bindings`, or the one in the user's config
+/// directory if there is one. Returns None to keep the defaults.
+fn load_bindings(filename: Option<String>) -> Option<KeyBindings> {
+    let filename = match filename {
+        Some(filename) => filename,
+        None => {
+            let path = utils::config_dir()?.join(BINDINGS_FILENAME);
+            if !path.exists() {
+                return None;
+            }
+            path.to_string_lossy().to_string()
+        }
+    };
+
+    match KeyBindings::load(&filename) {
+        Ok(bindings) => {
+            info!("Loaded key bindings from `{}`", filename);
+            Some(bindings)
+        }
+        Err(e) => {
+            error!("Using the default key bindings. {}", e);
+            None
+        }
+    }
+}
+
 #[derive(Copy, Clone, PartialEq, Eq, Debug, Hash)]
 pub enum InputType {
     Key(KeyCode),
     Mouse(MouseButton),
+    /// A button on any connected gamepad.
+    Gamepad(GamepadButtonType),
 }
 
 impl InputType {
-    /// Returns the 4 directional keycodes.
-    pub fn directional_keycodes() -> [InputType; 4] {
+    /// Returns the 4 directional keycodes, followed by the 4 directions of a gamepad's d-pad.
+    pub fn directional_keycodes() -> [InputType; 8] {
         [
             InputType::Key(KeyCode::Up),
             InputType::Key(KeyCode::Down),
             InputType::Key(KeyCode::Left),
             InputType::Key(KeyCode::Right),
+            InputType::Gamepad(GamepadButtonType::DPadUp),
+            InputType::Gamepad(GamepadButtonType::DPadDown),
+            InputType::Gamepad(GamepadButtonType::DPadLeft),
+            InputType::Gamepad(GamepadButtonType::DPadRight),
         ]
     }
 
@@ -69,6 +128,7 @@ impl InputType {
 }
 
 /// An enum of the 4 directional inputs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum DirectionalInput {
     Left,
     Right,
@@ -81,10 +141,18 @@ impl DirectionalInput {
     /// is not a 4-directional input.
     pub fn from_input_type(key: InputType) -> Option<DirectionalInput> {
         Some(match key {
-            InputType::Key(KeyCode::Up) => DirectionalInput::Up,
-            InputType::Key(KeyCode::Down) => DirectionalInput::Down,
-            InputType::Key(KeyCode::Left) => DirectionalInput::Left,
-            InputType::Key(KeyCode::Right) => DirectionalInput::Right,
+            InputType::Key(KeyCode::Up) | InputType::Gamepad(GamepadButtonType::DPadUp) => {
+                DirectionalInput::Up
+            }
+            InputType::Key(KeyCode::Down) | InputType::Gamepad(GamepadButtonType::DPadDown) => {
+                DirectionalInput::Down
+            }
+            InputType::Key(KeyCode::Left) | InputType::Gamepad(GamepadButtonType::DPadLeft) => {
+                DirectionalInput::Left
+            }
+            InputType::Key(KeyCode::Right) | InputType::Gamepad(GamepadButtonType::DPadRight) => {
+                DirectionalInput::Right
+            }
             _ => return None,
         })
     }
@@ -110,6 +178,15 @@ impl DirectionalInput {
     }
 }
 
+/// What a finger on the touchscreen is touching.
+#[derive(Copy, Clone, PartialEq, Debug)]
+enum Touch {
+    /// The screen, acting as the left mouse button. Stores where the finger last was.
+    Screen { last_y: f32 },
+    /// A button of the touch pad.
+    Button(InputType),
+}
+
 /// A struct containing user inputs.
 pub struct InputRes {
     /// The currently pressed keys, along with when they started being held.
@@ -133,6 +210,21 @@ pub struct InputRes {
     key_delay: Option<u64>,
     /// The time between repeating key presses.
     key_repeat: u64,
+
+    /// The inputs each action is bound to.
+    bindings: KeyBindings,
+
+    /// The fingers on the touchscreen, by their IDs.
+    touches: HashMap<u64, Touch>,
+    /// Whether the touch pad is shown below the screen.
+    touch_pad: bool,
+
+    /// Whether changes are being recorded.
+    recording: bool,
+    /// The changes recorded since they were last taken, along with the frame they happened on.
+    recorded: Vec<(u64, InputChange)>,
+    /// The changes left to replay, in order. Live inputs are ignored until it's empty.
+    replay: VecDeque<(u64, InputChange)>,
 }
 
 impl InputRes {
@@ -147,12 +239,21 @@ impl InputRes {
             last_cursor_move: None,
             key_delay: Some(7),
             key_repeat: 3,
+            bindings: KeyBindings::default(),
+            touches: HashMap::new(),
+            touch_pad: false,
+            recording: false,
+            recorded: vec![],
+            replay: VecDeque::new(),
         }
     }
 
     /// Next frame reset.
     pub fn next_frame(&mut self) {
         self.current_time += 1;
+        // Get the remainder from the previous frame
+        // and set it to the current frame
+        self.scroll_delta = self.scroll_delta.rem_euclid(1.);
     }
 
     /// Gets the current time
@@ -253,6 +354,205 @@ impl InputRes {
         }
     }
 
+    /// Returns the inputs each action is bound to.
+    pub fn get_bindings(&self) -> &KeyBindings {
+        &self.bindings
+    }
+
+    /// Sets the inputs each action is bound to.
+    pub fn set_bindings(&mut self, bindings: KeyBindings) {
+        self.bindings = bindings;
+    }
+
+    /// Returns true if all the inputs of one of the action's bindings are being pressed.
+    /// NOTE: Other inputs may be pressed.
+    pub fn action_pressed(&self, action: Action) -> bool {
+        self.bindings
+            .get(action)
+            .iter()
+            .any(|binding| self.is_pressed(binding.get_inputs()))
+    }
+
+    /// Check if one of the action's bindings was pressed this frame.
+    pub fn action_just_pressed(&self, action: Action) -> bool {
+        self.bindings.get(action).iter().any(|binding| {
+            self.is_pressed(binding.get_inputs()) && self.just_pressed(binding.get_trigger())
+        })
+    }
+
+    /// Check if one of the action's bindings has been triggered, with key-delay/repeat.
+    pub fn action_dr_pressed(&self, action: Action) -> bool {
+        self.bindings.get(action).iter().any(|binding| {
+            self.is_pressed(binding.get_inputs()) && self.dr_pressed(binding.get_trigger())
+        })
+    }
+
+    /// Returns true if only the inputs of one of the action's bindings are being held.
+    pub fn action_exclusively_pressed(&self, action: Action) -> bool {
+        self.bindings
+            .get(action)
+            .iter()
+            .any(|binding| self.exclusively_pressed(binding.get_inputs()))
+    }
+
+    /// Check if one of the action's bindings was pressed this frame, with nothing else held.
+    pub fn action_exclusively_just_pressed(&self, action: Action) -> bool {
+        self.bindings.get(action).iter().any(|binding| {
+            self.exclusively_pressed(binding.get_inputs())
+                && self.just_pressed(binding.get_trigger())
+        })
+    }
+
+    /// Check if one of the action's bindings has been triggered with nothing else held,
+    /// with key-delay/repeat.
+    pub fn action_exclusively_dr_pressed(&self, action: Action) -> bool {
+        self.bindings.get(action).iter().any(|binding| {
+            self.exclusively_pressed(binding.get_inputs()) && self.dr_pressed(binding.get_trigger())
+        })
+    }
+
+    /// Returns true if scrolling changes the value under the cursor.
+    /// That is while the Edit action is held, or while a finger swipes on the screen.
+    pub fn scroll_editing(&self) -> bool {
+        self.action_exclusively_pressed(Action::Edit) || self.is_touching_screen()
+    }
+
+    /// Returns true if a finger is on the screen, rather than the touch pad.
+    pub fn is_touching_screen(&self) -> bool {
+        self.touches
+            .values()
+            .any(|touch| matches!(touch, Touch::Screen { .. }))
+    }
+
+    /// Returns true if the touch pad is shown below the screen.
+    pub fn has_touch_pad(&self) -> bool {
+        self.touch_pad
+    }
+
+    /// Starts a touch at a position on the window, in pixels from the top-left.
+    /// Touching the screen moves the cursor there and presses the left mouse button.
+    fn start_touch(&mut self, id: u64, position: (i32, i32), y: f32) {
+        let touch = if position.1 >= 144 {
+            if !self.touch_pad {
+                return;
+            }
+            match touch_pad::button_at(position.0 / 8, (position.1 - 144) / 8) {
+                Some(button) => Touch::Button(button),
+                None => return,
+            }
+        } else {
+            // Only one finger can act as the mouse.
+            if self.is_touching_screen() {
+                return;
+            }
+            self.cursor_position = position;
+            self.last_cursor_move = Some(self.current_time);
+            Touch::Screen { last_y: y }
+        };
+
+        match touch {
+            Touch::Screen { .. } => self.press_key(InputType::Mouse(MouseButton::Left)),
+            Touch::Button(button) => self.press_key(button),
+        }
+        self.touches.insert(id, touch);
+    }
+
+    /// Moves a touch. Swiping up or down the screen scrolls, one step for every `SWIPE_STEP`
+    /// pixels, leaving the cursor where the swipe started.
+    fn move_touch(&mut self, id: u64, y: f32) {
+        if let Some(Touch::Screen { last_y }) = self.touches.get_mut(&id) {
+            // Up the window is towards larger `y` values.
+            let moved = y - *last_y;
+            *last_y = y;
+            self.scroll_delta += moved / SWIPE_STEP;
+        }
+    }
+
+    /// Ends a touch, releasing what it pressed.
+    fn end_touch(&mut self, id: u64) {
+        match self.touches.remove(&id) {
+            Some(Touch::Screen { .. }) => {
+                self.release_key(InputType::Mouse(MouseButton::Left));
+                self.last_click_release = Some(self.current_time);
+            }
+            Some(Touch::Button(button)) => self.release_key(button),
+            None => (),
+        }
+    }
+
+    /// Changes the inputs, recording the change if recording.
+    /// Inputs that can't be bound are ignored, as they can't be recorded or replayed.
+    pub fn apply(&mut self, change: InputChange) {
+        if !change.is_recordable() {
+            return;
+        }
+        // Skip changes that do nothing, such as releasing what isn't pressed.
+        let changes = match change {
+            InputChange::Press(input) => !self.pressed_inputs.contains_key(&input),
+            InputChange::Release(input) => self.pressed_inputs.contains_key(&input),
+            InputChange::Cursor(x, y) => (x, y) != self.cursor_position,
+            InputChange::Scroll(delta) => delta != 0.,
+            _ => true,
+        };
+        if self.recording && changes {
+            self.recorded.push((self.current_time, change));
+        }
+
+        match change {
+            InputChange::Press(input) => self.press_key(input),
+            InputChange::Release(input) => {
+                self.release_key(input);
+                if input == InputType::Mouse(MouseButton::Left) {
+                    self.last_click_release = Some(self.current_time);
+                }
+            }
+            InputChange::Cursor(x, y) => {
+                if (x, y) != self.cursor_position {
+                    self.last_cursor_move = Some(self.current_time)
+                }
+                self.cursor_position = (x, y);
+            }
+            InputChange::Scroll(delta) => self.scroll_delta += delta,
+            InputChange::TouchStart { id, x, y, window_y } => {
+                self.start_touch(id, (x, y), window_y)
+            }
+            InputChange::TouchMove { id, window_y } => self.move_touch(id, window_y),
+            InputChange::TouchEnd { id } => self.end_touch(id),
+        }
+    }
+
+    /// Starts recording every change to the inputs.
+    pub fn start_recording(&mut self) {
+        self.recording = true;
+    }
+
+    /// Returns the changes recorded since this was last called.
+    pub fn take_recorded(&mut self) -> Vec<(u64, InputChange)> {
+        std::mem::take(&mut self.recorded)
+    }
+
+    /// Replays a recording, from the next frame onwards.
+    /// Its frames count from when the recording started, which is the start of the app.
+    pub fn start_replay(&mut self, recording: InputRecording) {
+        self.replay = recording.get_changes().iter().cloned().collect();
+    }
+
+    /// Returns true if a recording is being replayed.
+    pub fn is_replaying(&self) -> bool {
+        !self.replay.is_empty()
+    }
+
+    /// Applies the replayed changes of the current frame.
+    pub fn replay_frame(&mut self) {
+        while let Some((frame, _)) = self.replay.front() {
+            if *frame > self.current_time {
+                break;
+            }
+            let (_, change) = self.replay.pop_front().unwrap();
+            self.apply(change);
+        }
+    }
+
     /// Check if a double click has occured this frame.
     pub fn double_click(&self) -> bool {
         // Clicked within 30 frames of releasing and DID NOT MOVE THE CURSOR.
@@ -281,21 +581,41 @@ impl InputRes {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 fn update_inputs(
     mut input: ResMut<InputRes>,
     wnds: Res<Windows>,
     mut keyboard: EventReader<KeyboardInput>,
     mut mouse: EventReader<MouseButtonInput>,
     mut wheel: EventReader<MouseWheel>,
+    mut gamepad: EventReader<GamepadEvent>,
+    gamepad_settings: Res<GamepadSettings>,
+    mut touch: EventReader<TouchInput>,
 ) {
     input.next_frame();
 
-    // https://bevy-cheatbook.github.io/cookbook/cursor2world.html
-    // get the primary window
-    let wnd = wnds.get_primary().unwrap();
+    // A replay takes the place of live inputs until it ends.
+    if input.is_replaying() {
+        input.replay_frame();
+        if !input.is_replaying() {
+            info!("Finished replaying the inputs");
+        }
+        return;
+    }
 
-    // check if the cursor is in the primary window
-    if let Some(cursor_pos) = wnd.cursor_position() {
+    // https://bevy-cheatbook.github.io/cookbook/cursor2world.html
+    // get the primary window. There is none when running headless.
+    let wnd = match wnds.get_primary() {
+        Some(wnd) => wnd,
+        None => return,
+    };
+
+    // check if the cursor is in the primary window.
+    // A finger on the screen keeps the cursor where it touched, so the swipe edits that value.
+    let cursor_pos = wnd
+        .cursor_position()
+        .filter(|_| !input.is_touching_screen());
+    if let Some(cursor_pos) = cursor_pos {
         // NOTE: This works for when the user can manually resize the window,
         // where the game screen runs in letterboxed mode
 
@@ -314,21 +634,8 @@ fn update_inputs(
         // let cursor_x = ((cursor_pos.x - border_x) / game_ratio) as i32;
         // let cursor_y = ((cursor_pos.y - border_y) / game_ratio) as i32;
 
-        let cursor_x = cursor_pos.x as i32;
-        let cursor_y = cursor_pos.y as i32;
-
-        // The Y-value does not conform to standard computer-based positioning.
-        // Adjust it so it is.
-        // 143 is the correct value so the top is 0 and bottom is 143. Not 144.
-        let cursor_y = -cursor_y + 143;
-
-        let new_cursor_pos = (cursor_x, cursor_y);
-
-        if new_cursor_pos != input.cursor_position {
-            input.last_cursor_move = Some(input.current_time)
-        }
-
-        input.cursor_position = new_cursor_pos;
+        let (cursor_x, cursor_y) = window_to_screen(cursor_pos, wnd.height());
+        input.apply(InputChange::Cursor(cursor_x, cursor_y));
     }
 
     // For each keyboard event (releases or presses)
@@ -336,9 +643,9 @@ fn update_inputs(
         // For some reason Keycode can be None?
         if let Some(keycode) = key.key_code {
             if key.state.is_pressed() {
-                input.press_key(InputType::Key(keycode))
+                input.apply(InputChange::Press(InputType::Key(keycode)))
             } else {
-                input.release_key(InputType::Key(keycode))
+                input.apply(InputChange::Release(InputType::Key(keycode)))
             }
         }
     }
@@ -346,22 +653,86 @@ fn update_inputs(
     // For each mouse button event (releases or presses)
     for btn in mouse.iter() {
         if btn.state.is_pressed() {
-            input.press_key(InputType::Mouse(btn.button));
+            input.apply(InputChange::Press(InputType::Mouse(btn.button)));
         } else {
-            input.release_key(InputType::Mouse(btn.button));
+            input.apply(InputChange::Release(InputType::Mouse(btn.button)));
+        }
+    }
 
-            if btn.button == MouseButton::Left {
-                input.last_click_release = Some(input.current_time);
+    // For each gamepad button or axis change.
+    // Every gamepad presses the same inputs, so any of them can be used.
+    for GamepadEvent(pad, event) in gamepad.iter() {
+        match event {
+            GamepadEventType::ButtonChanged(button, value) => {
+                let settings = gamepad_settings.get_button_settings(GamepadButton(*pad, *button));
+                if *value >= settings.press {
+                    input.apply(InputChange::Press(InputType::Gamepad(*button)));
+                } else if *value <= settings.release {
+                    input.apply(InputChange::Release(InputType::Gamepad(*button)));
+                }
             }
+            // Some d-pads, and the left stick, are axes rather than buttons.
+            GamepadEventType::AxisChanged(axis, value) => {
+                let (negative, positive) = match axis {
+                    GamepadAxisType::DPadX | GamepadAxisType::LeftStickX => {
+                        (GamepadButtonType::DPadLeft, GamepadButtonType::DPadRight)
+                    }
+                    GamepadAxisType::DPadY | GamepadAxisType::LeftStickY => {
+                        (GamepadButtonType::DPadDown, GamepadButtonType::DPadUp)
+                    }
+                    _ => continue,
+                };
+                for (button, pressed) in [
+                    (negative, *value <= -AXIS_THRESHOLD),
+                    (positive, *value >= AXIS_THRESHOLD),
+                ] {
+                    if pressed {
+                        input.apply(InputChange::Press(InputType::Gamepad(button)));
+                    } else {
+                        input.apply(InputChange::Release(InputType::Gamepad(button)));
+                    }
+                }
+            }
+            GamepadEventType::Connected => info!("Gamepad {} connected", pad.0),
+            GamepadEventType::Disconnected => info!("Gamepad {} disconnected", pad.0),
         }
     }
 
-    // Get the remainder from the previous frame
-    // and set it to the current frame
-    input.scroll_delta = input.scroll_delta.rem_euclid(1.);
     // For each mouse wheel event (there should only be one)
     // Note: Wheel is empty if there is no change in scroll.
     for whl in wheel.iter() {
-        input.scroll_delta += whl.y;
+        input.apply(InputChange::Scroll(whl.y));
     }
+
+    // For each finger touching, moving or leaving the touchscreen.
+    // Touches are positioned the same way as the cursor.
+    for event in touch.iter() {
+        match event.phase {
+            TouchPhase::Started => {
+                let (x, y) = window_to_screen(event.position, wnd.height());
+                input.apply(InputChange::TouchStart {
+                    id: event.id,
+                    x,
+                    y,
+                    window_y: event.position.y,
+                });
+            }
+            TouchPhase::Moved => input.apply(InputChange::TouchMove {
+                id: event.id,
+                window_y: event.position.y,
+            }),
+            TouchPhase::Ended | TouchPhase::Cancelled => {
+                input.apply(InputChange::TouchEnd { id: event.id })
+            }
+        }
+    }
+}
+
+/// Converts a position on the window, from its bottom-left, to pixels from the top-left.
+fn window_to_screen(position: Vec2, window_height: f32) -> (i32, i32) {
+    // The Y-value does not conform to standard computer-based positioning.
+    // Adjust it so it is, where the top is 0 and the bottom is one less than the height.
+    let x = position.x as i32;
+    let y = window_height as i32 - 1 - position.y as i32;
+    (x, y)
 }
@@ -0,0 +1,204 @@
+//! The Game Boy's buttons, and the combos LSDj edits with.
+
+use super::bindings::Action;
+use super::input::{DirectionalInput, InputRes, InputType, UPDATE_INPUTS};
+use super::types::Command;
+use bevy::prelude::*;
+
+pub struct JoypadPlugin;
+
+impl Plugin for JoypadPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<JoypadEvent>();
+        app.insert_resource(LastValues::default());
+        app.add_system_to_stage(CoreStage::PreUpdate, read_joypad.after(UPDATE_INPUTS));
+    }
+}
+
+/// The Game Boy buttons being held.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct HeldButtons {
+    pub a: bool,
+    pub b: bool,
+    pub select: bool,
+}
+
+/// What the Game Boy's buttons were pressed to do this frame.
+///
+/// Sent for the screens to act on the cell under their cursor.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum JoypadEvent {
+    /// A: insert the last used value into the empty cell under the cursor.
+    Insert,
+    /// B + A: delete the value under the cursor.
+    Delete,
+    /// A + arrows, or B + arrows: change the value under the cursor. Left and right change
+    /// it by the fine step, up and down by the coarse one (0x10, or an octave for notes).
+    Change { delta: i32, coarse: bool },
+    /// Select + arrows: switch to the neighbouring screen on the navbar.
+    Navigate(DirectionalInput),
+}
+
+impl JoypadEvent {
+    /// Returns the event a combo makes, given the buttons held and the one just pressed.
+    /// Arrows held with nothing else move the cursor, so make no event.
+    pub fn from_combo(
+        held: HeldButtons,
+        a_pressed: bool,
+        arrow: Option<DirectionalInput>,
+    ) -> Option<JoypadEvent> {
+        if held.select {
+            return arrow.map(JoypadEvent::Navigate);
+        }
+        if a_pressed {
+            return Some(if held.b {
+                JoypadEvent::Delete
+            } else {
+                JoypadEvent::Insert
+            });
+        }
+        if !held.a && !held.b {
+            return None;
+        }
+        Some(match arrow? {
+            DirectionalInput::Left => JoypadEvent::Change {
+                delta: -1,
+                coarse: false,
+            },
+            DirectionalInput::Right => JoypadEvent::Change {
+                delta: 1,
+                coarse: false,
+            },
+            DirectionalInput::Down => JoypadEvent::Change {
+                delta: -1,
+                coarse: true,
+            },
+            DirectionalInput::Up => JoypadEvent::Change {
+                delta: 1,
+                coarse: true,
+            },
+        })
+    }
+}
+
+/// Turns the Game Boy buttons pressed this frame into events.
+/// Arrows repeat while held, using the key delay/repeat.
+fn read_joypad(input: Res<InputRes>, mut joypad_writer: EventWriter<JoypadEvent>) {
+    let held = HeldButtons {
+        a: input.action_pressed(Action::ButtonA),
+        b: input.action_pressed(Action::ButtonB),
+        select: input.action_pressed(Action::Select),
+    };
+
+    if input.action_just_pressed(Action::ButtonA) {
+        if let Some(event) = JoypadEvent::from_combo(held, true, None) {
+            joypad_writer.send(event);
+        }
+    }
+
+    for key in InputType::directional_keycodes() {
+        if !input.dr_pressed(&key) {
+            continue;
+        }
+        let arrow = DirectionalInput::from_input_type(key);
+        if let Some(event) = JoypadEvent::from_combo(held, false, arrow) {
+            joypad_writer.send(event);
+        }
+    }
+}
+
+/// The values last inserted or changed with the buttons, which A inserts into empty cells.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct LastValues {
+    chain: Option<u8>,
+    phrase: Option<u8>,
+    note: Option<u8>,
+    instr: Option<u8>,
+    cmd: Option<(Command, u8)>,
+}
+
+impl LastValues {
+    /// Returns the last chain used on the Song screen.
+    pub fn get_chain(&self) -> Option<u8> {
+        self.chain
+    }
+
+    pub fn set_chain(&mut self, chain: u8) {
+        self.chain = Some(chain);
+    }
+
+    /// Returns the last phrase used on the Chain screen.
+    pub fn get_phrase(&self) -> Option<u8> {
+        self.phrase
+    }
+
+    pub fn set_phrase(&mut self, phrase: u8) {
+        self.phrase = Some(phrase);
+    }
+
+    /// Returns the last note used on the Phrase screen.
+    pub fn get_note(&self) -> Option<u8> {
+        self.note
+    }
+
+    pub fn set_note(&mut self, note: u8) {
+        self.note = Some(note);
+    }
+
+    /// Returns the last instrument used on the Phrase screen.
+    pub fn get_instr(&self) -> Option<u8> {
+        self.instr
+    }
+
+    pub fn set_instr(&mut self, instr: u8) {
+        self.instr = Some(instr);
+    }
+
+    /// Returns the last command used on the Phrase screen, with its value.
+    pub fn get_cmd(&self) -> Option<(Command, u8)> {
+        self.cmd
+    }
+
+    pub fn set_cmd(&mut self, cmd: (Command, u8)) {
+        self.cmd = Some(cmd);
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    /// Each combo makes the event LSDj would act on.
+    fn combos() {
+        let up = Some(DirectionalInput::Up);
+        let none = HeldButtons::default();
+        let a = HeldButtons { a: true, ..none };
+        let b = HeldButtons { b: true, ..none };
+        let select = HeldButtons {
+            select: true,
+            ..none
+        };
+
+        assert_eq!(
+            JoypadEvent::from_combo(a, true, None),
+            Some(JoypadEvent::Insert)
+        );
+        assert_eq!(
+            JoypadEvent::from_combo(HeldButtons { a: true, ..b }, true, None),
+            Some(JoypadEvent::Delete)
+        );
+        let up_change = Some(JoypadEvent::Change {
+            delta: 1,
+            coarse: true,
+        });
+        assert_eq!(JoypadEvent::from_combo(a, false, up), up_change);
+        assert_eq!(JoypadEvent::from_combo(b, false, up), up_change);
+        assert_eq!(
+            JoypadEvent::from_combo(HeldButtons { b: true, ..select }, false, up),
+            Some(JoypadEvent::Navigate(DirectionalInput::Up))
+        );
+        assert_eq!(JoypadEvent::from_combo(none, false, up), None);
+        assert_eq!(JoypadEvent::from_combo(select, true, None), None);
+    }
+}
@@ -1,8 +1,13 @@
 use bevy::prelude::*;
 
+pub mod bindings;
 pub mod cursors;
 pub mod edited;
 pub mod input;
+pub mod joypad;
+pub mod piano;
+pub mod recording;
+pub mod touch_pad;
 pub mod types;
 
 pub use cursors::Cursor;
@@ -13,7 +18,10 @@ impl Plugin for ResourcePlugin {
     fn build(&self, app: &mut App) {
         app.add_plugin(types::TypeResourcePlugin);
         app.add_plugin(input::InputPlugin);
+        app.add_plugin(recording::RecordingPlugin);
+        app.add_plugin(joypad::JoypadPlugin);
         app.add_plugin(edited::EditedPlugin);
         app.add_plugin(cursors::CursorPlugin);
+        app.add_plugin(piano::PianoPlugin);
     }
 }
@@ -0,0 +1,140 @@
+use super::input::InputType;
+use crate::resources::types::note::LARGEST_NOTE;
+use bevy::prelude::*;
+
+/// The number of octaves notes can be played in.
+pub const OCTAVE_COUNT: u8 = LARGEST_NOTE / 12;
+/// The octave the piano starts on, counting from the lowest.
+pub const DEFAULT_OCTAVE: u8 = 1;
+
+/// The keys of the piano and the semitone above the octave's C each plays,
+/// laid out like a tracker. The bottom row starts at C and the top row an octave
+/// above it, with the row above each holding the black keys.
+pub const PIANO_KEYS: [(KeyCode, u8); 34] = [
+    (KeyCode::Z, 0),
+    (KeyCode::S, 1),
+    (KeyCode::X, 2),
+    (KeyCode::D, 3),
+    (KeyCode::C, 4),
+    (KeyCode::V, 5),
+    (KeyCode::G, 6),
+    (KeyCode::B, 7),
+    (KeyCode::H, 8),
+    (KeyCode::N, 9),
+    (KeyCode::J, 10),
+    (KeyCode::M, 11),
+    (KeyCode::Comma, 12),
+    (KeyCode::L, 13),
+    (KeyCode::Period, 14),
+    (KeyCode::Semicolon, 15),
+    (KeyCode::Slash, 16),
+    (KeyCode::Q, 12),
+    (KeyCode::Key2, 13),
+    (KeyCode::W, 14),
+    (KeyCode::Key3, 15),
+    (KeyCode::E, 16),
+    (KeyCode::R, 17),
+    (KeyCode::Key5, 18),
+    (KeyCode::T, 19),
+    (KeyCode::Key6, 20),
+    (KeyCode::Y, 21),
+    (KeyCode::Key7, 22),
+    (KeyCode::U, 23),
+    (KeyCode::I, 24),
+    (KeyCode::Key9, 25),
+    (KeyCode::O, 26),
+    (KeyCode::Key0, 27),
+    (KeyCode::P, 28),
+];
+
+pub struct PianoPlugin;
+
+impl Plugin for PianoPlugin {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(PianoMode::default());
+    }
+}
+
+/// Whether the keyboard plays notes on the Phrase screen like a piano, and in which octave.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PianoMode {
+    enabled: bool,
+    /// The octave of the bottom row's C, counting from the lowest.
+    octave: u8,
+}
+
+impl PianoMode {
+    /// Returns true if the keyboard plays notes.
+    pub fn is_enabled(&self) -> bool {
+        self.enabled
+    }
+
+    /// Sets whether the keyboard plays notes.
+    pub fn set_enabled(&mut self, enabled: bool) {
+        self.enabled = enabled;
+    }
+
+    /// Returns the octave of the bottom row's C, counting from the lowest.
+    pub fn get_octave(&self) -> u8 {
+        self.octave
+    }
+
+    /// Moves up an octave, if there is one.
+    pub fn octave_up(&mut self) {
+        self.octave = (self.octave + 1).min(OCTAVE_COUNT - 1);
+    }
+
+    /// Moves down an octave, if there is one.
+    pub fn octave_down(&mut self) {
+        self.octave = self.octave.saturating_sub(1);
+    }
+
+    /// Returns the `Note` value a key plays in the current octave. Returns None if
+    /// it isn't a piano key, or the note would be too high.
+    pub fn get_note(&self, input: &InputType) -> Option<u8> {
+        let semitone = PIANO_KEYS
+            .iter()
+            .find(|(key, _)| *input == InputType::Key(*key))?
+            .1;
+        let note = self.octave * 12 + semitone + 1;
+        if note > LARGEST_NOTE {
+            return None;
+        }
+        Some(note)
+    }
+}
+
+impl Default for PianoMode {
+    fn default() -> Self {
+        PianoMode {
+            enabled: false,
+            octave: DEFAULT_OCTAVE,
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    /// The two rows overlap by an octave, and notes stop at the highest LSDj has.
+    fn piano_notes() {
+        let mut piano = PianoMode::default();
+        let z = InputType::Key(KeyCode::Z);
+        let q = InputType::Key(KeyCode::Q);
+        assert_eq!(Some(13), piano.get_note(&z));
+        assert_eq!(
+            piano.get_note(&InputType::Key(KeyCode::Comma)),
+            piano.get_note(&q)
+        );
+        assert_eq!(None, piano.get_note(&InputType::Key(KeyCode::A)));
+
+        for _ in 0..OCTAVE_COUNT {
+            piano.octave_up();
+        }
+        assert_eq!(OCTAVE_COUNT - 1, piano.get_octave());
+        assert_eq!(Some(LARGEST_NOTE - 11), piano.get_note(&z));
+        assert_eq!(None, piano.get_note(&q));
+    }
+}
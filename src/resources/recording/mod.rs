@@ -0,0 +1,342 @@
+//! Recording every change to the inputs, and replaying it into a fresh app.
+
+use super::bindings::{input_from_name, input_name};
+use super::input::{InputRes, InputType};
+use crate::cli::CliArgs;
+use bevy::prelude::*;
+use std::fmt;
+use std::fs::{self, File};
+use std::io::{BufWriter, Write};
+use std::str::FromStr;
+
+pub mod recording_error;
+
+pub use recording_error::*;
+
+pub struct RecordingPlugin;
+
+impl Plugin for RecordingPlugin {
+    fn build(&self, app: &mut App) {
+        let cli = app
+            .world
+            .get_resource::<CliArgs>()
+            .cloned()
+            .unwrap_or_default();
+
+        if let Some(filename) = &cli.replay {
+            match InputRecording::load(filename) {
+                Ok(recording) => {
+                    info!("Replaying the inputs from `{}`", filename);
+                    let mut input = app.world.get_resource_mut::<InputRes>().unwrap();
+                    input.start_replay(recording);
+                }
+                Err(e) => error!("Could not replay `{}`. {}", filename, e),
+            }
+        }
+
+        if let Some(filename) = &cli.record {
+            let file = File::create(filename).and_then(|file| {
+                let mut file = BufWriter::new(file);
+                writeln!(file, "# frame change values")?;
+                Ok(file)
+            });
+            match file {
+                Ok(file) => {
+                    info!("Recording the inputs to `{}`", filename);
+                    let mut input = app.world.get_resource_mut::<InputRes>().unwrap();
+                    input.start_recording();
+                    app.insert_resource(Recorder { file });
+                    app.add_system_to_stage(CoreStage::Last, write_recording);
+                }
+                Err(e) => error!("Could not record to `{}`. {}", filename, e),
+            }
+        }
+    }
+}
+
+/// A change to the inputs, made in `update_inputs`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum InputChange {
+    Press(InputType),
+    Release(InputType),
+    /// The cursor moved to this position on the screen, in pixels from the top-left.
+    Cursor(i32, i32),
+    Scroll(f32),
+    /// A finger touched the window, at this position in pixels from the top-left.
+    /// `window_y` is where it touched, from the bottom of the window.
+    TouchStart {
+        id: u64,
+        x: i32,
+        y: i32,
+        window_y: f32,
+    },
+    TouchMove {
+        id: u64,
+        window_y: f32,
+    },
+    TouchEnd {
+        id: u64,
+    },
+}
+
+impl InputChange {
+    /// Returns true if the change can be written to a recording.
+    /// Presses and releases of inputs that can't be bound aren't, as they have no name.
+    pub fn is_recordable(&self) -> bool {
+        match self {
+            InputChange::Press(input) | InputChange::Release(input) => input_name(input).is_some(),
+            _ => true,
+        }
+    }
+
+    /// Parses a change from its name and the words following it.
+    fn parse<'a>(
+        line: usize,
+        name: &str,
+        mut values: impl Iterator<Item = &'a str>,
+    ) -> Result<InputChange, RecordingError> {
+        let mut input = || {
+            let name = values.next().ok_or(RecordingError::Invalid { line })?;
+            input_from_name(name).ok_or_else(|| RecordingError::UnknownInput {
+                line,
+                name: name.to_string(),
+            })
+        };
+        let change = match name {
+            "press" => InputChange::Press(input()?),
+            "release" => InputChange::Release(input()?),
+            "cursor" => InputChange::Cursor(
+                parse_value(line, values.next())?,
+                parse_value(line, values.next())?,
+            ),
+            "scroll" => InputChange::Scroll(parse_value(line, values.next())?),
+            "touch-start" => InputChange::TouchStart {
+                id: parse_value(line, values.next())?,
+                x: parse_value(line, values.next())?,
+                y: parse_value(line, values.next())?,
+                window_y: parse_value(line, values.next())?,
+            },
+            "touch-move" => InputChange::TouchMove {
+                id: parse_value(line, values.next())?,
+                window_y: parse_value(line, values.next())?,
+            },
+            "touch-end" => InputChange::TouchEnd {
+                id: parse_value(line, values.next())?,
+            },
+            _ => {
+                return Err(RecordingError::UnknownChange {
+                    line,
+                    name: name.to_string(),
+                })
+            }
+        };
+
+        match values.next() {
+            Some(_) => Err(RecordingError::Invalid { line }),
+            None => Ok(change),
+        }
+    }
+}
+
+impl fmt::Display for InputChange {
+    /// Writes the change as in a recording, such as `press ctrl` or `cursor 40 16`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InputChange::Press(input) => write!(f, "press {}", input_name(input).unwrap_or("?")),
+            InputChange::Release(input) => {
+                write!(f, "release {}", input_name(input).unwrap_or("?"))
+            }
+            InputChange::Cursor(x, y) => write!(f, "cursor {} {}", x, y),
+            InputChange::Scroll(delta) => write!(f, "scroll {}", delta),
+            InputChange::TouchStart { id, x, y, window_y } => {
+                write!(f, "touch-start {} {} {} {}", id, x, y, window_y)
+            }
+            InputChange::TouchMove { id, window_y } => write!(f, "touch-move {} {}", id, window_y),
+            InputChange::TouchEnd { id } => write!(f, "touch-end {}", id),
+        }
+    }
+}
+
+/// Parses a number following a change's name.
+fn parse_value<T: FromStr>(line: usize, value: Option<&str>) -> Result<T, RecordingError> {
+    value
+        .and_then(|value| value.parse().ok())
+        .ok_or(RecordingError::Invalid { line })
+}
+
+/// Every change to the inputs while recording, along with the frame it happened on.
+#[derive(Debug, Clone, PartialEq, Default)]
+pub struct InputRecording {
+    changes: Vec<(u64, InputChange)>,
+}
+
+impl InputRecording {
+    pub fn new(changes: Vec<(u64, InputChange)>) -> InputRecording {
+        InputRecording { changes }
+    }
+
+    /// Reads a recording.
+    ///
+    /// # FILE STRUCTURE
+    ///
+    /// Each line is the frame a change happened on, counting from the start of the app,
+    /// followed by the change and its values, separated by spaces. For example,
+    /// `120 press ctrl` or `121 cursor 40 16`. Lines starting with `#` are comments.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if the file can't be read, names an unknown change or input,
+    /// or its frames aren't in order.
+    pub fn load(filename: &str) -> Result<InputRecording, RecordingError> {
+        InputRecording::parse(&fs::read_to_string(filename)?)
+    }
+
+    /// Parses the contents of a recording. See `InputRecording::load`.
+    pub fn parse(contents: &str) -> Result<InputRecording, RecordingError> {
+        let mut changes: Vec<(u64, InputChange)> = vec![];
+
+        for (idx, line) in contents.lines().enumerate() {
+            let line_number = idx + 1;
+            let mut words = line.split_whitespace();
+            let frame = match words.next() {
+                Some(frame) if !frame.starts_with('#') => frame,
+                _ => continue,
+            };
+            let frame: u64 = parse_value(line_number, Some(frame))?;
+            if matches!(changes.last(), Some((last, _)) if frame < *last) {
+                return Err(RecordingError::OutOfOrder {
+                    line: line_number,
+                    frame,
+                });
+            }
+
+            let name = words
+                .next()
+                .ok_or(RecordingError::Invalid { line: line_number })?;
+            changes.push((frame, InputChange::parse(line_number, name, words)?));
+        }
+
+        Ok(InputRecording { changes })
+    }
+
+    /// Returns the changes, in the order they happened.
+    pub fn get_changes(&self) -> &[(u64, InputChange)] {
+        &self.changes
+    }
+}
+
+impl fmt::Display for InputRecording {
+    /// Writes the recording as in a file. See `InputRecording::load`.
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (frame, change) in &self.changes {
+            writeln!(f, "{} {}", frame, change)?;
+        }
+        Ok(())
+    }
+}
+
+/// The file inputs are being recorded to.
+struct Recorder {
+    file: BufWriter<File>,
+}
+
+/// Writes the changes made this frame to the recording.
+/// They're written every frame, so the recording survives the app closing or crashing.
+fn write_recording(mut input: ResMut<InputRes>, mut recorder: ResMut<Recorder>) {
+    let changes = input.take_recorded();
+    if changes.is_empty() {
+        return;
+    }
+
+    let written = InputRecording::new(changes).to_string();
+    let result = recorder
+        .file
+        .write_all(written.as_bytes())
+        .and_then(|_| recorder.file.flush());
+    if let Err(e) = result {
+        error!("Could not write to the recording. {}", e);
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    /// Replaying a recording into fresh inputs gives the same inputs on every frame.
+    fn record_and_replay() {
+        let ctrl = InputType::Key(KeyCode::LControl);
+        let unnamed = InputType::Key(KeyCode::Numpad0);
+        let lmb = InputType::Mouse(MouseButton::Left);
+        let frames = [
+            vec![InputChange::Cursor(40, 16)],
+            vec![InputChange::Press(ctrl), InputChange::Scroll(1.5)],
+            vec![],
+            vec![InputChange::Press(unnamed)],
+            vec![InputChange::Scroll(-0.25), InputChange::Release(ctrl)],
+            vec![InputChange::Press(lmb), InputChange::Release(lmb)],
+            vec![InputChange::TouchStart {
+                id: 3,
+                x: 80,
+                y: 64,
+                window_y: 79.5,
+            }],
+            vec![InputChange::TouchMove {
+                id: 3,
+                window_y: 99.5,
+            }],
+            vec![InputChange::TouchEnd { id: 3 }],
+        ];
+
+        let mut recorded = InputRes::new();
+        recorded.start_recording();
+        let mut states = vec![];
+        for changes in &frames {
+            recorded.next_frame();
+            for change in changes {
+                recorded.apply(*change);
+            }
+            states.push((
+                recorded.get_pressed_inputs(),
+                recorded.get_cursor_position(),
+                recorded.get_scroll_delta(),
+                recorded.scroll_editing(),
+            ));
+        }
+
+        // Write and read back the recording, as `--record` and `--replay` do.
+        let recording = InputRecording::new(recorded.take_recorded());
+        let recording = InputRecording::parse(&recording.to_string()).unwrap();
+        assert_eq!(recording.get_changes().len(), 10);
+
+        let mut replayed = InputRes::new();
+        replayed.start_replay(recording);
+        for state in states {
+            replayed.next_frame();
+            replayed.replay_frame();
+            assert_eq!(
+                (
+                    replayed.get_pressed_inputs(),
+                    replayed.get_cursor_position(),
+                    replayed.get_scroll_delta(),
+                    replayed.scroll_editing(),
+                ),
+                state
+            );
+        }
+        assert!(!replayed.is_replaying());
+
+        assert!(matches!(
+            InputRecording::parse("# frame change values\n2 press ctrl\n1 release ctrl"),
+            Err(RecordingError::OutOfOrder { line: 3, frame: 1 })
+        ));
+        assert!(matches!(
+            InputRecording::parse("1 jump"),
+            Err(RecordingError::UnknownChange { line: 1, .. })
+        ));
+        assert!(matches!(
+            InputRecording::parse("1 cursor 4"),
+            Err(RecordingError::Invalid { line: 1 })
+        ));
+    }
+}
@@ -0,0 +1,16 @@
+use std::io;
+
+/// An error that occurs while reading an input recording.
+#[derive(thiserror::Error, Debug)]
+pub enum RecordingError {
+    #[error("Could not read the recording: {0}")]
+    Io(#[from] io::Error),
+    #[error("Line {line}: unknown change `{name}`")]
+    UnknownChange { line: usize, name: String },
+    #[error("Line {line}: unknown key or button `{name}`")]
+    UnknownInput { line: usize, name: String },
+    #[error("Line {line}: expected a frame, a change and its values")]
+    Invalid { line: usize },
+    #[error("Line {line}: frame {frame} comes before the frame of the line above")]
+    OutOfOrder { line: usize, frame: u64 },
+}
@@ -0,0 +1,140 @@
+//! The virtual Game Boy buttons shown under the screen for touchscreens.
+
+use super::input::InputType;
+use bevy::prelude::*;
+
+/// The rows of tiles the touch pad takes up below the screen.
+pub const TOUCH_PAD_ROWS: usize = 5;
+/// The height of the touch pad in pixels.
+pub const TOUCH_PAD_HEIGHT: f32 = (TOUCH_PAD_ROWS * 8) as f32;
+
+/// A virtual button, covering a rectangle of tiles on the touch pad.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PadButton {
+    pub x: usize,
+    pub y: usize,
+    pub width: usize,
+    pub height: usize,
+    /// The text drawn on its top-left tile.
+    pub label: &'static str,
+    /// The input held while the button is touched.
+    pub input: InputType,
+}
+
+impl PadButton {
+    /// Returns true if the tile is on the button.
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x as i32
+            && x < (self.x + self.width) as i32
+            && y >= self.y as i32
+            && y < (self.y + self.height) as i32
+    }
+}
+
+/// The buttons of the touch pad. They press the same inputs as a gamepad's,
+/// so they do whatever the gamepad is bound to.
+pub const PAD_BUTTONS: [PadButton; 8] = [
+    PadButton {
+        x: 2,
+        y: 0,
+        width: 2,
+        height: 2,
+        label: "u",
+        input: InputType::Gamepad(GamepadButtonType::DPadUp),
+    },
+    PadButton {
+        x: 0,
+        y: 2,
+        width: 2,
+        height: 1,
+        label: "l",
+        input: InputType::Gamepad(GamepadButtonType::DPadLeft),
+    },
+    PadButton {
+        x: 4,
+        y: 2,
+        width: 2,
+        height: 1,
+        label: "r",
+        input: InputType::Gamepad(GamepadButtonType::DPadRight),
+    },
+    PadButton {
+        x: 2,
+        y: 3,
+        width: 2,
+        height: 2,
+        label: "d",
+        input: InputType::Gamepad(GamepadButtonType::DPadDown),
+    },
+    PadButton {
+        x: 7,
+        y: 4,
+        width: 3,
+        height: 1,
+        label: "sel",
+        input: InputType::Gamepad(GamepadButtonType::Select),
+    },
+    PadButton {
+        x: 11,
+        y: 4,
+        width: 5,
+        height: 1,
+        label: "start",
+        input: InputType::Gamepad(GamepadButtonType::Start),
+    },
+    PadButton {
+        x: 14,
+        y: 2,
+        width: 2,
+        height: 2,
+        label: "b",
+        input: InputType::Gamepad(GamepadButtonType::South),
+    },
+    PadButton {
+        x: 17,
+        y: 1,
+        width: 2,
+        height: 2,
+        label: "a",
+        input: InputType::Gamepad(GamepadButtonType::East),
+    },
+];
+
+/// Returns the input of the button on a tile of the touch pad.
+pub fn button_at(x: i32, y: i32) -> Option<InputType> {
+    PAD_BUTTONS
+        .iter()
+        .find(|button| button.contains(x, y))
+        .map(|button| button.input)
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    /// Every tile of a button presses it, and the gaps between buttons press nothing.
+    fn buttons_at_tiles() {
+        let a = InputType::Gamepad(GamepadButtonType::East);
+        assert_eq!(button_at(17, 1), Some(a));
+        assert_eq!(button_at(18, 2), Some(a));
+        assert_eq!(button_at(19, 2), None);
+        assert_eq!(
+            button_at(3, 4),
+            Some(InputType::Gamepad(GamepadButtonType::DPadDown))
+        );
+        assert_eq!(button_at(2, 2), None);
+        assert_eq!(button_at(0, 0), None);
+
+        // No two buttons overlap.
+        for (idx, button) in PAD_BUTTONS.iter().enumerate() {
+            for other in &PAD_BUTTONS[idx + 1..] {
+                for x in 0..20 {
+                    for y in 0..TOUCH_PAD_ROWS as i32 {
+                        assert!(!(button.contains(x, y) && other.contains(x, y)));
+                    }
+                }
+            }
+        }
+    }
+}
@@ -32,10 +32,10 @@ impl Default for Instruments {
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Instrument {
-    Pulse(),
-    Wave(),
+    Pulse(PulseInstrument),
+    Wave(WaveInstrument),
     Kit(),
-    Noise(),
+    Noise(NoiseInstrument),
     Speech(),
 }
 
@@ -53,6 +53,74 @@ impl Instrument {
 
 impl Default for Instrument {
     fn default() -> Self {
-        Instrument::Pulse()
+        Instrument::Pulse(PulseInstrument::default())
+    }
+}
+
+/// The settings of a pulse instrument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PulseInstrument {
+    /// The volume envelope, stored the same way as NR12.
+    /// The high nibble is the initial volume, bit 3 is set if the volume
+    /// increases, and the lowest 3 bits are how fast it changes.
+    pub envelope: u8,
+    /// The duty cycle, from 0 (12.5%) to 3 (75%).
+    pub duty: u8,
+    /// How long the note plays for, from 0x00 (longest) to 0x3f, or None to play until stopped.
+    pub length: Option<u8>,
+    /// The frequency sweep, stored the same way as NR10.
+    pub sweep: u8,
+}
+
+impl Default for PulseInstrument {
+    fn default() -> Self {
+        PulseInstrument {
+            envelope: 0xa8,
+            duty: 2,
+            length: None,
+            sweep: 0x00,
+        }
+    }
+}
+
+/// The settings of a wave instrument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct WaveInstrument {
+    /// The output level, from 0 (mute) to 3 (full volume), stored the same way as NR32.
+    pub volume: u8,
+    /// The wave played.
+    pub wave: u8,
+    /// How long the note plays for, from 0x00 (longest) to 0xff, or None to play until stopped.
+    pub length: Option<u8>,
+}
+
+impl Default for WaveInstrument {
+    fn default() -> Self {
+        WaveInstrument {
+            volume: 3,
+            wave: 0,
+            length: None,
+        }
+    }
+}
+
+/// The settings of a noise instrument.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoiseInstrument {
+    /// The volume envelope, stored the same way as NR42.
+    pub envelope: u8,
+    /// How long the note plays for, from 0x00 (longest) to 0x3f, or None to play until stopped.
+    pub length: Option<u8>,
+    /// True if the noise uses the short 7-bit pattern, which sounds more metallic.
+    pub short: bool,
+}
+
+impl Default for NoiseInstrument {
+    fn default() -> Self {
+        NoiseInstrument {
+            envelope: 0xa1,
+            length: None,
+            short: false,
+        }
     }
 }
@@ -0,0 +1,75 @@
+/// The rate LSDj plays kit samples back at, in Hz.
+pub const SAMPLE_RATE: u32 = 11468;
+/// The maximum number of samples in a single kit.
+pub const SAMPLES_PER_KIT: usize = 15;
+
+/// All sample kits available to the song.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Kits {
+    kits: Vec<Kit>,
+}
+
+impl Kits {
+    /// Get a kit by its index.
+    pub fn get(&self, index: usize) -> Option<&Kit> {
+        self.kits.get(index)
+    }
+
+    /// Gets a mutable kit by its index.
+    ///
+    /// Returns None if the specified index does not exist.
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Kit> {
+        self.kits.get_mut(index)
+    }
+
+    /// Returns all kits.
+    pub fn get_all(&self) -> &Vec<Kit> {
+        &self.kits
+    }
+
+    /// Replaces all kits with a new set.
+    pub fn set_all(&mut self, kits: Vec<Kit>) {
+        self.kits = kits;
+    }
+
+    /// Returns the number of kits.
+    pub fn len(&self) -> usize {
+        self.kits.len()
+    }
+
+    /// Returns true if there are no kits.
+    pub fn is_empty(&self) -> bool {
+        self.kits.is_empty()
+    }
+}
+
+/// A named bank of up to 15 drum samples.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Kit {
+    /// The name of the kit. Up to 6 characters.
+    pub name: String,
+    /// The samples in the kit.
+    pub samples: Vec<Sample>,
+}
+
+/// A single 4-bit sample from a kit.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Sample {
+    /// The name of the sample. Up to 3 characters.
+    pub name: String,
+    /// The sample data, packed two 4-bit frames per byte with
+    /// the high nibble played first.
+    pub data: Vec<u8>,
+}
+
+impl Sample {
+    /// Returns the unpacked 4-bit frames of the sample.
+    pub fn frames(&self) -> impl Iterator<Item = u8> + '_ {
+        self.data.iter().flat_map(|byte| [byte >> 4, byte & 0x0f])
+    }
+
+    /// Returns the length of the sample in seconds.
+    pub fn duration(&self) -> f32 {
+        (self.data.len() * 2) as f32 / SAMPLE_RATE as f32
+    }
+}
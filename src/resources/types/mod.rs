@@ -4,15 +4,19 @@ pub mod chain;
 pub mod channel;
 pub mod command;
 pub mod instrument;
+pub mod kit;
 pub mod note;
 pub mod phrase;
+pub mod project;
 
 pub use chain::Chains;
 pub use channel::Channels;
 pub use command::Command;
 pub use instrument::Instruments;
+pub use kit::Kits;
 pub use note::Note;
 pub use phrase::Phrases;
+pub use project::Project;
 
 pub struct TypeResourcePlugin;
 
@@ -22,5 +26,7 @@ impl Plugin for TypeResourcePlugin {
         app.insert_resource(Chains::default());
         app.insert_resource(Phrases::default());
         app.insert_resource(Instruments::default());
+        app.insert_resource(Kits::default());
+        app.insert_resource(Project::default());
     }
 }
@@ -0,0 +1,63 @@
+/// The tempo a new song starts at.
+pub const DEFAULT_TEMPO: u16 = 128;
+/// The lowest tempo LSDj allows.
+pub const MIN_TEMPO: u16 = 40;
+/// The highest tempo LSDj allows.
+pub const MAX_TEMPO: u16 = 295;
+/// The maximum number of characters in a song title.
+pub const TITLE_LENGTH: usize = 8;
+
+/// Song-wide settings, as shown on the Project screen.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Project {
+    /// The title of the song.
+    title: String,
+    /// The tempo of the song in BPM.
+    tempo: u16,
+}
+
+impl Project {
+    /// Returns the title of the song.
+    pub fn get_title(&self) -> &str {
+        &self.title
+    }
+
+    /// Sets the title of the song, truncating it to 8 characters.
+    pub fn set_title(&mut self, title: &str) {
+        self.title = title.chars().take(TITLE_LENGTH).collect();
+    }
+
+    /// Returns the tempo of the song in BPM.
+    pub fn get_tempo(&self) -> u16 {
+        self.tempo
+    }
+
+    /// Sets the tempo of the song, clamping it between 40 and 295 BPM.
+    pub fn set_tempo(&mut self, tempo: u16) {
+        self.tempo = tempo.clamp(MIN_TEMPO, MAX_TEMPO);
+    }
+
+    /// Returns the tempo as LSDj stores it in a single byte, where
+    /// tempos above 255 wrap around to 0x00-0x27.
+    pub fn get_tempo_byte(&self) -> u8 {
+        (self.tempo & 0xff) as u8
+    }
+
+    /// Sets the tempo from LSDj's single byte representation.
+    pub fn set_tempo_byte(&mut self, tempo: u8) {
+        if (tempo as u16) < MIN_TEMPO {
+            self.set_tempo(tempo as u16 + 0x100)
+        } else {
+            self.set_tempo(tempo as u16)
+        }
+    }
+}
+
+impl Default for Project {
+    fn default() -> Self {
+        Project {
+            title: String::new(),
+            tempo: DEFAULT_TEMPO,
+        }
+    }
+}
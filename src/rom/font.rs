@@ -0,0 +1,153 @@
+use super::*;
+
+/// The number of fonts stored in an LSDj ROM.
+pub const FONT_COUNT: usize = 3;
+/// The number of characters in a font name.
+pub const FONT_NAME_LENGTH: usize = 4;
+/// The number of bytes in a 2bpp 8x8 tile.
+pub const TILE_BYTES: usize = 16;
+/// The size of a single font in bytes.
+pub const FONT_SIZE: usize = FONT_TILE_NAMES.len() * TILE_BYTES;
+
+/// The TileIds of each tile in an LSDj font, in the order they are stored.
+/// The bundled `lowr` font uses the same order.
+pub const FONT_TILE_NAMES: [&str; 71] = [
+    "musicnote",
+    "cursor",
+    "space",
+    "0",
+    "1",
+    "2",
+    "3",
+    "4",
+    "5",
+    "6",
+    "7",
+    "8",
+    "9",
+    "a",
+    "b",
+    "c",
+    "d",
+    "e",
+    "f",
+    "g",
+    "h",
+    "i",
+    "j",
+    "k",
+    "l",
+    "m",
+    "n",
+    "o",
+    "p",
+    "q",
+    "r",
+    "s",
+    "t",
+    "u",
+    "v",
+    "w",
+    "x",
+    "y",
+    "z",
+    "-",
+    "#",
+    "?",
+    "!",
+    "copyright",
+    "special",
+    ",",
+    ".",
+    ":",
+    "=",
+    "saw_down",
+    "saw_up",
+    "output_left_inactive",
+    "output_right_inactive",
+    "confirm",
+    "delete",
+    "triangle_down",
+    "triangle_up",
+    "square_down",
+    "square_up",
+    "+",
+    "(",
+    "pu12",
+    "pu25",
+    "pu50",
+    "puend",
+    "pu75",
+    "pu75end",
+    "%",
+    ")",
+    ";",
+    "/",
+];
+
+/// Converts a 2bpp Game Boy tile into an 8x8 TileSprite.
+///
+/// Each row of the tile is stored as two bytes, the first holding the
+/// low bit of each pixel and the second holding the high bit.
+pub fn tile_to_sprite(tile: &[u8]) -> TileSprite {
+    let mut sprite = Vec::with_capacity(64);
+    for row in tile.chunks_exact(2) {
+        let (low, high) = (row[0], row[1]);
+        for bit in (0..8).rev() {
+            let color = ((high >> bit) & 1) << 1 | ((low >> bit) & 1);
+            sprite.push(color as PixelColorId);
+        }
+    }
+    TileSprite::new(sprite)
+}
+
+/// Reads a font from the ROM, returning its tiles with their TileIds
+/// in the same form `TileSpriteParser::parse` does.
+pub fn read_font(data: &[u8], prefix: &str) -> Vec<(String, TileSprite)> {
+    FONT_TILE_NAMES
+        .iter()
+        .zip(data.chunks_exact(TILE_BYTES))
+        .map(|(name, tile)| (format!("{}_{}", prefix, name), tile_to_sprite(tile)))
+        .collect()
+}
+
+/// Converts an 8x8 TileSprite into a 2bpp Game Boy tile.
+///
+/// Returns None if the sprite is not 8x8 or uses a PixelColorId above 3.
+pub fn sprite_to_tile(sprite: &TileSprite) -> Option<Vec<u8>> {
+    if sprite.size() != 64 || sprite.read().iter().any(|p| *p > 3) {
+        return None;
+    }
+
+    let mut tile = Vec::with_capacity(TILE_BYTES);
+    for row in sprite.read().chunks_exact(8) {
+        let (mut low, mut high) = (0u8, 0u8);
+        for (x, pixel) in row.iter().enumerate() {
+            low |= ((pixel & 1) as u8) << (7 - x);
+            high |= (((pixel >> 1) & 1) as u8) << (7 - x);
+        }
+        tile.push(low);
+        tile.push(high);
+    }
+    Some(tile)
+}
+
+/// Converts the tiles of a font, as returned by `TileSpriteParser::parse`,
+/// into the bytes LSDj stores a font as.
+///
+/// # ERRORS
+///
+/// Errors if a tile in `FONT_TILE_NAMES` is missing or can't be stored as a 2bpp tile.
+pub fn write_font(tilesprites: &[(String, TileSprite)], prefix: &str) -> Result<Vec<u8>, RomError> {
+    let mut data = Vec::with_capacity(FONT_SIZE);
+    for name in FONT_TILE_NAMES {
+        let tileid = format!("{}_{}", prefix, name);
+        let (_, sprite) = tilesprites
+            .iter()
+            .find(|(id, _)| *id == tileid)
+            .ok_or_else(|| RomError::MissingTile(tileid.clone()))?;
+        let tile = sprite_to_tile(sprite).ok_or(RomError::InvalidTile(tileid))?;
+        data.extend(tile);
+    }
+    Ok(data)
+}
@@ -0,0 +1,129 @@
+use super::*;
+use std::fmt;
+use std::ops::{Range, RangeInclusive};
+
+/// The cartridge header title, excluding the CGB flag at 0x143.
+pub const TITLE_RANGE: Range<usize> = 0x134..0x143;
+/// The bytes covered by the header checksum.
+const HEADER_CHECKSUM_RANGE: RangeInclusive<usize> = 0x134..=0x14c;
+/// The header checksum byte.
+pub const HEADER_CHECKSUM: usize = 0x14d;
+/// The (big-endian) global checksum word.
+pub const GLOBAL_CHECKSUM: usize = 0x14e;
+
+/// Where the fonts, palettes and kits are stored for each family of LSDj versions.
+/// Versions with a major number outside of these ranges are rejected.
+const KNOWN_LAYOUTS: &[(RangeInclusive<u8>, RomLayout)] = &[(
+    5..=9,
+    RomLayout {
+        font_bank: 0x1e,
+        palette_bank: 0x1f,
+        palette_count: 6,
+        first_kit_bank: 0x20,
+    },
+)];
+
+/// The version of LSDj a ROM was built as.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct RomVersion {
+    pub major: u8,
+    pub minor: u8,
+    pub patch: u8,
+}
+
+impl fmt::Display for RomVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}.{}", self.major, self.minor, self.patch)
+    }
+}
+
+/// The location of the data BSDj reads from (and writes to) a ROM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RomLayout {
+    /// The bank the fonts are stored at, one after the other.
+    pub font_bank: usize,
+    /// The bank holding the palettes, followed by the palette and font name tables.
+    pub palette_bank: usize,
+    /// The number of palettes the ROM holds.
+    pub palette_count: usize,
+    /// The first bank kits can be stored in. Every bank after it is reserved for kits.
+    pub first_kit_bank: usize,
+}
+
+impl RomLayout {
+    /// Returns the layout used by a version of LSDj, or None if the version is unknown.
+    pub fn for_version(version: &RomVersion) -> Option<RomLayout> {
+        KNOWN_LAYOUTS
+            .iter()
+            .find(|(majors, _)| majors.contains(&version.major))
+            .map(|(_, layout)| *layout)
+    }
+}
+
+/// Returns the title stored in the cartridge header.
+pub fn read_title(rom: &[u8]) -> String {
+    rom[TITLE_RANGE]
+        .iter()
+        .take_while(|b| **b != 0)
+        .map(|b| *b as char)
+        .collect()
+}
+
+/// Finds the LSDj version from the cartridge header title.
+/// LSDj titles itself `LSDj-vX.Y.Z`, where the minor and patch numbers are optional.
+pub fn detect_version(rom: &[u8]) -> Result<RomVersion, RomError> {
+    let title = read_title(rom);
+    if !title.to_ascii_lowercase().starts_with("lsdj") {
+        return Err(RomError::NotLsdj(title));
+    }
+
+    let version_str = title[4..].trim_start_matches(&['-', 'v', 'V'][..]);
+    let mut numbers = version_str.split(&['.', '_'][..]).map(|n| n.parse::<u8>());
+
+    let version = match (numbers.next(), numbers.next(), numbers.next()) {
+        (Some(Ok(major)), minor, patch) => RomVersion {
+            major,
+            minor: minor.and_then(|n| n.ok()).unwrap_or(0),
+            patch: patch.and_then(|n| n.ok()).unwrap_or(0),
+        },
+        _ => return Err(RomError::UnknownVersion(title)),
+    };
+
+    Ok(version)
+}
+
+/// Calculates the header checksum the boot ROM verifies.
+pub fn header_checksum(rom: &[u8]) -> u8 {
+    rom[HEADER_CHECKSUM_RANGE]
+        .iter()
+        .fold(0u8, |sum, byte| sum.wrapping_sub(*byte).wrapping_sub(1))
+}
+
+/// Checks the header checksum matches what is stored in the ROM.
+pub fn verify_header_checksum(rom: &[u8]) -> Result<(), RomError> {
+    let expected = header_checksum(rom);
+    let received = rom[HEADER_CHECKSUM];
+    if received != expected {
+        return Err(RomError::HeaderChecksum { received, expected });
+    }
+    Ok(())
+}
+
+/// Calculates the global checksum, the sum of every byte in the ROM
+/// except for the checksum itself.
+pub fn global_checksum(rom: &[u8]) -> u16 {
+    rom.iter()
+        .enumerate()
+        .filter(|(idx, _)| *idx != GLOBAL_CHECKSUM && *idx != GLOBAL_CHECKSUM + 1)
+        .fold(0u16, |sum, (_, byte)| sum.wrapping_add(*byte as u16))
+}
+
+/// Recalculates and stores both the header and global checksums.
+/// The header checksum must be set first as it is part of the global checksum.
+pub fn fix_checksums(rom: &mut [u8]) {
+    rom[HEADER_CHECKSUM] = header_checksum(rom);
+
+    let global = global_checksum(rom).to_be_bytes();
+    rom[GLOBAL_CHECKSUM] = global[0];
+    rom[GLOBAL_CHECKSUM + 1] = global[1];
+}
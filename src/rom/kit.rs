@@ -0,0 +1,127 @@
+use super::*;
+use crate::resources::types::kit::{Kit, Sample, SAMPLES_PER_KIT};
+
+/// The first two bytes of a bank holding a kit.
+/// They are also the address of the first sample, 0x4060.
+pub const KIT_MAGIC: [u8; 2] = [0x60, 0x40];
+/// The first two bytes of a bank reserved for a kit, but left empty.
+pub const EMPTY_KIT_MAGIC: [u8; 2] = [0xff, 0xff];
+
+/// Where the sample names are stored in a kit bank, 3 characters each.
+pub const SAMPLE_NAMES_OFFSET: usize = 0x22;
+pub const SAMPLE_NAME_LENGTH: usize = 3;
+/// Where the kit name is stored in a kit bank.
+pub const KIT_NAME_OFFSET: usize = 0x52;
+pub const KIT_NAME_LENGTH: usize = 6;
+/// Where the sample data starts in a kit bank.
+pub const SAMPLE_DATA_OFFSET: usize = 0x60;
+/// Sample pointers are stored as addresses in the switchable bank area.
+pub const BANK_ADDRESS: usize = 0x4000;
+
+/// Returns true if the bank holds a kit.
+pub fn is_kit_bank(bank: &[u8]) -> bool {
+    bank[0..2] == KIT_MAGIC
+}
+
+/// Returns true if the bank is reserved for a kit but is empty.
+pub fn is_empty_kit_bank(bank: &[u8]) -> bool {
+    bank[0..2] == EMPTY_KIT_MAGIC
+}
+
+/// Reads a kit from a kit bank.
+///
+/// The bank starts with 16 little-endian sample pointers, where sample `n`
+/// runs from pointer `n` up to pointer `n + 1`. A zero pointer ends the list.
+pub fn read_kit(bank: &[u8], bank_index: usize) -> Result<Kit, RomError> {
+    let pointers = bank[..(SAMPLES_PER_KIT + 1) * 2]
+        .chunks_exact(2)
+        .map(|p| u16::from_le_bytes([p[0], p[1]]) as usize)
+        .collect::<Vec<_>>();
+
+    let mut samples = vec![];
+    for (idx, window) in pointers.windows(2).enumerate() {
+        let (start, end) = (window[0], window[1]);
+        if end == 0 {
+            break;
+        }
+        if start < BANK_ADDRESS + SAMPLE_DATA_OFFSET
+            || end < start
+            || end > BANK_ADDRESS + BANK_SIZE
+        {
+            return Err(RomError::CorruptKit {
+                bank: bank_index,
+                reason: format!(
+                    "Sample {} has invalid bounds {:#06x}-{:#06x}",
+                    idx, start, end
+                ),
+            });
+        }
+
+        let name_offset = SAMPLE_NAMES_OFFSET + idx * SAMPLE_NAME_LENGTH;
+        samples.push(Sample {
+            name: read_name(&bank[name_offset..name_offset + SAMPLE_NAME_LENGTH]),
+            data: bank[start - BANK_ADDRESS..end - BANK_ADDRESS].to_vec(),
+        });
+    }
+
+    Ok(Kit {
+        name: read_name(&bank[KIT_NAME_OFFSET..KIT_NAME_OFFSET + KIT_NAME_LENGTH]),
+        samples,
+    })
+}
+
+/// Writes a name into a fixed-size field, uppercased and padded with spaces.
+pub fn write_name(field: &mut [u8], name: &str) {
+    let name = name.to_ascii_uppercase();
+    let mut bytes = name.bytes().chain(std::iter::repeat(b' '));
+    for byte in field.iter_mut() {
+        *byte = bytes.next().unwrap();
+    }
+}
+
+/// Writes a kit into a kit bank, replacing its samples and names.
+/// The sample area past the new samples is cleared, but any other bytes of the bank
+/// BSDj does not understand are left as they were.
+///
+/// # ERRORS
+///
+/// Errors if the kit has more than 15 samples or its samples do not fit in the bank.
+pub fn write_kit(bank: &mut [u8], kit: &Kit) -> Result<(), RomError> {
+    let size = kit.samples.iter().map(|s| s.data.len()).sum::<usize>();
+    let max = BANK_SIZE - SAMPLE_DATA_OFFSET;
+    if kit.samples.len() > SAMPLES_PER_KIT || size > max {
+        return Err(RomError::KitTooLarge {
+            name: kit.name.clone(),
+            size,
+            max,
+        });
+    }
+
+    // Clear the sample pointers, then point each sample at its data.
+    bank[..(SAMPLES_PER_KIT + 1) * 2].fill(0);
+
+    let mut address = BANK_ADDRESS + SAMPLE_DATA_OFFSET;
+    bank[0..2].copy_from_slice(&(address as u16).to_le_bytes());
+    for (idx, sample) in kit.samples.iter().enumerate() {
+        let start = address - BANK_ADDRESS;
+        bank[start..start + sample.data.len()].copy_from_slice(&sample.data);
+        address += sample.data.len();
+
+        let pointer = (idx + 1) * 2;
+        bank[pointer..pointer + 2].copy_from_slice(&(address as u16).to_le_bytes());
+
+        let name_offset = SAMPLE_NAMES_OFFSET + idx * SAMPLE_NAME_LENGTH;
+        write_name(
+            &mut bank[name_offset..name_offset + SAMPLE_NAME_LENGTH],
+            &sample.name,
+        );
+    }
+
+    bank[address - BANK_ADDRESS..].fill(0);
+
+    write_name(
+        &mut bank[KIT_NAME_OFFSET..KIT_NAME_OFFSET + KIT_NAME_LENGTH],
+        &kit.name,
+    );
+    Ok(())
+}
@@ -0,0 +1,367 @@
+use crate::cli::CliArgs;
+use crate::resources::types::{kit::Kit, Kits};
+use crate::tilerender::*;
+use bevy::prelude::*;
+use std::fs;
+
+pub mod font;
+pub mod header;
+pub mod kit;
+pub mod palette;
+pub mod patch;
+pub mod rom_error;
+pub mod sram;
+
+pub use header::{RomLayout, RomVersion};
+pub use rom_error::*;
+
+/// The size of a single ROM bank.
+pub const BANK_SIZE: usize = 0x4000;
+
+/// A font's name, along with its TileSprites and their TileIds.
+pub type RomFont = (String, Vec<(String, TileSprite)>);
+/// A palette's name, along with its ColorSets and their ColorIds.
+pub type RomPalette = (String, Vec<(ColorId, ColorSet)>);
+
+// region:      RomPlugin
+
+pub struct RomPlugin;
+
+impl Plugin for RomPlugin {
+    fn build(&self, app: &mut App) {
+        let filename = app
+            .world
+            .get_resource::<CliArgs>()
+            .and_then(|cli| cli.rom.clone());
+
+        if let Some(filename) = filename {
+            if let Err(e) = load_rom(&filename, &mut app.world) {
+                error!("Could not load LSDj ROM `{}`: {}", filename, e);
+            }
+        }
+    }
+}
+
+/// Loads the kits, fonts and palettes from a ROM into the app.
+/// Nothing is loaded if any part of the ROM could not be read.
+fn load_rom(filename: &str, world: &mut World) -> Result<(), RomError> {
+    let rom = LsdjRom::open(filename)?;
+    let kits = rom.kits()?;
+    let fonts = rom.fonts()?;
+    let palettes = rom.palettes()?;
+
+    let mut lh = world
+        .get_resource_mut::<LayerHandler>()
+        .expect("RomPlugin must be added after the TileRenderPlugin.");
+    for (name, tilesprites) in fonts {
+        for (tileid, tilesprite) in tilesprites {
+            lh.get_renderer_mut()
+                .add_tilesprite(&tileid, tilesprite)
+                .expect("ROM font tiles are always 8x8.");
+        }
+        if !lh.font_names.contains(&name) {
+            lh.font_names.push(name);
+        }
+    }
+    for (name, colorsets) in palettes {
+        for (colorsetid, colorset) in colorsets {
+            lh.get_renderer_mut().add_colorset(&colorsetid, colorset);
+        }
+        if !lh.color_names.contains(&name) {
+            lh.color_names.push(name);
+        }
+    }
+
+    world
+        .get_resource_mut::<Kits>()
+        .expect("RomPlugin must be added after the ResourcePlugin.")
+        .set_all(kits);
+
+    info!("Loaded LSDj {} ROM `{}`", rom.version(), filename);
+    Ok(())
+}
+
+// endregion:   RomPlugin
+
+/// A user-supplied LSDj ROM.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LsdjRom {
+    data: Vec<u8>,
+    version: RomVersion,
+    layout: RomLayout,
+}
+
+impl LsdjRom {
+    /// Reads an LSDj ROM from a `.gb` file.
+    pub fn open(filename: &str) -> Result<LsdjRom, RomError> {
+        let data = fs::read(filename).map_err(|e| RomError::Io(filename.to_string(), e))?;
+        LsdjRom::from_bytes(data)
+    }
+
+    /// Validates a ROM image and detects its version.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if the image is not a whole number of banks, the header is corrupt,
+    /// if it is not a version of LSDj BSDj knows the layout of, or if it is too
+    /// small to hold the fonts and palettes.
+    pub fn from_bytes(data: Vec<u8>) -> Result<LsdjRom, RomError> {
+        if data.is_empty() || !data.len().is_multiple_of(BANK_SIZE) {
+            return Err(RomError::InvalidSize(data.len()));
+        }
+        header::verify_header_checksum(&data)?;
+
+        let version = header::detect_version(&data)?;
+        let layout = RomLayout::for_version(&version)
+            .ok_or_else(|| RomError::UnknownVersion(version.to_string()))?;
+
+        let banks = data.len() / BANK_SIZE;
+        let needed = layout.font_bank.max(layout.palette_bank);
+        if banks <= needed {
+            return Err(RomError::TooFewBanks { banks, needed });
+        }
+
+        Ok(LsdjRom {
+            data,
+            version,
+            layout,
+        })
+    }
+
+    /// Returns the version of LSDj this ROM is.
+    pub fn version(&self) -> RomVersion {
+        self.version
+    }
+
+    /// Returns where the fonts and palettes are stored in this ROM.
+    pub fn layout(&self) -> RomLayout {
+        self.layout
+    }
+
+    /// Returns the raw ROM image.
+    pub fn data(&self) -> &Vec<u8> {
+        &self.data
+    }
+
+    /// Returns the number of banks in the ROM.
+    pub fn bank_count(&self) -> usize {
+        self.data.len() / BANK_SIZE
+    }
+
+    /// Returns the contents of a bank.
+    ///
+    /// # PANICS
+    ///
+    /// Panics if the bank is larger than or equal to `self.bank_count()`
+    pub fn bank(&self, index: usize) -> &[u8] {
+        &self.data[index * BANK_SIZE..(index + 1) * BANK_SIZE]
+    }
+
+    /// Returns the indexes of all banks reserved for kits, including empty ones.
+    /// Only the layout's kit banks are looked at, so code is never taken for a kit.
+    pub fn kit_banks(&self) -> Vec<usize> {
+        (self.layout.first_kit_bank..self.bank_count())
+            .filter(|idx| {
+                let bank = self.bank(*idx);
+                kit::is_kit_bank(bank) || kit::is_empty_kit_bank(bank)
+            })
+            .collect()
+    }
+
+    /// Reads all non-empty kits in the ROM, in bank order.
+    pub fn kits(&self) -> Result<Vec<Kit>, RomError> {
+        self.kit_banks()
+            .into_iter()
+            .filter(|idx| kit::is_kit_bank(self.bank(*idx)))
+            .map(|idx| kit::read_kit(self.bank(idx), idx))
+            .collect()
+    }
+
+    /// Returns the offset of the palette name table within the palette bank.
+    pub fn palette_names_offset(&self) -> Result<usize, RomError> {
+        let bank = self.bank(self.layout.palette_bank);
+        let table_size = self.layout.palette_count * palette::PALETTE_NAME_SIZE;
+
+        find_name_table(bank, self.layout.palette_count, palette::PALETTE_NAME_SIZE)
+            .filter(|offset| *offset >= self.layout.palette_count * palette::PALETTE_SIZE)
+            // Font names follow on directly from the palette names.
+            .filter(|offset| {
+                let font_names = &bank[offset + table_size..];
+                find_name_table(font_names, font::FONT_COUNT, palette::PALETTE_NAME_SIZE) == Some(0)
+            })
+            .ok_or(RomError::TableNotFound(
+                "palette name table",
+                self.layout.palette_bank,
+            ))
+    }
+
+    /// Returns the name of each palette.
+    pub fn palette_names(&self) -> Result<Vec<String>, RomError> {
+        let offset = self.palette_names_offset()?;
+        Ok(self.read_names(offset, self.layout.palette_count))
+    }
+
+    /// Returns the name of each font.
+    pub fn font_names(&self) -> Result<Vec<String>, RomError> {
+        let offset =
+            self.palette_names_offset()? + self.layout.palette_count * palette::PALETTE_NAME_SIZE;
+        Ok(self.read_names(offset, font::FONT_COUNT))
+    }
+
+    /// Reads `count` names from a name table in the palette bank.
+    fn read_names(&self, offset: usize, count: usize) -> Vec<String> {
+        let bank = self.bank(self.layout.palette_bank);
+        (0..count)
+            .map(|idx| {
+                let start = offset + idx * palette::PALETTE_NAME_SIZE;
+                read_name(&bank[start..start + font::FONT_NAME_LENGTH])
+            })
+            .collect()
+    }
+
+    /// Reads all fonts in the ROM, along with their names.
+    pub fn fonts(&self) -> Result<Vec<RomFont>, RomError> {
+        let bank = self.bank(self.layout.font_bank);
+        let fonts = self
+            .font_names()?
+            .into_iter()
+            .zip(bank.chunks_exact(font::FONT_SIZE))
+            .map(|(name, data)| {
+                let tilesprites = font::read_font(data, &name);
+                (name, tilesprites)
+            })
+            .collect();
+        Ok(fonts)
+    }
+
+    /// Reads all palettes in the ROM, along with their names.
+    pub fn palettes(&self) -> Result<Vec<RomPalette>, RomError> {
+        let bank = self.bank(self.layout.palette_bank);
+        let start =
+            self.palette_names_offset()? - self.layout.palette_count * palette::PALETTE_SIZE;
+
+        let palettes = self
+            .palette_names()?
+            .into_iter()
+            .zip(bank[start..].chunks_exact(palette::PALETTE_SIZE))
+            .map(|(name, data)| {
+                let colorsets = palette::read_palette(data, &name);
+                (name, colorsets)
+            })
+            .collect();
+        Ok(palettes)
+    }
+}
+
+/// Reads a name stored in the ROM, stopping at the first zero byte.
+/// Names are stored in uppercase, but BSDj's fonts only have lowercase letters.
+pub fn read_name(bytes: &[u8]) -> String {
+    bytes
+        .iter()
+        .take_while(|b| **b != 0)
+        .map(|b| match *b {
+            b @ 0x20..=0x7e => (b as char).to_ascii_lowercase(),
+            _ => ' ',
+        })
+        .collect::<String>()
+        .trim_end()
+        .to_string()
+}
+
+/// Returns true if a byte can appear in a name table.
+fn is_name_char(byte: u8) -> bool {
+    byte.is_ascii_alphanumeric() || byte == b' '
+}
+
+/// Finds the first run of `count` names in `bytes`, each `size` bytes long
+/// and made of `size - 1` name characters followed by a zero.
+fn find_name_table(bytes: &[u8], count: usize, size: usize) -> Option<usize> {
+    let table_size = count * size;
+    if bytes.len() < table_size {
+        return None;
+    }
+
+    (0..=bytes.len() - table_size).find(|offset| {
+        bytes[*offset..offset + table_size]
+            .chunks_exact(size)
+            .all(|name| {
+                name[size - 1] == 0
+                    && name[..size - 1].iter().all(|b| is_name_char(*b))
+                    && name[..size - 1].iter().any(|b| *b != b' ')
+            })
+    })
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    /// Builds a blank LSDj v9 ROM with an empty kit bank and valid name tables.
+    /// Bank 2 starts like an empty kit bank, but is before the kit banks.
+    pub fn blank_rom() -> LsdjRom {
+        LsdjRom::from_bytes(blank_data(0x21)).unwrap()
+    }
+
+    /// Builds the image of a blank LSDj v9 ROM with `banks` banks.
+    fn blank_data(banks: usize) -> Vec<u8> {
+        let mut data = vec![0; banks * BANK_SIZE];
+        let title = b"LSDJ-V9.2.0";
+        data[header::TITLE_RANGE.start..header::TITLE_RANGE.start + title.len()]
+            .copy_from_slice(title);
+        data[2 * BANK_SIZE..2 * BANK_SIZE + 2].copy_from_slice(&kit::EMPTY_KIT_MAGIC);
+
+        let layout = RomLayout::for_version(&header::detect_version(&data).unwrap()).unwrap();
+        if banks > layout.first_kit_bank {
+            let kit_bank = layout.first_kit_bank * BANK_SIZE;
+            data[kit_bank..kit_bank + 2].copy_from_slice(&kit::EMPTY_KIT_MAGIC);
+        }
+        if banks > layout.palette_bank {
+            let names =
+                layout.palette_bank * BANK_SIZE + layout.palette_count * palette::PALETTE_SIZE;
+            for idx in 0..layout.palette_count + font::FONT_COUNT {
+                let start = names + idx * palette::PALETTE_NAME_SIZE;
+                data[start..start + font::FONT_NAME_LENGTH].copy_from_slice(b"NONE");
+            }
+        }
+
+        header::fix_checksums(&mut data);
+        data
+    }
+
+    #[test]
+    /// The fonts and palettes are read with their names, and only the kit banks hold kits.
+    fn read_rom() {
+        let rom = blank_rom();
+        assert_eq!(rom.version().to_string(), "9.2.0");
+
+        let fonts = rom.fonts().unwrap();
+        assert_eq!(fonts.len(), font::FONT_COUNT);
+        assert_eq!(fonts[0].0, "none");
+        assert!(!fonts[0].1.is_empty());
+
+        let palettes = rom.palettes().unwrap();
+        assert_eq!(palettes.len(), rom.layout().palette_count);
+        assert_eq!(palettes[5].0, "none");
+
+        assert_eq!(rom.kit_banks(), vec![rom.layout().first_kit_bank]);
+        assert!(rom.kits().unwrap().is_empty());
+    }
+
+    #[test]
+    /// ROMs that aren't whole banks, or are too small to hold the fonts and palettes, are rejected.
+    fn reject_short_rom() {
+        let mut data = blank_data(0x21);
+        data.pop();
+        assert!(matches!(
+            LsdjRom::from_bytes(data),
+            Err(RomError::InvalidSize(_))
+        ));
+        assert!(matches!(
+            LsdjRom::from_bytes(blank_data(0x10)),
+            Err(RomError::TooFewBanks {
+                banks: 0x10,
+                needed: 0x1f
+            })
+        ));
+    }
+}
@@ -0,0 +1,92 @@
+use super::*;
+use crate::tilerender::parse_colorset::{from_555, to_555};
+use image::Rgba;
+
+/// The number of colorsets in an LSDj palette.
+/// LSDj has no `empty` colorset, so it is always left transparent.
+pub const COLORSETS_PER_PALETTE: usize = 5;
+/// The number of colors in a colorset.
+pub const COLORS_PER_COLORSET: usize = 4;
+/// The size of a single palette in bytes. Each color is a little-endian RGB555 word.
+pub const PALETTE_SIZE: usize = COLORSETS_PER_PALETTE * COLORS_PER_COLORSET * 2;
+/// The size of a palette name in the name table, including the terminating zero.
+pub const PALETTE_NAME_SIZE: usize = 5;
+
+/// Converts a little-endian RGB555 Game Boy Color word into an RGBA struct.
+pub fn word_to_rgba(word: u16) -> Rgba<u8> {
+    from_555(
+        (word & 0x1f) as u8,
+        ((word >> 5) & 0x1f) as u8,
+        ((word >> 10) & 0x1f) as u8,
+        31,
+    )
+}
+
+/// Reads a palette from the ROM, returning its ColorSets with their ColorIds
+/// in the same form `ColorSetParser::parse` does.
+pub fn read_palette(data: &[u8], prefix: &str) -> Vec<(ColorId, ColorSet)> {
+    let mut colorsets = vec![];
+
+    // LSDj stores its colorsets in the same order as `Colors`, minus `empty`.
+    for (slot, colors) in Colors::iter()
+        .iter()
+        .zip(data.chunks_exact(COLORS_PER_COLORSET * 2))
+    {
+        let colors = colors
+            .chunks_exact(2)
+            .enumerate()
+            .map(|(id, word)| {
+                let word = u16::from_le_bytes([word[0], word[1]]);
+                (id as PixelColorId, word_to_rgba(word))
+            })
+            .collect::<Vec<_>>();
+
+        colorsets.push((
+            format!("{}_{}", prefix, slot),
+            ColorSet::from_tuple(&colors),
+        ));
+    }
+
+    let empty = (0..COLORS_PER_COLORSET as PixelColorId)
+        .map(|id| (id, Rgba([0, 0, 0, 0])))
+        .collect::<Vec<_>>();
+    colorsets.push((
+        format!("{}_{}", prefix, Colors::Empty),
+        ColorSet::from_tuple(&empty),
+    ));
+
+    colorsets
+}
+
+/// Converts an RGBA struct into a little-endian RGB555 Game Boy Color word.
+/// The alpha channel is dropped.
+pub fn rgba_to_word(color: &Rgba<u8>) -> u16 {
+    let (r, g, b) = to_555(color);
+    r as u16 | (g as u16) << 5 | (b as u16) << 10
+}
+
+/// Converts the ColorSets of a palette, as returned by `ColorSetParser::parse`,
+/// into the bytes LSDj stores a palette as.
+///
+/// # ERRORS
+///
+/// Errors if any of the colorsets LSDj uses are missing.
+pub fn write_palette(colorsets: &[(ColorId, ColorSet)], prefix: &str) -> Result<Vec<u8>, RomError> {
+    let mut data = Vec::with_capacity(PALETTE_SIZE);
+    for slot in Colors::iter().iter().take(COLORSETS_PER_PALETTE) {
+        let colorid = format!("{}_{}", prefix, slot);
+        let (_, colorset) = colorsets
+            .iter()
+            .find(|(id, _)| *id == colorid)
+            .ok_or_else(|| RomError::MissingColorSet(colorid.clone()))?;
+
+        for pixel in 0..COLORS_PER_COLORSET as PixelColorId {
+            let word = colorset
+                .get_color(&pixel)
+                .map(rgba_to_word)
+                .ok_or_else(|| RomError::MissingColorSet(colorid.clone()))?;
+            data.extend(word.to_le_bytes());
+        }
+    }
+    Ok(data)
+}
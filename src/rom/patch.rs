@@ -0,0 +1,199 @@
+use super::*;
+use crate::formats::text;
+use crate::tilerender::{
+    assets::asset_name, parse_colorset::ColorSetParser, parse_tilesprite::TileSpriteParser,
+};
+use std::path::Path;
+
+/// Writes BSDj content into a copy of an LSDj ROM.
+///
+/// Call `finish` once all changes are made to fix the checksums
+/// and retrieve the new ROM image.
+pub struct RomPatcher {
+    rom: LsdjRom,
+}
+
+impl RomPatcher {
+    /// Starts patching a copy of the ROM.
+    pub fn new(rom: &LsdjRom) -> RomPatcher {
+        RomPatcher { rom: rom.clone() }
+    }
+
+    /// Returns a mutable slice of a bank.
+    fn bank_mut(&mut self, index: usize) -> &mut [u8] {
+        &mut self.rom.data[index * BANK_SIZE..(index + 1) * BANK_SIZE]
+    }
+
+    /// Replaces the kits in the ROM, in bank order.
+    /// Kit banks after the last kit are left as they were.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if there are more kits than kit banks, or a kit does not fit in a bank.
+    pub fn replace_kits(&mut self, kits: &[Kit]) -> Result<(), RomError> {
+        let banks = self.rom.kit_banks();
+        if kits.len() > banks.len() {
+            return Err(RomError::TooManyKits {
+                count: kits.len(),
+                banks: banks.len(),
+            });
+        }
+
+        for (bank, kit) in banks.into_iter().zip(kits) {
+            kit::write_kit(self.bank_mut(bank), kit)?;
+        }
+        Ok(())
+    }
+
+    /// Replaces one of the ROM's fonts with the tiles from a TileSprite file.
+    /// The font is renamed to `name`, which is cut down to 4 characters.
+    pub fn replace_font(
+        &mut self,
+        slot: usize,
+        name: &str,
+        tilesprites: &[(String, TileSprite)],
+        prefix: &str,
+    ) -> Result<(), RomError> {
+        if slot >= font::FONT_COUNT {
+            return Err(RomError::InvalidSlot("font", slot, font::FONT_COUNT));
+        }
+
+        let data = font::write_font(tilesprites, prefix)?;
+        let names_offset = self.rom.palette_names_offset()?
+            + (self.rom.layout.palette_count + slot) * palette::PALETTE_NAME_SIZE;
+        let layout = self.rom.layout;
+
+        let font_bank = self.bank_mut(layout.font_bank);
+        font_bank[slot * font::FONT_SIZE..(slot + 1) * font::FONT_SIZE].copy_from_slice(&data);
+
+        let palette_bank = self.bank_mut(layout.palette_bank);
+        kit::write_name(
+            &mut palette_bank[names_offset..names_offset + font::FONT_NAME_LENGTH],
+            name,
+        );
+        Ok(())
+    }
+
+    /// Replaces one of the ROM's palettes with ColorSets from a ColorSet file.
+    /// The palette is renamed to `name`, which is cut down to 4 characters.
+    pub fn replace_palette(
+        &mut self,
+        slot: usize,
+        name: &str,
+        colorsets: &[(ColorId, ColorSet)],
+        prefix: &str,
+    ) -> Result<(), RomError> {
+        let layout = self.rom.layout;
+        if slot >= layout.palette_count {
+            return Err(RomError::InvalidSlot("palette", slot, layout.palette_count));
+        }
+
+        let data = palette::write_palette(colorsets, prefix)?;
+        let names_offset = self.rom.palette_names_offset()?;
+        let palette_offset = names_offset - (layout.palette_count - slot) * palette::PALETTE_SIZE;
+        let name_offset = names_offset + slot * palette::PALETTE_NAME_SIZE;
+
+        let palette_bank = self.bank_mut(layout.palette_bank);
+        palette_bank[palette_offset..palette_offset + palette::PALETTE_SIZE].copy_from_slice(&data);
+        kit::write_name(
+            &mut palette_bank[name_offset..name_offset + font::FONT_NAME_LENGTH],
+            name,
+        );
+        Ok(())
+    }
+
+    /// Fixes the checksums and returns the patched ROM image.
+    pub fn finish(mut self) -> Vec<u8> {
+        header::fix_checksums(&mut self.rom.data);
+        self.rom.data
+    }
+}
+
+/// Runs the `patch` command, writing a new ROM (and optionally a `.sav`) to disk.
+pub fn run(args: &crate::cli::PatchArgs) -> Result<(), RomError> {
+    let rom = LsdjRom::open(&args.input)?;
+    let mut patcher = RomPatcher::new(&rom);
+
+    if let Some(kits) = &args.kits {
+        patcher.replace_kits(&LsdjRom::open(kits)?.kits()?)?;
+    }
+    for (slot, filename) in &args.fonts {
+        let name = asset_name(Path::new(filename))?;
+        let tilesprites = TileSpriteParser::parse(filename, &name)?;
+        patcher.replace_font(*slot, &name, &tilesprites, &name)?;
+    }
+    for (slot, filename) in &args.palettes {
+        let name = asset_name(Path::new(filename))?;
+        let colorsets = ColorSetParser::parse(filename, &name)?;
+        patcher.replace_palette(*slot, &name, &colorsets, &name)?;
+    }
+
+    // Read the song before writing anything, so a broken song doesn't leave half the output.
+    let mut channels = Default::default();
+    let mut chains = Default::default();
+    let mut phrases = Default::default();
+    let mut instruments = Default::default();
+    let mut project = Default::default();
+    if let Some(song) = &args.song {
+        text::load_song(
+            song,
+            &mut channels,
+            &mut chains,
+            &mut phrases,
+            &mut instruments,
+            &mut project,
+        )?;
+    }
+
+    fs::write(&args.output, patcher.finish()).map_err(|e| RomError::Io(args.output.clone(), e))?;
+
+    if let Some(sav) = &args.sav {
+        let sram = sram::create_sram(&channels, &chains, &phrases, &instruments, &project);
+        fs::write(sav, sram).map_err(|e| RomError::Io(sav.clone(), e))?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::resources::types::kit::Sample;
+    use crate::rom::test::blank_rom;
+
+    #[test]
+    /// Patch a kit, font and palette into a ROM and read them back out.
+    fn patch_round_trip() {
+        let rom = blank_rom();
+        let (_, font) = rom.fonts().unwrap().remove(0);
+        let (_, palette) = rom.palettes().unwrap().remove(0);
+        let kit = Kit {
+            name: "test".to_string(),
+            samples: vec![Sample {
+                name: "bd".to_string(),
+                data: vec![0x12, 0x34, 0x56],
+            }],
+        };
+
+        let mut patcher = RomPatcher::new(&rom);
+        patcher.replace_kits(std::slice::from_ref(&kit)).unwrap();
+        patcher.replace_font(1, "fnt", &font, "none").unwrap();
+        patcher.replace_palette(2, "pal", &palette, "none").unwrap();
+        let patched = LsdjRom::from_bytes(patcher.finish()).unwrap();
+
+        assert_eq!(vec![kit], patched.kits().unwrap());
+        assert_eq!("fnt", patched.font_names().unwrap()[1]);
+        assert_eq!("pal", patched.palette_names().unwrap()[2]);
+    }
+
+    #[test]
+    /// Kits that don't have a bank to go in are rejected.
+    fn too_many_kits() {
+        let rom = blank_rom();
+        let mut patcher = RomPatcher::new(&rom);
+        let result = patcher.replace_kits(&[Kit::default(), Kit::default()]);
+        assert!(matches!(
+            result,
+            Err(RomError::TooManyKits { count: 2, banks: 1 })
+        ));
+    }
+}
@@ -0,0 +1,40 @@
+/// An error that occurs when reading or patching an LSDj ROM.
+#[derive(thiserror::Error, Debug)]
+pub enum RomError {
+    #[error("Could not read ROM file `{0}`: {1}")]
+    Io(String, std::io::Error),
+    #[error("ROM size `{0:#x}` is not a whole number of banks.")]
+    InvalidSize(usize),
+    #[error("ROM has `{banks:#04x}` banks, too few to hold bank `{needed:#04x}`")]
+    TooFewBanks { banks: usize, needed: usize },
+    #[error("Header checksum mismatch: Got `{received:#04x}`, expected `{expected:#04x}`")]
+    HeaderChecksum { received: u8, expected: u8 },
+    #[error("Not an LSDj ROM: Header title is `{0}`")]
+    NotLsdj(String),
+    #[error("Unknown LSDj version `{0}`")]
+    UnknownVersion(String),
+    #[error("Could not find the {0} in bank `{1:#04x}`")]
+    TableNotFound(&'static str, usize),
+    #[error("Kit bank `{bank:#04x}` is corrupt: {reason}")]
+    CorruptKit { bank: usize, reason: String },
+    #[error("Kit `{name}` does not fit in a bank: Got `{size:#x}` bytes of samples, expected at most `{max:#x}`")]
+    KitTooLarge {
+        name: String,
+        size: usize,
+        max: usize,
+    },
+    #[error("Too many kits: Got `{count}`, but the ROM only has `{banks}` kit banks")]
+    TooManyKits { count: usize, banks: usize },
+    #[error("Invalid {0} slot `{1}`: Expected `0..{2}`")]
+    InvalidSlot(&'static str, usize, usize),
+    #[error("Font is missing the TileSprite `{0}`")]
+    MissingTile(String),
+    #[error("TileSprite `{0}` can not be stored in a ROM font: Only 8x8 tiles with PixelColorIds 0-3 are allowed")]
+    InvalidTile(String),
+    #[error("Palette is missing the ColorSet `{0}`")]
+    MissingColorSet(String),
+    #[error("{0}")]
+    Song(#[from] crate::formats::text::TextError),
+    #[error("{0}")]
+    Asset(#[from] crate::tilerender::TileRendererError),
+}
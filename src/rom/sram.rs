@@ -0,0 +1,313 @@
+use crate::resources::types::*;
+
+/// The size of a full LSDj `.sav` image.
+pub const SRAM_SIZE: usize = 0x20000;
+/// The size of the working song at the start of SRAM, which LSDj loads on boot.
+pub const SONG_SIZE: usize = 0x8000;
+
+// region:      Working song layout
+
+const PHRASE_NOTES: usize = 0x0000;
+const BOOKMARKS: usize = 0x0ff0;
+const GROOVES: usize = 0x1090;
+const SONG_CHAINS: usize = 0x1290;
+const INIT_CHECK_1: usize = 0x1e78;
+const INSTRUMENT_ALLOC: usize = 0x2020;
+const CHAIN_PHRASES: usize = 0x2080;
+const CHAIN_TRANSPOSES: usize = 0x2880;
+const INSTRUMENT_PARAMS: usize = 0x3080;
+const INIT_CHECK_2: usize = 0x3e80;
+const PHRASE_ALLOC: usize = 0x3e82;
+const CHAIN_ALLOC: usize = 0x3ea2;
+const TEMPO: usize = 0x3fb4;
+const PHRASE_COMMANDS: usize = 0x4000;
+const PHRASE_COMMAND_VALUES: usize = 0x4ff0;
+const PHRASE_INSTRUMENTS: usize = 0x7000;
+const INIT_CHECK_3: usize = 0x7ff0;
+const SONG_VERSION: usize = 0x7fff;
+
+/// Written in 3 places of the working song so LSDj knows it has been initialised.
+const INIT_CHECK: [u8; 2] = *b"rb";
+/// The number of bytes each instrument's parameters take up.
+const INSTRUMENT_SIZE: usize = 0x10;
+/// The number of instruments in the working song. BSDj's extra instrument 0x40 is
+/// the speech instrument, which LSDj does not store.
+const INSTRUMENT_COUNT: usize = 0x40;
+/// Where each parameter is within an instrument's 16 bytes.
+const INSTRUMENT_TYPE: usize = 0;
+const INSTRUMENT_ENVELOPE: usize = 1;
+const INSTRUMENT_SYNTH: usize = 2;
+const INSTRUMENT_LENGTH: usize = 3;
+const INSTRUMENT_WAVE: usize = 3;
+const INSTRUMENT_SWEEP: usize = 4;
+const INSTRUMENT_DUTY_PAN: usize = 7;
+/// Set in the length byte when the sound length is on.
+const LENGTH_ON: u8 = 0x40;
+/// The pan bits for both speakers. LSDj mutes an instrument panned to neither.
+const PAN_BOTH: u8 = 0x03;
+/// The wave volume bytes LSDj stores for volumes 0 to 3, the same as NR32.
+const WAVE_VOLUMES: [u8; 4] = [0x00, 0x60, 0x40, 0x20];
+/// The bit of the noise shape, stored the same way as NR43, that picks the short pattern.
+const NOISE_SHORT: u8 = 0x08;
+/// The number of bookmarks stored per channel.
+const BOOKMARKS_PER_CHANNEL: usize = 16;
+/// The default groove LSDj starts with, 6 ticks per step.
+const DEFAULT_GROOVE: [u8; 2] = [0x06, 0x06];
+
+// endregion:   Working song layout
+
+// region:      File system layout
+
+const FILE_SYSTEM_CHECK: usize = 0x813e;
+const ACTIVE_FILE: usize = 0x8140;
+const BLOCK_ALLOC: usize = 0x8141;
+const BLOCK_ALLOC_END: usize = 0x8200;
+/// Written to the file system header so LSDj knows SRAM has been initialised.
+const FILE_SYSTEM_INIT: [u8; 2] = *b"jk";
+/// The value for no file or an unused block.
+const EMPTY_FILE: u8 = 0xff;
+
+// endregion:   File system layout
+
+/// Creates a `.sav` image holding the song as the working song.
+/// No songs are saved in the file system, so LSDj will boot straight into it.
+pub fn create_sram(
+    channels: &Channels,
+    chains: &Chains,
+    phrases: &Phrases,
+    instruments: &Instruments,
+    project: &Project,
+) -> Vec<u8> {
+    let mut sram = vec![0; SRAM_SIZE];
+    write_song(
+        &mut sram[..SONG_SIZE],
+        channels,
+        chains,
+        phrases,
+        instruments,
+        project,
+    );
+
+    sram[FILE_SYSTEM_CHECK..FILE_SYSTEM_CHECK + 2].copy_from_slice(&FILE_SYSTEM_INIT);
+    sram[ACTIVE_FILE] = EMPTY_FILE;
+    sram[BLOCK_ALLOC..BLOCK_ALLOC_END].fill(EMPTY_FILE);
+    sram
+}
+
+/// Writes the song into a 32KiB working song image.
+///
+/// # PANICS
+///
+/// Panics if `song` is not `SONG_SIZE` bytes.
+pub fn write_song(
+    song: &mut [u8],
+    channels: &Channels,
+    chains: &Chains,
+    phrases: &Phrases,
+    instruments: &Instruments,
+    project: &Project,
+) {
+    assert_eq!(
+        song.len(),
+        SONG_SIZE,
+        "Working song image has the wrong size: Got `{:#x}`, expected `{:#x}`",
+        song.len(),
+        SONG_SIZE
+    );
+    song.fill(0);
+
+    let mut used_chains = [false; 0x80];
+    let mut used_phrases = [false; 0xff];
+
+    // Song rows are stored 4 bytes at a time, one for each channel.
+    for (channel_idx, channel) in channels.get_all().iter().enumerate() {
+        let mut bookmarks = vec![];
+        for row in 0..=0xff {
+            let chain = channel.get_chain(row);
+            if let Some(chain) = chain {
+                used_chains[chain as usize] = true;
+            }
+            song[SONG_CHAINS + row as usize * 4 + channel_idx] = chain.unwrap_or(0xff);
+
+            if channel.is_bookmarked(row) {
+                bookmarks.push(row);
+            }
+        }
+
+        let start = BOOKMARKS + channel_idx * BOOKMARKS_PER_CHANNEL;
+        let bookmarks = bookmarks.into_iter().chain(std::iter::repeat(0xff));
+        for (byte, bookmark) in song[start..start + BOOKMARKS_PER_CHANNEL]
+            .iter_mut()
+            .zip(bookmarks)
+        {
+            *byte = bookmark;
+        }
+    }
+
+    for (chain_idx, used_chain) in used_chains.iter_mut().enumerate() {
+        let chain = chains.get(chain_idx).unwrap();
+        for step in 0..0x10 {
+            let offset = chain_idx * 0x10 + step;
+            let phrase = chain.get_phrase(step);
+            if let Some(phrase) = phrase {
+                *used_chain = true;
+                used_phrases[phrase as usize] = true;
+            }
+            song[CHAIN_PHRASES + offset] = phrase.unwrap_or(0xff);
+            song[CHAIN_TRANSPOSES + offset] = chain.get_transpose(step).unwrap();
+        }
+    }
+
+    let mut used_instruments = [false; INSTRUMENT_COUNT];
+    for (phrase_idx, used_phrase) in used_phrases.iter_mut().enumerate() {
+        let phrase = phrases.get(phrase_idx).unwrap();
+        if *phrase != phrase::Phrase::default() {
+            *used_phrase = true;
+        }
+
+        for row in 0..0x10 {
+            let offset = phrase_idx * 0x10 + row;
+            let instr = phrase.get_instr(row);
+            if let Some(instr) = instr.filter(|i| (*i as usize) < INSTRUMENT_COUNT) {
+                used_instruments[instr as usize] = true;
+            }
+
+            song[PHRASE_NOTES + offset] = phrase.get_note(row).map(|n| n.0).unwrap_or(0);
+            song[PHRASE_INSTRUMENTS + offset] = instr.unwrap_or(0xff);
+            song[PHRASE_COMMANDS + offset] = phrase.get_cmd(row).unwrap_or_default().to_num();
+            song[PHRASE_COMMAND_VALUES + offset] = phrase.get_cmd_val(row).unwrap();
+        }
+    }
+
+    for (idx, used) in used_instruments.iter().enumerate() {
+        let instr = instruments.get(idx).unwrap();
+        song[INSTRUMENT_ALLOC + idx] = *used as u8;
+        let start = INSTRUMENT_PARAMS + idx * INSTRUMENT_SIZE;
+        write_instrument(&mut song[start..start + INSTRUMENT_SIZE], instr);
+    }
+
+    write_alloc_bits(&mut song[PHRASE_ALLOC..PHRASE_ALLOC + 0x20], &used_phrases);
+    write_alloc_bits(&mut song[CHAIN_ALLOC..CHAIN_ALLOC + 0x10], &used_chains);
+
+    song[GROOVES..GROOVES + DEFAULT_GROOVE.len()].copy_from_slice(&DEFAULT_GROOVE);
+
+    song[TEMPO] = project.get_tempo_byte();
+
+    for check in [INIT_CHECK_1, INIT_CHECK_2, INIT_CHECK_3] {
+        song[check..check + 2].copy_from_slice(&INIT_CHECK);
+    }
+    song[SONG_VERSION] = 0;
+}
+
+/// Writes an instrument's parameters into its 16 bytes.
+/// LSDj's wave instruments always play until stopped, so a wave's length is not stored.
+fn write_instrument(bytes: &mut [u8], instr: &instrument::Instrument) {
+    bytes[INSTRUMENT_TYPE] = instrument_type(instr);
+    bytes[INSTRUMENT_DUTY_PAN] = PAN_BOTH;
+    match instr {
+        instrument::Instrument::Pulse(pulse) => {
+            bytes[INSTRUMENT_ENVELOPE] = pulse.envelope;
+            bytes[INSTRUMENT_LENGTH] = sound_length(pulse.length);
+            bytes[INSTRUMENT_SWEEP] = pulse.sweep;
+            bytes[INSTRUMENT_DUTY_PAN] |= (pulse.duty & 0x03) << 6;
+        }
+        instrument::Instrument::Wave(wave) => {
+            bytes[INSTRUMENT_ENVELOPE] = WAVE_VOLUMES[(wave.volume & 0x03) as usize];
+            bytes[INSTRUMENT_SYNTH] = wave.wave & 0xf0;
+            bytes[INSTRUMENT_WAVE] = wave.wave;
+        }
+        instrument::Instrument::Noise(noise) => {
+            bytes[INSTRUMENT_ENVELOPE] = noise.envelope;
+            bytes[INSTRUMENT_LENGTH] = sound_length(noise.length);
+            bytes[INSTRUMENT_SWEEP] = if noise.short { NOISE_SHORT } else { 0 };
+        }
+        instrument::Instrument::Kit() | instrument::Instrument::Speech() => {}
+    }
+}
+
+/// Returns the byte LSDj uses to store a pulse or noise length.
+fn sound_length(length: Option<u8>) -> u8 {
+    match length {
+        Some(length) => LENGTH_ON | (length & 0x3f),
+        None => 0,
+    }
+}
+
+/// Returns the byte LSDj uses to store the type of an instrument.
+fn instrument_type(instr: &instrument::Instrument) -> u8 {
+    match instr {
+        instrument::Instrument::Pulse(_) => 0,
+        instrument::Instrument::Wave(_) => 1,
+        instrument::Instrument::Kit() => 2,
+        instrument::Instrument::Noise(_) => 3,
+        // LSDj does not have a speech instrument type, so store it as a wave.
+        instrument::Instrument::Speech() => 1,
+    }
+}
+
+/// Packs a list of flags into a bitfield, least significant bit first.
+fn write_alloc_bits(bytes: &mut [u8], used: &[bool]) {
+    bytes.fill(0);
+    for (idx, used) in used.iter().enumerate() {
+        if *used {
+            bytes[idx / 8] |= 1 << (idx % 8);
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::formats::text;
+
+    #[test]
+    /// A song's rows, chains, phrases and instruments are written where LSDj reads them.
+    fn write_text_song() {
+        let (mut channels, mut chains, mut phrases, mut instruments, mut project) =
+            Default::default();
+        let song = "tempo 150\n\n[song]\n00 00 -- 01 --\n01 00*-- -- --\n\n\
+            [chain 00]\n0 00 00\n1 01 0c\n\n[phrase 01]\n0 c#4 02 v34\n\n\
+            [instruments]\n02 pulse envelope=f3 duty=1 length=-- sweep=15\n\
+            03 wave volume=2 wave=34 length=--\n04 noise envelope=a1 length=20 short=1\n";
+        text::read_song(
+            song,
+            &mut channels,
+            &mut chains,
+            &mut phrases,
+            &mut instruments,
+            &mut project,
+        )
+        .unwrap();
+        let sram = create_sram(&channels, &chains, &phrases, &instruments, &project);
+        assert_eq!(sram.len(), SRAM_SIZE);
+
+        assert_eq!(sram[SONG_CHAINS..SONG_CHAINS + 4], [0x00, 0xff, 0x01, 0xff]);
+        assert_eq!(sram[BOOKMARKS], 0x01);
+        assert_eq!(sram[BOOKMARKS + BOOKMARKS_PER_CHANNEL], 0xff);
+        assert_eq!(sram[CHAIN_PHRASES..CHAIN_PHRASES + 3], [0x00, 0x01, 0xff]);
+        assert_eq!(sram[CHAIN_TRANSPOSES + 1], 0x0c);
+
+        let note = phrases.get(1).unwrap().get_note(0).unwrap();
+        assert_ne!(note.0, 0);
+        assert_eq!(sram[PHRASE_NOTES + 0x10], note.0);
+        assert_eq!(sram[PHRASE_INSTRUMENTS + 0x10], 0x02);
+        assert_eq!(sram[PHRASE_COMMAND_VALUES + 0x10], 0x34);
+        assert_eq!(sram[INSTRUMENT_ALLOC + 2], 1);
+
+        let instrument = |idx: usize| {
+            let start = INSTRUMENT_PARAMS + idx * INSTRUMENT_SIZE;
+            &sram[start..start + INSTRUMENT_SIZE]
+        };
+        assert_eq!(instrument(2)[..8], [0, 0xf3, 0, 0, 0x15, 0, 0, 0x43]);
+        assert_eq!(instrument(3)[..8], [1, 0x40, 0x30, 0x34, 0, 0, 0, 0x03]);
+        assert_eq!(instrument(4)[..8], [3, 0xa1, 0, 0x60, 0x08, 0, 0, 0x03]);
+        assert_eq!(sram[PHRASE_ALLOC], 0b11);
+        assert_eq!(sram[CHAIN_ALLOC], 0b11);
+        assert_eq!(sram[TEMPO], 150);
+        assert_eq!(sram[INIT_CHECK_3..INIT_CHECK_3 + 2], INIT_CHECK);
+        assert_eq!(
+            sram[FILE_SYSTEM_CHECK..FILE_SYSTEM_CHECK + 2],
+            FILE_SYSTEM_INIT
+        );
+    }
+}
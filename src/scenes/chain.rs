@@ -1,4 +1,11 @@
-use crate::resources::{input::*, *};
+use crate::merge::{Cell, Conflicts};
+use crate::resources::{
+    bindings::Action,
+    input::*,
+    joypad::{JoypadEvent, LastValues},
+    *,
+};
+use crate::session::PeerCursors;
 use crate::states;
 use crate::tilerender::*;
 use crate::utils::u8_utils::WrappingAdd;
@@ -10,12 +17,18 @@ impl Plugin for ChainScene {
     fn build(&self, app: &mut App) {
         app.add_system_set(
             SystemSet::on_update(states::States::Chain)
+                .label(super::EDIT_SCENE)
                 .with_system(enter_scene)
                 .with_system(handle_scroll)
                 .with_system(move_cursor)
                 .with_system(type_value)
                 .with_system(delete_value_system)
-                .with_system(open_phrase_system)
+                .with_system(joypad_edit)
+                .with_system(open_phrase_system),
+        );
+        app.add_system_set(
+            SystemSet::on_update(states::States::Chain)
+                .after(super::EDIT_SCENE)
                 .with_system(draw_screen),
         );
     }
@@ -76,40 +89,50 @@ fn handle_scroll(
         return;
     }
 
-    // Require control key to be pressed to change value of phrase or transpose.
-    if !input.exclusively_pressed(&[InputType::Key(KeyCode::LControl)]) {
+    // Require edit (Control by default) to be held, or a swipe, to change value of phrase or transpose.
+    if !input.scroll_editing() {
         return;
     }
 
     if let Some(cursor_pos) = input.get_cursor_tile_position() {
         if let Some((is_chain, index, is_left)) = hover(cursor_pos) {
-            // Get the chain info
             if let Some(chain) = chains.get_mut(edited_chain.0 as usize) {
-                let change = if is_left { 0x10 } else { 0x01 };
-
-                if is_chain {
-                    // If there is no chain there already, set to 0.
-                    // Else add the change, but clamp between 0 and 0xfe
-                    let mut new = 0;
-                    if let Some(phrase) = chain.get_phrase(index as usize) {
-                        new = phrase as i32 + change * scroll_delta;
-                        new = new.clamp(0, 0xfe)
-                    }
-                    chain.set_phrase(index as usize, new as u8);
-                } else {
-                    // Editing the transpose.
-                    // Add to the transpose, overflowing/underflowing
-                    // if necessary.
-                    if let Some(transpose) = chain.get_transpose(index as usize) {
-                        let new = transpose.w_add((change * scroll_delta) as isize);
-                        chain.set_transpose(index as usize, new);
-                    }
-                }
+                change_value(chain, is_chain, index, is_left, scroll_delta);
             }
         }
     }
 }
 
+/// Changes a phrase or transpose by `delta`, times 0x10 if `is_left`.
+fn change_value(
+    chain: &mut types::chain::Chain,
+    is_chain: bool,
+    index: u8,
+    is_left: bool,
+    delta: i32,
+) {
+    let change = if is_left { 0x10 } else { 0x01 };
+
+    if is_chain {
+        // If there is no chain there already, set to 0.
+        // Else add the change, but clamp between 0 and 0xfe
+        let mut new = 0;
+        if let Some(phrase) = chain.get_phrase(index as usize) {
+            new = phrase as i32 + change * delta;
+            new = new.clamp(0, 0xfe)
+        }
+        chain.set_phrase(index as usize, new as u8);
+    } else {
+        // Editing the transpose.
+        // Add to the transpose, overflowing/underflowing
+        // if necessary.
+        if let Some(transpose) = chain.get_transpose(index as usize) {
+            let new = transpose.w_add((change * delta) as isize);
+            chain.set_transpose(index as usize, new);
+        }
+    }
+}
+
 fn move_cursor(
     input: Res<InputRes>,
     mut chain_cursor: ResMut<cursors::ChainCursor>,
@@ -128,16 +151,8 @@ fn move_cursor(
     }
 
     // Move the cursor based on directional inputs (Up, Left, Down, and Right)
-    for key in InputType::directional_keycodes() {
-        if input.dr_pressed(&key) && input.exclusively_pressed(&[key]) {
-            match key {
-                InputType::Key(KeyCode::Up) => chain_cursor.sub_y(),
-                InputType::Key(KeyCode::Down) => chain_cursor.add_y(),
-                InputType::Key(KeyCode::Left) => chain_cursor.sub_x(),
-                InputType::Key(KeyCode::Right) => chain_cursor.add_x(),
-                _ => panic!("what"),
-            }
-        }
+    if let Some(key) = input.directional_input() {
+        key.move_cursor(&mut *chain_cursor);
     }
 
     // If a phrase exists at the new cursor location, set the currently edited chain to that.
@@ -200,16 +215,11 @@ fn delete_value_system(
     edited_chain: ResMut<edited::EditedChain>,
 ) {
     if let Some(chain) = chains.get_mut(edited_chain.0 as usize) {
-        // If the `Delete` or `Backspace` keys are pressed,
+        // If delete is pressed (`Delete` or `Backspace` by default),
         // delete the phrase or transpose at the songcursor position.
-        for key in [
-            InputType::Key(KeyCode::Delete),
-            InputType::Key(KeyCode::Back),
-        ] {
-            if input.dr_pressed(&key) {
-                let (cursor_x, cursor_y) = chain_cursor.get_pos();
-                delete_value(cursor_x == 0, cursor_y, chain);
-            }
+        if input.action_dr_pressed(Action::Delete) {
+            let (cursor_x, cursor_y) = chain_cursor.get_pos();
+            delete_value(cursor_x == 0, cursor_y, chain);
         }
 
         // If the middle mouse button is clicked, delete the chain
@@ -224,6 +234,45 @@ fn delete_value_system(
     }
 }
 
+/// Edits the value under the cursor with the Game Boy's buttons.
+/// A inserts the last used phrase into an empty step, or the edited phrase if none was used yet.
+fn joypad_edit(
+    mut joypad_reader: EventReader<JoypadEvent>,
+    chain_cursor: Res<cursors::ChainCursor>,
+    edited_chain: Res<edited::EditedChain>,
+    edited_phrase: Res<edited::EditedPhrase>,
+    mut last_values: ResMut<LastValues>,
+    mut chains: ResMut<types::chain::Chains>,
+) {
+    let chain = match chains.get_mut(edited_chain.0 as usize) {
+        Some(chain) => chain,
+        None => return,
+    };
+    let (cursor_x, cursor_y) = chain_cursor.get_pos();
+    let is_chain = cursor_x == 0;
+
+    for event in joypad_reader.iter() {
+        match *event {
+            JoypadEvent::Insert if is_chain && chain.get_phrase(cursor_y as usize).is_none() => {
+                let phrase = last_values.get_phrase().unwrap_or(edited_phrase.0);
+                chain.set_phrase(cursor_y as usize, phrase);
+            }
+            JoypadEvent::Delete => {
+                delete_value(is_chain, cursor_y, chain);
+                continue;
+            }
+            JoypadEvent::Change { delta, coarse } => {
+                change_value(chain, is_chain, cursor_y, coarse, delta)
+            }
+            _ => continue,
+        }
+
+        if let Some(phrase) = chain.get_phrase(cursor_y as usize).filter(|_| is_chain) {
+            last_values.set_phrase(phrase);
+        }
+    }
+}
+
 /// Delete a value at this position.
 fn delete_value(is_chain: bool, index: u8, chain: &mut types::chain::Chain) {
     if is_chain {
@@ -300,6 +349,8 @@ fn draw_screen(
     chain_cursor: Res<cursors::ChainCursor>,
     chains: Res<types::Chains>,
     edited_chain: Res<edited::EditedChain>,
+    conflicts: Res<Conflicts>,
+    peer_cursors: Res<PeerCursors>,
 ) {
     let chain = chains.get(edited_chain.0 as usize);
 
@@ -323,6 +374,16 @@ fn draw_screen(
 
     for y in 0..16 {
         let mut color = [colors::Colors::Background, colors::Colors::Highlight];
+        let cell = Cell::ChainStep {
+            chain: edited_chain.0 as usize,
+            step: y,
+        };
+        if conflicts.contains(&cell) {
+            color = [colors::Colors::Conflict; 2];
+        }
+        if peer_cursors.contains(&cell) {
+            color = [colors::Colors::Ghost; 2];
+        }
 
         // Draw the phrase
         // Set color if cursor is currently there.
@@ -353,3 +414,44 @@ fn draw_screen(
         .unwrap();
     }
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::harness::Harness;
+
+    #[test]
+    /// Phrases and transposes typed, scrolled and deleted show up in the chain and on the screen.
+    fn edit_steps() {
+        let mut harness = Harness::new();
+        harness.press(KeyCode::Tab);
+        harness.tap(KeyCode::Right);
+        harness.release(KeyCode::Tab);
+        harness.step();
+        assert_eq!(harness.row("map", 0), "chain 00");
+
+        harness.tap(KeyCode::Key1);
+        harness.tap(KeyCode::Key2);
+        assert_eq!(harness.row("map", 2), "0 12 00");
+
+        // Ctrl + scroll changes the digit under the cursor.
+        harness.press(KeyCode::LControl);
+        harness.scroll(3, 2, 1.);
+        harness.scroll(5, 2, 1.);
+        harness.release(KeyCode::LControl);
+        harness.step();
+        assert_eq!(harness.row("map", 2), "0 13 10");
+
+        // Typing on the transpose shifts in the digit, and delete clears the phrase.
+        harness.tap(KeyCode::Right);
+        harness.tap(KeyCode::Key3);
+        harness.tap(KeyCode::Left);
+        harness.tap(KeyCode::Delete);
+        assert_eq!(harness.row("map", 2), "0 -- 03");
+        assert_eq!(harness.row("map", 3), "1 -- 00");
+
+        let chain = harness.resource::<types::Chains>().get(0).unwrap();
+        assert_eq!(chain.get_phrase(0), None);
+        assert_eq!(chain.get_transpose(0), Some(0x03));
+    }
+}
@@ -0,0 +1,462 @@
+//! The font editor, for painting the active font's glyphs and saving them as a `.tilesprite`.
+
+use crate::resources::{
+    bindings::Action, cursors::FontCursor, edited::EditedGlyph, input::*, joypad::JoypadEvent,
+    Cursor,
+};
+use crate::states;
+use crate::tilerender::{parse_tilesprite::write_tilesprites, *};
+use crate::utils::config_dir::config_dir;
+use bevy::prelude::*;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub struct FontScene;
+
+impl Plugin for FontScene {
+    fn build(&self, app: &mut App) {
+        app.insert_resource(Pen(3));
+        app.add_system(open_editor);
+        app.add_system_set(
+            SystemSet::on_update(states::States::Font)
+                .label(super::EDIT_SCENE)
+                .with_system(enter_scene)
+                .with_system(move_cursor)
+                .with_system(paint)
+                .with_system(save_font),
+        );
+        app.add_system_set(
+            SystemSet::on_update(states::States::Font)
+                .after(super::EDIT_SCENE)
+                .with_system(draw_screen),
+        );
+    }
+}
+
+/// The PixelColorId the font editor paints with.
+pub struct Pen(pub PixelColorId);
+
+/// The number of PixelColorIds a font's pixels can be, as on the Game Boy.
+const PIXEL_COLORS: PixelColorId = 4;
+/// The number of glyphs on each row of the glyph grid.
+const GLYPH_COLUMNS: usize = 15;
+/// The first row of the glyph grid.
+const GLYPH_ROW: usize = 2;
+/// The row the edited glyph's name is on.
+const NAME_ROW: usize = 8;
+/// The number of glyphs the grid shows at once. Longer fonts are shown a page at a time.
+const GLYPH_PAGE: usize = GLYPH_COLUMNS * (NAME_ROW - GLYPH_ROW);
+/// The most glyphs a font can have to be edited, as `EditedGlyph` is a u8.
+const MAX_GLYPHS: usize = u8::MAX as usize + 1;
+/// The top row of the edited glyph's pixels.
+const PIXEL_ROW: usize = 9;
+/// The column the preview is drawn from.
+const PREVIEW_X: usize = 9;
+/// The row the preview's label is on, below the sidebar.
+const PREVIEW_ROW: usize = 11;
+/// The row the pen and its swatches are on.
+const PEN_ROW: usize = 17;
+/// The column the pen's swatches start on.
+const SWATCH_X: usize = 6;
+
+/// What the mouse is over on the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hover {
+    /// A glyph in the glyph grid, by its index.
+    Glyph(usize),
+    /// A pixel of the edited glyph.
+    Pixel(u8, u8),
+    /// One of the pen's swatches.
+    Swatch(PixelColorId),
+}
+
+/// Returns the TileIds of the active font, in the order its file has them.
+pub fn font_tile_ids(lh: &LayerHandler) -> Vec<String> {
+    let prefix = format!("{}_", lh.active_font);
+    lh.get_renderer()
+        .get_all_tile_ids()
+        .iter()
+        .filter(|id| id.starts_with(&prefix))
+        .cloned()
+        .collect()
+}
+
+/// Returns the name of a glyph of the active font, as drawn with `LayerHandler::set_tile`.
+fn glyph_name<'a>(lh: &LayerHandler, tile_id: &'a str) -> &'a str {
+    tile_id
+        .strip_prefix(&format!("{}_", lh.active_font))
+        .unwrap_or(tile_id)
+}
+
+/// Returns where a font is saved: the `fonts` folder of the user's config,
+/// or the working directory if there's none.
+pub fn font_path(name: &str) -> PathBuf {
+    let filename = format!("{}.tilesprite", name);
+    match config_dir() {
+        Some(dir) => dir.join("fonts").join(filename),
+        None => PathBuf::from(filename),
+    }
+}
+
+/// Writes the active font to a `.tilesprite`, making its folder if needed.
+///
+/// # ERRORS
+///
+/// Errors if the file can't be written.
+pub fn save(lh: &LayerHandler, path: &Path) -> io::Result<()> {
+    let renderer = lh.get_renderer();
+    let tilesprites: Vec<_> = font_tile_ids(lh)
+        .into_iter()
+        .map(|id| {
+            let tilesprite = renderer.get_tilesprite(&id).unwrap().clone();
+            (id, tilesprite)
+        })
+        .collect();
+    let contents = write_tilesprites(
+        &tilesprites,
+        &lh.active_font,
+        renderer.get_tile_width(),
+        renderer.get_tile_height(),
+    );
+
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, contents)
+}
+
+/// Sets a pixel of a TileSprite in the renderer, so everything drawn with it changes too.
+fn set_pixel(lh: &mut LayerHandler, tile_id: &str, (x, y): (u8, u8), color: PixelColorId) {
+    let renderer = lh.get_renderer_mut();
+    let index = y as usize * renderer.get_tile_width() + x as usize;
+    let mut sprite = renderer.get_tilesprite(tile_id).unwrap().read().clone();
+    if sprite[index] == color {
+        return;
+    }
+    sprite[index] = color;
+    renderer
+        .add_tilesprite(tile_id, TileSprite::new(sprite))
+        .unwrap();
+}
+
+/// Returns the index of the first glyph on the page of the glyph grid showing a glyph.
+fn page_start(index: usize) -> usize {
+    index / GLYPH_PAGE * GLYPH_PAGE
+}
+
+/// Opens the font editor when `font` (F9 by default) is pressed,
+/// unless the active font has too many glyphs to edit.
+fn open_editor(
+    input: Res<InputRes>,
+    lh: Res<LayerHandler>,
+    mut next_screen: ResMut<states::NextScreen>,
+) {
+    if !input.action_just_pressed(Action::FontEditor) {
+        return;
+    }
+    let glyph_count = font_tile_ids(&lh).len();
+    if glyph_count > MAX_GLYPHS {
+        error!(
+            "Could not edit the font `{}`. It has {} glyphs, but at most {} can be edited",
+            lh.active_font, glyph_count, MAX_GLYPHS
+        );
+        return;
+    }
+    next_screen.0 = Some(states::States::Font);
+}
+
+fn enter_scene(mut lh: ResMut<LayerHandler>, load_scene: ResMut<states::LoadState>) {
+    // Dont try enter the scene if the scene should not be loaded.
+    if !load_scene.0 {
+        return;
+    }
+
+    // Clear the map
+    lh.clear_layer("map", "space", Colors::Background).unwrap();
+
+    let heading = format!("font {}", lh.active_font);
+    lh.set_tiles_string("map", 0, 0, &heading, Colors::Background)
+        .unwrap();
+    lh.set_tiles_string("map", PREVIEW_X, PREVIEW_ROW, "preview", Colors::Background)
+        .unwrap();
+    lh.set_tiles_string("map", 0, PEN_ROW, "pen", Colors::Background)
+        .unwrap();
+}
+
+/// Moves the cursor with the arrows or a click, picks the glyph or pen that's clicked,
+/// and picks the pen with `0`-`3`.
+fn move_cursor(
+    input: Res<InputRes>,
+    lh: Res<LayerHandler>,
+    mut font_cursor: ResMut<FontCursor>,
+    mut glyph: ResMut<EditedGlyph>,
+    mut pen: ResMut<Pen>,
+) {
+    if input.just_pressed(&InputType::Mouse(MouseButton::Left)) {
+        let glyph_count = font_tile_ids(&lh).len();
+        let first = page_start((glyph.0 as usize).min(glyph_count.saturating_sub(1)));
+        match input
+            .get_cursor_tile_position()
+            .and_then(|pos| hover(pos, glyph_count, first))
+        {
+            Some(Hover::Glyph(index)) => glyph.0 = index as u8,
+            Some(Hover::Pixel(x, y)) => {
+                font_cursor.set_x(x as isize);
+                font_cursor.set_y(y as isize);
+            }
+            Some(Hover::Swatch(color)) => pen.0 = color,
+            None => (),
+        }
+    }
+
+    if let Some(direction) = input.directional_input() {
+        direction.move_cursor(&mut *font_cursor);
+    }
+
+    for (color, key) in InputType::hex_keycodes()
+        .iter()
+        .take(PIXEL_COLORS as usize)
+        .enumerate()
+    {
+        if input.just_pressed(key) {
+            pen.0 = color as PixelColorId;
+        }
+    }
+}
+
+/// Paints the pixel under the cursor with A, or clears it with B + A.
+/// Holding the mouse paints the pixels it's dragged over.
+/// A or B with Left/Right switches to the previous/next glyph, and with Up/Down a row of them.
+fn paint(
+    input: Res<InputRes>,
+    mut joypad_reader: EventReader<JoypadEvent>,
+    mut lh: ResMut<LayerHandler>,
+    font_cursor: Res<FontCursor>,
+    mut glyph: ResMut<EditedGlyph>,
+    pen: Res<Pen>,
+) {
+    let ids = font_tile_ids(&lh);
+    if ids.is_empty() {
+        return;
+    }
+    let index = (glyph.0 as usize).min(ids.len() - 1);
+
+    for event in joypad_reader.iter() {
+        match event {
+            JoypadEvent::Insert => set_pixel(&mut lh, &ids[index], font_cursor.get_pos(), pen.0),
+            JoypadEvent::Delete => set_pixel(&mut lh, &ids[index], font_cursor.get_pos(), 0),
+            JoypadEvent::Change { delta, coarse } => {
+                let step = if *coarse { GLYPH_COLUMNS as i32 } else { 1 };
+                // Up is a positive change, but moves up the grid.
+                let delta = if *coarse { -delta } else { *delta };
+                let new_index = index as i32 + delta * step;
+                glyph.0 = new_index.clamp(0, ids.len() as i32 - 1) as u8;
+            }
+            JoypadEvent::Navigate(_) => (),
+        }
+    }
+
+    if input.is_pressed(&[InputType::Mouse(MouseButton::Left)]) {
+        if let Some(Hover::Pixel(x, y)) = input
+            .get_cursor_tile_position()
+            .and_then(|pos| hover(pos, ids.len(), page_start(index)))
+        {
+            set_pixel(&mut lh, &ids[index], (x, y), pen.0);
+        }
+    }
+}
+
+/// Saves the active font when `save-as` (Ctrl + S by default) is pressed.
+fn save_font(input: Res<InputRes>, lh: Res<LayerHandler>) {
+    if !input.action_just_pressed(Action::SaveAs) {
+        return;
+    }
+
+    let path = font_path(&lh.active_font);
+    match save(&lh, &path) {
+        Ok(()) => info!("Saved the font to `{}`", path.display()),
+        Err(e) => error!("Could not save the font to `{}`: {}", path.display(), e),
+    }
+}
+
+/// Returns what the mouse is over, with the glyph grid showing the page from glyph `first`.
+fn hover(cursor_pos: (i32, i32), glyph_count: usize, first: usize) -> Option<Hover> {
+    let (x, y) = cursor_pos;
+    if x < 0 || y < 0 {
+        return None;
+    }
+    let (x, y) = (x as usize, y as usize);
+
+    if x < GLYPH_COLUMNS && (GLYPH_ROW..NAME_ROW).contains(&y) {
+        let index = first + (y - GLYPH_ROW) * GLYPH_COLUMNS + x;
+        if index < glyph_count {
+            return Some(Hover::Glyph(index));
+        }
+        return None;
+    }
+    if x < 8 && (PIXEL_ROW..PIXEL_ROW + 8).contains(&y) {
+        return Some(Hover::Pixel(x as u8, (y - PIXEL_ROW) as u8));
+    }
+    let swatches = SWATCH_X..SWATCH_X + PIXEL_COLORS as usize;
+    if y == PEN_ROW && swatches.contains(&x) {
+        return Some(Hover::Swatch((x - SWATCH_X) as PixelColorId));
+    }
+    None
+}
+
+fn draw_screen(
+    mut lh: ResMut<LayerHandler>,
+    font_cursor: Res<FontCursor>,
+    glyph: Res<EditedGlyph>,
+    pen: Res<Pen>,
+) {
+    let ids = font_tile_ids(&lh);
+    if ids.is_empty() {
+        return;
+    }
+    let index = (glyph.0 as usize).min(ids.len() - 1);
+
+    // Only the page with the edited glyph is drawn, blanking the rest of a short page.
+    let first = page_start(index);
+    for slot in 0..GLYPH_PAGE {
+        let (name, color) = match ids.get(first + slot) {
+            Some(id) if first + slot == index => (glyph_name(&lh, id).to_string(), Colors::Cursor),
+            Some(id) => (glyph_name(&lh, id).to_string(), Colors::Highlight),
+            None => ("space".to_string(), Colors::Background),
+        };
+        lh.set_tile(
+            "map",
+            slot % GLYPH_COLUMNS,
+            GLYPH_ROW + slot / GLYPH_COLUMNS,
+            &name,
+            color,
+        )
+        .unwrap();
+    }
+
+    // Underscores aren't in the font, so show them as spaces.
+    let name: String = glyph_name(&lh, &ids[index])
+        .replace('_', " ")
+        .chars()
+        .take(GLYPH_COLUMNS)
+        .collect();
+    lh.set_tiles_string(
+        "map",
+        0,
+        NAME_ROW,
+        &" ".repeat(GLYPH_COLUMNS),
+        Colors::Background,
+    )
+    .unwrap();
+    lh.set_tiles_string("map", 0, NAME_ROW, &name, Colors::Highlight)
+        .unwrap();
+
+    // Each pixel is drawn as a tile filled with its PixelColorId.
+    let sprite = lh
+        .get_renderer()
+        .get_tilesprite(&ids[index])
+        .unwrap()
+        .clone();
+    let width = lh.get_renderer().get_tile_width();
+    for (idx, pixel) in sprite.read().iter().enumerate() {
+        let (x, y) = (idx % width, idx / width);
+        let color = if (x as u8, y as u8) == font_cursor.get_pos() {
+            Colors::Cursor
+        } else {
+            Colors::Background
+        };
+        let tile = format!("pixel{}", pixel.min(&(PIXEL_COLORS - 1)));
+        lh.set_tile("map", x, PIXEL_ROW + y, &tile, color).unwrap();
+    }
+
+    // The glyph between its neighbours, in the colors text is drawn in.
+    let previous = glyph_name(&lh, &ids[(index + ids.len() - 1) % ids.len()]).to_string();
+    let next = glyph_name(&lh, &ids[(index + 1) % ids.len()]).to_string();
+    let current = glyph_name(&lh, &ids[index]).to_string();
+    let preview = [previous, current.clone(), next, current];
+    for (y, color) in [Colors::Background, Colors::Highlight, Colors::Cursor]
+        .into_iter()
+        .enumerate()
+    {
+        lh.set_tiles("map", PREVIEW_X, PREVIEW_ROW + 2 + y, &preview, color)
+            .unwrap();
+    }
+
+    lh.set_tiles_hex("map", 4, PEN_ROW, pen.0 as usize, 1, Colors::Cursor)
+        .unwrap();
+    for color in 0..PIXEL_COLORS {
+        let tile = format!("pixel{}", color);
+        lh.set_tile(
+            "map",
+            SWATCH_X + color as usize,
+            PEN_ROW,
+            &tile,
+            Colors::Background,
+        )
+        .unwrap();
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::harness::Harness;
+    use crate::tilerender::parse_tilesprite::TileSpriteParser;
+
+    #[test]
+    /// Painting a pixel changes the font, and a saved font parses back the same.
+    fn paint_and_save() {
+        let mut harness = Harness::new();
+        harness.tap(KeyCode::F9);
+        // The screen is cleared as it's entered, so is drawn in full the frame after.
+        harness.step();
+        assert_eq!(harness.row("map", 0), "font lowr");
+        // The musicnote glyph comes first, and is blank in the corner.
+        assert_eq!(harness.row("map", NAME_ROW), "musicnote");
+
+        harness.tap(KeyCode::Key1);
+        harness.tap(KeyCode::LShift);
+        let lh = harness.resource::<LayerHandler>();
+        let musicnote = lh
+            .get_renderer()
+            .get_tilesprite("lowr_musicnote")
+            .unwrap()
+            .clone();
+        assert_eq!(musicnote.get_pixel(0), 1);
+
+        // A or B with Right moves to the next glyph.
+        harness.press(KeyCode::LAlt);
+        harness.step();
+        harness.tap(KeyCode::Right);
+        harness.release(KeyCode::LAlt);
+        harness.step();
+        assert_eq!(harness.row("map", NAME_ROW), "cursor");
+
+        let path = std::env::temp_dir().join("bsdj_paint_and_save.tilesprite");
+        let lh = harness.resource::<LayerHandler>();
+        save(lh, &path).unwrap();
+        let parsed = TileSpriteParser::parse(path.to_str().unwrap(), "lowr").unwrap();
+        assert_eq!(parsed.len(), font_tile_ids(lh).len());
+        assert_eq!(parsed[0], ("lowr_musicnote".to_string(), musicnote));
+        std::fs::remove_file(path).unwrap();
+    }
+
+    #[test]
+    /// Fonts too long for the glyph grid are shown a page at a time.
+    fn page_glyphs() {
+        assert_eq!(page_start(GLYPH_PAGE - 1), 0);
+        assert_eq!(page_start(GLYPH_PAGE + 3), GLYPH_PAGE);
+        let corner = (0, GLYPH_ROW as i32);
+        assert_eq!(hover(corner, 200, 0), Some(Hover::Glyph(0)));
+        assert_eq!(
+            hover(corner, 200, GLYPH_PAGE),
+            Some(Hover::Glyph(GLYPH_PAGE))
+        );
+        assert_eq!(
+            hover((1, GLYPH_ROW as i32), GLYPH_PAGE + 1, GLYPH_PAGE),
+            None
+        );
+        // The glyph grid never reaches the name.
+        assert_eq!(hover((0, NAME_ROW as i32), 200, 0), None);
+    }
+}
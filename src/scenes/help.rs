@@ -1,4 +1,4 @@
-// use crate::resources::{input::*, *};
+use crate::resources::{bindings::Action, input::*};
 use crate::states;
 use crate::tilerender::*;
 use bevy::prelude::*;
@@ -7,34 +7,69 @@ pub struct HelpScene;
 
 impl Plugin for HelpScene {
     fn build(&self, app: &mut App) {
-        app.add_system_set(
-            SystemSet::on_update(states::States::Help).with_system(enter_scene), // .with_system(draw_screen),
-        );
+        app.add_system_set(SystemSet::on_update(states::States::Help).with_system(draw_screen));
     }
 }
 
-fn enter_scene(mut lh: ResMut<LayerHandler>, load_scene: ResMut<states::LoadState>) {
-    // Dont try enter the scene if the scene should not be loaded.
-    if !load_scene.0 {
+/// The column the bindings are drawn from.
+const BINDING_X: usize = 10;
+/// The most tiles a binding can take before running into the sidebar.
+const BINDING_WIDTH: usize = 6;
+/// The first row the bindings are listed on.
+const FIRST_ROW: usize = 2;
+/// The number of bindings shown at once.
+const VISIBLE_ROWS: usize = 16;
+
+/// Returns a row per binding, with the action's name on its first.
+/// Actions with no bindings get a `-`.
+fn binding_rows(input: &InputRes) -> Vec<(&'static str, String)> {
+    let mut rows = vec![];
+    for action in Action::iter() {
+        let bindings = input.get_bindings().get(action);
+        if bindings.is_empty() {
+            rows.push((action.get_name(), "-".to_string()));
+        }
+        for (idx, binding) in bindings.iter().enumerate() {
+            let name = if idx == 0 { action.get_name() } else { "" };
+            let text = binding.to_string().chars().take(BINDING_WIDTH).collect();
+            rows.push((name, text));
+        }
+    }
+    rows
+}
+
+/// Lists every action with the inputs it's bound to. Up and Down scroll the list.
+fn draw_screen(
+    mut lh: ResMut<LayerHandler>,
+    load_scene: ResMut<states::LoadState>,
+    input: Res<InputRes>,
+    mut scroll: Local<usize>,
+) {
+    let rows = binding_rows(&input);
+    let max_scroll = rows.len().saturating_sub(VISIBLE_ROWS);
+
+    let old_scroll = *scroll;
+    match input.directional_input() {
+        Some(DirectionalInput::Up) => *scroll = scroll.saturating_sub(1),
+        Some(DirectionalInput::Down) => *scroll = (*scroll + 1).min(max_scroll),
+        _ => (),
+    }
+
+    // Only redraw when entering the scene or scrolling.
+    if !load_scene.0 && *scroll == old_scroll {
         return;
     }
 
     // Clear the map
     lh.clear_layer("map", "space", Colors::Background).unwrap();
 
-    // Show a 'TO IMPLEMENT' error.
-    lh.set_tiles_string("map", 2, 4, "-------------", Colors::Highlight)
-        .unwrap();
-    lh.set_tiles_string("map", 2, 5, "    error:   ", Colors::Highlight)
+    lh.set_tiles_string("map", 0, 0, "key bindings", Colors::Background)
         .unwrap();
-    lh.set_tiles_string("map", 2, 6, "    help     ", Colors::Highlight)
-        .unwrap();
-    lh.set_tiles_string("map", 2, 7, "   not yet   ", Colors::Highlight)
-        .unwrap();
-    lh.set_tiles_string("map", 2, 8, " implemented ", Colors::Highlight)
-        .unwrap();
-    lh.set_tiles_string("map", 2, 9, "-------------", Colors::Highlight)
-        .unwrap();
-}
 
-// fn draw_screen(mut lh: ResMut<LayerHandler>) {}
+    for (y, (name, binding)) in rows.iter().skip(*scroll).take(VISIBLE_ROWS).enumerate() {
+        lh.set_tiles_string("map", 1, FIRST_ROW + y, name, Colors::Background)
+            .unwrap();
+        lh.set_tiles_string("map", BINDING_X, FIRST_ROW + y, binding, Colors::Highlight)
+            .unwrap();
+    }
+}
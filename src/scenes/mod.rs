@@ -2,11 +2,13 @@ use bevy::prelude::*;
 
 pub mod chain;
 pub mod file;
+pub mod font;
 pub mod groove;
 pub mod help;
 pub mod instrument;
 pub mod live;
 pub mod navbar;
+pub mod palette;
 pub mod phrase;
 pub mod project;
 pub mod sidebar;
@@ -14,9 +16,14 @@ pub mod song;
 pub mod speech;
 pub mod synth;
 pub mod table;
+pub mod touch_pad;
 pub mod wave;
 pub mod word;
 
+/// The label of the systems that enter and edit a scene.
+/// A scene is drawn after them so a change shows the frame it's made.
+pub const EDIT_SCENE: &str = "edit_scene";
+
 pub struct ScenePlugin;
 
 impl Plugin for ScenePlugin {
@@ -35,8 +42,11 @@ impl Plugin for ScenePlugin {
         app.add_plugin(groove::GrooveScene);
         app.add_plugin(file::FileScene);
         app.add_plugin(help::HelpScene);
+        app.add_plugin(font::FontScene);
+        app.add_plugin(palette::PaletteScene);
 
         app.add_plugin(sidebar::SideBarPlugin);
         app.add_plugin(navbar::NavBarPlugin);
+        app.add_plugin(touch_pad::TouchPadPlugin);
     }
 }
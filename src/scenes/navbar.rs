@@ -2,6 +2,7 @@ use crate::resources::{
     cursors::{Cursor, NavCursor},
     edited::EditedInstrument,
     input::*,
+    joypad::JoypadEvent,
 };
 use crate::states::*;
 use crate::tilerender::*;
@@ -12,6 +13,7 @@ pub struct NavBarPlugin;
 impl Plugin for NavBarPlugin {
     fn build(&self, app: &mut App) {
         app.add_system(input_switch_scene);
+        app.add_system(joypad_switch_scene);
         app.add_system(draw_screen);
         app.add_system_to_stage(CoreStage::PostUpdate, switch_scene);
     }
@@ -59,6 +61,7 @@ pub fn get_navbar_order(editing_instrument: &EditedInstrument) -> [[States; 5];
 #[allow(unused_must_use)]
 fn switch_scene(
     mut new_state_pos: ResMut<NextState>,
+    mut next_screen: ResMut<NextScreen>,
     mut state: ResMut<State<States>>,
     mut load_scene: ResMut<LoadState>,
     mut nav_cursor: ResMut<NavCursor>,
@@ -80,6 +83,13 @@ fn switch_scene(
         nav_cursor.set_x(new_state_pos.0 as isize);
         nav_cursor.set_y(new_state_pos.1 as isize);
     }
+
+    // Screens off the navbar leave the navbar cursor where it was,
+    // so Select + arrows carry on from the last screen on it.
+    if let Some(screen) = next_screen.0.take() {
+        state.overwrite_replace(screen);
+        load_scene.0 = true;
+    }
 }
 
 fn input_switch_scene(
@@ -105,6 +115,49 @@ fn input_switch_scene(
     }
 }
 
+/// Returns the position of the next screen in a direction on the navbar,
+/// skipping over repeats of the current screen, or None at the navbar's edge.
+pub fn neighbour(
+    structure: &[[States; 5]; 3],
+    pos: (u8, u8),
+    direction: DirectionalInput,
+) -> Option<(u8, u8)> {
+    let (dx, dy) = match direction {
+        DirectionalInput::Up => (0, -1),
+        DirectionalInput::Down => (0, 1),
+        DirectionalInput::Left => (-1, 0),
+        DirectionalInput::Right => (1, 0),
+    };
+    let current = structure[pos.1 as usize][pos.0 as usize];
+
+    let (mut x, mut y) = (pos.0 as isize, pos.1 as isize);
+    loop {
+        x += dx;
+        y += dy;
+        let state = structure.get(y as usize)?.get(x as usize)?;
+        if *state != current {
+            return Some((x as u8, y as u8));
+        }
+    }
+}
+
+/// Switches to the neighbouring screen on the navbar with Select + arrows.
+fn joypad_switch_scene(
+    mut joypad_reader: EventReader<JoypadEvent>,
+    nav_cursor: Res<NavCursor>,
+    mut state: ResMut<NextState>,
+    instrument: Res<EditedInstrument>,
+) {
+    let structure = get_navbar_order(&instrument);
+    for event in joypad_reader.iter() {
+        if let JoypadEvent::Navigate(direction) = event {
+            if let Some(pos) = neighbour(&structure, nav_cursor.get_pos(), *direction) {
+                state.0 = Some(pos);
+            }
+        }
+    }
+}
+
 fn draw_screen(
     nav_cursor: Res<NavCursor>,
     mut lh: ResMut<LayerHandler>,
@@ -145,3 +198,30 @@ fn draw_screen(
         }
     }
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    /// Moving along the navbar skips screens that take up more than one tile.
+    fn navbar_neighbours() {
+        let structure = get_navbar_order(&EditedInstrument(0));
+
+        assert_eq!(
+            neighbour(&structure, (0, 1), DirectionalInput::Right),
+            Some((1, 1))
+        );
+        assert_eq!(neighbour(&structure, (0, 1), DirectionalInput::Left), None);
+        assert_eq!(
+            neighbour(&structure, (0, 1), DirectionalInput::Up),
+            Some((0, 0))
+        );
+        // The groove screen fills the rest of the bottom row.
+        assert_eq!(neighbour(&structure, (1, 2), DirectionalInput::Right), None);
+        assert_eq!(
+            neighbour(&structure, (4, 2), DirectionalInput::Left),
+            Some((0, 2))
+        );
+    }
+}
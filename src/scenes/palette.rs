@@ -0,0 +1,475 @@
+//! The palette editor, for changing the active palette's colors and saving them as a `.colorset`.
+
+use crate::resources::{
+    bindings::Action, cursors::PaletteCursor, edited::EditedColorSlot, input::*,
+    joypad::JoypadEvent, Cursor,
+};
+use crate::states;
+use crate::tilerender::{assets::*, parse_colorset::*, *};
+use crate::utils::config_dir::config_dir;
+use bevy::prelude::*;
+use image::Rgba;
+use std::io;
+use std::path::{Path, PathBuf};
+
+pub struct PaletteScene;
+
+impl Plugin for PaletteScene {
+    fn build(&self, app: &mut App) {
+        app.add_system(open_editor);
+        app.add_system_set(
+            SystemSet::on_update(states::States::Palette)
+                .label(super::EDIT_SCENE)
+                .with_system(enter_scene)
+                .with_system(move_cursor)
+                .with_system(change_value)
+                .with_system(save_palette),
+        );
+        app.add_system_set(
+            SystemSet::on_update(states::States::Palette)
+                .after(super::EDIT_SCENE)
+                .with_system(draw_screen),
+        );
+    }
+}
+
+/// The number of colors in each of a palette's slots.
+const SLOT_COLORS: PixelColorId = 4;
+/// The lowest contrast between a slot's background and text colors before it's warned about.
+const MIN_CONTRAST: f64 = 2.;
+/// The first row of the slot list.
+const SLOT_ROW: usize = 2;
+/// The column the slots' swatches are drawn from.
+const SWATCH_X: usize = 12;
+/// The first row of the edited slot's colors.
+const COLOR_ROW: usize = 9;
+/// The row the contrast warning is on, with the slot it's about below it.
+const WARNING_ROW: usize = 15;
+/// The width of the contrast warning.
+const WARNING_WIDTH: usize = 14;
+
+/// What the mouse is over on the screen.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Hover {
+    /// A slot in the slot list, by its index.
+    Slot(usize),
+    /// The red, green or blue of one of the edited slot's colors.
+    Channel(PixelColorId, usize),
+}
+
+/// Returns the column the red, green or blue of a color is drawn from.
+fn channel_x(channel: usize) -> usize {
+    3 + channel * 3
+}
+
+/// Returns the slot at an index of `Colors::iter`, or the first if it's out of range.
+fn slot(index: u8) -> Colors {
+    Colors::iter()
+        .get(index as usize)
+        .copied()
+        .unwrap_or(Colors::Background)
+}
+
+/// Returns the ColorSets of a palette with their ColorIds, in the order a `.colorset` has them.
+/// Colors BSDj adds to LSDj's come last, if the palette has them.
+pub fn palette_colorsets(lh: &LayerHandler, name: &str) -> Vec<(ColorId, ColorSet)> {
+    let renderer = lh.get_renderer();
+    Colors::iter()
+        .into_iter()
+        .chain([Colors::Conflict, Colors::Ghost])
+        .filter_map(|color| {
+            let id = format!("{}_{}", name, color);
+            let colorset = renderer.get_colorset(&id).ok()?.clone();
+            Some((id, colorset))
+        })
+        .collect()
+}
+
+/// Returns where a palette is saved: the `colorsets` folder of the user's config,
+/// or the working directory if there's none.
+pub fn palette_path(name: &str) -> PathBuf {
+    let filename = format!("{}.colorset", name);
+    match config_dir() {
+        Some(dir) => dir.join("colorsets").join(filename),
+        None => PathBuf::from(filename),
+    }
+}
+
+/// Returns a name for a copy of the active palette that isn't taken, such as `red1` for `red`.
+fn new_palette_name(lh: &LayerHandler) -> Option<String> {
+    let base: String = lh.active_colorset.chars().take(3).collect();
+    (1..=9)
+        .map(|number| format!("{}{}", base, number))
+        .find(|name| {
+            !lh.color_names.iter().any(|taken| taken == name) && !palette_path(name).exists()
+        })
+}
+
+/// Changes the red, green or blue of a color in the active palette by `delta` in RGB555,
+/// replacing the slot's ColorSet so it shows everywhere it's drawn.
+fn change_channel(
+    lh: &mut LayerHandler,
+    slot: Colors,
+    pixel_id: PixelColorId,
+    channel: usize,
+    delta: i32,
+) {
+    let id = format!("{}_{}", lh.active_colorset, slot);
+    let renderer = lh.get_renderer_mut();
+    let mut colors: Vec<(PixelColorId, Rgba<u8>)> = match renderer.get_colorset(&id) {
+        Ok(colorset) => (0..SLOT_COLORS)
+            .filter_map(|pixel| colorset.get_color(&pixel).map(|color| (pixel, *color)))
+            .collect(),
+        Err(_) => return,
+    };
+
+    if let Some((_, color)) = colors.iter_mut().find(|(pixel, _)| *pixel == pixel_id) {
+        let (r, g, b) = to_555(color);
+        let mut rgb = [r, g, b];
+        rgb[channel] = (rgb[channel] as i32 + delta).clamp(0, 31) as u8;
+        let alpha = color[3];
+        *color = from_555(rgb[0], rgb[1], rgb[2], 31);
+        color[3] = alpha;
+    }
+    renderer.add_colorset(&id, ColorSet::from_tuple(&colors));
+}
+
+/// Returns the first slot of the active palette whose text is hard to read on its background,
+/// the first and last colors of the slot.
+pub fn low_contrast_slot(lh: &LayerHandler) -> Option<Colors> {
+    let renderer = lh.get_renderer();
+    Colors::iter()
+        .into_iter()
+        .filter(|slot| *slot != Colors::Empty)
+        .find(|slot| {
+            let id = format!("{}_{}", lh.active_colorset, slot);
+            let colorset = match renderer.get_colorset(&id) {
+                Ok(colorset) => colorset,
+                Err(_) => return false,
+            };
+            match (colorset.get_color(&0), colorset.get_color(&3)) {
+                (Some(background), Some(text)) if background[3] != 0 && text[3] != 0 => {
+                    contrast(background, text) < MIN_CONTRAST
+                }
+                _ => false,
+            }
+        })
+}
+
+/// Writes the active palette to a new `.colorset` and switches to it under `name`,
+/// putting the palette it was copied from back as its file has it.
+///
+/// # ERRORS
+///
+/// Errors if the file can't be written.
+pub fn save_as(lh: &mut LayerHandler, name: &str, path: &Path) -> io::Result<()> {
+    let original = lh.active_colorset.clone();
+    let colorsets = palette_colorsets(lh, &original);
+    if let Some(dir) = path.parent() {
+        std::fs::create_dir_all(dir)?;
+    }
+    std::fs::write(path, write_colorsets(&colorsets, &original))?;
+
+    let user_assets = lh.user_assets;
+    let renderer = lh.get_renderer_mut();
+    for (id, colorset) in colorsets {
+        let slot = id.strip_prefix(&format!("{}_", original)).unwrap_or(&id);
+        renderer.add_colorset(&format!("{}_{}", name, slot), colorset);
+    }
+    if let Some(file) = find_asset(AssetKind::Palette, &original, user_assets) {
+        let filename = file.to_string_lossy();
+        if let Err(e) = ColorSetParser::parse_and_add(&filename, &original, renderer) {
+            warn!("Could not put palette `{}` back: {}", original, e);
+        }
+    }
+
+    lh.color_names.push(name.to_string());
+    lh.active_colorset = name.to_string();
+    Ok(())
+}
+
+/// Opens the palette editor when `palette` (F10 by default) is pressed.
+fn open_editor(input: Res<InputRes>, mut next_screen: ResMut<states::NextScreen>) {
+    if input.action_just_pressed(Action::PaletteEditor) {
+        next_screen.0 = Some(states::States::Palette);
+    }
+}
+
+fn enter_scene(mut lh: ResMut<LayerHandler>, load_scene: ResMut<states::LoadState>) {
+    // Dont try enter the scene if the scene should not be loaded.
+    if !load_scene.0 {
+        return;
+    }
+
+    // Clear the map
+    lh.clear_layer("map", "space", Colors::Background).unwrap();
+
+    let heading = format!("palette {}", lh.active_colorset);
+    lh.set_tiles_string("map", 0, 0, &heading, Colors::Background)
+        .unwrap();
+}
+
+/// Moves the cursor with the arrows or a click. Moving onto a slot edits it.
+/// Right goes from the slots to the edited slot's colors, and Left from the red back.
+fn move_cursor(
+    input: Res<InputRes>,
+    mut palette_cursor: ResMut<PaletteCursor>,
+    mut edited_slot: ResMut<EditedColorSlot>,
+) {
+    if input.just_pressed(&InputType::Mouse(MouseButton::Left)) {
+        match input.get_cursor_tile_position().and_then(hover) {
+            Some(Hover::Slot(index)) => palette_cursor.set_y(index as isize),
+            Some(Hover::Channel(pixel_id, channel)) => {
+                palette_cursor.set_x(channel as isize);
+                palette_cursor.set_y((Colors::iter().len() + pixel_id as usize) as isize);
+            }
+            None => (),
+        }
+    }
+
+    let slot_count = Colors::iter().len() as isize;
+    let y = palette_cursor.get_y() as isize;
+    let on_slots = y < slot_count;
+    match input.directional_input() {
+        Some(DirectionalInput::Right) if on_slots => {
+            palette_cursor.set_x(0);
+            palette_cursor.set_y(slot_count);
+        }
+        Some(DirectionalInput::Left) if !on_slots && palette_cursor.get_x() == 0 => {
+            palette_cursor.set_y(edited_slot.0 as isize);
+        }
+        // Up and Down stay in the slots or the colors.
+        Some(DirectionalInput::Down) if y == slot_count - 1 => (),
+        Some(DirectionalInput::Up) if y == slot_count => (),
+        Some(direction) => direction.move_cursor(&mut *palette_cursor),
+        None => (),
+    }
+
+    let y = palette_cursor.get_y();
+    if (y as isize) < slot_count {
+        edited_slot.0 = y;
+    }
+}
+
+/// Changes the value under the cursor with A or B and the arrows,
+/// or the one under the mouse with Ctrl + scroll.
+fn change_value(
+    input: Res<InputRes>,
+    mut joypad_reader: EventReader<JoypadEvent>,
+    mut lh: ResMut<LayerHandler>,
+    palette_cursor: Res<PaletteCursor>,
+    edited_slot: Res<EditedColorSlot>,
+) {
+    let slot = slot(edited_slot.0);
+
+    for event in joypad_reader.iter() {
+        if let JoypadEvent::Change { delta, coarse } = event {
+            let y = palette_cursor.get_y() as usize;
+            if y < Colors::iter().len() {
+                continue;
+            }
+            let pixel_id = (y - Colors::iter().len()) as PixelColorId;
+            let delta = if *coarse { delta * 0x10 } else { *delta };
+            let channel = palette_cursor.get_x() as usize;
+            change_channel(&mut lh, slot, pixel_id, channel, delta);
+        }
+    }
+
+    let scroll_delta = input.get_scroll_delta();
+    if scroll_delta != 0 && input.scroll_editing() {
+        if let Some(Hover::Channel(pixel_id, channel)) =
+            input.get_cursor_tile_position().and_then(hover)
+        {
+            change_channel(&mut lh, slot, pixel_id, channel, scroll_delta);
+        }
+    }
+}
+
+/// Saves the active palette as a new one when `save-as` (Ctrl + S by default) is pressed.
+fn save_palette(
+    input: Res<InputRes>,
+    mut lh: ResMut<LayerHandler>,
+    mut load_scene: ResMut<states::LoadState>,
+) {
+    if !input.action_just_pressed(Action::SaveAs) {
+        return;
+    }
+
+    let name = match new_palette_name(&lh) {
+        Some(name) => name,
+        None => {
+            error!(
+                "Could not find a free name to save `{}` as",
+                lh.active_colorset
+            );
+            return;
+        }
+    };
+    let path = palette_path(&name);
+    match save_as(&mut lh, &name, &path) {
+        Ok(()) => info!("Saved the palette as `{}` to `{}`", name, path.display()),
+        Err(e) => error!("Could not save the palette to `{}`: {}", path.display(), e),
+    }
+    // Redraw everything in the new palette.
+    load_scene.0 = true;
+}
+
+/// Returns what the mouse is over.
+fn hover(cursor_pos: (i32, i32)) -> Option<Hover> {
+    let (x, y) = cursor_pos;
+    if x < 0 || y < 0 {
+        return None;
+    }
+    let (x, y) = (x as usize, y as usize);
+
+    let slot_rows = SLOT_ROW..SLOT_ROW + Colors::iter().len();
+    if x < SWATCH_X + SLOT_COLORS as usize && slot_rows.contains(&y) {
+        return Some(Hover::Slot(y - SLOT_ROW));
+    }
+    let color_rows = COLOR_ROW..COLOR_ROW + SLOT_COLORS as usize;
+    let channel =
+        (0..3).find(|channel| (channel_x(*channel)..channel_x(*channel) + 2).contains(&x));
+    match channel {
+        Some(channel) if color_rows.contains(&y) => {
+            Some(Hover::Channel((y - COLOR_ROW) as PixelColorId, channel))
+        }
+        _ => None,
+    }
+}
+
+fn draw_screen(
+    mut lh: ResMut<LayerHandler>,
+    palette_cursor: Res<PaletteCursor>,
+    edited_slot: Res<EditedColorSlot>,
+) {
+    let (cursor_x, cursor_y) = palette_cursor.get_pos();
+    let edited = slot(edited_slot.0);
+
+    for (idx, slot) in Colors::iter().into_iter().enumerate() {
+        let (marker, marker_color) = if slot == edited {
+            ("cursor", Colors::Cursor)
+        } else {
+            ("space", Colors::Background)
+        };
+        lh.set_tile("map", 0, SLOT_ROW + idx, marker, marker_color)
+            .unwrap();
+        let color = if cursor_y as usize == idx {
+            Colors::Cursor
+        } else {
+            Colors::Highlight
+        };
+        lh.set_tiles_string("map", 1, SLOT_ROW + idx, &slot.to_string(), color)
+            .unwrap();
+        for pixel_id in 0..SLOT_COLORS {
+            let tile = format!("pixel{}", pixel_id);
+            lh.set_tile(
+                "map",
+                SWATCH_X + pixel_id as usize,
+                SLOT_ROW + idx,
+                &tile,
+                slot,
+            )
+            .unwrap();
+        }
+    }
+
+    // The edited slot's colors, in RGB555.
+    let id = format!("{}_{}", lh.active_colorset, edited);
+    let colors: Vec<Option<(u8, u8, u8)>> = (0..SLOT_COLORS)
+        .map(|pixel_id| {
+            let colorset = lh.get_renderer().get_colorset(&id).ok()?;
+            colorset.get_color(&pixel_id).map(to_555)
+        })
+        .collect();
+    for (pixel_id, color) in colors.into_iter().enumerate() {
+        let y = COLOR_ROW + pixel_id;
+        let tile = format!("pixel{}", pixel_id);
+        lh.set_tile("map", 1, y, &tile, edited).unwrap();
+
+        let (r, g, b) = color.unwrap_or_default();
+        for (channel, value) in [r, g, b].into_iter().enumerate() {
+            let on_cursor = (cursor_x as usize, cursor_y as usize)
+                == (channel, Colors::iter().len() + pixel_id);
+            let color = if on_cursor {
+                Colors::Cursor
+            } else {
+                Colors::Highlight
+            };
+            let value = format!("{:02}", value);
+            lh.set_tiles_string("map", channel_x(channel), y, &value, color)
+                .unwrap();
+        }
+    }
+
+    let blank = " ".repeat(WARNING_WIDTH);
+    for y in WARNING_ROW..WARNING_ROW + 2 {
+        lh.set_tiles_string("map", 0, y, &blank, Colors::Background)
+            .unwrap();
+    }
+    if let Some(slot) = low_contrast_slot(&lh) {
+        lh.set_tiles_string("map", 0, WARNING_ROW, "low contrast", Colors::Cursor)
+            .unwrap();
+        lh.set_tiles_string(
+            "map",
+            1,
+            WARNING_ROW + 1,
+            &slot.to_string(),
+            Colors::Highlight,
+        )
+        .unwrap();
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::harness::Harness;
+
+    #[test]
+    /// Changing a color shows it live and warns when text can't be read, and saving
+    /// makes a new palette, leaving the one it was copied from as it was.
+    fn edit_and_save_as() {
+        let mut harness = Harness::new();
+        harness.tap(KeyCode::F10);
+        // The screen is cleared as it's entered, so is drawn in full the frame after.
+        harness.step();
+        assert_eq!(harness.row("map", 0), "palette gray");
+        assert_eq!(harness.row("map", COLOR_ROW), " * 00 00 05");
+
+        // Move to the red of the background's first color, and raise it.
+        harness.tap(KeyCode::Right);
+        harness.press(KeyCode::LShift);
+        harness.step();
+        harness.tap(KeyCode::Right);
+        harness.release(KeyCode::LShift);
+        harness.step();
+        assert_eq!(harness.row("map", COLOR_ROW), " * 01 00 05");
+        let lh = harness.resource::<LayerHandler>();
+        let background = lh.get_renderer().get_colorset("gray_background").unwrap();
+        assert_eq!(background.get_color(&0), Some(&from_555(1, 0, 5, 31)));
+        assert_eq!(low_contrast_slot(lh), None);
+
+        // Text as dark as the background can't be read.
+        let mut lh = harness.resource_mut::<LayerHandler>();
+        for channel in 0..3 {
+            change_channel(&mut lh, Colors::Background, 3, channel, -31);
+        }
+        assert_eq!(low_contrast_slot(&lh), Some(Colors::Background));
+        harness.step();
+        assert_eq!(harness.row("map", WARNING_ROW), "low contrast");
+        assert_eq!(harness.row("map", WARNING_ROW + 1), " background");
+
+        let path = std::env::temp_dir().join("bsdj_edit_and_save_as.colorset");
+        let mut lh = harness.resource_mut::<LayerHandler>();
+        let edited = palette_colorsets(&lh, "gray");
+        save_as(&mut lh, "gra1", &path).unwrap();
+        assert_eq!(lh.active_colorset, "gra1");
+        assert!(lh.color_names.contains(&"gra1".to_string()));
+
+        let parsed = ColorSetParser::parse(path.to_str().unwrap(), "gray").unwrap();
+        assert_eq!(parsed, edited);
+        let background = lh.get_renderer().get_colorset("gray_background").unwrap();
+        assert_eq!(background.get_color(&0), Some(&from_555(0, 0, 5, 31)));
+        std::fs::remove_file(path).unwrap();
+    }
+}
@@ -1,5 +1,16 @@
-use crate::resources::{cursors::phrase, input::*, *};
+use crate::merge::{Cell, Conflicts};
+use crate::midi::{entry, MidiMessage, NoteEntry};
+use crate::resources::{
+    bindings::Action,
+    cursors::phrase,
+    input::*,
+    joypad::{JoypadEvent, LastValues},
+    piano::*,
+    *,
+};
+use crate::session::PeerCursors;
 use crate::states;
+use crate::sync::Transport;
 use crate::tilerender::*;
 use crate::utils::u8_utils::WrappingAdd;
 use bevy::prelude::*;
@@ -10,11 +21,23 @@ impl Plugin for PhraseScene {
     fn build(&self, app: &mut App) {
         app.add_system_set(
             SystemSet::on_update(states::States::Phrase)
+                .label(super::EDIT_SCENE)
                 .with_system(enter_scene)
                 .with_system(set_edited_to_row)
                 .with_system(handle_scroll)
                 .with_system(move_cursor)
-                .with_system(draw_screen),
+                .with_system(delete_value_system)
+                .with_system(joypad_edit)
+                .with_system(change_note_entry)
+                .with_system(enter_midi_notes)
+                .with_system(toggle_piano)
+                .with_system(play_piano),
+        );
+        app.add_system_set(
+            SystemSet::on_update(states::States::Phrase)
+                .after(super::EDIT_SCENE)
+                .with_system(draw_screen)
+                .with_system(draw_note_entry),
         );
     }
 }
@@ -129,8 +152,8 @@ fn handle_scroll(
         return;
     }
 
-    // Require control key to be pressed to change value of anything.
-    if !input.exclusively_pressed(&[InputType::Key(KeyCode::LControl)]) {
+    // Require edit (Control by default) to be held, or a swipe, to change value of anything.
+    if !input.scroll_editing() {
         return;
     }
 
@@ -155,6 +178,18 @@ fn handle_scroll(
     }
     let phrase = phrase.unwrap();
 
+    change_value(phrase, column, index, is_left, scroll_delta);
+}
+
+/// Changes the value in a column by `delta`. `is_left` picks the coarse step,
+/// except for notes where it picks single notes rather than octaves.
+fn change_value(
+    phrase: &mut types::phrase::Phrase,
+    column: phrase::PhraseCursorColumn,
+    index: u8,
+    is_left: bool,
+    delta: i32,
+) {
     match column {
         phrase::PhraseCursorColumn::Note => {
             // Scrolling on the note.
@@ -168,7 +203,7 @@ fn handle_scroll(
             let mut new = 1;
             if let Some(note) = phrase.get_note(index as usize) {
                 // As get_note() is valid, get() on the note is always Some.
-                new = note.get().unwrap() as i32 + change * scroll_delta;
+                new = note.get().unwrap() as i32 + change * delta;
                 new = new.clamp(1, types::note::LARGEST_NOTE as i32);
             }
             phrase.set_note(index as usize, new as u8);
@@ -186,7 +221,7 @@ fn handle_scroll(
             // The -1 accounts for 0-indexing.
             let mut new = 0;
             if let Some(instr) = phrase.get_instr(index as usize) {
-                new = instr as i32 + change * scroll_delta;
+                new = instr as i32 + change * delta;
                 new = new.clamp(0, types::instrument::INSTR_COUNT as i32 - 1);
             }
             phrase.set_instr(index as usize, new as u8);
@@ -202,7 +237,7 @@ fn handle_scroll(
             // Start at 1 as 0 is no command.
             let mut new = 1;
             if let Some(cmd) = phrase.get_cmd(index as usize) {
-                new = cmd.to_num() as i32 + change * scroll_delta;
+                new = cmd.to_num() as i32 + change * delta;
                 new = new.clamp(1, types::command::COMMAND_COUNT as i32);
             }
             phrase.set_cmd(
@@ -217,7 +252,7 @@ fn handle_scroll(
             // If scrolling on the left side, increase/decrease by 0x10.
             // If scrolling on the right side, increase/decrease by 1.
             let change = if is_left { 0x10 } else { 1 };
-            let change = (change * scroll_delta) as isize;
+            let change = (change * delta) as isize;
 
             let mut cmdval = phrase.get_cmd_val(index as usize).unwrap();
 
@@ -236,7 +271,7 @@ fn handle_scroll(
                 types::Command::Pan => {
                     // If is_left is set, set L on or off depending on the scroll.
                     // If is_left is not set, set R on or off depending on the scroll.
-                    if scroll_delta < 0 {
+                    if delta < 0 {
                         // Toggle off if scrolling down.
                         cmdval &= if is_left { 0b10 } else { 0b01 };
                     } else {
@@ -245,8 +280,8 @@ fn handle_scroll(
                     }
                 }
                 types::Command::Wave => {
-                    // Add or remove the scroll_delta, clamping between 0 and 3.
-                    cmdval = (cmdval as i32 + scroll_delta).clamp(0, 3) as u8;
+                    // Add or remove the delta, clamping between 0 and 3.
+                    cmdval = (cmdval as i32 + delta).clamp(0, 3) as u8;
                 }
                 _ => {
                     // Add to the command value, overflowing/underflowing if necessary.
@@ -277,6 +312,263 @@ fn move_cursor(input: Res<InputRes>, mut phrase_cursor: ResMut<cursors::PhraseCu
     }
 }
 
+/// Deletes the value under the cursor when delete is pressed (`Delete` or `Backspace` by default).
+fn delete_value_system(
+    input: Res<InputRes>,
+    phrase_cursor: Res<cursors::PhraseCursor>,
+    edited_phrase: Res<edited::EditedPhrase>,
+    mut phrases: ResMut<types::Phrases>,
+) {
+    if !input.action_dr_pressed(Action::Delete) {
+        return;
+    }
+    if let Some(phrase) = phrases.get_mut(edited_phrase.0 as usize) {
+        delete_value(phrase, phrase_cursor.get_column(), phrase_cursor.get_y());
+    }
+}
+
+/// Clears the value in a column. A command's value is cleared along with it.
+fn delete_value(phrase: &mut types::phrase::Phrase, column: phrase::PhraseCursorColumn, index: u8) {
+    let index = index as usize;
+    match column {
+        phrase::PhraseCursorColumn::Note => {
+            phrase.clear_note(index);
+        }
+        phrase::PhraseCursorColumn::Instrument => {
+            phrase.clear_instr(index);
+        }
+        phrase::PhraseCursorColumn::Command => {
+            phrase.set_cmd(index, types::Command::None);
+            phrase.set_cmd_val(index, 0);
+        }
+        phrase::PhraseCursorColumn::CommandValue => {
+            phrase.set_cmd_val(index, 0);
+        }
+    }
+}
+
+/// Edits the value under the cursor with the Game Boy's buttons.
+/// A inserts the last used value into an empty note, instrument or command,
+/// or its first value if none was used yet.
+fn joypad_edit(
+    mut joypad_reader: EventReader<JoypadEvent>,
+    phrase_cursor: Res<cursors::PhraseCursor>,
+    edited_phrase: Res<edited::EditedPhrase>,
+    mut last_values: ResMut<LastValues>,
+    mut phrases: ResMut<types::Phrases>,
+) {
+    let phrase = match phrases.get_mut(edited_phrase.0 as usize) {
+        Some(phrase) => phrase,
+        None => return,
+    };
+    let column = phrase_cursor.get_column();
+    let index = phrase_cursor.get_y();
+
+    let row = index as usize;
+
+    for event in joypad_reader.iter() {
+        match *event {
+            // With no last value, changing an empty value by nothing gives it its first value.
+            JoypadEvent::Insert => match column {
+                phrase::PhraseCursorColumn::Note if phrase.get_note(row).is_none() => {
+                    match last_values.get_note() {
+                        Some(note) => {
+                            phrase.set_note(row, note);
+                        }
+                        None => change_value(phrase, column, index, false, 0),
+                    }
+                }
+                phrase::PhraseCursorColumn::Instrument if phrase.get_instr(row).is_none() => {
+                    match last_values.get_instr() {
+                        Some(instr) => {
+                            phrase.set_instr(row, instr);
+                        }
+                        None => change_value(phrase, column, index, false, 0),
+                    }
+                }
+                phrase::PhraseCursorColumn::Command if phrase.get_cmd(row).is_none() => {
+                    match last_values.get_cmd() {
+                        Some((cmd, val)) => {
+                            phrase.set_cmd(row, cmd);
+                            phrase.set_cmd_val(row, val);
+                        }
+                        None => change_value(phrase, column, index, false, 0),
+                    }
+                }
+                _ => continue,
+            },
+            JoypadEvent::Delete => {
+                delete_value(phrase, column, index);
+                continue;
+            }
+            JoypadEvent::Change { delta, coarse } => {
+                // Notes change by an octave on their right side, everything else on its left.
+                let is_left = if column == phrase::PhraseCursorColumn::Note {
+                    !coarse
+                } else {
+                    coarse
+                };
+                change_value(phrase, column, index, is_left, delta);
+            }
+            JoypadEvent::Navigate(_) => continue,
+        }
+
+        // Remember what the row holds now, for the next insert.
+        match column {
+            phrase::PhraseCursorColumn::Note => {
+                if let Some(note) = phrase.get_note(row).and_then(|note| note.get()) {
+                    last_values.set_note(note);
+                }
+            }
+            phrase::PhraseCursorColumn::Instrument => {
+                if let Some(instr) = phrase.get_instr(row) {
+                    last_values.set_instr(instr);
+                }
+            }
+            phrase::PhraseCursorColumn::Command | phrase::PhraseCursorColumn::CommandValue => {
+                if let Some(cmd) = phrase.get_cmd(row) {
+                    last_values.set_cmd((cmd, phrase.get_cmd_val(row).unwrap()));
+                }
+            }
+        }
+    }
+}
+
+/// Changes how notes from a MIDI keyboard are entered.
+/// step-up/step-dn (Ctrl + Up/Down by default) change how far the cursor moves after
+/// each note, and record (Ctrl + R by default) toggles live recording.
+fn change_note_entry(input: Res<InputRes>, mut note_entry: ResMut<NoteEntry>) {
+    if input.action_exclusively_dr_pressed(Action::NoteStepUp) {
+        let step = note_entry.get_step();
+        note_entry.set_step(step + 1);
+    }
+    if input.action_exclusively_dr_pressed(Action::NoteStepDown) {
+        let step = note_entry.get_step();
+        note_entry.set_step(step.saturating_sub(1));
+    }
+    if input.action_just_pressed(Action::Record) {
+        let live_record = note_entry.is_live_record();
+        note_entry.set_live_record(!live_record);
+    }
+}
+
+/// Writes the notes played on a MIDI keyboard into the phrase, along with the
+/// edited instrument. Notes go on the cursor's row, which then moves down by the
+/// step, or on the row being played when live recording.
+#[allow(clippy::too_many_arguments)]
+fn enter_midi_notes(
+    mut midi_events: EventReader<MidiMessage>,
+    note_entry: Res<NoteEntry>,
+    transport: Res<Transport>,
+    song_cursor: Res<cursors::SongCursor>,
+    edited_phrase: Res<edited::EditedPhrase>,
+    edited_instr: Res<edited::EditedInstrument>,
+    mut phrase_cursor: ResMut<cursors::PhraseCursor>,
+    mut phrases: ResMut<types::Phrases>,
+) {
+    for event in midi_events.iter() {
+        let note = match event {
+            MidiMessage::NoteOn { note, .. } => *note,
+            _ => continue,
+        };
+        // Notes out of LSDj's range are left out.
+        let note = match entry::from_midi_note(note, song_cursor.is_wav()) {
+            Some(note) => note,
+            None => continue,
+        };
+        let phrase = match phrases.get_mut(edited_phrase.0 as usize) {
+            Some(phrase) => phrase,
+            None => return,
+        };
+
+        let live = note_entry.is_live_record() && transport.is_playing();
+        let row = if live {
+            entry::playing_row(&transport)
+        } else {
+            phrase_cursor.get_y() as usize
+        };
+        phrase.set_note(row, note);
+        phrase.set_instr(row, edited_instr.0);
+
+        if !live {
+            // The cursor wraps back around to the top of the phrase.
+            let y = (row + note_entry.get_step() as usize) % 0x10;
+            phrase_cursor.set_y(y as isize);
+        }
+    }
+}
+
+/// Piano (Ctrl + P by default) toggles piano mode.
+/// While on, oct-dn and oct-up (- and = by default) move down and up an octave.
+fn toggle_piano(input: Res<InputRes>, mut piano: ResMut<PianoMode>) {
+    if input.action_just_pressed(Action::Piano) {
+        let enabled = piano.is_enabled();
+        piano.set_enabled(!enabled);
+    }
+
+    if !piano.is_enabled() {
+        return;
+    }
+    if input.action_exclusively_just_pressed(Action::OctaveDown) {
+        piano.octave_down();
+    }
+    if input.action_exclusively_just_pressed(Action::OctaveUp) {
+        piano.octave_up();
+    }
+}
+
+/// In piano mode, writes the note of each piano key pressed at the cursor,
+/// which then moves down by the note entry step.
+fn play_piano(
+    input: Res<InputRes>,
+    piano: Res<PianoMode>,
+    note_entry: Res<NoteEntry>,
+    edited_phrase: Res<edited::EditedPhrase>,
+    mut phrase_cursor: ResMut<cursors::PhraseCursor>,
+    mut phrases: ResMut<types::Phrases>,
+) {
+    // Keys pressed with edit (Ctrl by default) held are shortcuts rather than notes.
+    if !piano.is_enabled() || input.action_pressed(Action::Edit) {
+        return;
+    }
+    let phrase = match phrases.get_mut(edited_phrase.0 as usize) {
+        Some(phrase) => phrase,
+        None => return,
+    };
+
+    for (key, _) in PIANO_KEYS {
+        let key = InputType::Key(key);
+        if !input.just_pressed(&key) {
+            continue;
+        }
+        if let Some(note) = piano.get_note(&key) {
+            let row = phrase_cursor.get_y() as usize;
+            phrase.set_note(row, note);
+            let y = (row + note_entry.get_step() as usize) % 0x10;
+            phrase_cursor.set_y(y as isize);
+        }
+    }
+}
+
+/// Draws the MIDI note entry step, and `rec` while live recording.
+fn draw_note_entry(mut lh: ResMut<LayerHandler>, note_entry: Res<NoteEntry>) {
+    lh.set_tiles_string(
+        "map",
+        10,
+        0,
+        &format!("+{:x}", note_entry.get_step()),
+        Colors::Background,
+    )
+    .unwrap();
+    let record = if note_entry.is_live_record() {
+        "rec"
+    } else {
+        "   "
+    };
+    lh.set_tiles_string("map", 13, 0, record, Colors::Highlight)
+        .unwrap();
+}
+
 /// Determines where the user cursor is on an inputtable value.
 /// Returns an Option. None means no inputtable value is hovered.
 ///
@@ -361,6 +653,7 @@ fn note_index_is_speech(
     false
 }
 
+#[allow(clippy::too_many_arguments)]
 fn draw_screen(
     mut lh: ResMut<LayerHandler>,
     song_cursor: Res<cursors::SongCursor>,
@@ -368,6 +661,8 @@ fn draw_screen(
     phrases: Res<types::Phrases>,
     edited_phrase: Res<edited::EditedPhrase>,
     instruments: Res<types::Instruments>,
+    conflicts: Res<Conflicts>,
+    peer_cursors: Res<PeerCursors>,
 ) {
     // Get the phrase currently being worked on.
     let phrase = phrases.get(edited_phrase.0 as usize);
@@ -432,6 +727,16 @@ fn draw_screen(
                 Colors::Highlight,
                 Colors::Background,
             ];
+            let cell = Cell::PhraseRow {
+                phrase: edited_phrase.0 as usize,
+                row: y,
+            };
+            if conflicts.contains(&cell) {
+                tile_colors = [Colors::Conflict; 4];
+            }
+            if peer_cursors.contains(&cell) {
+                tile_colors = [Colors::Ghost; 4];
+            }
             // Change the tile colour if it's where the cursor is.
             if phrase_cursor_pos.1 as usize == y {
                 tile_colors[phrase_cursor_pos.0 as usize] = Colors::Cursor;
@@ -480,3 +785,49 @@ fn draw_screen(
         }
     }
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::harness::Harness;
+
+    #[test]
+    /// Notes played on the piano, and values scrolled and deleted, show up in the phrase
+    /// and on the screen.
+    fn edit_rows() {
+        let mut harness = Harness::new();
+        harness.press(KeyCode::Tab);
+        harness.tap(KeyCode::Right);
+        harness.tap(KeyCode::Right);
+        harness.release(KeyCode::Tab);
+        harness.step();
+        assert!(harness.row("map", 0).starts_with("phrase 00"));
+
+        // In piano mode, S plays C# and moves the cursor down a row.
+        harness.press(KeyCode::LControl);
+        harness.tap(KeyCode::P);
+        harness.release(KeyCode::LControl);
+        harness.step();
+        harness.tap(KeyCode::S);
+        assert_eq!(harness.row("map", 2), "0 c#4 i-- -00");
+        assert_eq!(harness.resource::<cursors::PhraseCursor>().get_y(), 1);
+
+        // Ctrl + scroll sets an instrument and raises the note a semitone.
+        harness.press(KeyCode::LControl);
+        harness.scroll(8, 2, 1.);
+        harness.scroll(8, 2, 1.);
+        harness.scroll(2, 2, 1.);
+        harness.release(KeyCode::LControl);
+        harness.step();
+        assert_eq!(harness.row("map", 2), "0 d 4 i01 -00");
+
+        // Delete clears the note under the cursor, leaving the instrument.
+        harness.tap(KeyCode::Up);
+        harness.tap(KeyCode::Delete);
+        assert_eq!(harness.row("map", 2), "0 --- i01 -00");
+
+        let phrase = harness.resource::<types::Phrases>().get(0).unwrap();
+        assert!(phrase.get_note(0).is_none());
+        assert_eq!(phrase.get_instr(0), Some(0x01));
+    }
+}
@@ -1,5 +1,6 @@
-// use crate::resources::{input::*, *};
+use crate::resources::{input::*, *};
 use crate::states;
+use crate::sync::{SyncSettings, Transport};
 use crate::tilerender::*;
 use bevy::prelude::*;
 
@@ -8,11 +9,23 @@ pub struct ProjectScene;
 impl Plugin for ProjectScene {
     fn build(&self, app: &mut App) {
         app.add_system_set(
-            SystemSet::on_update(states::States::Project).with_system(enter_scene), // .with_system(draw_screen),
+            SystemSet::on_update(states::States::Project)
+                .label(super::EDIT_SCENE)
+                .with_system(enter_scene)
+                .with_system(move_cursor)
+                .with_system(change_value),
+        );
+        app.add_system_set(
+            SystemSet::on_update(states::States::Project)
+                .after(super::EDIT_SCENE)
+                .with_system(draw_screen),
         );
     }
 }
 
+/// The settings on the screen, in the order of the cursor's Y.
+const SETTINGS: [&str; 2] = ["tempo", "sync"];
+
 fn enter_scene(mut lh: ResMut<LayerHandler>, load_scene: ResMut<states::LoadState>) {
     // Dont try enter the scene if the scene should not be loaded.
     if !load_scene.0 {
@@ -22,19 +35,100 @@ fn enter_scene(mut lh: ResMut<LayerHandler>, load_scene: ResMut<states::LoadStat
     // Clear the map
     lh.clear_layer("map", "space", Colors::Background).unwrap();
 
-    // Show a 'TO IMPLEMENT' error.
-    lh.set_tiles_string("map", 2, 4, "-------------", Colors::Highlight)
-        .unwrap();
-    lh.set_tiles_string("map", 2, 5, "    error:   ", Colors::Highlight)
-        .unwrap();
-    lh.set_tiles_string("map", 2, 6, "   project   ", Colors::Highlight)
-        .unwrap();
-    lh.set_tiles_string("map", 2, 7, "   not yet   ", Colors::Highlight)
-        .unwrap();
-    lh.set_tiles_string("map", 2, 8, " implemented ", Colors::Highlight)
-        .unwrap();
-    lh.set_tiles_string("map", 2, 9, "-------------", Colors::Highlight)
+    lh.set_tiles_string("map", 0, 0, "project", Colors::Background)
         .unwrap();
+    for (y, setting) in SETTINGS.iter().enumerate() {
+        lh.set_tiles_string("map", 1, y + 2, setting, Colors::Background)
+            .unwrap();
+    }
+}
+
+fn move_cursor(input: Res<InputRes>, mut project_cursor: ResMut<cursors::ProjectCursor>) {
+    // Move the cursor to the setting the user clicked.
+    if input.just_pressed(&InputType::Mouse(MouseButton::Left)) {
+        if let Some(cursor_pos) = input.get_cursor_tile_position() {
+            if let Some(index) = hover(cursor_pos) {
+                project_cursor.set_y(index as isize);
+            }
+        }
+    }
+
+    match input.directional_input() {
+        Some(DirectionalInput::Up) => project_cursor.sub_y(),
+        Some(DirectionalInput::Down) => project_cursor.add_y(),
+        _ => (),
+    }
+}
+
+/// Changes the setting under the cursor with Left and Right,
+/// or the one under the mouse with Ctrl + scroll.
+fn change_value(
+    input: Res<InputRes>,
+    project_cursor: Res<cursors::ProjectCursor>,
+    mut project: ResMut<types::Project>,
+    mut sync_settings: ResMut<SyncSettings>,
+) {
+    let mut change = match input.directional_input() {
+        Some(DirectionalInput::Left) => Some((project_cursor.get_y() as usize, -1)),
+        Some(DirectionalInput::Right) => Some((project_cursor.get_y() as usize, 1)),
+        _ => None,
+    };
+
+    let scroll_delta = input.get_scroll_delta();
+    if scroll_delta != 0 && input.scroll_editing() {
+        if let Some(index) = input.get_cursor_tile_position().and_then(hover) {
+            change = Some((index, scroll_delta));
+        }
+    }
+
+    match change {
+        // The tempo comes from elsewhere when following.
+        Some((0, delta)) if !sync_settings.get_mode().is_following() => {
+            let tempo = project.get_tempo() as i32 + delta;
+            project.set_tempo(tempo.max(0) as u16);
+        }
+        Some((1, delta)) => {
+            let mode = sync_settings.get_mode();
+            let mode = if delta > 0 { mode.next() } else { mode.prev() };
+            sync_settings.set_mode(mode);
+        }
+        _ => (),
+    }
 }
 
-// fn draw_screen(mut lh: ResMut<LayerHandler>) {}
+/// Returns the index of the setting the mouse is over.
+fn hover(cursor_pos: (i32, i32)) -> Option<usize> {
+    let (x, y) = cursor_pos;
+    if !(1..15).contains(&x) || !(2..2 + SETTINGS.len() as i32).contains(&y) {
+        return None;
+    }
+    Some(y as usize - 2)
+}
+
+fn draw_screen(
+    mut lh: ResMut<LayerHandler>,
+    project_cursor: Res<cursors::ProjectCursor>,
+    project: Res<types::Project>,
+    sync_settings: Res<SyncSettings>,
+    transport: Res<Transport>,
+) {
+    // Show the tempo being followed, if any.
+    let tempo = if sync_settings.get_mode().is_following() {
+        transport.get_tempo().round() as u16
+    } else {
+        project.get_tempo()
+    };
+    let values = [tempo.to_string(), sync_settings.get_mode().to_string()];
+
+    for (y, value) in values.iter().enumerate() {
+        let color = if project_cursor.get_y() as usize == y {
+            Colors::Cursor
+        } else {
+            Colors::Highlight
+        };
+        // Clear what a longer value left behind.
+        lh.set_tiles_string("map", 8, y + 2, "      ", Colors::Background)
+            .unwrap();
+        lh.set_tiles_string("map", 8, y + 2, value, color).unwrap();
+    }
+}
@@ -1,5 +1,6 @@
-use crate::resources::*;
+use crate::resources::{piano::PianoMode, *};
 use crate::states;
+use crate::sync::{Transport, PULSES_PER_BEAT};
 use crate::tilerender::*;
 use bevy::prelude::*;
 
@@ -42,8 +43,21 @@ fn enter_scene(mut lh: ResMut<LayerHandler>, load_scene: ResMut<states::LoadStat
 fn draw_screen(
     song_cursor: Res<cursors::SongCursor>,
     chain_cursor: Res<cursors::ChainCursor>,
+    transport: Res<Transport>,
+    piano: Res<PianoMode>,
+    state: Res<State<states::States>>,
     mut lh: ResMut<LayerHandler>,
 ) {
+    // Flash the note on the first half of every beat while playing.
+    let beat_pulse = transport.get_pulse() % PULSES_PER_BEAT as u64;
+    let on_beat = beat_pulse < PULSES_PER_BEAT as u64 / 2;
+    let note_color = if transport.is_playing() && on_beat {
+        colors::Colors::Highlight
+    } else {
+        colors::Colors::Background
+    };
+    lh.set_tile("ui", 16, 4, "musicnote", note_color).unwrap();
+
     lh.set_tiles_string(
         "ui",
         17,
@@ -67,4 +81,14 @@ fn draw_screen(
         colors::Colors::Highlight,
     )
     .unwrap();
+
+    // Show the piano's octave, as the Phrase screen shows it, while it takes the keyboard.
+    let piano_text = if piano.is_enabled() && *state.current() == states::States::Phrase {
+        let octave = piano.get_octave() + if song_cursor.is_wav() { 2 } else { 3 };
+        format!("pn{:x}", octave)
+    } else {
+        "   ".to_string()
+    };
+    lh.set_tiles_string("ui", 17, 1, &piano_text, colors::Colors::Highlight)
+        .unwrap();
 }
@@ -1,5 +1,12 @@
 use crate::events::HeadingTextEvent;
-use crate::resources::{input::*, *};
+use crate::merge::{Cell, Conflicts};
+use crate::resources::{
+    bindings::Action,
+    input::*,
+    joypad::{JoypadEvent, LastValues},
+    *,
+};
+use crate::session::PeerCursors;
 use crate::states;
 use crate::tilerender::*;
 use bevy::prelude::*;
@@ -10,13 +17,19 @@ impl Plugin for SongScene {
     fn build(&self, app: &mut App) {
         app.add_system_set(
             SystemSet::on_update(states::States::Song)
+                .label(super::EDIT_SCENE)
                 .with_system(enter_scene)
                 .with_system(handle_scroll)
                 .with_system(move_cursor)
                 .with_system(type_chain)
                 .with_system(delete_chain_system)
+                .with_system(joypad_edit)
                 .with_system(bookmark_chain_system)
-                .with_system(open_chain_system)
+                .with_system(open_chain_system),
+        );
+        app.add_system_set(
+            SystemSet::on_update(states::States::Song)
+                .after(super::EDIT_SCENE)
                 .with_system(draw_screen),
         );
         // app.add_system_set(
@@ -60,32 +73,40 @@ fn handle_scroll(
         return;
     }
 
-    // Control key pressed: Change value of chain
-    if input.exclusively_pressed(&[InputType::Key(KeyCode::LControl)]) {
-        if let Some(cursor_pos) = input.get_cursor_tile_position() {
-            if let Some((channel_index, chain_y, left)) = hover_on_chain(cursor_pos, cam) {
-                // Get the chain info
-                let channel = channels.get_mut(channel_index as usize);
-                let current = channel.get_chain(chain_y);
-
-                let change = if left { 0x10 } else { 0x01 };
-
-                // If there is no chain there already, set to 0.
-                // Else add the change, but clamp between 0 and 0x7f
-                let mut new = 0;
-                if let Some(chain) = current {
-                    new = chain as i32 + change * scroll_delta;
-                    new = new.clamp(0x00, 0x7f)
-                }
-
-                channel.set_chain(chain_y, new as u8);
-            }
+    // Edit held (Control by default), or swiping: Change value of chain
+    if input.scroll_editing() {
+        let hovered = input
+            .get_cursor_tile_position()
+            .and_then(|cursor_pos| hover_on_chain(cursor_pos, cam));
+        if let Some((channel_index, chain_y, left)) = hovered {
+            let channel = channels.get_mut(channel_index as usize);
+            change_chain(channel, chain_y, left, scroll_delta);
+            return;
+        }
+        // Swipes off the chains scroll like the wheel.
+        if !input.is_touching_screen() {
+            return;
         }
-    } else {
-        // Subtract so scrolling down causes cam to increase
-        let new_cam = cam as isize - scroll_delta as isize;
-        song_cursor.set_cam(new_cam);
     }
+
+    // Subtract so scrolling down causes cam to increase
+    let new_cam = cam as isize - scroll_delta as isize;
+    song_cursor.set_cam(new_cam);
+}
+
+/// Changes a chain by `delta`, times 0x10 if `left`.
+fn change_chain(channel: &mut types::channel::SongChannel, chain_y: u8, left: bool, delta: i32) {
+    let change = if left { 0x10 } else { 0x01 };
+
+    // If there is no chain there already, set to 0.
+    // Else add the change, but clamp between 0 and 0x7f
+    let mut new = 0;
+    if let Some(chain) = channel.get_chain(chain_y) {
+        new = chain as i32 + change * delta;
+        new = new.clamp(0x00, 0x7f)
+    }
+
+    channel.set_chain(chain_y, new as u8);
 }
 
 fn move_cursor(
@@ -105,16 +126,8 @@ fn move_cursor(
         }
     }
 
-    for key in InputType::directional_keycodes() {
-        if input.dr_pressed(&key) && input.exclusively_pressed(&[key]) {
-            match key {
-                InputType::Key(KeyCode::Up) => song_cursor.sub_y(),
-                InputType::Key(KeyCode::Down) => song_cursor.add_y(),
-                InputType::Key(KeyCode::Left) => song_cursor.sub_x(),
-                InputType::Key(KeyCode::Right) => song_cursor.add_x(),
-                _ => panic!("what"),
-            }
-        }
+    if let Some(key) = input.directional_input() {
+        key.move_cursor(&mut *song_cursor);
     }
 
     let channel = channels.get(song_cursor.get_x() as usize);
@@ -154,16 +167,11 @@ fn delete_chain_system(
     song_cursor: Res<cursors::SongCursor>,
     mut channels: ResMut<types::channel::Channels>,
 ) {
-    // If the `Delete` or `Backspace` keys are pressed,
+    // If delete is pressed (`Delete` or `Backspace` by default),
     // delete the chain at the songcursor position.
-    for key in [
-        InputType::Key(KeyCode::Delete),
-        InputType::Key(KeyCode::Back),
-    ] {
-        if input.dr_pressed(&key) {
-            let (cursor_x, cursor_y) = song_cursor.get_pos();
-            delete_chain(cursor_x as usize, cursor_y, &mut channels);
-        }
+    if input.action_dr_pressed(Action::Delete) {
+        let (cursor_x, cursor_y) = song_cursor.get_pos();
+        delete_chain(cursor_x as usize, cursor_y, &mut channels);
     }
 
     // If the middle mouse button is clicked, delete the chain
@@ -179,6 +187,37 @@ fn delete_chain_system(
     }
 }
 
+/// Edits the chain under the cursor with the Game Boy's buttons.
+/// A inserts the last used chain into an empty row, or the edited chain if none was used yet.
+fn joypad_edit(
+    mut joypad_reader: EventReader<JoypadEvent>,
+    song_cursor: Res<cursors::SongCursor>,
+    edited_chain: Res<edited::EditedChain>,
+    mut last_values: ResMut<LastValues>,
+    mut channels: ResMut<types::channel::Channels>,
+) {
+    let (cursor_x, cursor_y) = song_cursor.get_pos();
+    for event in joypad_reader.iter() {
+        let channel = channels.get_mut(cursor_x as usize);
+        match *event {
+            JoypadEvent::Insert if channel.get_chain(cursor_y).is_none() => {
+                let chain = last_values.get_chain().unwrap_or(edited_chain.0);
+                channel.set_chain(cursor_y, chain);
+            }
+            JoypadEvent::Delete => {
+                delete_chain(cursor_x as usize, cursor_y, &mut channels);
+                continue;
+            }
+            JoypadEvent::Change { delta, coarse } => change_chain(channel, cursor_y, coarse, delta),
+            _ => continue,
+        }
+
+        if let Some(chain) = channels.get(cursor_x as usize).get_chain(cursor_y) {
+            last_values.set_chain(chain);
+        }
+    }
+}
+
 /// Delete or remove a chain at this position.
 fn delete_chain(channel_index: usize, chain_y: u8, channels: &mut types::channel::Channels) {
     let channel = channels.get_mut(channel_index as usize);
@@ -200,14 +239,12 @@ fn bookmark_chain_system(
     mut channels: ResMut<types::channel::Channels>,
     mut headtext_writer: EventWriter<HeadingTextEvent>,
 ) {
-    // If the `M` key is pressed, bookmark the tile the songcursor is on.
-    for key in [InputType::Key(KeyCode::M)] {
-        if input.just_pressed(&key) {
-            let (cursor_x, cursor_y) = song_cursor.get_pos();
+    // If bookmark is pressed (`M` by default), bookmark the tile the songcursor is on.
+    if input.action_just_pressed(Action::Bookmark) {
+        let (cursor_x, cursor_y) = song_cursor.get_pos();
 
-            if let Err(e) = bookmark_chain(cursor_x as usize, cursor_y, &mut channels) {
-                headtext_writer.send(HeadingTextEvent(e.to_string()));
-            }
+        if let Err(e) = bookmark_chain(cursor_x as usize, cursor_y, &mut channels) {
+            headtext_writer.send(HeadingTextEvent(e.to_string()));
         }
     }
 
@@ -297,6 +334,8 @@ fn draw_screen(
     song_cursor: Res<cursors::SongCursor>,
     mut lh: ResMut<LayerHandler>,
     channels: Res<types::channel::Channels>,
+    conflicts: Res<Conflicts>,
+    peer_cursors: Res<PeerCursors>,
 ) {
     let cam_pos = song_cursor.get_cam() as usize;
     // For the 16 columns
@@ -319,6 +358,16 @@ fn draw_screen(
             if channel.is_bookmarked(y_cam) {
                 color = colors::Colors::Highlight;
             }
+            let cell = Cell::SongRow {
+                channel: i,
+                row: y_cam,
+            };
+            if conflicts.contains(&cell) {
+                color = colors::Colors::Conflict;
+            }
+            if peer_cursors.contains(&cell) {
+                color = colors::Colors::Ghost;
+            }
             if song_cursor.get_pos() == (i as u8, y_cam) {
                 color = colors::Colors::Cursor;
             }
@@ -329,3 +378,37 @@ fn draw_screen(
         }
     }
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::harness::Harness;
+
+    #[test]
+    /// Chains typed, scrolled and clicked on show up in the song and on the screen.
+    fn edit_chains() {
+        let mut harness = Harness::new();
+
+        harness.tap(KeyCode::Key1);
+        harness.tap(KeyCode::Key2);
+        assert_eq!(harness.row("map", 2), "00 12 -- -- --");
+
+        // Ctrl + scroll changes the digit under the cursor.
+        harness.press(KeyCode::LControl);
+        harness.scroll(4, 2, 1.);
+        harness.scroll(3, 2, 1.);
+        harness.release(KeyCode::LControl);
+        harness.step();
+        assert_eq!(harness.row("map", 2), "00 23 -- -- --");
+
+        // Clicking moves the cursor, where typing sets the chain.
+        harness.click(6, 3);
+        harness.tap(KeyCode::Key5);
+        assert_eq!(harness.row("map", 3), "01 -- 05 -- --");
+
+        let channels = harness.resource::<types::channel::Channels>();
+        assert_eq!(channels.get(0).get_chain(0), Some(0x23));
+        assert_eq!(channels.get(1).get_chain(1), Some(0x05));
+        assert_eq!(harness.resource::<cursors::SongCursor>().get_pos(), (1, 1));
+    }
+}
@@ -0,0 +1,65 @@
+use crate::cli::CliArgs;
+use crate::resources::{input::InputRes, touch_pad::*};
+use crate::states;
+use crate::tilerender::*;
+use bevy::prelude::*;
+
+/// The layer the touch pad is drawn on.
+const LAYER: &str = "touchpad";
+
+pub struct TouchPadPlugin;
+
+impl Plugin for TouchPadPlugin {
+    fn build(&self, app: &mut App) {
+        let shown = app
+            .world
+            .get_resource::<CliArgs>()
+            .map(|cli| cli.touch_pad)
+            .unwrap_or(false);
+        if !shown {
+            return;
+        }
+
+        app.add_startup_system(add_layer);
+        app.add_system(draw_screen);
+    }
+}
+
+/// Adds the touch pad's layer below the screen.
+fn add_layer(mut lh: ResMut<LayerHandler>) {
+    let mut layer = TileLayer::new(LAYER.to_string(), 20, TOUCH_PAD_ROWS);
+    layer.set_z_index(10.);
+    layer.set_position(Vec2::new(0., -(144. + TOUCH_PAD_HEIGHT) / 2.));
+    lh.add_layer(layer);
+}
+
+/// Draws the buttons, with the ones being held in the cursor's color.
+fn draw_screen(
+    mut lh: ResMut<LayerHandler>,
+    load_scene: Res<states::LoadState>,
+    input: Res<InputRes>,
+) {
+    // Clear when the palette may have changed.
+    if load_scene.0 {
+        lh.clear_layer(LAYER, "space", Colors::Background).unwrap();
+    }
+
+    let pressed = input.get_pressed_inputs();
+    for button in PAD_BUTTONS {
+        let color = if pressed.contains(&button.input) {
+            Colors::Cursor
+        } else {
+            Colors::Highlight
+        };
+        // Set each tile once, so unchanged buttons don't reload the layer.
+        for y in button.y..button.y + button.height {
+            for x in button.x..button.x + button.width {
+                let label = match button.label.get(x - button.x..=x - button.x) {
+                    Some(letter) if y == button.y => letter,
+                    _ => "space",
+                };
+                lh.set_tile(LAYER, x, y, label, color).unwrap();
+            }
+        }
+    }
+}
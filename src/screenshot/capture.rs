@@ -0,0 +1,154 @@
+use super::ScreenshotError;
+use crate::tilerender::{compositor, LayerHandler};
+use image::codecs::gif::{GifEncoder, Repeat};
+use image::{Delay, Frame, RgbaImage};
+use std::fs::File;
+use std::io::BufWriter;
+
+/// The Game Boy's clock speed, in cycles per second.
+const CLOCK_SPEED: u64 = 4_194_304;
+/// The cycles the Game Boy takes to draw a frame, so it runs at about 59.73 frames a second.
+const CYCLES_PER_FRAME: u64 = 70_224;
+
+/// Returns when a frame is shown, in the hundredths of a second GIFs are timed in.
+fn frame_to_centis(frame: u64) -> u64 {
+    let ms = frame * CYCLES_PER_FRAME * 1000 / CLOCK_SPEED;
+    (ms + 5) / 10
+}
+
+/// Returns true if a layer or the renderer changed since the layers were last drawn.
+pub fn screen_changed(lh: &LayerHandler) -> bool {
+    lh.get_renderer().reload_required()
+        || lh
+            .get_layer_names()
+            .filter_map(|name| lh.get_layer(name))
+            .any(|layer| layer.requires_reload)
+}
+
+/// An animated GIF of the screen being written.
+///
+/// Screens are only encoded when they change, and each is shown until the next change,
+/// timed as if the app ran at the Game Boy's frame rate.
+pub struct Capture {
+    encoder: GifEncoder<BufWriter<File>>,
+    scale: u32,
+    /// The screen waiting to be encoded, along with the frame it was first shown on.
+    /// It's encoded once it's known how long it's shown for.
+    pending: Option<(RgbaImage, u64)>,
+}
+
+impl Capture {
+    /// Creates the GIF, which loops forever.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if the file can't be created.
+    pub fn create(filename: &str, scale: u32) -> Result<Capture, ScreenshotError> {
+        let mut encoder = GifEncoder::new(BufWriter::new(File::create(filename)?));
+        encoder.set_repeat(Repeat::Infinite)?;
+        Ok(Capture {
+            encoder,
+            scale,
+            pending: None,
+        })
+    }
+
+    /// Adds the screen shown from `frame` on, if it changed or is the first.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if the previous screen can't be written.
+    pub fn add_frame(&mut self, lh: &LayerHandler, frame: u64) -> Result<(), ScreenshotError> {
+        if self.pending.is_some() && !screen_changed(lh) {
+            return Ok(());
+        }
+
+        let screen = compositor::flatten(lh, self.scale);
+        let shown_from = match self.pending.take() {
+            Some((previous, shown_from))
+                if frame_to_centis(frame) > frame_to_centis(shown_from) =>
+            {
+                self.encode(previous, shown_from, frame)?;
+                frame
+            }
+            // Screens shown for less than a hundredth of a second are never seen,
+            // so are replaced, keeping when they were shown so the timing doesn't drift.
+            Some((_, shown_from)) => shown_from,
+            None => frame,
+        };
+        self.pending = Some((screen, shown_from));
+        Ok(())
+    }
+
+    /// Encodes the last screen, shown until `frame`, and finishes the GIF.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if the last screen can't be written.
+    pub fn finish(mut self, frame: u64) -> Result<(), ScreenshotError> {
+        if let Some((screen, shown_from)) = self.pending.take() {
+            // Show the last screen for at least a frame.
+            let frame = frame.max(shown_from + 1);
+            self.encode(screen, shown_from, frame)?;
+        }
+        // The GIF is finished when the encoder is dropped.
+        Ok(())
+    }
+
+    /// Encodes a screen shown from one frame until another.
+    fn encode(&mut self, screen: RgbaImage, from: u64, until: u64) -> Result<(), ScreenshotError> {
+        let centis = frame_to_centis(until) - frame_to_centis(from);
+        let delay = Delay::from_numer_denom_ms(centis.max(1) as u32 * 10, 1);
+        self.encoder
+            .encode_frame(Frame::from_parts(screen, 0, 0, delay))?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::harness::Harness;
+    use crate::resources::input::InputRes;
+    use bevy::prelude::*;
+    use image::codecs::gif::GifDecoder;
+    use image::AnimationDecoder;
+
+    /// Adds the screen to the capture after stepping a frame.
+    fn step(harness: &mut Harness, capture: &mut Capture) {
+        harness.step();
+        let frame = harness.resource::<InputRes>().get_time();
+        capture.add_frame(harness.resource(), frame).unwrap();
+    }
+
+    #[test]
+    /// Only screens that changed are encoded, each shown for as long as it was on screen.
+    fn capture_changes() {
+        assert_eq!(frame_to_centis(0), 0);
+        assert_eq!(frame_to_centis(60), 100);
+
+        let filename = std::env::temp_dir().join("bsdj_capture_changes.gif");
+        let filename = filename.to_str().unwrap();
+        let mut harness = Harness::new();
+        let mut capture = Capture::create(filename, 2).unwrap();
+
+        for _ in 0..30 {
+            step(&mut harness, &mut capture);
+        }
+        harness.press(KeyCode::Key1);
+        for _ in 0..30 {
+            step(&mut harness, &mut capture);
+        }
+        let frame = harness.resource::<InputRes>().get_time();
+        capture.finish(frame).unwrap();
+
+        let decoder = GifDecoder::new(File::open(filename).unwrap()).unwrap();
+        let frames = decoder.into_frames().collect_frames().unwrap();
+        assert_eq!(frames.len(), 2);
+        assert_eq!(frames[0].buffer().dimensions(), (320, 288));
+        // Half a second, give or take the rounding to hundredths.
+        let (numer, denom) = frames[0].delay().numer_denom_ms();
+        assert!((490..=510).contains(&(numer / denom)));
+        std::fs::remove_file(filename).unwrap();
+    }
+}
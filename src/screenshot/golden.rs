@@ -0,0 +1,108 @@
+//! Comparing screenshots against the golden images in `tests/golden`,
+//! so changes to the screens show up as pixel diffs.
+
+use image::{Rgba, RgbaImage};
+use std::fs;
+use std::path::PathBuf;
+
+/// Setting this environment variable to `1` rewrites the golden images instead of checking them.
+pub const UPDATE_VAR: &str = "UPDATE_GOLDEN";
+
+/// Returns the path of a golden image, or of where a failing image is written.
+fn path(dir: &str, name: &str) -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join(dir)
+        .join(format!("{}.png", name))
+}
+
+/// Returns the number of pixels that differ, and an image with them in red
+/// over a faded copy of `expected`.
+pub fn diff(expected: &RgbaImage, actual: &RgbaImage) -> (usize, RgbaImage) {
+    let mut count = 0;
+    let diff = RgbaImage::from_fn(expected.width(), expected.height(), |x, y| {
+        let pixel = expected.get_pixel(x, y);
+        let same = x < actual.width() && y < actual.height() && actual.get_pixel(x, y) == pixel;
+        if same {
+            let Rgba([r, g, b, a]) = *pixel;
+            Rgba([r / 4, g / 4, b / 4, a])
+        } else {
+            count += 1;
+            Rgba([255, 0, 0, 255])
+        }
+    });
+    (count, diff)
+}
+
+/// Checks an image against the golden image with this name.
+///
+/// # PANICS
+///
+/// Panics if there's no golden image or a pixel differs. The image and a diff of the
+/// two are written to `target/golden` to look at. Run with `UPDATE_GOLDEN=1` to make
+/// the image the new golden image instead.
+pub fn assert_golden(actual: &RgbaImage, name: &str) {
+    let golden = path("tests/golden", name);
+    if std::env::var(UPDATE_VAR).as_deref() == Ok("1") {
+        fs::create_dir_all(golden.parent().unwrap()).unwrap();
+        actual.save(&golden).unwrap();
+        return;
+    }
+
+    let expected = match image::open(&golden) {
+        Ok(expected) => expected.to_rgba8(),
+        Err(e) => panic!(
+            "Could not open the golden image `{}`: {}. Run with {}=1 to write it",
+            golden.display(),
+            e,
+            UPDATE_VAR
+        ),
+    };
+
+    let (count, diff_image) = diff(&expected, actual);
+    if count == 0 && expected.dimensions() == actual.dimensions() {
+        return;
+    }
+
+    let failed = path("target/golden", name);
+    let failed_diff = path("target/golden", &format!("{}.diff", name));
+    fs::create_dir_all(failed.parent().unwrap()).unwrap();
+    actual.save(&failed).unwrap();
+    diff_image.save(&failed_diff).unwrap();
+    panic!(
+        "{} pixels differ from `{}`. See `{}` and `{}`, or run with {}=1 if the change is wanted",
+        count,
+        golden.display(),
+        failed.display(),
+        failed_diff.display(),
+        UPDATE_VAR
+    );
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::harness::Harness;
+    use crate::tilerender::{compositor, LayerHandler};
+    use bevy::prelude::*;
+
+    #[test]
+    /// The Song screen, with a chain typed in, looks as it did.
+    fn song_screen() {
+        let mut harness = Harness::new();
+        harness.tap(KeyCode::Key1);
+        harness.tap(KeyCode::Key2);
+
+        let lh = harness.resource::<LayerHandler>();
+        let screen = compositor::flatten(lh, 1);
+        assert_eq!(screen.dimensions(), (160, 144));
+        assert_eq!(compositor::flatten(lh, 3).dimensions(), (480, 432));
+        assert_golden(&screen, "song");
+
+        // A single changed pixel shows up in the diff.
+        let mut changed = screen.clone();
+        changed.put_pixel(5, 5, Rgba([1, 2, 3, 255]));
+        let (count, diff_image) = diff(&screen, &changed);
+        assert_eq!(count, 1);
+        assert_eq!(diff_image.get_pixel(5, 5), &Rgba([255, 0, 0, 255]));
+    }
+}
@@ -0,0 +1,231 @@
+//! Saving the screen as a PNG or capturing it to a GIF, from the app or without opening it.
+
+use crate::cli::{CliArgs, ScreenshotArgs};
+use crate::meta_actions::export::export_filename;
+use crate::resources::{
+    bindings::Action, input::InputRes, recording::InputRecording, types::Project,
+};
+use crate::tilerender::{compositor, LayerHandler};
+use bevy::prelude::*;
+use std::path::Path;
+
+pub mod capture;
+#[cfg(test)]
+pub mod golden;
+pub mod screenshot_error;
+
+pub use capture::Capture;
+pub use screenshot_error::*;
+
+pub struct ScreenshotPlugin;
+
+impl Plugin for ScreenshotPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_system(take_screenshot);
+        // Capture once the screens have drawn this frame.
+        app.add_system_to_stage(CoreStage::Last, capture_screen);
+    }
+}
+
+/// Flattens the layers and writes them to a PNG.
+///
+/// # ERRORS
+///
+/// Errors if the file can't be written.
+pub fn save(lh: &LayerHandler, scale: u32, filename: &str) -> Result<(), ScreenshotError> {
+    compositor::flatten(lh, scale).save(filename)?;
+    Ok(())
+}
+
+/// Returns the first name for a screenshot or capture that isn't taken, in the working directory.
+fn screenshot_filename(project: &Project, extension: &str) -> String {
+    (1..)
+        .map(|number| export_filename(project, &format!("{:03}.{}", number, extension)))
+        .find(|filename| !Path::new(filename).exists())
+        .unwrap()
+}
+
+/// Returns the window's zoom, to save the screen at.
+fn window_scale(windows: &Windows) -> u32 {
+    windows
+        .get_primary()
+        .map(|window| window.scale_factor().round() as u32)
+        .unwrap_or(1)
+}
+
+/// Saves the screen when `shot` (F12 by default) is pressed, at the window's zoom.
+fn take_screenshot(
+    inputs: Res<InputRes>,
+    lh: Res<LayerHandler>,
+    project: Res<Project>,
+    windows: Res<Windows>,
+) {
+    if !inputs.action_just_pressed(Action::Screenshot) {
+        return;
+    }
+
+    let filename = screenshot_filename(&project, "png");
+    match save(&lh, window_scale(&windows), &filename) {
+        Ok(()) => info!("Saved a screenshot to `{}`", filename),
+        Err(e) => error!("{}", e),
+    }
+}
+
+/// Starts capturing the screen to a GIF when `capture` (F11 by default) is pressed,
+/// at the window's zoom, and finishes it when pressed again.
+fn capture_screen(
+    inputs: Res<InputRes>,
+    lh: Res<LayerHandler>,
+    project: Res<Project>,
+    windows: Res<Windows>,
+    mut capture: Local<Option<Capture>>,
+) {
+    let frame = inputs.get_time();
+    if inputs.action_just_pressed(Action::Capture) {
+        match capture.take() {
+            Some(finished) => match finished.finish(frame) {
+                Ok(()) => info!("Finished capturing the screen"),
+                Err(e) => error!("{}", e),
+            },
+            None => {
+                let filename = screenshot_filename(&project, "gif");
+                match Capture::create(&filename, window_scale(&windows)) {
+                    Ok(started) => {
+                        info!("Capturing the screen to `{}`", filename);
+                        *capture = Some(started);
+                    }
+                    Err(e) => error!("{}", e),
+                }
+            }
+        }
+    }
+
+    if let Some(started) = capture.as_mut() {
+        if let Err(e) = started.add_frame(&lh, frame) {
+            error!("Stopped capturing the screen. {}", e);
+            *capture = None;
+        }
+    }
+}
+
+/// Builds an app with no window, with the song loaded, the replay started and the palette set.
+///
+/// # ERRORS
+///
+/// Errors if the replay can't be loaded or the palette doesn't exist.
+fn command_app(args: &ScreenshotArgs) -> Result<App, ScreenshotError> {
+    // Loaded here rather than by the RecordingPlugin, which only logs a failure.
+    let recording = match &args.replay {
+        Some(filename) => Some(
+            InputRecording::load(filename)
+                .map_err(|e| ScreenshotError::Replay(filename.clone(), e))?,
+        ),
+        None => None,
+    };
+    let cli = CliArgs {
+        song: args.song.clone(),
+        ..Default::default()
+    };
+    let mut app = crate::headless_app(cli);
+    if let Some(recording) = recording {
+        let mut input = app.world.get_resource_mut::<InputRes>().unwrap();
+        input.start_replay(recording);
+    }
+
+    if let Some(palette) = &args.palette {
+        let mut lh = app.world.get_resource_mut::<LayerHandler>().unwrap();
+        let name = lh
+            .color_names
+            .iter()
+            .find(|name| *name == palette)
+            .cloned()
+            .ok_or_else(|| ScreenshotError::UnknownPalette(palette.clone()))?;
+        lh.active_colorset = name;
+    }
+
+    Ok(app)
+}
+
+/// Returns true if the app is still replaying the inputs.
+fn is_replaying(app: &App) -> bool {
+    app.world.get_resource::<InputRes>().unwrap().is_replaying()
+}
+
+/// Runs the `screenshot` command, loading the song and replaying the inputs
+/// in an app with no window before saving the screen.
+///
+/// # ERRORS
+///
+/// Errors if the replay can't be loaded, the palette doesn't exist or the file can't be written.
+pub fn run(args: &ScreenshotArgs) -> Result<(), ScreenshotError> {
+    let mut app = command_app(args)?;
+
+    app.update();
+    while is_replaying(&app) {
+        app.update();
+    }
+    for _ in 0..args.frames {
+        app.update();
+    }
+
+    let lh = app.world.get_resource::<LayerHandler>().unwrap();
+    save(lh, args.scale, &args.output)
+}
+
+/// Runs the `capture` command, capturing the screen to a GIF from loading the song
+/// until the replay ends, and for the frames after.
+///
+/// # ERRORS
+///
+/// Errors if the replay can't be loaded, the palette doesn't exist or the file can't be written.
+pub fn run_capture(args: &ScreenshotArgs) -> Result<(), ScreenshotError> {
+    let mut app = command_app(args)?;
+    let mut capture = Capture::create(&args.output, args.scale)?;
+
+    let mut frames_left = args.frames;
+    loop {
+        app.update();
+        let lh = app.world.get_resource::<LayerHandler>().unwrap();
+        let frame = app.world.get_resource::<InputRes>().unwrap().get_time();
+        capture.add_frame(lh, frame)?;
+
+        if !is_replaying(&app) {
+            if frames_left == 0 {
+                return capture.finish(frame + 1);
+            }
+            frames_left -= 1;
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    /// A replay that can't be loaded fails the commands rather than saving the screen without it.
+    fn reject_missing_replay() {
+        let output = std::env::temp_dir().join("bsdj_reject_missing_replay.png");
+        let args = ScreenshotArgs {
+            output: output.to_str().unwrap().to_string(),
+            song: None,
+            replay: Some("missing_recording.txt".to_string()),
+            frames: 0,
+            scale: 1,
+            palette: None,
+        };
+        assert!(matches!(run(&args), Err(ScreenshotError::Replay(..))));
+        assert!(!output.exists());
+
+        let output = std::env::temp_dir().join("bsdj_reject_missing_replay.gif");
+        let args = ScreenshotArgs {
+            output: output.to_str().unwrap().to_string(),
+            ..args
+        };
+        assert!(matches!(
+            run_capture(&args),
+            Err(ScreenshotError::Replay(..))
+        ));
+        assert!(!output.exists());
+    }
+}
@@ -0,0 +1,15 @@
+use crate::resources::recording::RecordingError;
+use std::io;
+
+/// An error that occurs while taking a screenshot or capturing the screen.
+#[derive(thiserror::Error, Debug)]
+pub enum ScreenshotError {
+    #[error("Could not create the file: {0}")]
+    Io(#[from] io::Error),
+    #[error("Could not write the screenshot: {0}")]
+    Image(#[from] image::ImageError),
+    #[error("There is no palette named `{0}`")]
+    UnknownPalette(String),
+    #[error("Could not replay `{0}`. {1}")]
+    Replay(String, RecordingError),
+}
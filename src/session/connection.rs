@@ -0,0 +1,148 @@
+use super::*;
+use std::io::{ErrorKind, Read, Write};
+use std::net::TcpStream;
+
+/// The largest message that will be read, which is far more than a song as text needs.
+const MAX_MESSAGE_LEN: usize = 1 << 24;
+/// The most bytes that can wait to be sent before the other peer is taken to be gone.
+/// This is enough for the largest message along with plenty of edits after it.
+const MAX_QUEUED_LEN: usize = 2 * MAX_MESSAGE_LEN;
+
+/// A connection to another peer, sending and receiving messages without blocking.
+///
+/// Each message is sent as its length, as 4 bytes little endian, followed by the message.
+/// Messages are queued and sent as the other peer reads them, so a slow peer never
+/// holds up the frame.
+pub struct Connection {
+    stream: TcpStream,
+    /// Bytes received that don't make up a whole message yet.
+    received: Vec<u8>,
+    /// Bytes queued that the stream hasn't taken yet.
+    outgoing: Vec<u8>,
+}
+
+impl Connection {
+    /// Wraps a connected stream, making it non-blocking.
+    pub fn new(stream: TcpStream) -> Result<Connection, SessionError> {
+        stream.set_nonblocking(true)?;
+        stream.set_nodelay(true)?;
+        Ok(Connection {
+            stream,
+            received: vec![],
+            outgoing: vec![],
+        })
+    }
+
+    /// Queues a message and sends as much of the queue as the stream takes.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if the other peer is gone, or has stopped reading and let the queue fill up.
+    pub fn send(&mut self, message: &Message) -> Result<(), SessionError> {
+        let data = message.encode();
+        if self.outgoing.len() + 4 + data.len() > MAX_QUEUED_LEN {
+            return Err(SessionError::Disconnected);
+        }
+        self.outgoing.extend((data.len() as u32).to_le_bytes());
+        self.outgoing.extend(data);
+        self.flush()
+    }
+
+    /// Sends as much of the queue as the stream takes without blocking.
+    /// This is called every frame, so the rest goes out as the other peer reads.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if the other peer is gone.
+    pub fn flush(&mut self) -> Result<(), SessionError> {
+        let mut written = 0;
+        while written < self.outgoing.len() {
+            match self.stream.write(&self.outgoing[written..]) {
+                Ok(0) => return Err(SessionError::Disconnected),
+                Ok(len) => written += len,
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+        self.outgoing.drain(..written);
+        Ok(())
+    }
+
+    /// Returns every whole message received since the last call.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if the other peer is gone or sent something that isn't a message.
+    pub fn receive(&mut self) -> Result<Vec<Message>, SessionError> {
+        let mut buffer = [0; 4096];
+        loop {
+            match self.stream.read(&mut buffer) {
+                Ok(0) => return Err(SessionError::Disconnected),
+                Ok(len) => self.received.extend(&buffer[..len]),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                Err(e) => return Err(e.into()),
+            }
+        }
+
+        let mut messages = vec![];
+        while self.received.len() >= 4 {
+            let len = u32::from_le_bytes([
+                self.received[0],
+                self.received[1],
+                self.received[2],
+                self.received[3],
+            ]) as usize;
+            if len > MAX_MESSAGE_LEN {
+                return Err(SessionError::InvalidMessage);
+            }
+            if self.received.len() < 4 + len {
+                break;
+            }
+            let message =
+                Message::decode(&self.received[4..4 + len]).ok_or(SessionError::InvalidMessage)?;
+            messages.push(message);
+            self.received.drain(..4 + len);
+        }
+        Ok(messages)
+    }
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use std::net::TcpListener;
+
+    #[test]
+    /// Sending to a peer that doesn't read never blocks, and drops the peer once the
+    /// queue is full. A peer that reads slowly still gets every message.
+    fn queue_for_slow_peers() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let addr = listener.local_addr().unwrap();
+        let song = "-".repeat(1 << 20);
+        let welcome = Message::Welcome { id: 1, song };
+
+        let mut slow = Connection::new(TcpStream::connect(addr).unwrap()).unwrap();
+        let mut host = Connection::new(listener.accept().unwrap().0).unwrap();
+        for _ in 0..3 {
+            host.send(&welcome).unwrap();
+        }
+        let mut received = vec![];
+        for _ in 0..1000 {
+            received.extend(slow.receive().unwrap());
+            host.flush().unwrap();
+            if received.len() == 3 {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+        assert_eq!(received, vec![welcome.clone(); 3]);
+
+        let _stopped = TcpStream::connect(addr).unwrap();
+        let mut host = Connection::new(listener.accept().unwrap().0).unwrap();
+        // The stream takes a few messages before its buffers fill, then the queue does.
+        let error = (0..1000).find_map(|_| host.send(&welcome).err());
+        assert!(matches!(error, Some(SessionError::Disconnected)));
+    }
+}
@@ -0,0 +1,261 @@
+//! The messages sent between peers, and how they are written to bytes.
+
+use super::*;
+use crate::resources::types::instrument::*;
+
+/// A message between the host and a peer.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// Sent by the host to a peer that joined, with its id and the song as text.
+    Welcome { id: u8, song: String },
+    /// A cell was edited. Peers send these to the host, which sends them on to
+    /// every peer in the order they are applied.
+    Set { cell: Cell, value: CellValue },
+    /// A peer's cursor moved, or left the screens that show cells.
+    Cursor { peer: u8, cell: Option<Cell> },
+    /// A peer left the session.
+    Leave { peer: u8 },
+}
+
+impl Message {
+    /// Writes the message to bytes.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = vec![];
+        match self {
+            Message::Welcome { id, song } => {
+                data.extend([0, *id]);
+                data.extend((song.len() as u32).to_le_bytes());
+                data.extend(song.as_bytes());
+            }
+            Message::Set { cell, value } => {
+                data.push(1);
+                encode_cell(&mut data, cell);
+                encode_value(&mut data, value);
+            }
+            Message::Cursor { peer, cell } => {
+                data.extend([2, *peer, cell.is_some() as u8]);
+                if let Some(cell) = cell {
+                    encode_cell(&mut data, cell);
+                }
+            }
+            Message::Leave { peer } => data.extend([3, *peer]),
+        }
+        data
+    }
+
+    /// Reads a message written by `encode`. Returns None if it is invalid.
+    pub fn decode(data: &[u8]) -> Option<Message> {
+        let mut reader = Reader { data, pos: 0 };
+        let message = match reader.byte()? {
+            0 => {
+                let id = reader.byte()?;
+                let len = u32::from_le_bytes([
+                    reader.byte()?,
+                    reader.byte()?,
+                    reader.byte()?,
+                    reader.byte()?,
+                ]);
+                let song = reader.bytes(len as usize)?;
+                Message::Welcome {
+                    id,
+                    song: String::from_utf8(song.to_vec()).ok()?,
+                }
+            }
+            1 => Message::Set {
+                cell: reader.cell()?,
+                value: reader.value()?,
+            },
+            2 => {
+                let peer = reader.byte()?;
+                let cell = match reader.byte()? {
+                    0 => None,
+                    _ => Some(reader.cell()?),
+                };
+                Message::Cursor { peer, cell }
+            }
+            3 => Message::Leave {
+                peer: reader.byte()?,
+            },
+            _ => return None,
+        };
+
+        // Anything left over means the message wasn't what it seemed.
+        if reader.pos != data.len() {
+            return None;
+        }
+        Some(message)
+    }
+}
+
+// region:      Writing
+
+fn encode_cell(data: &mut Vec<u8>, cell: &Cell) {
+    match *cell {
+        Cell::Title => data.push(0),
+        Cell::Tempo => data.push(1),
+        Cell::SongRow { channel, row } => data.extend([2, channel as u8, row]),
+        Cell::ChainStep { chain, step } => data.extend([3, chain as u8, step as u8]),
+        Cell::PhraseRow { phrase, row } => data.extend([4, phrase as u8, row as u8]),
+        Cell::Instrument(id) => data.extend([5, id as u8]),
+    }
+}
+
+fn encode_value(data: &mut Vec<u8>, value: &CellValue) {
+    let option = |data: &mut Vec<u8>, value: Option<u8>| {
+        data.extend([value.is_some() as u8, value.unwrap_or(0)]);
+    };
+
+    match value {
+        CellValue::Title(title) => {
+            data.extend([0, title.len() as u8]);
+            data.extend(title.as_bytes());
+        }
+        CellValue::Tempo(tempo) => {
+            data.push(1);
+            data.extend(tempo.to_le_bytes());
+        }
+        CellValue::SongRow { chain, bookmarked } => {
+            data.push(2);
+            option(data, *chain);
+            data.push(*bookmarked as u8);
+        }
+        CellValue::ChainStep { phrase, transpose } => {
+            data.push(3);
+            option(data, *phrase);
+            data.push(*transpose);
+        }
+        CellValue::PhraseRow {
+            note,
+            instr,
+            cmd,
+            val,
+        } => {
+            data.push(4);
+            option(data, *note);
+            option(data, *instr);
+            data.extend([cmd.to_num(), *val]);
+        }
+        CellValue::Instrument(instrument) => {
+            data.push(5);
+            match instrument {
+                Instrument::Pulse(pulse) => {
+                    data.extend([0, pulse.envelope, pulse.duty]);
+                    option(data, pulse.length);
+                    data.push(pulse.sweep);
+                }
+                Instrument::Wave(wave) => {
+                    data.extend([1, wave.volume, wave.wave]);
+                    option(data, wave.length);
+                }
+                Instrument::Noise(noise) => {
+                    data.extend([2, noise.envelope]);
+                    option(data, noise.length);
+                    data.push(noise.short as u8);
+                }
+                Instrument::Kit() => data.push(3),
+                Instrument::Speech() => data.push(4),
+            }
+        }
+    }
+}
+
+// endregion:   Writing
+
+// region:      Reading
+
+struct Reader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn byte(&mut self) -> Option<u8> {
+        let byte = *self.data.get(self.pos)?;
+        self.pos += 1;
+        Some(byte)
+    }
+
+    fn bytes(&mut self, len: usize) -> Option<&'a [u8]> {
+        let bytes = self.data.get(self.pos..self.pos + len)?;
+        self.pos += len;
+        Some(bytes)
+    }
+
+    fn option(&mut self) -> Option<Option<u8>> {
+        let is_some = self.byte()? != 0;
+        let value = self.byte()?;
+        Some(if is_some { Some(value) } else { None })
+    }
+
+    fn cell(&mut self) -> Option<Cell> {
+        let cell = match self.byte()? {
+            0 => Cell::Title,
+            1 => Cell::Tempo,
+            2 => Cell::SongRow {
+                channel: self.byte()? as usize,
+                row: self.byte()?,
+            },
+            3 => Cell::ChainStep {
+                chain: self.byte()? as usize,
+                step: self.byte()? as usize,
+            },
+            4 => Cell::PhraseRow {
+                phrase: self.byte()? as usize,
+                row: self.byte()? as usize,
+            },
+            5 => Cell::Instrument(self.byte()? as usize),
+            _ => return None,
+        };
+        Some(cell)
+    }
+
+    fn value(&mut self) -> Option<CellValue> {
+        let value = match self.byte()? {
+            0 => {
+                let len = self.byte()?;
+                let title = self.bytes(len as usize)?;
+                CellValue::Title(String::from_utf8(title.to_vec()).ok()?)
+            }
+            1 => CellValue::Tempo(u16::from_le_bytes([self.byte()?, self.byte()?])),
+            2 => CellValue::SongRow {
+                chain: self.option()?,
+                bookmarked: self.byte()? != 0,
+            },
+            3 => CellValue::ChainStep {
+                phrase: self.option()?,
+                transpose: self.byte()?,
+            },
+            4 => CellValue::PhraseRow {
+                note: self.option()?,
+                instr: self.option()?,
+                cmd: Command::from_num(self.byte()?).ok()?,
+                val: self.byte()?,
+            },
+            5 => CellValue::Instrument(match self.byte()? {
+                0 => Instrument::Pulse(PulseInstrument {
+                    envelope: self.byte()?,
+                    duty: self.byte()?,
+                    length: self.option()?,
+                    sweep: self.byte()?,
+                }),
+                1 => Instrument::Wave(WaveInstrument {
+                    volume: self.byte()?,
+                    wave: self.byte()?,
+                    length: self.option()?,
+                }),
+                2 => Instrument::Noise(NoiseInstrument {
+                    envelope: self.byte()?,
+                    length: self.option()?,
+                    short: self.byte()? != 0,
+                }),
+                3 => Instrument::Kit(),
+                4 => Instrument::Speech(),
+                _ => return None,
+            }),
+            _ => return None,
+        };
+        Some(value)
+    }
+}
+
+// endregion:   Reading
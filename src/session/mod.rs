@@ -0,0 +1,656 @@
+//! Editing a song together over a LAN.
+//!
+//! One instance hosts the session and the others join it. The host decides the
+//! order of every edit: edits made by a peer are sent to the host, which applies
+//! them and sends them on to every peer, the one that made it included. Everyone
+//! applies edits in the order the host sent them, so all copies end up the same.
+//!
+//! Edits are found by comparing the song to a copy of it from the last sync, a
+//! cell at a time, so the screens don't have to know about the session.
+
+use crate::cli::CliArgs;
+use crate::formats::text;
+use crate::merge::{diff, Cell, CellValue, Song};
+use crate::resources::cursors::{ChainCursor, Cursor, PhraseCursor, SongCursor};
+use crate::resources::edited::{EditedChain, EditedPhrase};
+use crate::resources::types::*;
+use crate::states::States;
+use bevy::prelude::*;
+use std::collections::BTreeMap;
+use std::net::{TcpListener, TcpStream, ToSocketAddrs};
+
+pub mod connection;
+pub mod message;
+pub mod session_error;
+
+pub use connection::Connection;
+pub use message::Message;
+pub use session_error::*;
+
+/// The id of the host of a session.
+pub const HOST_ID: u8 = 0;
+
+/// This instance's side of a session.
+enum Role {
+    Host {
+        listener: TcpListener,
+        /// Every peer that joined, by id.
+        peers: BTreeMap<u8, Connection>,
+        /// The id the next peer to join gets.
+        next_id: u8,
+    },
+    Peer {
+        connection: Connection,
+        /// Whether the host has sent its song yet.
+        welcomed: bool,
+    },
+}
+
+/// A session to edit a song together with other instances.
+pub struct Session {
+    role: Role,
+    /// The id of this instance in the session.
+    id: u8,
+    /// The song as of the last sync, to find the edits made since.
+    synced: Song,
+    /// Where this instance's cursor was last sent to be.
+    cursor: Option<Cell>,
+    /// The cell each other instance's cursor is on, by id.
+    cursors: BTreeMap<u8, Cell>,
+}
+
+impl Session {
+    /// Hosts a session on the address, which other instances can join.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if the address can't be listened on.
+    pub fn host(addr: impl ToSocketAddrs, song: &Song) -> Result<Session, SessionError> {
+        let listener = TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        Ok(Session {
+            role: Role::Host {
+                listener,
+                peers: BTreeMap::new(),
+                next_id: HOST_ID + 1,
+            },
+            id: HOST_ID,
+            synced: song.clone(),
+            cursor: None,
+            cursors: BTreeMap::new(),
+        })
+    }
+
+    /// Joins the session hosted at the address.
+    /// The song is replaced with the host's on the first sync after it arrives.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if the host can't be connected to.
+    pub fn join(addr: impl ToSocketAddrs) -> Result<Session, SessionError> {
+        let connection = Connection::new(TcpStream::connect(addr)?)?;
+        Ok(Session {
+            role: Role::Peer {
+                connection,
+                welcomed: false,
+            },
+            id: HOST_ID,
+            synced: Song::default(),
+            cursor: None,
+            cursors: BTreeMap::new(),
+        })
+    }
+
+    /// Returns the id of this instance in the session.
+    pub fn get_id(&self) -> u8 {
+        self.id
+    }
+
+    /// Returns the address the session is hosted on, if this instance is the host.
+    pub fn get_host_addr(&self) -> Option<std::net::SocketAddr> {
+        match &self.role {
+            Role::Host { listener, .. } => listener.local_addr().ok(),
+            Role::Peer { .. } => None,
+        }
+    }
+
+    /// Returns the cell each other instance's cursor is on, by id.
+    pub fn get_cursors(&self) -> &BTreeMap<u8, Cell> {
+        &self.cursors
+    }
+
+    /// Sends the edits made to the song since the last sync and where the cursor
+    /// is, then applies the edits received from the session to the song.
+    ///
+    /// Returns true if the song was changed by the session.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if this instance joined and the host is gone or sent something invalid.
+    /// Peers that leave the host's session are dropped instead.
+    pub fn sync(&mut self, song: &mut Song, cursor: Option<Cell>) -> Result<bool, SessionError> {
+        match self.role {
+            Role::Host { .. } => Ok(self.sync_host(song, cursor)),
+            Role::Peer { .. } => self.sync_peer(song, cursor),
+        }
+    }
+
+    /// Syncs as the host, which applies edits as they arrive and sends them on.
+    fn sync_host(&mut self, song: &mut Song, cursor: Option<Cell>) -> bool {
+        let (listener, peers, next_id) = match &mut self.role {
+            Role::Host {
+                listener,
+                peers,
+                next_id,
+            } => (listener, peers, next_id),
+            Role::Peer { .. } => unreachable!(),
+        };
+        let mut outgoing = vec![];
+
+        for (cell, _, value) in diff(&self.synced, song) {
+            outgoing.push(Message::Set { cell, value });
+        }
+        if cursor != self.cursor {
+            self.cursor = cursor;
+            outgoing.push(Message::Cursor {
+                peer: HOST_ID,
+                cell: cursor,
+            });
+        }
+
+        // Welcome anyone that joined with the song as it is now.
+        while let Ok((stream, addr)) = listener.accept() {
+            if *next_id == u8::MAX {
+                break;
+            }
+            let id = *next_id;
+            let welcome = Connection::new(stream).and_then(|mut connection| {
+                connection.send(&Message::Welcome {
+                    id,
+                    song: song.to_text(),
+                })?;
+                let mut cursors = self.cursors.clone();
+                cursors.extend(self.cursor.map(|cell| (HOST_ID, cell)));
+                for (peer, cell) in cursors {
+                    connection.send(&Message::Cursor {
+                        peer,
+                        cell: Some(cell),
+                    })?;
+                }
+                Ok(connection)
+            });
+            match welcome {
+                Ok(connection) => {
+                    info!("Peer {} joined from {}", id, addr);
+                    peers.insert(id, connection);
+                    *next_id += 1;
+                }
+                Err(e) => warn!("Could not welcome {}: {}", addr, e),
+            }
+        }
+
+        let mut changed = false;
+        let mut left = vec![];
+        for (id, connection) in peers.iter_mut() {
+            let messages = match connection.receive() {
+                Ok(messages) => messages,
+                Err(e) => {
+                    info!("Peer {} left: {}", id, e);
+                    left.push(*id);
+                    continue;
+                }
+            };
+            for message in messages {
+                match message {
+                    Message::Set { cell, value } if fits(song, cell, &value) => {
+                        song.set_cell(cell, value.clone());
+                        changed = true;
+                        outgoing.push(Message::Set { cell, value });
+                    }
+                    Message::Cursor { cell, .. } => {
+                        match cell {
+                            Some(cell) => self.cursors.insert(*id, cell),
+                            None => self.cursors.remove(id),
+                        };
+                        outgoing.push(Message::Cursor { peer: *id, cell });
+                    }
+                    message => warn!("Peer {} sent {:?}", id, message),
+                }
+            }
+        }
+        for id in left {
+            peers.remove(&id);
+            self.cursors.remove(&id);
+            outgoing.push(Message::Leave { peer: id });
+        }
+
+        // Every peer gets everything, so they apply edits in the same order as the host.
+        for message in &outgoing {
+            let mut left = vec![];
+            for (id, connection) in peers.iter_mut() {
+                let own_cursor = matches!(message, Message::Cursor { peer, .. } if peer == id);
+                if !own_cursor && connection.send(message).is_err() {
+                    left.push(*id);
+                }
+            }
+            for id in left {
+                peers.remove(&id);
+                self.cursors.remove(&id);
+            }
+        }
+        // Send on what slow peers haven't taken yet, even with nothing new for them.
+        let mut left = vec![];
+        for (id, connection) in peers.iter_mut() {
+            if let Err(e) = connection.flush() {
+                info!("Peer {} left: {}", id, e);
+                left.push(*id);
+            }
+        }
+        for id in left {
+            peers.remove(&id);
+            self.cursors.remove(&id);
+        }
+
+        self.synced = song.clone();
+        changed
+    }
+
+    /// Syncs as a peer, which sends its edits to the host and applies what the host sends.
+    fn sync_peer(&mut self, song: &mut Song, cursor: Option<Cell>) -> Result<bool, SessionError> {
+        let (connection, welcomed) = match &mut self.role {
+            Role::Peer {
+                connection,
+                welcomed,
+            } => (connection, welcomed),
+            Role::Host { .. } => unreachable!(),
+        };
+
+        // Edits made before the host's song arrived would be overwritten by it anyway.
+        if *welcomed {
+            for (cell, _, value) in diff(&self.synced, song) {
+                connection.send(&Message::Set { cell, value })?;
+            }
+            if cursor != self.cursor {
+                self.cursor = cursor;
+                connection.send(&Message::Cursor {
+                    peer: self.id,
+                    cell: cursor,
+                })?;
+            }
+        }
+
+        connection.flush()?;
+
+        let mut changed = false;
+        for message in connection.receive()? {
+            match message {
+                Message::Welcome { id, song: text } => {
+                    let mut new_song = Song::default();
+                    text::read_song(
+                        &text,
+                        &mut new_song.channels,
+                        &mut new_song.chains,
+                        &mut new_song.phrases,
+                        &mut new_song.instruments,
+                        &mut new_song.project,
+                    )?;
+                    *song = new_song;
+                    self.id = id;
+                    *welcomed = true;
+                    changed = true;
+                }
+                Message::Set { cell, value } => {
+                    if !fits(song, cell, &value) {
+                        return Err(SessionError::InvalidMessage);
+                    }
+                    song.set_cell(cell, value);
+                    changed = true;
+                }
+                Message::Cursor { peer, cell } => {
+                    match cell {
+                        Some(cell) => self.cursors.insert(peer, cell),
+                        None => self.cursors.remove(&peer),
+                    };
+                }
+                Message::Leave { peer } => {
+                    self.cursors.remove(&peer);
+                }
+            }
+        }
+
+        self.synced = song.clone();
+        Ok(changed)
+    }
+}
+
+/// Returns true if the cell is in the song and the value is one that kind of cell can hold.
+fn fits(song: &Song, cell: Cell, value: &CellValue) -> bool {
+    valid(value)
+        && match (cell, value) {
+            (Cell::Title, CellValue::Title(_)) | (Cell::Tempo, CellValue::Tempo(_)) => true,
+            (Cell::SongRow { channel, .. }, CellValue::SongRow { .. }) => channel < 4,
+            (Cell::ChainStep { chain, step }, CellValue::ChainStep { .. }) => {
+                song.chains.get(chain).is_some() && step < 0x10
+            }
+            (Cell::PhraseRow { phrase, row }, CellValue::PhraseRow { .. }) => {
+                song.phrases.get(phrase).is_some() && row < 0x10
+            }
+            (Cell::Instrument(id), CellValue::Instrument(_)) => song.instruments.get(id).is_some(),
+            _ => false,
+        }
+}
+
+/// Returns true if the value is in range, so setting it can't panic or leave the song broken.
+fn valid(value: &CellValue) -> bool {
+    match value {
+        CellValue::Title(title) => title.chars().count() <= project::TITLE_LENGTH,
+        CellValue::Tempo(tempo) => (project::MIN_TEMPO..=project::MAX_TEMPO).contains(tempo),
+        CellValue::SongRow { chain, .. } => chain.is_none_or(|chain| chain <= 0x7f),
+        CellValue::ChainStep { phrase, .. } => *phrase != Some(0xff),
+        CellValue::PhraseRow { note, instr, .. } => {
+            note.is_none_or(|note| note <= note::LARGEST_NOTE)
+                && instr.is_none_or(|instr| (instr as usize) < instrument::INSTR_COUNT)
+        }
+        CellValue::Instrument(instrument::Instrument::Pulse(pulse)) => pulse.duty < 4,
+        CellValue::Instrument(instrument::Instrument::Wave(wave)) => wave.volume < 4,
+        CellValue::Instrument(_) => true,
+    }
+}
+
+/// Where the cursors of the other instances in the session are, for the screens to show.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct PeerCursors {
+    /// The cell each other instance's cursor is on, by id.
+    cells: BTreeMap<u8, Cell>,
+}
+
+impl PeerCursors {
+    /// Returns true if another instance's cursor is on the cell.
+    pub fn contains(&self, cell: &Cell) -> bool {
+        self.cells.values().any(|c| c == cell)
+    }
+}
+
+// region:      SessionPlugin
+
+/// Hosts or joins the session passed to `--host` or `--join`, if any, and keeps the song in sync with it.
+pub struct SessionPlugin;
+
+impl Plugin for SessionPlugin {
+    fn build(&self, app: &mut App) {
+        let cli = app
+            .world
+            .get_resource::<CliArgs>()
+            .cloned()
+            .unwrap_or_default();
+
+        app.insert_resource(PeerCursors::default());
+
+        let session = if let Some(port) = cli.host {
+            let song = Song {
+                channels: app
+                    .world
+                    .get_resource::<Channels>()
+                    .cloned()
+                    .unwrap_or_default(),
+                chains: app
+                    .world
+                    .get_resource::<Chains>()
+                    .cloned()
+                    .unwrap_or_default(),
+                phrases: app
+                    .world
+                    .get_resource::<Phrases>()
+                    .cloned()
+                    .unwrap_or_default(),
+                instruments: app
+                    .world
+                    .get_resource::<Instruments>()
+                    .cloned()
+                    .unwrap_or_default(),
+                project: app
+                    .world
+                    .get_resource::<Project>()
+                    .cloned()
+                    .unwrap_or_default(),
+            };
+            match Session::host(("0.0.0.0", port), &song) {
+                Ok(session) => {
+                    info!("Hosting a session on port {}", port);
+                    Some(session)
+                }
+                Err(e) => {
+                    error!("Could not host a session: {}", e);
+                    None
+                }
+            }
+        } else if let Some(addr) = &cli.join {
+            match Session::join(addr.as_str()) {
+                Ok(session) => {
+                    info!("Joined the session at {}", addr);
+                    Some(session)
+                }
+                Err(e) => {
+                    error!("Could not join the session at {}: {}", addr, e);
+                    None
+                }
+            }
+        } else {
+            None
+        };
+
+        if let Some(session) = session {
+            app.insert_resource(session);
+            app.add_system(sync_session);
+        }
+    }
+}
+
+/// Sends local edits and the cursor to the session, and applies the edits received from it.
+#[allow(clippy::too_many_arguments)]
+fn sync_session(
+    mut commands: Commands,
+    session: Option<ResMut<Session>>,
+    mut peer_cursors: ResMut<PeerCursors>,
+    state: Res<State<States>>,
+    song_cursor: Res<SongCursor>,
+    chain_cursor: Res<ChainCursor>,
+    phrase_cursor: Res<PhraseCursor>,
+    edited_chain: Res<EditedChain>,
+    edited_phrase: Res<EditedPhrase>,
+    mut channels: ResMut<Channels>,
+    mut chains: ResMut<Chains>,
+    mut phrases: ResMut<Phrases>,
+    mut instruments: ResMut<Instruments>,
+    mut project: ResMut<Project>,
+) {
+    let mut session = match session {
+        Some(session) => session,
+        None => return,
+    };
+
+    let cursor = match state.current() {
+        States::Song => {
+            let (channel, row) = song_cursor.get_pos();
+            Some(Cell::SongRow {
+                channel: channel as usize,
+                row,
+            })
+        }
+        States::Chain => Some(Cell::ChainStep {
+            chain: edited_chain.0 as usize,
+            step: chain_cursor.get_y() as usize,
+        }),
+        States::Phrase => Some(Cell::PhraseRow {
+            phrase: edited_phrase.0 as usize,
+            row: phrase_cursor.get_y() as usize,
+        }),
+        _ => None,
+    };
+
+    let mut song = Song {
+        channels: channels.clone(),
+        chains: *chains,
+        phrases: *phrases,
+        instruments: *instruments,
+        project: project.clone(),
+    };
+    match session.sync(&mut song, cursor) {
+        // Only write back on changes, so that the screens don't redraw every frame.
+        Ok(true) => {
+            *channels = song.channels;
+            *chains = song.chains;
+            *phrases = song.phrases;
+            *instruments = song.instruments;
+            *project = song.project;
+        }
+        Ok(false) => (),
+        Err(e) => {
+            error!("Left the session: {}", e);
+            commands.remove_resource::<Session>();
+            *peer_cursors = PeerCursors::default();
+            return;
+        }
+    }
+
+    if peer_cursors.cells != *session.get_cursors() {
+        peer_cursors.cells = session.get_cursors().clone();
+    }
+}
+
+// endregion:   SessionPlugin
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use std::time::Duration;
+
+    /// Syncs both sides until the condition holds, panicking if it takes too long.
+    fn sync_until(
+        host: &mut (Session, Song),
+        peer: &mut (Session, Song),
+        condition: impl Fn(&(Session, Song), &(Session, Song)) -> bool,
+    ) {
+        for _ in 0..200 {
+            host.0.sync(&mut host.1, None).unwrap();
+            peer.0
+                .sync(&mut peer.1, Some(Cell::PhraseRow { phrase: 1, row: 2 }))
+                .unwrap();
+            if condition(host, peer) {
+                return;
+            }
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        panic!("The session did not sync");
+    }
+
+    #[test]
+    /// Host a session on localhost and join it, then edit on both sides.
+    fn edit_together() {
+        let mut song = Song::default();
+        song.phrases.get_mut(0).unwrap().set_note(0, 14);
+        let mut host = (Session::host("127.0.0.1:0", &song).unwrap(), song);
+        let addr = host.0.get_host_addr().unwrap();
+        let mut peer = (Session::join(addr).unwrap(), Song::default());
+
+        // Joining gives the host's song and an id.
+        sync_until(&mut host, &mut peer, |host, peer| host.1 == peer.1);
+        assert_eq!(1, peer.0.get_id());
+
+        // Edits go both ways, along with the peer's cursor.
+        peer.1.channels.get_mut(2).set_chain(3, 4);
+        host.1.project.set_tempo(150);
+        sync_until(&mut host, &mut peer, |host, peer| {
+            host.1 == peer.1
+                && host.1.project.get_tempo() == 150
+                && !host.0.get_cursors().is_empty()
+        });
+        assert_eq!(Some(4), host.1.channels.get(2).get_chain(3));
+        assert_eq!(
+            Some(&Cell::PhraseRow { phrase: 1, row: 2 }),
+            host.0.get_cursors().get(&1)
+        );
+
+        // Invalid messages are caught before reaching the song.
+        let message = Message::Set {
+            cell: Cell::Tempo,
+            value: CellValue::Tempo(150),
+        };
+        assert_eq!(Some(message.clone()), Message::decode(&message.encode()));
+        assert_eq!(None, Message::decode(&[1, 4, 0xff]));
+        assert!(!fits(
+            &host.1,
+            Cell::SongRow { channel: 4, row: 0 },
+            &CellValue::Tempo(150)
+        ));
+    }
+
+    #[test]
+    /// Values out of range are dropped by the host and rejected by a peer, rather than panicking.
+    fn reject_bad_values() {
+        let bad = [
+            (
+                Cell::SongRow { channel: 0, row: 0 },
+                CellValue::SongRow {
+                    chain: Some(0x80),
+                    bookmarked: false,
+                },
+            ),
+            (
+                Cell::ChainStep { chain: 0, step: 0 },
+                CellValue::ChainStep {
+                    phrase: Some(0xff),
+                    transpose: 0,
+                },
+            ),
+            (Cell::Tempo, CellValue::Tempo(project::MAX_TEMPO + 1)),
+            (Cell::Title, CellValue::Title("ninechars".to_string())),
+            (
+                Cell::Instrument(0),
+                CellValue::Instrument(instrument::Instrument::Pulse(instrument::PulseInstrument {
+                    duty: 4,
+                    ..Default::default()
+                })),
+            ),
+        ];
+        let song = Song::default();
+        for (cell, value) in &bad {
+            assert!(!fits(&song, *cell, value), "{} fits {:?}", cell, value);
+        }
+
+        // The host drops them.
+        let mut host = (Session::host("127.0.0.1:0", &song).unwrap(), song.clone());
+        let addr = host.0.get_host_addr().unwrap();
+        let mut sender = Connection::new(TcpStream::connect(addr).unwrap()).unwrap();
+        for (cell, value) in &bad {
+            let message = Message::Set {
+                cell: *cell,
+                value: value.clone(),
+            };
+            sender.send(&message).unwrap();
+        }
+        for _ in 0..20 {
+            host.0.sync(&mut host.1, None).unwrap();
+            std::thread::sleep(Duration::from_millis(5));
+        }
+        assert_eq!(host.1, song);
+
+        // A peer errors.
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let mut peer = (
+            Session::join(listener.local_addr().unwrap()).unwrap(),
+            song.clone(),
+        );
+        let mut sender = Connection::new(listener.accept().unwrap().0).unwrap();
+        let (cell, value) = bad[0].clone();
+        sender.send(&Message::Set { cell, value }).unwrap();
+        let result = (0..200)
+            .map(|_| {
+                std::thread::sleep(Duration::from_millis(5));
+                peer.0.sync(&mut peer.1, None)
+            })
+            .find(|result| !matches!(result, Ok(false)));
+        assert!(matches!(result, Some(Err(SessionError::InvalidMessage))));
+        assert_eq!(peer.1, song);
+    }
+}
@@ -0,0 +1,15 @@
+use crate::formats::text::TextError;
+use std::io;
+
+/// An error that occurs during a session.
+#[derive(thiserror::Error, Debug)]
+pub enum SessionError {
+    #[error("Network error: {0}")]
+    Io(#[from] io::Error),
+    #[error("The other side left the session.")]
+    Disconnected,
+    #[error("Received something that isn't a message.")]
+    InvalidMessage,
+    #[error("Could not read the host's song: {0}")]
+    Text(#[from] TextError),
+}
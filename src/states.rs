@@ -2,6 +2,10 @@
 /// None if there is no new scene to transition to.
 pub struct NextState(pub Option<(u8, u8)>);
 
+/// A screen to go to that isn't on the navbar, such as the font editor.
+/// None if there is no such screen to transition to.
+pub struct NextScreen(pub Option<States>);
+
 /// Used to indicate whether the scene should be loaded (or reloaded)
 /// Reloading an already-loaded state with `State.overwrite_replace(new_state).unwrap()`
 /// will error with the reason that it's already loaded, so our
@@ -28,6 +32,10 @@ pub enum States {
     Groove,
     File,
     Help,
+    /// The font editor, opened with `font` rather than from the navbar.
+    Font,
+    /// The palette editor, opened with `palette` rather than from the navbar.
+    Palette,
 }
 
 impl States {
@@ -48,6 +56,8 @@ impl States {
             States::Groove => "g",
             States::File => "f",
             States::Help => "h",
+            States::Font => "f",
+            States::Palette => "p",
         }
     }
 }
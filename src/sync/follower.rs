@@ -0,0 +1,73 @@
+/// The shortest time a measurement of the master's pulse length is taken over.
+/// Packets arrive in bursts each frame, so shorter measurements are mostly jitter.
+const MEASURE_TIME: f64 = 0.1;
+/// How far the smoothed pulse length moves towards each new measurement.
+const SMOOTHING: f64 = 0.2;
+/// How long the master can go without sending a pulse before it's considered stopped.
+pub const TIMEOUT: f64 = 0.5;
+
+/// Follows the clock of a master, smoothing out the jitter in when its pulses arrive.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct ClockFollower {
+    /// The pulse the current measurement started at, and when it arrived in seconds.
+    measure_from: Option<(u64, f64)>,
+    /// The last pulse received, and when it arrived in seconds.
+    last: Option<(u64, f64)>,
+    /// The smoothed time between pulses, in seconds.
+    pulse_length: Option<f64>,
+}
+
+impl ClockFollower {
+    /// Forgets everything received, for when the master starts again.
+    pub fn reset(&mut self) {
+        *self = ClockFollower::default();
+    }
+
+    /// Records a pulse received from the master.
+    pub fn receive(&mut self, pulse: u64, now: f64) {
+        // Packets that arrive out of order are of no use.
+        if matches!(self.last, Some((last, _)) if pulse <= last) {
+            return;
+        }
+        self.last = Some((pulse, now));
+
+        let (from_pulse, from_time) = match self.measure_from {
+            Some(from) => from,
+            None => {
+                self.measure_from = Some((pulse, now));
+                return;
+            }
+        };
+        if now - from_time < MEASURE_TIME {
+            return;
+        }
+
+        let measured = (now - from_time) / (pulse - from_pulse) as f64;
+        self.pulse_length = Some(match self.pulse_length {
+            // A single late burst is limited, so that it can't throw the tempo off.
+            Some(length) => {
+                let measured = measured.clamp(length / 2., length * 2.);
+                length + (measured - length) * SMOOTHING
+            }
+            None => measured,
+        });
+        self.measure_from = Some((pulse, now));
+    }
+
+    /// Returns the master's tempo in BPM, once enough pulses have been received to tell.
+    pub fn get_tempo(&self) -> Option<f64> {
+        let length = self.pulse_length?;
+        Some(60. / (length * super::PULSES_PER_BEAT as f64))
+    }
+
+    /// Estimates how far into the song the master is, in pulses.
+    pub fn get_position(&self, now: f64) -> Option<f64> {
+        let (pulse, time) = self.last?;
+        Some(pulse as f64 + (now - time) / self.pulse_length?)
+    }
+
+    /// Returns true if pulses were being received but have stopped arriving.
+    pub fn timed_out(&self, now: f64) -> bool {
+        matches!(self.last, Some((_, time)) if now - time > TIMEOUT)
+    }
+}
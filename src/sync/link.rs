@@ -0,0 +1,80 @@
+use super::*;
+use std::io::{self, ErrorKind};
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+
+/// The UDP socket a master sends its clock through, or a slave receives it through.
+pub struct SyncLink {
+    socket: UdpSocket,
+    /// Where a master sends its messages. None for a slave.
+    target: Option<SocketAddr>,
+}
+
+impl SyncLink {
+    /// Opens a link to send the clock to an address, which may be a broadcast address.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if the address can't be resolved, or no socket could be opened.
+    pub fn master(target: impl ToSocketAddrs) -> io::Result<SyncLink> {
+        let target = target
+            .to_socket_addrs()?
+            .next()
+            .ok_or_else(|| io::Error::new(ErrorKind::InvalidInput, "No address to sync to"))?;
+        let socket = UdpSocket::bind(("0.0.0.0", 0))?;
+        socket.set_broadcast(true)?;
+        socket.set_nonblocking(true)?;
+        Ok(SyncLink {
+            socket,
+            target: Some(target),
+        })
+    }
+
+    /// Opens a link to receive a clock on a port.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if the port is already in use.
+    pub fn slave(port: u16) -> io::Result<SyncLink> {
+        let socket = UdpSocket::bind(("0.0.0.0", port))?;
+        socket.set_nonblocking(true)?;
+        Ok(SyncLink {
+            socket,
+            target: None,
+        })
+    }
+
+    /// Returns the address the link receives on.
+    pub fn get_local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// Sends a message to the slaves. Does nothing on a slave's link.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if the message couldn't be sent.
+    pub fn send(&self, message: SyncMessage) -> io::Result<()> {
+        if let Some(target) = self.target {
+            self.socket.send_to(&message.encode(), target)?;
+        }
+        Ok(())
+    }
+
+    /// Returns every message received since the last call. Anything that isn't
+    /// a message is ignored, and an error stops receiving until the next call.
+    pub fn receive(&self) -> Vec<SyncMessage> {
+        let mut messages = vec![];
+        let mut buffer = [0; 64];
+        loop {
+            match self.socket.recv_from(&mut buffer) {
+                Ok((len, _)) => messages.extend(SyncMessage::decode(&buffer[..len])),
+                Err(e) if e.kind() == ErrorKind::WouldBlock => break,
+                Err(e) if e.kind() == ErrorKind::Interrupted => (),
+                // Errors from earlier sends can show up here, and may keep showing up,
+                // so they're left for the next frame rather than retried.
+                Err(_) => break,
+            }
+        }
+        messages
+    }
+}
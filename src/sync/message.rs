@@ -0,0 +1,43 @@
+/// Written at the start of every message, so that other traffic on the port is ignored.
+const MAGIC: &[u8; 4] = b"BSDJ";
+
+/// A message from the master to its slaves.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncMessage {
+    /// Playback started from the beginning.
+    Start,
+    /// Playback stopped.
+    Stop,
+    /// Playback reached a pulse. Sent at most once a frame, with the latest pulse.
+    Clock(u64),
+}
+
+impl SyncMessage {
+    /// Writes the message to bytes.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut data = MAGIC.to_vec();
+        match self {
+            SyncMessage::Start => data.push(0),
+            SyncMessage::Stop => data.push(1),
+            SyncMessage::Clock(pulse) => {
+                data.push(2);
+                data.extend(pulse.to_le_bytes());
+            }
+        }
+        data
+    }
+
+    /// Reads a message written by `encode`. Returns None if it is invalid.
+    pub fn decode(data: &[u8]) -> Option<SyncMessage> {
+        let data = data.strip_prefix(MAGIC)?;
+        match data {
+            [0] => Some(SyncMessage::Start),
+            [1] => Some(SyncMessage::Stop),
+            [2, pulse @ ..] => {
+                let pulse = <[u8; 8]>::try_from(pulse).ok()?;
+                Some(SyncMessage::Clock(u64::from_le_bytes(pulse)))
+            }
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,352 @@
+//! Keeping the tempo of BSDj instances in sync over UDP, like LSDj's link cable.
+//! Following the MIDI clock is left to the `midi` module.
+//!
+//! The master sends start, stop and its latest clock pulse every frame it plays.
+//! Slaves play along at the master's tempo, which they work out from when the
+//! pulses arrive, and slowly correct any drift from the master's position.
+
+use crate::cli::CliArgs;
+use crate::resources::bindings::Action;
+use crate::resources::input::InputRes;
+use crate::resources::types::Project;
+use bevy::prelude::*;
+use std::fmt;
+
+pub mod follower;
+pub mod link;
+pub mod message;
+pub mod transport;
+
+pub use follower::ClockFollower;
+pub use link::SyncLink;
+pub use message::SyncMessage;
+pub use transport::*;
+
+/// The address the master sends to and slaves listen on, if none is given.
+pub const DEFAULT_SYNC_ADDR: &str = "255.255.255.255:9860";
+/// The label of the system that opens the link, for systems that must run after it.
+pub const OPEN_LINK: &str = "open_link";
+/// How much of the drift from the master's position a slave corrects each frame.
+const CORRECTION: f64 = 0.1;
+
+/// Which side of the sync this instance is on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SyncMode {
+    /// Playback runs on its own.
+    Off,
+    /// Playback is started, stopped and clocked here, and sent to the slaves.
+    Master,
+    /// Playback follows a master.
+    Slave,
+    /// Playback follows the MIDI clock.
+    Midi,
+}
+
+impl SyncMode {
+    /// Returns every sync mode in the order the Project screen cycles through them.
+    pub fn iter() -> [SyncMode; 4] {
+        [
+            SyncMode::Off,
+            SyncMode::Master,
+            SyncMode::Slave,
+            SyncMode::Midi,
+        ]
+    }
+
+    /// Returns true if playback is started, stopped and clocked from elsewhere.
+    pub fn is_following(&self) -> bool {
+        matches!(self, SyncMode::Slave | SyncMode::Midi)
+    }
+
+    /// Returns the sync mode after this one, wrapping around.
+    pub fn next(&self) -> SyncMode {
+        let modes = SyncMode::iter();
+        let idx = modes.iter().position(|mode| mode == self).unwrap();
+        modes[(idx + 1) % modes.len()]
+    }
+
+    /// Returns the sync mode before this one, wrapping around.
+    pub fn prev(&self) -> SyncMode {
+        let modes = SyncMode::iter();
+        let idx = modes.iter().position(|mode| mode == self).unwrap();
+        modes[(idx + modes.len() - 1) % modes.len()]
+    }
+}
+
+impl fmt::Display for SyncMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let text = match self {
+            SyncMode::Off => "off",
+            SyncMode::Master => "master",
+            SyncMode::Slave => "slave",
+            SyncMode::Midi => "midi",
+        };
+        write!(f, "{}", text)
+    }
+}
+
+/// How this instance syncs with others.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SyncSettings {
+    mode: SyncMode,
+    /// The address the master sends to. Slaves listen on its port.
+    addr: String,
+}
+
+impl SyncSettings {
+    /// Returns which side of the sync this instance is on.
+    pub fn get_mode(&self) -> SyncMode {
+        self.mode
+    }
+
+    /// Sets which side of the sync this instance is on.
+    pub fn set_mode(&mut self, mode: SyncMode) {
+        self.mode = mode;
+    }
+
+    /// Returns the address the master sends to.
+    pub fn get_addr(&self) -> &str {
+        &self.addr
+    }
+
+    /// Opens the link for the sync mode. Returns None if sync is off.
+    ///
+    /// # ERRORS
+    ///
+    /// Errors if the address is invalid or its port can't be used.
+    pub fn open_link(&self) -> std::io::Result<Option<SyncLink>> {
+        match self.mode {
+            SyncMode::Off | SyncMode::Midi => Ok(None),
+            SyncMode::Master => SyncLink::master(self.addr.as_str()).map(Some),
+            SyncMode::Slave => {
+                let port = self
+                    .addr
+                    .rsplit_once(':')
+                    .and_then(|(_, port)| port.parse().ok())
+                    .ok_or_else(|| {
+                        std::io::Error::new(
+                            std::io::ErrorKind::InvalidInput,
+                            format!("No port in `{}`", self.addr),
+                        )
+                    })?;
+                SyncLink::slave(port).map(Some)
+            }
+        }
+    }
+}
+
+impl Default for SyncSettings {
+    fn default() -> Self {
+        SyncSettings {
+            mode: SyncMode::Off,
+            addr: DEFAULT_SYNC_ADDR.to_string(),
+        }
+    }
+}
+
+// region:      SyncPlugin
+
+/// The link to the other instances, and what's been received from the master.
+#[derive(Default)]
+struct SyncState {
+    link: Option<SyncLink>,
+    follower: ClockFollower,
+}
+
+/// Runs the transport, syncing it with the other instances as set on the Project screen.
+pub struct SyncPlugin;
+
+impl Plugin for SyncPlugin {
+    fn build(&self, app: &mut App) {
+        let cli = app
+            .world
+            .get_resource::<CliArgs>()
+            .cloned()
+            .unwrap_or_default();
+
+        let mut settings = SyncSettings::default();
+        if let Some(mode) = cli.sync {
+            settings.set_mode(mode);
+        }
+        if let Some(addr) = cli.sync_addr {
+            settings.addr = addr;
+        }
+
+        app.insert_resource(settings);
+        app.insert_resource(Transport::default());
+        app.insert_resource(SyncState::default());
+        app.add_system(open_link.label(OPEN_LINK));
+        app.add_system(run_transport.after(OPEN_LINK));
+    }
+}
+
+/// Reopens the link whenever the sync mode changes.
+fn open_link(settings: Res<SyncSettings>, mut state: ResMut<SyncState>) {
+    if !settings.is_changed() {
+        return;
+    }
+
+    state.follower.reset();
+    state.link = match settings.open_link() {
+        Ok(link) => {
+            info!("Sync is {}", settings.get_mode());
+            link
+        }
+        Err(e) => {
+            error!("Could not sync as {}: {}", settings.get_mode(), e);
+            None
+        }
+    };
+}
+
+/// Starts, stops and clocks playback, from Space or from the master.
+fn run_transport(
+    time: Res<Time>,
+    input: Res<InputRes>,
+    settings: Res<SyncSettings>,
+    project: Res<Project>,
+    mut transport: ResMut<Transport>,
+    mut state: ResMut<SyncState>,
+) {
+    let state = &mut *state;
+    let now = time.seconds_since_startup();
+    let delta = time.delta_seconds_f64();
+
+    match settings.get_mode() {
+        SyncMode::Slave => return follow_master(now, delta, &mut transport, state),
+        // The MIDI plugin follows the MIDI clock.
+        SyncMode::Midi => return,
+        SyncMode::Off | SyncMode::Master => (),
+    }
+
+    // Play (Space by default) starts and stops playback, unless it's following something else.
+    if input.action_just_pressed(Action::Play) {
+        let message = if transport.is_playing() {
+            transport.stop();
+            SyncMessage::Stop
+        } else {
+            transport.start();
+            SyncMessage::Start
+        };
+        send(&state.link, message);
+    }
+
+    transport.set_tempo(project.get_tempo() as f64);
+    if transport.advance(delta) > 0 {
+        send(&state.link, SyncMessage::Clock(transport.get_pulse()));
+    }
+}
+
+/// Plays along with what's been received from the master.
+fn follow_master(now: f64, delta: f64, transport: &mut Transport, state: &mut SyncState) {
+    let link = match &state.link {
+        Some(link) => link,
+        None => return,
+    };
+
+    for message in link.receive() {
+        match message {
+            SyncMessage::Start => {
+                transport.start();
+                state.follower.reset();
+            }
+            SyncMessage::Stop => transport.stop(),
+            SyncMessage::Clock(pulse) => {
+                // Start anyway if the master's start was lost on the way.
+                if !transport.is_playing() {
+                    transport.start();
+                    state.follower.reset();
+                    transport.move_to(pulse as f64);
+                }
+                state.follower.receive(pulse, now);
+            }
+        }
+    }
+
+    // Stop anyway if the master's stop was lost on the way.
+    if transport.is_playing() && state.follower.timed_out(now) {
+        transport.stop();
+        state.follower.reset();
+        return;
+    }
+
+    if let Some(tempo) = state.follower.get_tempo() {
+        transport.set_tempo(tempo);
+    }
+    let mut position =
+        transport.get_position() + delta * transport.get_tempo() / 60. * PULSES_PER_BEAT as f64;
+    // Move part of the way to where the master should be. When ahead of it,
+    // this slows playback down rather than moving it back.
+    if let Some(master) = state.follower.get_position(now) {
+        position += (master - position) * CORRECTION;
+    }
+    transport.move_to(position);
+}
+
+/// Sends a message to the slaves if this instance is the master.
+fn send(link: &Option<SyncLink>, message: SyncMessage) {
+    if let Some(link) = link {
+        if let Err(e) = link.send(message) {
+            warn!("Could not send {:?} to the slaves: {}", message, e);
+        }
+    }
+}
+
+// endregion:   SyncPlugin
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    /// Follow a master at 120 BPM whose pulses arrive in jittery bursts once a frame.
+    fn follow_jittery_master() {
+        let mut master = Transport::default();
+        master.set_tempo(120.);
+        master.start();
+        let mut follower = ClockFollower::default();
+
+        // A fixed pseudo-random jitter of up to 8ms, so the test is repeatable.
+        let mut seed = 1u32;
+        for frame in 1..=300 {
+            master.advance(1. / 60.);
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            let jitter = (seed >> 16) as f64 / 65536. * 0.008;
+            follower.receive(master.get_pulse(), frame as f64 / 60. + jitter);
+        }
+
+        let tempo = follower.get_tempo().unwrap();
+        assert!((tempo - 120.).abs() < 2., "Followed at {} BPM", tempo);
+        let position = follower.get_position(5. + 0.004).unwrap();
+        assert!((position - master.get_position()).abs() < 2.);
+        assert!(follower.timed_out(6.));
+    }
+
+    #[test]
+    /// Send the clock from a master to a slave on localhost.
+    fn link_on_localhost() {
+        let slave = SyncLink::slave(0).unwrap();
+        let port = slave.get_local_addr().unwrap().port();
+        let master = SyncLink::master(("127.0.0.1", port)).unwrap();
+
+        let sent = [
+            SyncMessage::Start,
+            SyncMessage::Clock(300),
+            SyncMessage::Stop,
+        ];
+        for message in sent {
+            master.send(message).unwrap();
+        }
+
+        let mut received = vec![];
+        for _ in 0..100 {
+            received.extend(slave.receive());
+            if received.len() == sent.len() {
+                break;
+            }
+            std::thread::sleep(std::time::Duration::from_millis(5));
+        }
+        assert_eq!(sent.to_vec(), received);
+        assert_eq!(None, SyncMessage::decode(b"BSDJ\x02\x01"));
+    }
+}
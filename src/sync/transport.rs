@@ -0,0 +1,88 @@
+use crate::resources::types::project::DEFAULT_TEMPO;
+
+/// The number of clock pulses in a beat, the same as MIDI clock.
+pub const PULSES_PER_BEAT: u32 = 24;
+/// The number of clock pulses in a phrase row, with 4 rows to a beat as in LSDj.
+pub const PULSES_PER_ROW: u32 = PULSES_PER_BEAT / 4;
+
+/// Whether the song is playing, and how far into it playback is.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Transport {
+    playing: bool,
+    /// How far into the song playback is, in pulses.
+    position: f64,
+    /// The tempo playback runs at, in BPM.
+    tempo: f64,
+}
+
+impl Transport {
+    /// Starts playback from the beginning.
+    pub fn start(&mut self) {
+        self.playing = true;
+        self.position = 0.;
+    }
+
+    /// Starts playback from where it stopped.
+    pub fn resume(&mut self) {
+        self.playing = true;
+    }
+
+    /// Stops playback.
+    pub fn stop(&mut self) {
+        self.playing = false;
+    }
+
+    /// Returns true if the song is playing.
+    pub fn is_playing(&self) -> bool {
+        self.playing
+    }
+
+    /// Returns how far into the song playback is, in pulses.
+    pub fn get_position(&self) -> f64 {
+        self.position
+    }
+
+    /// Returns the number of whole pulses played.
+    pub fn get_pulse(&self) -> u64 {
+        self.position as u64
+    }
+
+    /// Returns the tempo playback runs at, in BPM.
+    pub fn get_tempo(&self) -> f64 {
+        self.tempo
+    }
+
+    /// Sets the tempo playback runs at, in BPM.
+    pub fn set_tempo(&mut self, tempo: f64) {
+        self.tempo = tempo;
+    }
+
+    /// Moves playback on by some time at its tempo.
+    /// Returns the number of pulses passed.
+    pub fn advance(&mut self, secs: f64) -> u64 {
+        let pulses = secs * self.tempo / 60. * PULSES_PER_BEAT as f64;
+        self.move_to(self.position + pulses)
+    }
+
+    /// Moves playback to a position in pulses, if it's playing. Playback never
+    /// moves backwards, so that no pulse is played twice.
+    /// Returns the number of pulses passed.
+    pub fn move_to(&mut self, position: f64) -> u64 {
+        if !self.playing || position <= self.position {
+            return 0;
+        }
+        let passed = position.floor() as u64 - self.position.floor() as u64;
+        self.position = position;
+        passed
+    }
+}
+
+impl Default for Transport {
+    fn default() -> Self {
+        Transport {
+            playing: false,
+            position: 0.,
+            tempo: DEFAULT_TEMPO as f64,
+        }
+    }
+}
@@ -0,0 +1,228 @@
+//! Finds the fonts, glyphs and palettes bundled in `assets/` and saved in the config folder.
+
+use super::*;
+use crate::utils::config_dir::config_dir;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// A kind of asset the TileRenderer loads, each with its own folder.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AssetKind {
+    Font,
+    Glyph,
+    Palette,
+}
+
+impl AssetKind {
+    /// The folder the assets are found in, both in `assets/` and the config folder.
+    pub fn folder(&self) -> &'static str {
+        match self {
+            AssetKind::Font => "fonts",
+            AssetKind::Glyph => "glyphs",
+            AssetKind::Palette => "colorsets",
+        }
+    }
+
+    pub fn extension(&self) -> &'static str {
+        match self {
+            AssetKind::Font | AssetKind::Glyph => "tilesprite",
+            AssetKind::Palette => "colorset",
+        }
+    }
+}
+
+/// The glyphs every font needs, as the screens draw them.
+pub const FONT_GLYPHS: [&str; 71] = [
+    "musicnote",
+    "cursor",
+    "space",
+    "0",
+    "1",
+    "2",
+    "3",
+    "4",
+    "5",
+    "6",
+    "7",
+    "8",
+    "9",
+    "a",
+    "b",
+    "c",
+    "d",
+    "e",
+    "f",
+    "g",
+    "h",
+    "i",
+    "j",
+    "k",
+    "l",
+    "m",
+    "n",
+    "o",
+    "p",
+    "q",
+    "r",
+    "s",
+    "t",
+    "u",
+    "v",
+    "w",
+    "x",
+    "y",
+    "z",
+    "-",
+    "#",
+    "?",
+    "!",
+    "copyright",
+    "special",
+    ",",
+    ".",
+    ":",
+    "=",
+    "saw_down",
+    "saw_up",
+    "output_left_inactive",
+    "output_right_inactive",
+    "confirm",
+    "delete",
+    "triangle_down",
+    "triangle_up",
+    "square_down",
+    "square_up",
+    "+",
+    "(",
+    "pu12",
+    "pu25",
+    "pu50",
+    "puend",
+    "pu75",
+    "pu75end",
+    "%",
+    ")",
+    ";",
+    "/",
+];
+
+/// Returns the first glyph a font is missing, or None if it has them all.
+/// `ids` are the font's TileIds, each its name followed by `_` and the glyph.
+pub fn missing_glyph(name: &str, ids: &[&str]) -> Option<&'static str> {
+    FONT_GLYPHS
+        .into_iter()
+        .find(|glyph| !ids.contains(&format!("{}_{}", name, glyph).as_str()))
+}
+
+/// Returns the first of LSDj's slots a palette is missing, or None if it has them all.
+/// `ids` are the palette's ColorIds, each its name followed by `_` and the slot.
+pub fn missing_slot(name: &str, ids: &[&str]) -> Option<Colors> {
+    Colors::iter()
+        .into_iter()
+        .find(|slot| !ids.contains(&format!("{}_{}", name, slot).as_str()))
+}
+
+/// Returns the asset files of a kind, the bundled ones followed by the user's if `user_assets`,
+/// so that a user's asset replaces a bundled one with the same name.
+pub fn asset_files(kind: AssetKind, user_assets: bool) -> Vec<PathBuf> {
+    let mut files = files_in(&Path::new("assets").join(kind.folder()), kind.extension());
+    if let Some(dir) = config_dir().filter(|_| user_assets) {
+        files.extend(files_in(&dir.join(kind.folder()), kind.extension()));
+    }
+    files
+}
+
+/// Returns the files in `dir` with the extension, sorted by name.
+/// A folder that doesn't exist has no files.
+fn files_in(dir: &Path, extension: &str) -> Vec<PathBuf> {
+    let mut files: Vec<PathBuf> = match fs::read_dir(dir) {
+        Ok(entries) => entries
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|path| path.is_file())
+            .filter(|path| matches!(path.extension(), Some(ext) if ext == extension))
+            .collect(),
+        Err(_) => vec![],
+    };
+    files.sort();
+    files
+}
+
+/// Returns the name of an asset from its `@name` line, or from its filename if it doesn't have one.
+///
+/// # ERRORS
+///
+/// Errors if the file can't be read or its name is empty or has spaces.
+pub fn asset_name(path: &Path) -> Result<String, TileRendererError> {
+    let filename = path.to_string_lossy().to_string();
+    let contents =
+        fs::read_to_string(path).map_err(|e| TileRendererError::AssetIo(filename.clone(), e))?;
+    let name = match contents
+        .lines()
+        .map(str::trim)
+        .find_map(|line| line.strip_prefix("@name"))
+    {
+        Some(name) => name.trim().to_string(),
+        None => path
+            .file_stem()
+            .map(|stem| stem.to_string_lossy().trim().to_string())
+            .unwrap_or_default(),
+    };
+    if name.is_empty() || name.contains(char::is_whitespace) {
+        return Err(TileRendererError::InvalidAsset {
+            filename,
+            reason: format!("Invalid name `{}`", name),
+        });
+    }
+    Ok(name)
+}
+
+/// Returns the file the asset named `name` is loaded from, the user's if there's one
+/// and `user_assets`.
+pub fn find_asset(kind: AssetKind, name: &str, user_assets: bool) -> Option<PathBuf> {
+    asset_files(kind, user_assets)
+        .into_iter()
+        .rev()
+        .find(|path| matches!(asset_name(path), Ok(found) if found == name))
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use crate::tilerender::parse_colorset::ColorSetParser;
+
+    #[test]
+    /// Assets are found by extension and named by their `@name` line or their filename,
+    /// and a broken one errors rather than panicking.
+    fn find_and_name_assets() {
+        let dir = std::env::temp_dir().join("bsdj_find_and_name_assets");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("b.colorset"),
+            "@name pink\n_background\n    %31 20 20\n",
+        )
+        .unwrap();
+        fs::write(dir.join("a.colorset"), "_background\n    %99 00 00\n").unwrap();
+        fs::write(dir.join("notes.txt"), "").unwrap();
+
+        let files = files_in(&dir, "colorset");
+        assert_eq!(files, vec![dir.join("a.colorset"), dir.join("b.colorset")]);
+        assert_eq!(asset_name(&files[0]).unwrap(), "a");
+        assert_eq!(asset_name(&files[1]).unwrap(), "pink");
+        assert!(matches!(
+            ColorSetParser::parse(files[0].to_str().unwrap(), "a"),
+            Err(TileRendererError::InvalidAsset { .. })
+        ));
+        // Pink parses, but only has one of LSDj's slots.
+        let pink = ColorSetParser::parse(files[1].to_str().unwrap(), "pink").unwrap();
+        let ids: Vec<&str> = pink.iter().map(|(id, _)| id.as_str()).collect();
+        assert_eq!(ids, vec!["pink_background"]);
+        assert_eq!(missing_slot("pink", &ids), Some(Colors::Highlight));
+        assert!(files_in(&dir.join("missing"), "colorset").is_empty());
+        fs::remove_dir_all(dir).unwrap();
+
+        assert_eq!(
+            find_asset(AssetKind::Palette, "red", false),
+            Some(PathBuf::from("assets/colorsets/red.colorset"))
+        );
+    }
+}
@@ -8,6 +8,35 @@ pub enum Colors {
     Cursor,
     TextCursor,
     Empty,
+    /// Marks cells left conflicting by a merge.
+    /// Not one of LSDj's colors, so palettes without it show the cursor color instead.
+    Conflict,
+    /// Marks the cursors of the other people in a session.
+    /// Not one of LSDj's colors, so palettes without it show the highlight color instead.
+    Ghost,
+}
+
+impl Colors {
+    /// Returns the color to show instead if a palette doesn't have this one.
+    pub fn fallback(&self) -> Option<Colors> {
+        match self {
+            Colors::Conflict => Some(Colors::Cursor),
+            Colors::Ghost => Some(Colors::Highlight),
+            _ => None,
+        }
+    }
+
+    /// Returns an array of LSDj's Colors, in the order they appear in a `.colorset` file.
+    pub fn iter() -> [Colors; 6] {
+        [
+            Colors::Background,
+            Colors::Highlight,
+            Colors::Details,
+            Colors::Cursor,
+            Colors::TextCursor,
+            Colors::Empty,
+        ]
+    }
 }
 
 impl fmt::Display for Colors {
@@ -22,6 +51,8 @@ impl fmt::Display for Colors {
                 Colors::Cursor => "cursor",
                 Colors::TextCursor => "textcursor",
                 Colors::Empty => "empty",
+                Colors::Conflict => "conflict",
+                Colors::Ghost => "ghost",
             }
         )
     }
@@ -35,6 +35,24 @@ impl ColorSet {
     }
 }
 
+/// Returns the contrast ratio between two colors, from 1 for the same brightness
+/// to 21 for black on white, as the Web Content Accessibility Guidelines measure it.
+pub fn contrast(a: &Rgba<u8>, b: &Rgba<u8>) -> f64 {
+    let luminance = |color: &Rgba<u8>| {
+        let channel = |c: u8| {
+            let c = c as f64 / 255.;
+            if c <= 0.03928 {
+                c / 12.92
+            } else {
+                ((c + 0.055) / 1.055).powf(2.4)
+            }
+        };
+        0.2126 * channel(color[0]) + 0.7152 * channel(color[1]) + 0.0722 * channel(color[2])
+    };
+    let (a, b) = (luminance(a), luminance(b));
+    (a.max(b) + 0.05) / (a.min(b) + 0.05)
+}
+
 /// A color palette handler for the TileRenderer.
 #[derive(Clone, PartialEq, Default, Debug)]
 pub struct ColorHandler {
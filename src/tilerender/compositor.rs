@@ -0,0 +1,65 @@
+use super::*;
+use image::{imageops, RgbaImage};
+
+/// The width of the Game Boy's screen in pixels.
+pub const SCREEN_WIDTH: u32 = 160;
+/// The height of the Game Boy's screen in pixels.
+pub const SCREEN_HEIGHT: u32 = 144;
+
+/// Flattens every layer into an image of the screen, as the sprites would be drawn.
+/// Layers are drawn from the lowest z-index up, where they're positioned,
+/// and anything off the screen is cut off.
+///
+/// `scale` upscales each pixel into a square of `scale` pixels, and is at least 1.
+pub fn flatten(lh: &LayerHandler, scale: u32) -> RgbaImage {
+    let renderer = lh.get_renderer();
+    let mut layers: Vec<&TileLayer> = lh
+        .get_layer_names()
+        .filter_map(|name| lh.get_layer(name))
+        .collect();
+    // Sort by title too, so layers on the same z-index are always drawn in the same order.
+    layers.sort_by(|a, b| {
+        a.get_z_index()
+            .partial_cmp(&b.get_z_index())
+            .unwrap_or(std::cmp::Ordering::Equal)
+            .then_with(|| a.get_title().cmp(b.get_title()))
+    });
+
+    let mut screen = RgbaImage::new(SCREEN_WIDTH, SCREEN_HEIGHT);
+    for layer in layers {
+        let image = layer.as_rgba_image(renderer);
+        // Sprites are positioned by their centre, with y going up,
+        // where the centre of the screen is the origin.
+        let position = layer.get_position();
+        let left = (SCREEN_WIDTH as f32 - image.width() as f32) / 2. + position.x;
+        let top = (SCREEN_HEIGHT as f32 - image.height() as f32) / 2. - position.y;
+        draw_over(&mut screen, &image, left as i64, top as i64);
+    }
+
+    let scale = scale.max(1);
+    if scale == 1 {
+        return screen;
+    }
+    imageops::resize(
+        &screen,
+        SCREEN_WIDTH * scale,
+        SCREEN_HEIGHT * scale,
+        imageops::FilterType::Nearest,
+    )
+}
+
+/// Draws an image over another with its top-left at `(left, top)`, cutting off what
+/// falls outside. Tiles are either opaque or unset, so transparent pixels are skipped
+/// rather than blended.
+fn draw_over(bottom: &mut RgbaImage, top_image: &RgbaImage, left: i64, top: i64) {
+    for (x, y, pixel) in top_image.enumerate_pixels() {
+        let (screen_x, screen_y) = (left + x as i64, top + y as i64);
+        let on_screen = screen_x >= 0
+            && screen_y >= 0
+            && screen_x < bottom.width() as i64
+            && screen_y < bottom.height() as i64;
+        if on_screen && pixel.0[3] != 0 {
+            bottom.put_pixel(screen_x as u32, screen_y as u32, *pixel);
+        }
+    }
+}
@@ -1,19 +1,24 @@
-use crate::tilerender::*;
+use crate::cli::CliArgs;
+use crate::tilerender::{assets::*, *};
 use bevy::diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin};
-
-const FONTS: &[&str] = &["lowr"];
-const GLYPHS: &[&str] = &["dflt"];
-const COLORSETS: &[&str] = &["ami ", "crt ", "cute", "gray", "red ", "rgb "];
+use std::path::Path;
 
 pub struct InitRendererPlugin;
 
 impl Plugin for InitRendererPlugin {
     fn build(&self, app: &mut App) {
+        let cli = app
+            .world
+            .get_resource::<CliArgs>()
+            .cloned()
+            .unwrap_or_default();
+
         let tr = TileRenderer::new(8, 8);
         let mut lh = LayerHandler::new(tr);
-        init_fonts(&mut lh);
-        init_glyphs(&mut lh);
-        init_colorsets(&mut lh);
+        lh.user_assets = !cli.no_user_assets;
+        for kind in [AssetKind::Font, AssetKind::Glyph, AssetKind::Palette] {
+            init_assets(&mut lh, kind);
+        }
 
         construct_layers(&mut lh);
         app.insert_resource(lh);
@@ -24,34 +29,68 @@ impl Plugin for InitRendererPlugin {
     }
 }
 
-fn init_fonts(lh: &mut LayerHandler) {
-    for font in FONTS {
-        let tr = lh.get_renderer_mut();
-        let filename = format!("assets/fonts/{}.tilesprite", font);
-        parse_tilesprite::TileSpriteParser::parse_and_add(&filename, font, tr).unwrap();
-
-        lh.font_names.push(font.to_string());
-    }
-}
-
-fn init_glyphs(lh: &mut LayerHandler) {
-    for glyph in GLYPHS {
-        let tr = lh.get_renderer_mut();
-        let filename = format!("assets/glyphs/{}.tilesprite", glyph);
-        parse_tilesprite::TileSpriteParser::parse_and_add(&filename, glyph, tr).unwrap();
-
-        lh.glyph_names.push(glyph.to_string());
+/// Loads every asset of a kind that's bundled or in the config folder,
+/// skipping any that can't be parsed.
+fn init_assets(lh: &mut LayerHandler, kind: AssetKind) {
+    for path in asset_files(kind, lh.user_assets) {
+        if let Err(e) = load_asset(lh, kind, &path) {
+            warn!("Skipping asset `{}`: {}", path.display(), e);
+        }
     }
 }
 
-fn init_colorsets(lh: &mut LayerHandler) {
-    for colorset in COLORSETS {
-        let tr = lh.get_renderer_mut();
-        let filename = format!("assets/colorsets/{}.colorset", colorset);
-        parse_colorset::ColorSetParser::parse_and_add(&filename, colorset, tr).unwrap();
-
-        lh.color_names.push(colorset.to_string());
+/// Parses an asset into the TileRenderer and adds its name to the ones that can be switched to.
+///
+/// # ERRORS
+///
+/// Errors if the file can't be read or parsed, or if it's a font missing glyphs the screens draw
+/// or a palette missing any of LSDj's slots.
+fn load_asset(
+    lh: &mut LayerHandler,
+    kind: AssetKind,
+    path: &Path,
+) -> Result<(), TileRendererError> {
+    let name = asset_name(path)?;
+    let filename = path.to_string_lossy().to_string();
+    let invalid = |reason: String| TileRendererError::InvalidAsset {
+        filename: filename.clone(),
+        reason,
+    };
+
+    let tr = lh.get_renderer_mut();
+    let names = match kind {
+        AssetKind::Font | AssetKind::Glyph => {
+            let tilesprites = parse_tilesprite::TileSpriteParser::parse(&filename, &name)?;
+            let ids: Vec<&str> = tilesprites.iter().map(|(id, _)| id.as_str()).collect();
+            if kind == AssetKind::Font {
+                if let Some(glyph) = missing_glyph(&name, &ids) {
+                    return Err(invalid(format!("Missing the `{}` glyph", glyph)));
+                }
+            }
+            for (id, tilesprite) in tilesprites {
+                tr.add_tilesprite(&id, tilesprite)?;
+            }
+            match kind {
+                AssetKind::Font => &mut lh.font_names,
+                _ => &mut lh.glyph_names,
+            }
+        }
+        AssetKind::Palette => {
+            let colorsets = parse_colorset::ColorSetParser::parse(&filename, &name)?;
+            let ids: Vec<&str> = colorsets.iter().map(|(id, _)| id.as_str()).collect();
+            if let Some(slot) = missing_slot(&name, &ids) {
+                return Err(invalid(format!("Missing the `{}` slot", slot)));
+            }
+            for (id, colorset) in colorsets {
+                tr.add_colorset(&id, colorset);
+            }
+            &mut lh.color_names
+        }
+    };
+    if !names.contains(&name) {
+        names.push(name);
     }
+    Ok(())
 }
 
 fn construct_layers(lh: &mut LayerHandler) {
@@ -90,3 +129,45 @@ fn fps_counter(mut lh: ResMut<LayerHandler>, diagnostics: Res<Diagnostics>) {
         }
     }
 }
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    /// Bundled assets load, but fonts missing glyphs and palettes missing slots are skipped.
+    fn reject_incomplete_assets() {
+        let mut lh = LayerHandler::new(TileRenderer::new(8, 8));
+        for entry in fs::read_dir("assets/colorsets").unwrap() {
+            load_asset(&mut lh, AssetKind::Palette, &entry.unwrap().path()).unwrap();
+        }
+        let lowr = Path::new("assets/fonts/lowr.tilesprite");
+        load_asset(&mut lh, AssetKind::Font, lowr).unwrap();
+
+        let dir = std::env::temp_dir().join("bsdj_reject_incomplete_assets");
+        fs::create_dir_all(&dir).unwrap();
+        let pink = dir.join("pink.colorset");
+        fs::write(&pink, "_background\n    %31 20 20\n").unwrap();
+        let thin = dir.join("thin.tilesprite");
+        fs::write(
+            &thin,
+            format!("=8,8\n\n_a\n{}", "    .0 0 0 0 0 0 0 0\n".repeat(8)),
+        )
+        .unwrap();
+
+        assert!(matches!(
+            load_asset(&mut lh, AssetKind::Palette, &pink),
+            Err(TileRendererError::InvalidAsset { reason, .. }) if reason.contains("highlight")
+        ));
+        assert!(matches!(
+            load_asset(&mut lh, AssetKind::Font, &thin),
+            Err(TileRendererError::InvalidAsset { reason, .. }) if reason.contains("musicnote")
+        ));
+        assert!(!lh.color_names.contains(&"pink".to_string()));
+        assert!(!lh.font_names.contains(&"thin".to_string()));
+        assert!(!lh.get_renderer().has_tilesprite("thin_a"));
+        assert_eq!(lh.font_names, vec!["lowr".to_string()]);
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -13,6 +13,8 @@ pub struct LayerHandler {
     pub active_font: String,
     pub active_glyph: String,
     pub active_colorset: String,
+    /// If false, assets in the user's config folder aren't loaded or looked up.
+    pub user_assets: bool,
 }
 
 impl LayerHandler {
@@ -26,6 +28,7 @@ impl LayerHandler {
             active_font: "lowr".to_string(),
             active_glyph: "dflt".to_string(),
             active_colorset: "gray".to_string(),
+            user_assets: true,
         }
     }
 
@@ -91,6 +94,14 @@ impl LayerHandler {
         if has_colorset {
             return Ok(colorset);
         }
+        // Colors BSDj adds to LSDj's fall back to one of LSDj's.
+        let fallback = [colors::Colors::Conflict, colors::Colors::Ghost]
+            .iter()
+            .find(|color| color.to_string() == color_id)
+            .and_then(|color| color.fallback());
+        if let Some(fallback) = fallback {
+            return self.get_colorset_name(&fallback.to_string());
+        }
         Err(format!("Colorset not found - {}", colorset))
     }
 
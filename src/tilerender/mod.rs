@@ -1,7 +1,9 @@
 use bevy::prelude::*;
 use std::collections::HashMap;
 
+pub mod assets;
 pub mod colors;
+pub mod compositor;
 pub mod init_renderer;
 pub mod parse_colorset;
 pub mod parse_tilesprite;
@@ -136,6 +138,18 @@ fn reload_map(
     lh.get_renderer_mut().unset_require_reload();
 }
 
+/// Marks every layer as drawn, standing in for `reload_map` when there's no window
+/// to draw to, so changes to the screen can still be told apart frame to frame.
+pub fn mark_layers_drawn(mut lh: ResMut<LayerHandler>) {
+    let names: Vec<String> = lh.get_layer_names().cloned().collect();
+    for name in names {
+        if let Some(layer) = lh.get_layer_mut(&name) {
+            layer.requires_reload = false;
+        }
+    }
+    lh.get_renderer_mut().unset_require_reload();
+}
+
 /// Check if a components property's changed and update them.
 fn reload_map_properties(
     mut maps: Query<(&mut Transform, &TileLayerSprite)>,
@@ -15,10 +15,11 @@ const FIVE_RATIO: f64 = 255. / 31.;
 /// Lines containing `%xx xx xx` will be assumed as a 555 RGB code (or 5555 RGBA if `%xx xx xx xx`)
 /// Lines containing `*xx xx xx` will be assumed as a 888 RGB code (or 8888 RGBA if `*xx xx xx xx`)
 /// See the example file structure in `assets/colorsets/cute.colorset`
+/// An `@name [name]` line names the file's palette, see `assets::asset_name`.
 ///
-/// # PANICS
+/// # ERRORS
 ///
-/// This code will panic if the file structure is not valid.
+/// Parsing errors if the file can't be read or its structure is not valid.
 pub struct ColorSetParser {
     contents: String,
     colorsets: Vec<(ColorId, ColorSet)>,
@@ -31,7 +32,7 @@ impl ColorSetParser {
         prefix: &str,
         tilerenderer: &mut TileRenderer,
     ) -> Result<(), TileRendererError> {
-        let colorsets = ColorSetParser::parse(filename, prefix);
+        let colorsets = ColorSetParser::parse(filename, prefix)?;
         for (colorsetid, colorset) in colorsets {
             tilerenderer.add_colorset(&colorsetid, colorset)
         }
@@ -40,8 +41,12 @@ impl ColorSetParser {
 
     /// Parses the `.colorset` format found in the assets.
     /// Returns a hashmap of ColorSets to their ColorSet IDs.
-    pub fn parse(filename: &str, prefix: &str) -> Vec<(ColorId, ColorSet)> {
-        let contents = fs::read_to_string(filename).unwrap();
+    pub fn parse(
+        filename: &str,
+        prefix: &str,
+    ) -> Result<Vec<(ColorId, ColorSet)>, TileRendererError> {
+        let contents = fs::read_to_string(filename)
+            .map_err(|e| TileRendererError::AssetIo(filename.to_string(), e))?;
 
         let mut tsp = ColorSetParser {
             contents,
@@ -49,15 +54,19 @@ impl ColorSetParser {
             prefix: prefix.to_string(),
         };
 
-        tsp.parse_contents();
+        tsp.parse_contents()
+            .map_err(|reason| TileRendererError::InvalidAsset {
+                filename: filename.to_string(),
+                reason,
+            })?;
 
-        tsp.colorsets
+        Ok(tsp.colorsets)
     }
 
     /// Parse the contents of the file
-    pub fn parse_contents(&mut self) {
+    pub fn parse_contents(&mut self) -> Result<(), String> {
         // An iterable over all the lines.
-        // When this runs out, the program either panics or has finished.
+        // When this runs out, the file is either invalid or has finished.
         let mut line_iter = self.contents.lines();
 
         // The current line being processed
@@ -79,7 +88,7 @@ impl ColorSetParser {
 
             let mut colorset_line = line_iter.next();
             let mut colorset_line_trim = colorset_line
-                .expect("End of file when expecting more color data.")
+                .ok_or_else(|| "End of file when expecting more color data".to_string())?
                 .trim();
 
             let mut color_id = 0;
@@ -87,9 +96,9 @@ impl ColorSetParser {
             // (or end of file)
             while !colorset_line_trim.starts_with('_') {
                 let rgba = match colorset_line_trim.get(0..=0) {
-                    Some("#") => parse_hex(colorset_line_trim.get(1..).unwrap().as_bytes()),
-                    Some("%") => parse_555(colorset_line_trim.get(1..).unwrap()),
-                    Some("*") => parse_888(colorset_line_trim.get(1..).unwrap()),
+                    Some("#") => parse_hex(colorset_line_trim.get(1..).unwrap().as_bytes())?,
+                    Some("%") => parse_555(colorset_line_trim.get(1..).unwrap())?,
+                    Some("*") => parse_888(colorset_line_trim.get(1..).unwrap())?,
                     _ => {
                         colorset_line = line_iter.next();
                         if colorset_line.is_none() {
@@ -115,118 +124,135 @@ impl ColorSetParser {
 
             line = colorset_line;
         }
+        Ok(())
     }
 }
 
 /// Parses a hex color into an RGBA struct.
-pub fn parse_hex(hex: &[u8]) -> Rgba<u8> {
-    Rgba(match hex.len() {
-        8 => [
-            from_hex(hex[0]) * 16 + from_hex(hex[1]),
-            from_hex(hex[2]) * 16 + from_hex(hex[3]),
-            from_hex(hex[4]) * 16 + from_hex(hex[5]),
-            from_hex(hex[6]) * 16 + from_hex(hex[7]),
-        ],
-        6 => [
-            from_hex(hex[0]) * 16 + from_hex(hex[1]),
-            from_hex(hex[2]) * 16 + from_hex(hex[3]),
-            from_hex(hex[4]) * 16 + from_hex(hex[5]),
-            255,
-        ],
-        4 => [
-            from_hex(hex[0]) * 17,
-            from_hex(hex[1]) * 17,
-            from_hex(hex[2]) * 17,
-            from_hex(hex[3]) * 17,
-        ],
-        3 => [
-            from_hex(hex[0]) * 17,
-            from_hex(hex[1]) * 17,
-            from_hex(hex[2]) * 17,
-            255,
-        ],
-        _ => panic!(
-            "Unexpected length of hex string: Expected 3, 4, 6, or 8, got {}",
-            hex.len()
-        ),
-    })
+pub fn parse_hex(hex: &[u8]) -> Result<Rgba<u8>, String> {
+    let digits = hex
+        .iter()
+        .map(|c| from_hex(*c))
+        .collect::<Result<Vec<u8>, String>>()?;
+    Ok(Rgba(match digits[..] {
+        [r1, r0, g1, g0, b1, b0, a1, a0] => {
+            [r1 * 16 + r0, g1 * 16 + g0, b1 * 16 + b0, a1 * 16 + a0]
+        }
+        [r1, r0, g1, g0, b1, b0] => [r1 * 16 + r0, g1 * 16 + g0, b1 * 16 + b0, 255],
+        [r, g, b, a] => [r * 17, g * 17, b * 17, a * 17],
+        [r, g, b] => [r * 17, g * 17, b * 17, 255],
+        _ => {
+            return Err(format!(
+                "Unexpected length of hex string: Expected 3, 4, 6, or 8, got {}",
+                hex.len()
+            ))
+        }
+    }))
 }
 
 /// Parses a single character from hex.
-fn from_hex(c: u8) -> u8 {
+fn from_hex(c: u8) -> Result<u8, String> {
     match c {
-        b'0'..=b'9' => c - b'0',
-        b'a'..=b'f' => c - b'a' + 10,
-        b'A'..=b'F' => c - b'A' + 10,
-        _ => panic!("Invalid hex character: {}", c),
+        b'0'..=b'9' => Ok(c - b'0'),
+        b'a'..=b'f' => Ok(c - b'a' + 10),
+        b'A'..=b'F' => Ok(c - b'A' + 10),
+        _ => Err(format!("Invalid hex character: `{}`", c as char)),
     }
 }
 
-/// Parses a 555(5) RGB(A) color into an RGBA struct.
-pub fn parse_555(s: &str) -> Rgba<u8> {
+/// Parses the whitespace separated channels of a color, with the alpha defaulting to `max`.
+fn parse_channels(s: &str, format: &str, max: u8) -> Result<[u8; 4], String> {
     let mut split = s.split_ascii_whitespace();
-
-    let r: f64 = split
-        .next()
-        .expect("Expected Red value for 555 color.")
-        .parse()
-        .expect("Invalid Red value for 555 color.");
-    let g: f64 = split
-        .next()
-        .expect("Expected Green value for 555 color.")
-        .parse()
-        .expect("Invalid Green value for 555 color.");
-    let b: f64 = split
-        .next()
-        .expect("Expected Blue value for 555 color.")
-        .parse()
-        .expect("Invalid Blue value for 555 color.");
-    let a: f64 = split
-        .next()
-        .unwrap_or("31")
-        .parse()
-        .expect("Invalid Alpha value for 555 color.");
+    let mut channel = |name: &str, default: Option<u8>| match (split.next(), default) {
+        (Some(value), _) => value
+            .parse::<u8>()
+            .ok()
+            .filter(|value| *value <= max)
+            .ok_or_else(|| format!("Invalid {} value for {} color: `{}`", name, format, value)),
+        (None, Some(default)) => Ok(default),
+        (None, None) => Err(format!("Expected {} value for {} color", name, format)),
+    };
+    let channels = [
+        channel("Red", None)?,
+        channel("Green", None)?,
+        channel("Blue", None)?,
+        channel("Alpha", Some(max))?,
+    ];
 
     if split.next().is_some() {
-        panic!("Unexpected extra value(s) in 555 color.");
+        return Err(format!("Unexpected extra value(s) in {} color", format));
     }
+    Ok(channels)
+}
+
+/// Parses a 555(5) RGB(A) color into an RGBA struct.
+pub fn parse_555(s: &str) -> Result<Rgba<u8>, String> {
+    let [r, g, b, a] = parse_channels(s, "555", 31)?;
+    Ok(from_555(r, g, b, a))
+}
 
+/// Converts 5-bit RGBA channels (0-31) into an RGBA struct.
+pub fn from_555(r: u8, g: u8, b: u8, a: u8) -> Rgba<u8> {
     Rgba([
-        (r * FIVE_RATIO) as u8,
-        (g * FIVE_RATIO) as u8,
-        (b * FIVE_RATIO) as u8,
-        (a * FIVE_RATIO) as u8,
+        (r as f64 * FIVE_RATIO) as u8,
+        (g as f64 * FIVE_RATIO) as u8,
+        (b as f64 * FIVE_RATIO) as u8,
+        (a as f64 * FIVE_RATIO) as u8,
     ])
 }
 
+/// Converts an RGBA struct into the nearest 5-bit RGB channels (0-31).
+pub fn to_555(color: &Rgba<u8>) -> (u8, u8, u8) {
+    let channel = |c: u8| (c as f64 / FIVE_RATIO).round() as u8;
+    (channel(color[0]), channel(color[1]), channel(color[2]))
+}
+
 /// Parses an standard RGB(A) color into an RGBA struct.
-pub fn parse_888(s: &str) -> Rgba<u8> {
-    let mut split = s.split_ascii_whitespace();
+pub fn parse_888(s: &str) -> Result<Rgba<u8>, String> {
+    parse_channels(s, "888", 255).map(Rgba)
+}
 
-    let r = split
-        .next()
-        .expect("Expected Red value for 888 color.")
-        .parse()
-        .expect("Invalid Red value for 888 color.");
-    let g = split
-        .next()
-        .expect("Expected Green value for 888 color.")
-        .parse()
-        .expect("Invalid Green value for 888 color.");
-    let b = split
-        .next()
-        .expect("Expected Blue value for 888 color.")
-        .parse()
-        .expect("Invalid Blue value for 888 color.");
-    let a = split
-        .next()
-        .unwrap_or("255")
-        .parse()
-        .expect("Invalid Alpha value for 888 color.");
+/// Writes ColorSets in the `.colorset` format `ColorSetParser` reads, dropping `prefix`
+/// from the start of each ColorId. Colors are written as 555 RGB codes, with the
+/// alpha only written when the color isn't opaque.
+pub fn write_colorsets(colorsets: &[(ColorId, ColorSet)], prefix: &str) -> String {
+    let mut contents = String::new();
+    for (colorsetid, colorset) in colorsets {
+        let name = colorsetid
+            .strip_prefix(&format!("{}_", prefix))
+            .unwrap_or(colorsetid);
+        contents += &format!("_{}\n", name);
 
-    if split.next().is_some() {
-        panic!("Unexpected extra value(s) in 888 color.");
+        let mut pixel_id = 0;
+        while let Some(color) = colorset.get_color(&pixel_id) {
+            let (r, g, b) = to_555(color);
+            contents += &format!("    %{:2} {:2} {:2}", r, g, b);
+            if color[3] != 255 {
+                contents += &format!(" {:2}", (color[3] as f64 / FIVE_RATIO).round() as u8);
+            }
+            contents += "\n";
+            pixel_id += 1;
+        }
     }
+    contents
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
 
-    Rgba([r, g, b, a])
+    #[test]
+    /// A written palette parses back into the same ColorSets, transparent colors included.
+    fn colorset_round_trip() {
+        let colorsets = ColorSetParser::parse("assets/colorsets/gray.colorset", "gray").unwrap();
+        let contents = write_colorsets(&colorsets, "gray");
+        assert!(contents.starts_with("_background\n    % 0  0  5\n    %18 18 20\n"));
+        assert!(contents.contains("_empty\n    % 0  0  0  0\n"));
+
+        let filename = std::env::temp_dir().join("bsdj_colorset_round_trip.colorset");
+        let filename = filename.to_str().unwrap();
+        fs::write(filename, &contents).unwrap();
+        assert_eq!(ColorSetParser::parse(filename, "gray").unwrap(), colorsets);
+        fs::remove_file(filename).unwrap();
+    }
 }
@@ -10,10 +10,11 @@ use std::fs;
 /// `tileheight` lines by `.[PixelColorId] [PixelColorId] [PixelColorId]...`, where
 /// `[PixelColorId]` is repeated `tilewidth` times. Any lines not starting with
 /// `=`, `_`, or `.` are ignored. See `assets/fonts/lower.tilesprite` for an example in action.
+/// An `@name [name]` line names the file's font, see `assets::asset_name`.
 ///
-/// # PANICS
+/// # ERRORS
 ///
-/// This code will panic if the file structure is not valid.
+/// Parsing errors if the file can't be read or its structure is not valid.
 pub struct TileSpriteParser {
     contents: String,
     /// Where the TileSprites are stored, alongside their TileIds.
@@ -30,7 +31,7 @@ impl TileSpriteParser {
         prefix: &str,
         tilerenderer: &mut TileRenderer,
     ) -> Result<(), TileRendererError> {
-        let tilesprites = TileSpriteParser::parse(filename, prefix);
+        let tilesprites = TileSpriteParser::parse(filename, prefix)?;
         for (tileid, tilesprite) in tilesprites {
             tilerenderer.add_tilesprite(&tileid, tilesprite)?
         }
@@ -39,9 +40,17 @@ impl TileSpriteParser {
 
     /// Parses the `.tilesprite` format found in the assets.
     /// Returns a hashmap of TileSprites to their Tile IDs.
-    pub fn parse(filename: &str, prefix: &str) -> Vec<(TileId, TileSprite)> {
-        let contents = fs::read_to_string(filename).unwrap();
-        let (width, height) = TileSpriteParser::parse_metadata(&contents, filename);
+    pub fn parse(
+        filename: &str,
+        prefix: &str,
+    ) -> Result<Vec<(TileId, TileSprite)>, TileRendererError> {
+        let contents = fs::read_to_string(filename)
+            .map_err(|e| TileRendererError::AssetIo(filename.to_string(), e))?;
+        let invalid = |reason| TileRendererError::InvalidAsset {
+            filename: filename.to_string(),
+            reason,
+        };
+        let (width, height) = TileSpriteParser::parse_metadata(&contents).map_err(invalid)?;
 
         let mut tsp = TileSpriteParser {
             contents,
@@ -52,57 +61,37 @@ impl TileSpriteParser {
             tile_height: height,
         };
 
-        tsp.parse_contents();
+        tsp.parse_contents().map_err(invalid)?;
 
-        tsp.tilesprites
+        Ok(tsp.tilesprites)
     }
 
     /// Finds a line starting with `=` and attempts to find the
     /// tile width and tile height from it.
-    fn parse_metadata(contents: &str, filename: &str) -> (usize, usize) {
-        let mut lines = contents.lines();
-        let mut line = lines
-            .next()
-            .unwrap_or_else(|| {
-                panic!(
-                    "No metadata information found for TileSprite file `{}`",
-                    filename
-                )
-            })
-            .trim();
-
-        while !line.starts_with('=') {
-            line = lines
-                .next()
-                .unwrap_or_else(|| {
-                    panic!(
-                        "No metadata information found for TileSprite file `{}`",
-                        filename
-                    )
-                })
-                .trim();
-        }
+    fn parse_metadata(contents: &str) -> Result<(usize, usize), String> {
+        let line = contents
+            .lines()
+            .map(str::trim)
+            .find(|line| line.starts_with('='))
+            .ok_or_else(|| "No metadata information found".to_string())?;
 
         let mut metadata = line[1..].split(',');
-        let width = metadata.next().unwrap_or_else(|| {
-            panic!("No metadata width found for TileSprite file `{}`", filename)
-        });
-        let height = metadata.next().unwrap_or_else(|| {
-            panic!(
-                "No metadata height found for TileSprite file `{}`",
-                filename
-            )
-        });
-        (
-            width.parse::<usize>().unwrap(),
-            height.parse::<usize>().unwrap(),
-        )
+        let mut size = |side| {
+            let value = metadata
+                .next()
+                .ok_or_else(|| format!("No metadata {} found", side))?;
+            value
+                .trim()
+                .parse::<usize>()
+                .map_err(|_| format!("Invalid metadata {}: `{}`", side, value))
+        };
+        Ok((size("width")?, size("height")?))
     }
 
     /// Parse the contents of the file to find the TileSprite info.
-    fn parse_contents(&mut self) {
+    fn parse_contents(&mut self) -> Result<(), String> {
         // An iterable over all the lines.
-        // When this runs out, the program either panics or has finished.
+        // When this runs out, the file is either invalid or has finished.
         let mut line_iter = self.contents.lines();
 
         // The current line being processed
@@ -113,7 +102,7 @@ impl TileSpriteParser {
             // If theres a line starting with `.` here, the file
             // structure was messed up.
             if line_trimmed.starts_with('.') {
-                panic!("Unexpected spritedata line: {}", line_trimmed);
+                return Err(format!("Unexpected spritedata line: `{}`", line_trimmed));
             }
             if !line_trimmed.starts_with('_') {
                 line = line_iter.next();
@@ -131,11 +120,14 @@ impl TileSpriteParser {
             while data_lines < self.tile_height {
                 let spriteline = line_iter
                     .next()
-                    .expect("End of file when expecting more spritedata.");
+                    .ok_or_else(|| "End of file when expecting more spritedata".to_string())?;
                 let spriteline = spriteline.trim();
                 // If it starts with `_`, the user is missing lines
                 if spriteline.starts_with('_') {
-                    panic!("Expected spritedata line, got new tile: `{}`", spriteline);
+                    return Err(format!(
+                        "Expected spritedata line, got new tile: `{}`",
+                        spriteline
+                    ));
                 }
                 // If it doesn't hold spritedata, get the next line
                 if !spriteline.starts_with('.') {
@@ -147,11 +139,14 @@ impl TileSpriteParser {
                 let spriteline_split = spriteline[1..].split_whitespace();
                 let spriteline_count = spriteline_split.clone().count();
                 if spriteline_count != self.tile_width {
-                    panic!("Count of SpriteData on line not equal to width of tile. SpriteData Length: `{}`, TileWidth: `{}`, Line: `{}`", spriteline_count, self.tile_width, spriteline);
+                    return Err(format!("Count of SpriteData on line not equal to width of tile. SpriteData Length: `{}`, TileWidth: `{}`, Line: `{}`", spriteline_count, self.tile_width, spriteline));
                 }
 
                 for pixel in spriteline_split {
-                    spritedata.push(pixel.parse::<PixelColorId>().unwrap())
+                    let pixel = pixel
+                        .parse::<PixelColorId>()
+                        .map_err(|_| format!("Invalid PixelColorId `{}`", pixel))?;
+                    spritedata.push(pixel)
                 }
 
                 data_lines += 1;
@@ -160,8 +155,58 @@ impl TileSpriteParser {
             let tilesprite = TileSprite::new(spritedata);
             self.tilesprites.push((id, tilesprite));
 
-            // Ensures that it doesn't get crash due to `Unexpected spritedata line` when it next goes around
+            // Ensures that it doesn't fail due to `Unexpected spritedata line` when it next goes around
             line = line_iter.next();
         }
+        Ok(())
+    }
+}
+
+/// Writes TileSprites in the `.tilesprite` format `TileSpriteParser` reads,
+/// dropping `prefix` from the start of each TileId.
+///
+/// # PANICS
+///
+/// Panics if a TileSprite isn't `width` by `height`.
+pub fn write_tilesprites(
+    tilesprites: &[(TileId, TileSprite)],
+    prefix: &str,
+    width: usize,
+    height: usize,
+) -> String {
+    let mut contents = format!("={},{}\n", width, height);
+    for (tileid, tilesprite) in tilesprites {
+        assert_eq!(tilesprite.size(), width * height);
+        let name = tileid
+            .strip_prefix(&format!("{}_", prefix))
+            .unwrap_or(tileid);
+        contents += &format!("\n_{}\n", name);
+        for row in tilesprite.read().chunks(width) {
+            let row: Vec<String> = row.iter().map(|pixel| pixel.to_string()).collect();
+            contents += &format!("    .{}\n", row.join(" "));
+        }
+    }
+    contents
+}
+
+#[cfg(test)]
+pub mod test {
+    use super::*;
+
+    #[test]
+    /// A written font parses back into the same TileSprites.
+    fn tilesprite_round_trip() {
+        let tilesprites = TileSpriteParser::parse("assets/fonts/lowr.tilesprite", "lowr").unwrap();
+        let contents = write_tilesprites(&tilesprites, "lowr", 8, 8);
+        assert!(contents.starts_with("=8,8\n\n_musicnote\n    .0 0 0 0 0 0 0 0\n"));
+
+        let filename = std::env::temp_dir().join("bsdj_tilesprite_round_trip.tilesprite");
+        let filename = filename.to_str().unwrap();
+        fs::write(filename, &contents).unwrap();
+        assert_eq!(
+            TileSpriteParser::parse(filename, "lowr").unwrap(),
+            tilesprites
+        );
+        fs::remove_file(filename).unwrap();
     }
 }
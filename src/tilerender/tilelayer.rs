@@ -175,6 +175,21 @@ impl TileLayer {
 
     /// Returns an Image of the tilemap
     pub fn as_image(&self, renderer: &TileRenderer) -> Image {
+        let map = self.as_rgba_image(renderer);
+        Image::new(
+            Extent3d {
+                width: map.width(),
+                height: map.height(),
+                depth_or_array_layers: 1,
+            },
+            TextureDimension::D2,
+            map.into_raw(),
+            TextureFormat::Rgba8UnormSrgb,
+        )
+    }
+
+    /// Returns the pixels of the tilemap, drawn on the CPU.
+    pub fn as_rgba_image(&self, renderer: &TileRenderer) -> RgbaImage {
         let map_width = self.width as u32;
         let map_height = self.height as u32;
         let tile_width = renderer.get_tile_width() as u32;
@@ -215,15 +230,6 @@ impl TileLayer {
             }
         }
 
-        Image::new(
-            Extent3d {
-                width: map.width(),
-                height: map.height(),
-                depth_or_array_layers: 1,
-            },
-            TextureDimension::D2,
-            map.into_raw(),
-            TextureFormat::Rgba8UnormSrgb,
-        )
+        map
     }
 }
@@ -15,4 +15,8 @@ pub enum TileRendererError {
     IncorrectTileSpriteSize { recieved: usize, expected: usize },
     #[error("The Tile in the TileRenderer at position `({x}, {y})` has not yet been set.")]
     TileNotSet { x: usize, y: usize },
+    #[error("Could not read asset file `{0}`: {1}")]
+    AssetIo(String, std::io::Error),
+    #[error("Invalid asset file `{filename}`: {reason}")]
+    InvalidAsset { filename: String, reason: String },
 }
@@ -0,0 +1,17 @@
+use std::env;
+use std::path::PathBuf;
+
+/// Returns the directory the user's BSDj config lives in: `$XDG_CONFIG_HOME/bsdj`,
+/// `~/.config/bsdj`, or `%APPDATA%\bsdj` on Windows.
+///
+/// Returns None if none of those variables are set.
+pub fn config_dir() -> Option<PathBuf> {
+    let base = match env::var_os("XDG_CONFIG_HOME") {
+        Some(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => match env::var_os("HOME") {
+            Some(home) => PathBuf::from(home).join(".config"),
+            None => PathBuf::from(env::var_os("APPDATA")?),
+        },
+    };
+    Some(base.join("bsdj"))
+}
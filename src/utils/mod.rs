@@ -1,4 +1,6 @@
+pub mod config_dir;
 pub mod sized_headed_array;
 pub mod u8_utils;
 
+pub use config_dir::config_dir;
 pub use sized_headed_array::SizedHeadedArray;